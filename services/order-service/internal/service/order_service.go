@@ -28,6 +28,7 @@ type OrderService struct {
 	userServiceURL    string
 	productServiceURL string
 	paymentServiceURL string
+	paymentServiceKey string
 	client            *http.Client
 	tracer            trace.Tracer
 }
@@ -38,6 +39,7 @@ func New(repo *repository.OrderRepository, cfg *config.Config) *OrderService {
 		userServiceURL:    cfg.UserServiceURL,
 		productServiceURL: cfg.ProductServiceURL,
 		paymentServiceURL: cfg.PaymentServiceURL,
+		paymentServiceKey: cfg.PaymentServiceAPIKey,
 		client: &http.Client{
   Timeout: 10 * time.Second,
   CheckRedirect: func(req *http.Request, via []*http.Request) error {
@@ -163,6 +165,10 @@ func (s *OrderService) ProcessPayment(
 
 	req, _ := http.NewRequestWithContext(ctx, "POST", url, bytes.NewBuffer(body))
 	req.Header.Set("Content-Type", "application/json")
+	// The payment service refuses payment requests without a key or token
+	if s.paymentServiceKey != "" {
+		req.Header.Set("X-Merchant-Key", s.paymentServiceKey)
+	}
 
 	resp, err := s.client.Do(req)
 	metrics.PaymentProcessDuration.Observe(time.Since(start).Seconds())
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS payment_methods (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    method_type VARCHAR(50) NOT NULL,
+    brand VARCHAR(20) NOT NULL,
+    last4 VARCHAR(4) NOT NULL,
+    exp_month SMALLINT NOT NULL,
+    exp_year SMALLINT NOT NULL,
+    holder_name VARCHAR(255) NOT NULL,
+    encrypted_details BYTEA NOT NULL,
+    nonce BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_payment_methods_user_id ON payment_methods(user_id);
+
+ALTER TABLE payments ADD COLUMN payment_method_id UUID REFERENCES payment_methods(id);
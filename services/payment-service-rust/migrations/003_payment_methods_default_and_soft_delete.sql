@@ -0,0 +1,7 @@
+ALTER TABLE payment_methods ADD COLUMN is_default BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE payment_methods ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+-- At most one default method per user
+CREATE UNIQUE INDEX idx_payment_methods_user_default
+    ON payment_methods(user_id)
+    WHERE is_default AND deleted_at IS NULL;
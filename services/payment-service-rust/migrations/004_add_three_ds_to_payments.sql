@@ -0,0 +1,2 @@
+ALTER TABLE payments ADD COLUMN three_ds_redirect_url TEXT;
+ALTER TABLE payments ADD COLUMN three_ds_status VARCHAR(1);
@@ -0,0 +1,5 @@
+ALTER TABLE payments ADD COLUMN transfer_reference VARCHAR(20) UNIQUE;
+ALTER TABLE payments ADD COLUMN transfer_instructions JSONB;
+ALTER TABLE payments ADD COLUMN expires_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_payments_pending_expiry ON payments(expires_at) WHERE payment_status = 'PENDING';
@@ -0,0 +1,2 @@
+ALTER TABLE payments ADD COLUMN collection_note TEXT;
+ALTER TABLE payments ADD COLUMN collected_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS crypto_payments (
+    payment_id UUID PRIMARY KEY REFERENCES payments(id),
+    asset VARCHAR(10) NOT NULL,
+    deposit_address VARCHAR(255) NOT NULL,
+    provider_reference VARCHAR(255) NOT NULL UNIQUE,
+    exchange_rate NUMERIC(30, 10) NOT NULL,
+    expected_amount NUMERIC(30, 10) NOT NULL,
+    received_amount NUMERIC(30, 10) NOT NULL DEFAULT 0,
+    confirmations INTEGER NOT NULL DEFAULT 0,
+    required_confirmations INTEGER NOT NULL,
+    tx_hash VARCHAR(255),
+    deposit_status VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_crypto_payments_open ON crypto_payments(deposit_status)
+    WHERE deposit_status IN ('AWAITING_DEPOSIT', 'CONFIRMING', 'UNDERPAID');
@@ -0,0 +1,23 @@
+ALTER TABLE payment_methods ADD COLUMN bin VARCHAR(8);
+
+-- bin_prefix NULL is the method-wide default; a prefix overrides it for a bank's BIN range
+CREATE TABLE IF NOT EXISTS installment_fees (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    payment_method VARCHAR(50) NOT NULL,
+    bin_prefix VARCHAR(8),
+    bank_name VARCHAR(100),
+    installments SMALLINT NOT NULL CHECK (installments BETWEEN 2 AND 12),
+    fee_percent NUMERIC(5, 2) NOT NULL,
+    UNIQUE (payment_method, bin_prefix, installments)
+);
+
+INSERT INTO installment_fees (payment_method, installments, fee_percent) VALUES
+    ('CREDIT_CARD', 2, 2.50),
+    ('CREDIT_CARD', 3, 3.50),
+    ('CREDIT_CARD', 6, 6.50),
+    ('CREDIT_CARD', 9, 9.50),
+    ('CREDIT_CARD', 12, 12.50);
+
+ALTER TABLE payments ADD COLUMN installment_count SMALLINT NOT NULL DEFAULT 1;
+ALTER TABLE payments ADD COLUMN installment_fee_percent NUMERIC(5, 2) NOT NULL DEFAULT 0;
+ALTER TABLE payments ADD COLUMN installment_surcharge NUMERIC(10, 2) NOT NULL DEFAULT 0;
@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS subscriptions (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    plan_code VARCHAR(50) NOT NULL,
+    amount DECIMAL(10, 2) NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    billing_interval VARCHAR(10) NOT NULL,
+    interval_count INTEGER NOT NULL DEFAULT 1,
+    payment_method_id UUID NOT NULL REFERENCES payment_methods(id),
+    status VARCHAR(20) NOT NULL,
+    next_billing_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    cancelled_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_subscriptions_user_id ON subscriptions(user_id);
+CREATE INDEX idx_subscriptions_due ON subscriptions(next_billing_at) WHERE status = 'ACTIVE';
+
+ALTER TABLE payments ADD COLUMN subscription_id UUID REFERENCES subscriptions(id);
+CREATE INDEX idx_payments_subscription_id ON payments(subscription_id);
@@ -0,0 +1,4 @@
+ALTER TABLE subscriptions ADD COLUMN retry_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE subscriptions ADD COLUMN next_retry_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_subscriptions_retry_due ON subscriptions(next_retry_at) WHERE status = 'PAST_DUE';
@@ -0,0 +1,20 @@
+ALTER TABLE subscriptions ADD COLUMN credit_balance DECIMAL(10, 2) NOT NULL DEFAULT 0;
+
+-- Audit trail of mid-cycle plan changes and how the prorated amount was derived
+CREATE TABLE IF NOT EXISTS subscription_adjustments (
+    id UUID PRIMARY KEY,
+    subscription_id UUID NOT NULL REFERENCES subscriptions(id),
+    old_plan_code VARCHAR(50) NOT NULL,
+    new_plan_code VARCHAR(50) NOT NULL,
+    old_amount DECIMAL(10, 2) NOT NULL,
+    new_amount DECIMAL(10, 2) NOT NULL,
+    period_start TIMESTAMP WITH TIME ZONE NOT NULL,
+    period_end TIMESTAMP WITH TIME ZONE NOT NULL,
+    remaining_ratio NUMERIC(12, 10) NOT NULL,
+    -- Positive: charged now, negative: credited to the next invoices
+    prorated_amount DECIMAL(10, 2) NOT NULL,
+    payment_id UUID REFERENCES payments(id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_subscription_adjustments_subscription_id ON subscription_adjustments(subscription_id);
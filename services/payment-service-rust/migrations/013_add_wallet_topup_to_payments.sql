@@ -0,0 +1,2 @@
+-- Top-up payments credit the user's wallet once they complete instead of paying for an order
+ALTER TABLE payments ADD COLUMN wallet_topup BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS refunds (
+    id UUID PRIMARY KEY,
+    payment_id UUID NOT NULL REFERENCES payments(id),
+    amount DECIMAL(10, 2) NOT NULL CHECK (amount > 0),
+    currency VARCHAR(3) NOT NULL,
+    -- ORIGINAL_METHOD (back through the gateway) or WALLET
+    destination VARCHAR(20) NOT NULL,
+    reason TEXT,
+    transaction_id VARCHAR(255),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_refunds_payment_id ON refunds(payment_id);
@@ -0,0 +1,27 @@
+-- Gift cards / vouchers; balance shrinks with each (partial) redemption
+CREATE TABLE IF NOT EXISTS vouchers (
+    id UUID PRIMARY KEY,
+    code VARCHAR(32) NOT NULL UNIQUE,
+    initial_amount DECIMAL(10, 2) NOT NULL,
+    balance DECIMAL(10, 2) NOT NULL CHECK (balance >= 0),
+    currency VARCHAR(3) NOT NULL,
+    status VARCHAR(20) NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    voided_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- Negative amounts put balance back (e.g. the payment failed 3-D Secure)
+CREATE TABLE IF NOT EXISTS voucher_redemptions (
+    id UUID PRIMARY KEY,
+    voucher_id UUID NOT NULL REFERENCES vouchers(id),
+    payment_id UUID NOT NULL REFERENCES payments(id),
+    amount DECIMAL(10, 2) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_voucher_redemptions_voucher_id ON voucher_redemptions(voucher_id);
+
+ALTER TABLE payments ADD COLUMN voucher_id UUID REFERENCES vouchers(id);
+ALTER TABLE payments ADD COLUMN voucher_amount DECIMAL(10, 2) NOT NULL DEFAULT 0;
@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS promotions (
+    id UUID PRIMARY KEY,
+    code VARCHAR(32) NOT NULL UNIQUE,
+    -- PERCENT or FIXED
+    discount_type VARCHAR(10) NOT NULL,
+    discount_value DECIMAL(10, 2) NOT NULL,
+    -- Required for FIXED discounts, optional restriction for PERCENT
+    currency VARCHAR(3),
+    min_amount DECIMAL(10, 2) NOT NULL DEFAULT 0,
+    max_uses INTEGER,
+    used_count INTEGER NOT NULL DEFAULT 0,
+    starts_at TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- amount stays the charged (discounted) amount, gross_amount is the order total before the discount
+ALTER TABLE payments ADD COLUMN promotion_id UUID REFERENCES promotions(id);
+ALTER TABLE payments ADD COLUMN discount_amount DECIMAL(10, 2) NOT NULL DEFAULT 0;
+ALTER TABLE payments ADD COLUMN gross_amount DECIMAL(10, 2);
+UPDATE payments SET gross_amount = amount;
+ALTER TABLE payments ALTER COLUMN gross_amount SET NOT NULL;
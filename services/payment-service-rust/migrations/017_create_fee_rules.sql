@@ -0,0 +1,33 @@
+-- NULL payment_method / currency / gateway match anything; the most specific rule per fee_type wins
+CREATE TABLE IF NOT EXISTS fee_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- PLATFORM (our cut) or GATEWAY (what the processor charges us)
+    fee_type VARCHAR(20) NOT NULL,
+    payment_method VARCHAR(50),
+    currency VARCHAR(3),
+    gateway VARCHAR(50),
+    percent NUMERIC(5, 2) NOT NULL DEFAULT 0,
+    fixed_amount DECIMAL(10, 2) NOT NULL DEFAULT 0,
+    active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+INSERT INTO fee_rules (fee_type, payment_method, currency, gateway, percent, fixed_amount) VALUES
+    ('PLATFORM', NULL, NULL, NULL, 1.00, 0),
+    ('GATEWAY', NULL, NULL, 'MOCK', 2.49, 0),
+    ('GATEWAY', NULL, 'TRY', 'MOCK', 2.49, 0.25),
+    ('GATEWAY', 'DEBIT_CARD', NULL, 'MOCK', 1.49, 0),
+    ('GATEWAY', NULL, NULL, 'CRYPTO', 1.00, 0);
+
+CREATE TABLE IF NOT EXISTS payment_fees (
+    id UUID PRIMARY KEY,
+    payment_id UUID NOT NULL REFERENCES payments(id),
+    fee_type VARCHAR(20) NOT NULL,
+    fee_rule_id UUID REFERENCES fee_rules(id),
+    gateway VARCHAR(50),
+    percent NUMERIC(5, 2) NOT NULL,
+    fixed_amount DECIMAL(10, 2) NOT NULL,
+    amount DECIMAL(10, 2) NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    UNIQUE (payment_id, fee_type)
+);
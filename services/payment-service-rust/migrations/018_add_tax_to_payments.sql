@@ -0,0 +1,7 @@
+-- Amounts are tax inclusive (KDV dahil); the breakdown is derived when the payment is created
+ALTER TABLE payments ADD COLUMN tax_jurisdiction VARCHAR(3);
+ALTER TABLE payments ADD COLUMN tax_rate NUMERIC(5, 2) NOT NULL DEFAULT 0;
+ALTER TABLE payments ADD COLUMN taxable_base DECIMAL(10, 2);
+ALTER TABLE payments ADD COLUMN tax_amount DECIMAL(10, 2) NOT NULL DEFAULT 0;
+UPDATE payments SET taxable_base = amount;
+ALTER TABLE payments ALTER COLUMN taxable_base SET NOT NULL;
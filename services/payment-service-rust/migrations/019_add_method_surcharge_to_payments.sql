@@ -0,0 +1,3 @@
+-- Payment-method surcharge added on top of the amount (e.g. credit card +1.5%)
+ALTER TABLE payments ADD COLUMN method_surcharge_percent NUMERIC(5, 2) NOT NULL DEFAULT 0;
+ALTER TABLE payments ADD COLUMN method_surcharge DECIMAL(10, 2) NOT NULL DEFAULT 0;
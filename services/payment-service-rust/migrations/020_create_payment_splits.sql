@@ -0,0 +1,12 @@
+-- Marketplace payments: how the order total is shared between third-party sellers
+CREATE TABLE IF NOT EXISTS payment_splits (
+    id UUID PRIMARY KEY,
+    payment_id UUID NOT NULL REFERENCES payments(id),
+    merchant_id UUID NOT NULL,
+    amount DECIMAL(10, 2) NOT NULL CHECK (amount > 0),
+    currency VARCHAR(3) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    UNIQUE (payment_id, merchant_id)
+);
+
+CREATE INDEX idx_payment_splits_merchant_id ON payment_splits(merchant_id);
@@ -0,0 +1,14 @@
+-- Per-merchant commission; merchants without a row pay DEFAULT_COMMISSION_PERCENT
+CREATE TABLE IF NOT EXISTS merchant_terms (
+    merchant_id UUID PRIMARY KEY,
+    commission_percent NUMERIC(5, 2) NOT NULL CHECK (commission_percent BETWEEN 0 AND 100),
+    fixed_fee DECIMAL(10, 2) NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+ALTER TABLE payment_splits ADD COLUMN commission_percent NUMERIC(5, 2) NOT NULL DEFAULT 0;
+ALTER TABLE payment_splits ADD COLUMN commission_amount DECIMAL(10, 2) NOT NULL DEFAULT 0;
+ALTER TABLE payment_splits ADD COLUMN net_amount DECIMAL(10, 2);
+UPDATE payment_splits SET net_amount = amount;
+ALTER TABLE payment_splits ALTER COLUMN net_amount SET NOT NULL;
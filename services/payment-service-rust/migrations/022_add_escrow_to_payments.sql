@@ -0,0 +1,6 @@
+-- Escrowed payments are captured but held (status ESCROWED) until delivery is confirmed or the deadline passes
+ALTER TABLE payments ADD COLUMN escrow BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE payments ADD COLUMN escrow_release_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE payments ADD COLUMN escrow_released_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_payments_escrow_release_at ON payments(escrow_release_at) WHERE payment_status = 'ESCROWED';
@@ -0,0 +1,25 @@
+ALTER TABLE merchant_terms ADD COLUMN payout_account_reference VARCHAR(100);
+
+-- One payout batches the net amount of a merchant's settled splits in one currency
+CREATE TABLE IF NOT EXISTS payouts (
+    id UUID PRIMARY KEY,
+    merchant_id UUID NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    amount DECIMAL(12, 2) NOT NULL,
+    split_count INTEGER NOT NULL,
+    -- PENDING -> APPROVED -> PAID
+    status VARCHAR(20) NOT NULL,
+    -- Reference to the merchant's bank account kept by finance, not the IBAN itself
+    bank_account_reference VARCHAR(100),
+    bank_transfer_reference VARCHAR(100),
+    approved_at TIMESTAMP WITH TIME ZONE,
+    paid_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_payouts_merchant_id ON payouts(merchant_id);
+CREATE INDEX idx_payouts_status ON payouts(status);
+
+ALTER TABLE payment_splits ADD COLUMN payout_id UUID REFERENCES payouts(id);
+CREATE INDEX idx_payment_splits_unpaid ON payment_splits(merchant_id) WHERE payout_id IS NULL;
@@ -0,0 +1,37 @@
+-- Tenants of the service. The platform itself is the default merchant that pre-existing payments belong to
+CREATE TABLE IF NOT EXISTS merchants (
+    id UUID PRIMARY KEY,
+    name VARCHAR(200) NOT NULL,
+    contact_email VARCHAR(255),
+    -- ACTIVE or SUSPENDED
+    status VARCHAR(20) NOT NULL,
+    -- SHA-256 of the merchant API key, the key itself is only shown at onboarding
+    api_key_hash VARCHAR(64) UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+INSERT INTO merchants (id, name, status, created_at, updated_at)
+VALUES ('00000000-0000-0000-0000-000000000001', 'Platform', 'ACTIVE', NOW(), NOW())
+ON CONFLICT (id) DO NOTHING;
+
+-- Marketplace sellers seen so far become merchants so the foreign keys below hold
+INSERT INTO merchants (id, name, status, created_at, updated_at)
+SELECT merchant_id, 'Merchant ' || merchant_id, 'ACTIVE', NOW(), NOW()
+FROM (
+    SELECT merchant_id FROM payment_splits
+    UNION SELECT merchant_id FROM merchant_terms
+    UNION SELECT merchant_id FROM payouts
+) sellers
+ON CONFLICT (id) DO NOTHING;
+
+ALTER TABLE payments ADD COLUMN merchant_id UUID REFERENCES merchants(id);
+UPDATE payments SET merchant_id = '00000000-0000-0000-0000-000000000001';
+ALTER TABLE payments ALTER COLUMN merchant_id SET NOT NULL;
+
+DROP INDEX IF EXISTS idx_order_id;
+CREATE INDEX idx_payments_merchant_order_id ON payments(merchant_id, order_id);
+
+ALTER TABLE payment_splits ADD CONSTRAINT fk_payment_splits_merchant FOREIGN KEY (merchant_id) REFERENCES merchants(id);
+ALTER TABLE merchant_terms ADD CONSTRAINT fk_merchant_terms_merchant FOREIGN KEY (merchant_id) REFERENCES merchants(id);
+ALTER TABLE payouts ADD CONSTRAINT fk_payouts_merchant FOREIGN KEY (merchant_id) REFERENCES merchants(id);
@@ -0,0 +1,11 @@
+-- Merchants with their own gateway account; the rest are charged through the platform account from env
+CREATE TABLE IF NOT EXISTS merchant_gateway_credentials (
+    merchant_id UUID PRIMARY KEY REFERENCES merchants(id),
+    -- Shown to admins, not secret
+    account_id VARCHAR(100) NOT NULL,
+    -- GatewayCredentials sealed with the vault key
+    encrypted_credentials BYTEA NOT NULL,
+    nonce BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,3 @@
+-- Per-merchant overrides of TENANT_REQUESTS_PER_MINUTE / TENANT_PAYMENTS_PER_DAY, NULL means the default
+ALTER TABLE merchants ADD COLUMN requests_per_minute INTEGER CHECK (requests_per_minute > 0);
+ALTER TABLE merchants ADD COLUMN payments_per_day INTEGER CHECK (payments_per_day > 0);
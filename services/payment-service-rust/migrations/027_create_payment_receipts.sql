@@ -0,0 +1,9 @@
+-- Rendered receipt PDFs, regenerated when the payment changed since (e.g. refunded)
+CREATE TABLE IF NOT EXISTS payment_receipts (
+    payment_id UUID NOT NULL REFERENCES payments(id),
+    language VARCHAR(5) NOT NULL,
+    payment_updated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    pdf BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (payment_id, language)
+);
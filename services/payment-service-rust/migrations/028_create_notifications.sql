@@ -0,0 +1,18 @@
+-- Outbox of customer emails sent through the notification service; failed deliveries are retried
+CREATE TABLE IF NOT EXISTS notifications (
+    id UUID PRIMARY KEY,
+    template VARCHAR(50) NOT NULL,
+    -- One email per receipt / refund, however many times the trigger fires
+    dedup_key VARCHAR(100) NOT NULL UNIQUE,
+    user_id UUID NOT NULL,
+    payload JSONB NOT NULL,
+    -- PENDING -> SENT, or FAILED after too many attempts
+    status VARCHAR(20) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    sent_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_notifications_due ON notifications(next_attempt_at) WHERE status = 'PENDING';
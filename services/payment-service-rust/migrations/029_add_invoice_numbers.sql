@@ -0,0 +1,15 @@
+-- Gapless invoice numbers: the counter row is locked until the payment is stamped, so a rollback gives the number back
+CREATE TABLE IF NOT EXISTS invoice_counters (
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    year INTEGER NOT NULL,
+    last_number BIGINT NOT NULL,
+    PRIMARY KEY (merchant_id, year)
+);
+
+-- e-Fatura format: 3 character prefix, year, 9 digit sequence, e.g. BTR2024000000042
+ALTER TABLE payments ADD COLUMN invoice_number VARCHAR(16);
+ALTER TABLE payments ADD COLUMN invoiced_at TIMESTAMP WITH TIME ZONE;
+
+CREATE UNIQUE INDEX idx_payments_invoice_number ON payments(merchant_id, invoice_number);
+CREATE INDEX idx_payments_uninvoiced ON payments(updated_at)
+    WHERE payment_status = 'COMPLETED' AND invoice_number IS NULL AND NOT wallet_topup;
@@ -0,0 +1,15 @@
+-- Seller details printed on e-Fatura / e-Arsiv invoices
+ALTER TABLE merchants ADD COLUMN tax_id VARCHAR(11);
+ALTER TABLE merchants ADD COLUMN tax_office VARCHAR(100);
+ALTER TABLE merchants ADD COLUMN address VARCHAR(255);
+ALTER TABLE merchants ADD COLUMN city VARCHAR(100);
+
+-- UBL-TR invoices ready to hand to the GIB-integrated provider
+CREATE TABLE IF NOT EXISTS efatura_documents (
+    payment_id UUID PRIMARY KEY REFERENCES payments(id),
+    invoice_number VARCHAR(16) NOT NULL,
+    -- ETTN, the invoice's UUID at GIB
+    ettn UUID NOT NULL UNIQUE,
+    xml TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,21 @@
+-- Chargeable links sent to customers (e.g. over WhatsApp); paying one creates the actual payment
+CREATE TABLE IF NOT EXISTS payment_links (
+    id UUID PRIMARY KEY,
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    token VARCHAR(128) NOT NULL UNIQUE,
+    order_id UUID NOT NULL,
+    -- Set when the link is meant for a known customer
+    user_id UUID,
+    amount DECIMAL(10, 2) NOT NULL CHECK (amount > 0),
+    currency VARCHAR(3) NOT NULL,
+    description VARCHAR(255),
+    -- ACTIVE -> PROCESSING -> PAID, back to ACTIVE when the payment fails
+    status VARCHAR(20) NOT NULL,
+    payment_id UUID REFERENCES payments(id),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_by UUID NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_payment_links_merchant_order ON payment_links(merchant_id, order_id);
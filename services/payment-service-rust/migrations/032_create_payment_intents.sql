@@ -0,0 +1,21 @@
+-- Two-step payments for the mobile SDK: the intent fixes the amount, confirming it charges the collected method
+CREATE TABLE IF NOT EXISTS payment_intents (
+    id UUID PRIMARY KEY,
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    order_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    amount DECIMAL(10, 2) NOT NULL CHECK (amount > 0),
+    currency VARCHAR(3) NOT NULL,
+    promo_code VARCHAR(50),
+    country VARCHAR(2),
+    escrow BOOLEAN NOT NULL DEFAULT FALSE,
+    -- sha256 of the client secret, which is only returned when the intent is created
+    client_secret_hash VARCHAR(64) NOT NULL,
+    -- REQUIRES_CONFIRMATION -> PROCESSING -> SUCCEEDED, back to REQUIRES_CONFIRMATION when the payment fails
+    status VARCHAR(30) NOT NULL,
+    payment_id UUID REFERENCES payments(id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_payment_intents_merchant_order ON payment_intents(merchant_id, order_id);
@@ -0,0 +1,14 @@
+-- Payments accepted in async mode: the row is a PENDING placeholder until a worker processes the stored request
+ALTER TABLE payments ADD COLUMN IF NOT EXISTS queued BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS payment_jobs (
+    payment_id UUID PRIMARY KEY REFERENCES payments(id),
+    request JSONB NOT NULL,
+    -- QUEUED -> RUNNING -> DONE or FAILED
+    status VARCHAR(20) NOT NULL,
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_payment_jobs_queued ON payment_jobs(created_at) WHERE status = 'QUEUED';
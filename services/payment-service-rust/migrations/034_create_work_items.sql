@@ -0,0 +1,35 @@
+-- Shared work queue: async payments and notification delivery, processed by the worker pool
+CREATE TABLE IF NOT EXISTS work_items (
+    id UUID PRIMARY KEY,
+    kind VARCHAR(30) NOT NULL,
+    payload JSONB NOT NULL,
+    -- QUEUED -> RUNNING, deleted when done; DEAD once the kind's attempts are used up
+    status VARCHAR(20) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    run_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    -- RUNNING items whose worker didn't finish in time are picked up again
+    locked_until TIMESTAMP WITH TIME ZONE,
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_work_items_due ON work_items(run_at) WHERE status = 'QUEUED';
+CREATE INDEX idx_work_items_locked ON work_items(locked_until) WHERE status = 'RUNNING';
+
+-- Carry over what the dedicated payment worker and the notification retry job hadn't processed yet
+INSERT INTO work_items (id, kind, payload, status, run_at, created_at, updated_at)
+SELECT gen_random_uuid(), 'PAYMENT',
+       jsonb_build_object('payment_id', j.payment_id, 'merchant_id', p.merchant_id, 'request', j.request),
+       'QUEUED', NOW(), j.created_at, NOW()
+FROM payment_jobs j JOIN payments p ON p.id = j.payment_id
+WHERE j.status IN ('QUEUED', 'RUNNING');
+
+INSERT INTO work_items (id, kind, payload, status, attempts, run_at, created_at, updated_at)
+SELECT gen_random_uuid(), 'NOTIFICATION', jsonb_build_object('notification_id', id),
+       'QUEUED', attempts, next_attempt_at, created_at, NOW()
+FROM notifications
+WHERE status = 'PENDING';
+
+DROP TABLE payment_jobs;
+ALTER TABLE notifications DROP COLUMN next_attempt_at;
@@ -0,0 +1,8 @@
+-- Daily request/payment counts per merchant API key, flushed from the Redis counters
+CREATE TABLE IF NOT EXISTS api_key_usage (
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    day DATE NOT NULL,
+    requests BIGINT NOT NULL DEFAULT 0,
+    payments BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (merchant_id, day)
+);
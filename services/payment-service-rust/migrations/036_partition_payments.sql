@@ -0,0 +1,60 @@
+-- Monthly range partitions of payments on created_at, so period queries only touch their months.
+-- Postgres wants the partition key in every unique constraint: the primary key becomes (id, created_at),
+-- transfer_reference and (merchant_id, invoice_number) lose their unique indexes (both are generated by the
+-- service, invoice numbers through invoice_counters), and foreign keys to payments(id) can't be kept.
+
+DO $$
+DECLARE
+    fk RECORD;
+BEGIN
+    FOR fk IN SELECT conrelid::regclass AS tbl, conname FROM pg_constraint
+              WHERE contype = 'f' AND confrelid = 'payments'::regclass
+    LOOP
+        EXECUTE format('ALTER TABLE %s DROP CONSTRAINT %I', fk.tbl, fk.conname);
+    END LOOP;
+END $$;
+
+ALTER TABLE payments RENAME TO payments_unpartitioned;
+
+CREATE TABLE payments (LIKE payments_unpartitioned INCLUDING DEFAULTS INCLUDING CONSTRAINTS)
+    PARTITION BY RANGE (created_at);
+ALTER TABLE payments ADD PRIMARY KEY (id, created_at);
+
+-- Idempotent, called by the partition job for the coming months
+CREATE OR REPLACE FUNCTION create_payments_partition(month DATE) RETURNS VOID AS $$
+DECLARE
+    start_at DATE := date_trunc('month', month)::DATE;
+BEGIN
+    EXECUTE format(
+        'CREATE TABLE IF NOT EXISTS %I PARTITION OF payments FOR VALUES FROM (%L) TO (%L)',
+        'payments_' || to_char(start_at, 'YYYY_MM'),
+        start_at,
+        (start_at + INTERVAL '1 month')::DATE
+    );
+END;
+$$ LANGUAGE plpgsql;
+
+-- Every month from the oldest payment through next month
+SELECT create_payments_partition(month::DATE)
+FROM generate_series(
+    date_trunc('month', COALESCE((SELECT MIN(created_at) FROM payments_unpartitioned), NOW())),
+    date_trunc('month', NOW()) + INTERVAL '1 month',
+    INTERVAL '1 month'
+) AS month;
+
+-- Safety net for rows outside the created months; the job keeps it empty by staying ahead
+CREATE TABLE payments_default PARTITION OF payments DEFAULT;
+
+INSERT INTO payments SELECT * FROM payments_unpartitioned;
+DROP TABLE payments_unpartitioned;
+
+CREATE INDEX idx_payments_order_id ON payments(order_id);
+CREATE INDEX idx_payments_user_id ON payments(user_id);
+CREATE INDEX idx_payments_transfer_reference ON payments(transfer_reference);
+CREATE INDEX idx_payments_pending_expiry ON payments(expires_at) WHERE payment_status = 'PENDING';
+CREATE INDEX idx_payments_subscription_id ON payments(subscription_id);
+CREATE INDEX idx_payments_escrow_release_at ON payments(escrow_release_at) WHERE payment_status = 'ESCROWED';
+CREATE INDEX idx_payments_merchant_order_id ON payments(merchant_id, order_id);
+CREATE INDEX idx_payments_invoice_number ON payments(merchant_id, invoice_number);
+CREATE INDEX idx_payments_uninvoiced ON payments(updated_at)
+    WHERE payment_status = 'COMPLETED' AND invoice_number IS NULL AND NOT wallet_topup;
@@ -0,0 +1,15 @@
+-- Month partitions of payments moved to object storage as gzipped NDJSON
+CREATE TABLE IF NOT EXISTS payment_archives (
+    month DATE PRIMARY KEY,
+    object_key TEXT NOT NULL,
+    row_count BIGINT NOT NULL,
+    -- Of the uncompressed NDJSON, rows ordered by id
+    sha256 VARCHAR(64) NOT NULL,
+    archived_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- Where an archived payment went, so it can be read back without scanning every archive
+CREATE TABLE IF NOT EXISTS archived_payments (
+    payment_id UUID PRIMARY KEY,
+    month DATE NOT NULL REFERENCES payment_archives(month)
+);
@@ -0,0 +1,3 @@
+-- What POST /api/payments does for an order that already has a non-failed payment: REPLAY answers with that
+-- payment, REJECT with a DUPLICATE_ORDER conflict. NULL means the DUPLICATE_ORDERS default
+ALTER TABLE merchants ADD COLUMN duplicate_orders VARCHAR(10) CHECK (duplicate_orders IN ('REPLAY', 'REJECT'));
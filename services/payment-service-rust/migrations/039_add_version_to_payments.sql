@@ -0,0 +1,13 @@
+-- Optimistic locking: every update of a payment bumps its version, mutating endpoints compare it with If-Match.
+-- Bumped by trigger so no UPDATE in the service (or run by hand) can forget it
+ALTER TABLE payments ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE OR REPLACE FUNCTION bump_payment_version() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER payments_bump_version BEFORE UPDATE ON payments
+    FOR EACH ROW EXECUTE FUNCTION bump_payment_version();
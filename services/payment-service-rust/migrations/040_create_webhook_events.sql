@@ -0,0 +1,10 @@
+-- Inbound webhook deliveries that were applied, so redeliveries by the provider are skipped
+CREATE TABLE IF NOT EXISTS webhook_events (
+    id UUID PRIMARY KEY,
+    -- Sender, e.g. CRYPTO for the crypto provider
+    source VARCHAR(20) NOT NULL,
+    -- The provider's event id, or a hash of the payload when it sends none
+    event_id VARCHAR(128) NOT NULL,
+    received_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    UNIQUE (source, event_id)
+);
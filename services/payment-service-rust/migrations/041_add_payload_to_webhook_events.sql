@@ -0,0 +1,9 @@
+-- Keep what the provider sent and how processing went, so a failed event can be replayed after a fix
+ALTER TABLE webhook_events ADD COLUMN IF NOT EXISTS payload TEXT;
+-- RECEIVED -> PROCESSED, or FAILED with the error; rows from before this migration were all processed
+ALTER TABLE webhook_events ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'PROCESSED';
+ALTER TABLE webhook_events ADD COLUMN IF NOT EXISTS last_error TEXT;
+ALTER TABLE webhook_events ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE webhook_events ADD COLUMN IF NOT EXISTS processed_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_webhook_events_status ON webhook_events(status, received_at DESC);
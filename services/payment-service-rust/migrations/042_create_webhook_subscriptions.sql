@@ -0,0 +1,37 @@
+-- Outbound webhooks: endpoints that are sent our events, and every delivery made to them
+CREATE TABLE IF NOT EXISTS webhook_subscriptions (
+    id UUID PRIMARY KEY,
+    -- Only this merchant's payment events; NULL receives every event
+    merchant_id UUID REFERENCES merchants(id),
+    url TEXT NOT NULL,
+    description VARCHAR(255),
+    -- Event types to send, e.g. payment.refunded; empty sends all of them
+    event_types TEXT[] NOT NULL DEFAULT '{}',
+    -- Signing secret sealed with the vault key
+    encrypted_secret BYTEA NOT NULL,
+    nonce BYTEA NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    secret_rotated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id UUID PRIMARY KEY,
+    subscription_id UUID NOT NULL REFERENCES webhook_subscriptions(id) ON DELETE CASCADE,
+    event_type VARCHAR(50) NOT NULL,
+    payload JSONB NOT NULL,
+    -- PENDING -> DELIVERED, or FAILED once the attempts are used up
+    status VARCHAR(20) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- HTTP status of the last attempt, NULL when the endpoint couldn't be reached
+    response_status INTEGER,
+    response_time_ms INTEGER,
+    last_error TEXT,
+    last_attempt_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    delivered_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_webhook_subscriptions_active ON webhook_subscriptions(active) WHERE active;
+CREATE INDEX idx_webhook_deliveries_subscription ON webhook_deliveries(subscription_id, created_at DESC);
@@ -0,0 +1,4 @@
+-- Scopes the merchant's API key is limited to, e.g. {payments:read} for an analytics integration.
+-- NULL means unrestricted
+ALTER TABLE merchants ADD COLUMN scopes TEXT[]
+    CHECK (scopes <@ ARRAY['payments:read', 'payments:write', 'payments:refund', 'payments:admin']);
@@ -0,0 +1,16 @@
+-- What refunds take back from a seller's split: one negative row per split and refund, pro rata to the refunded
+-- share of the payment. A payout claims them together with their split, or with the next payout when the split
+-- was already paid out
+CREATE TABLE IF NOT EXISTS split_adjustments (
+    id UUID PRIMARY KEY,
+    split_id UUID NOT NULL REFERENCES payment_splits(id),
+    refund_id UUID NOT NULL REFERENCES refunds(id),
+    merchant_id UUID NOT NULL REFERENCES merchants(id),
+    currency VARCHAR(3) NOT NULL,
+    amount DECIMAL(10, 2) NOT NULL CHECK (amount < 0),
+    payout_id UUID REFERENCES payouts(id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    UNIQUE (split_id, refund_id)
+);
+
+CREATE INDEX idx_split_adjustments_unpaid ON split_adjustments(merchant_id) WHERE payout_id IS NULL;
@@ -0,0 +1,4 @@
+-- Gateway refunds are stored PENDING before the gateway is called and turn COMPLETED or FAILED after.
+-- Refunds made before all went through
+ALTER TABLE refunds ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'COMPLETED';
+ALTER TABLE refunds ALTER COLUMN status DROP DEFAULT;
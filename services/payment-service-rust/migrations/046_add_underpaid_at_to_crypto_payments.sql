@@ -0,0 +1,6 @@
+-- When a deposit first fell short; the customer can top it up until CRYPTO_UNDERPAID_EXPIRY_HOURS later, then it
+-- expires (EXPIRED) and what arrived is credited to their wallet
+ALTER TABLE crypto_payments ADD COLUMN underpaid_at TIMESTAMP WITH TIME ZONE;
+UPDATE crypto_payments SET underpaid_at = updated_at WHERE deposit_status = 'UNDERPAID';
+
+CREATE INDEX idx_crypto_payments_underpaid ON crypto_payments(underpaid_at) WHERE deposit_status = 'UNDERPAID';
@@ -0,0 +1,111 @@
+//! Failure injection for dependency calls, to check that fallbacks and retries hold up.
+//! Only built with `--features chaos`; otherwise `inject` compiles to nothing.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    Database,
+    Redis,
+    Gateway,
+}
+
+#[cfg(not(feature = "chaos"))]
+#[inline(always)]
+pub fn inject(_target: Target) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(feature = "chaos")]
+pub use experiments::*;
+
+#[cfg(feature = "chaos")]
+mod experiments {
+    use super::Target;
+    use chrono::{DateTime, Utc};
+    use rand::Rng;
+    use serde::Serialize;
+    use std::{
+        collections::HashMap,
+        sync::{Mutex, OnceLock},
+    };
+
+    /// Longest experiment an admin can start, so a forgotten one ends by itself.
+    pub const MAX_DURATION_SECS: i64 = 60 * 60;
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Experiment {
+        pub target: &'static str,
+        pub failure_percent: u8,
+        pub until: DateTime<Utc>,
+    }
+
+    // Global rather than in AppState: the hooks sit in code that only gets a pool or config
+    fn experiments() -> &'static Mutex<HashMap<Target, Experiment>> {
+        static EXPERIMENTS: OnceLock<Mutex<HashMap<Target, Experiment>>> = OnceLock::new();
+        EXPERIMENTS.get_or_init(|| Mutex::new(HashMap::new()))
+    }
+
+    impl Target {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Target::Database => "DATABASE",
+                Target::Redis => "REDIS",
+                Target::Gateway => "GATEWAY",
+            }
+        }
+
+        pub fn parse(value: &str) -> Option<Self> {
+            match value.to_uppercase().as_str() {
+                "DATABASE" => Some(Target::Database),
+                "REDIS" => Some(Target::Redis),
+                "GATEWAY" => Some(Target::Gateway),
+                _ => None,
+            }
+        }
+    }
+
+    /// Fails the call with the experiment's probability while one is running for the target.
+    pub fn inject(target: Target) -> anyhow::Result<()> {
+        let mut experiments = experiments().lock().unwrap_or_else(|e| e.into_inner());
+        let Some(experiment) = experiments.get(&target) else {
+            return Ok(());
+        };
+        if experiment.until <= Utc::now() {
+            experiments.remove(&target);
+            tracing::info!(target = target.as_str(), "chaos experiment ended");
+            return Ok(());
+        }
+
+        if rand::thread_rng().gen_range(0..100) < experiment.failure_percent {
+            anyhow::bail!("chaos: injected {} failure", target.as_str());
+        }
+        Ok(())
+    }
+
+    pub fn start(target: Target, failure_percent: u8, duration_secs: i64) -> Experiment {
+        let experiment = Experiment {
+            target: target.as_str(),
+            failure_percent: failure_percent.min(100),
+            until: Utc::now() + chrono::Duration::seconds(duration_secs.clamp(1, MAX_DURATION_SECS)),
+        };
+        tracing::warn!(
+            target = target.as_str(),
+            failure_percent = experiment.failure_percent,
+            until = %experiment.until,
+            "chaos experiment started"
+        );
+        experiments().lock().unwrap_or_else(|e| e.into_inner()).insert(target, experiment.clone());
+        experiment
+    }
+
+    pub fn active() -> Vec<Experiment> {
+        let now = Utc::now();
+        let mut experiments = experiments().lock().unwrap_or_else(|e| e.into_inner());
+        experiments.retain(|_, experiment| experiment.until > now);
+        experiments.values().cloned().collect()
+    }
+
+    pub fn stop_all() {
+        experiments().lock().unwrap_or_else(|e| e.into_inner()).clear();
+        tracing::info!("chaos experiments stopped");
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    config::{Config, Listen},
+    database,
+    services::{mtls, seed},
+};
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+const HEALTHCHECK_PATH: &str = "/api/health/ready";
+const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Payment service. Runs the API server when no command is given.
+#[derive(Debug, Parser)]
+#[command(name = "payment-service", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the API server and background jobs
+    Serve,
+    /// Apply pending database migrations and exit
+    Migrate,
+    /// Exit 0 when the local server reports ready, 1 otherwise; for Docker HEALTHCHECK without curl
+    Healthcheck,
+    /// Insert demo payments; refused when ENVIRONMENT=production
+    Seed {
+        #[arg(long, default_value_t = seed::DEFAULT_COUNT)]
+        count: usize,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Load the configuration from the environment and report problems; fails on any in production
+    Check,
+}
+
+pub async fn migrate(config: &Config) -> anyhow::Result<()> {
+    let pool = database::connect(config).await?;
+    database::migrate(&pool).await?;
+    println!("Migrations applied");
+
+    Ok(())
+}
+
+/// Asks the server on LISTEN for its readiness endpoint, over the socket file for `unix:` listeners.
+pub async fn healthcheck(config: &Config) -> anyhow::Result<()> {
+    let status = tokio::time::timeout(HEALTHCHECK_TIMEOUT, health_status(config))
+        .await
+        .context("health check timed out")??;
+    if status != 200 {
+        bail!("health check answered {}", status);
+    }
+
+    Ok(())
+}
+
+async fn health_status(config: &Config) -> anyhow::Result<u16> {
+    match &config.listen {
+        Listen::Tcp(addr) => {
+            let (host, port) = addr.rsplit_once(':').context("LISTEN has no port")?;
+            // The server binds every interface, the check goes through loopback
+            let host = match host {
+                "" | "0.0.0.0" => "127.0.0.1",
+                "[::]" => "[::1]",
+                host => host,
+            };
+            let scheme = if config.tls_cert_path.is_some() { "https" } else { "http" };
+            // The certificate names the public host, not loopback. Behind TLS_CLIENT_CA_PATH the check presents
+            // the service's own client certificate
+            let mut client = reqwest::Client::builder().danger_accept_invalid_certs(true);
+            if let Some(identity) = mtls::identity(config)? {
+                client = client.identity(identity);
+            }
+            let client = client.build()?;
+            let response = client.get(format!("{}://{}:{}{}", scheme, host, port, HEALTHCHECK_PATH)).send().await?;
+
+            Ok(response.status().as_u16())
+        }
+        Listen::Unix(path) => {
+            if config.tls_cert_path.is_some() {
+                bail!("health check over a TLS unix socket is not supported");
+            }
+            let mut stream = UnixStream::connect(path)
+                .await
+                .with_context(|| format!("cannot connect to {}", path.display()))?;
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", HEALTHCHECK_PATH);
+            stream.write_all(request.as_bytes()).await?;
+
+            // Only the status line matters: "HTTP/1.1 200 OK"
+            let mut head = [0u8; 12];
+            stream.read_exact(&mut head).await?;
+            std::str::from_utf8(&head[9..12])?
+                .parse()
+                .context("malformed status line")
+        }
+    }
+}
+
+pub async fn seed(config: &Config, count: usize) -> anyhow::Result<()> {
+    let pool = database::connect(config).await?;
+    let summary = seed::seed(&pool, config, count).await?;
+    println!(
+        "Seeded {} payments for {} users and {} merchants between {} and {}",
+        summary.payments,
+        summary.users,
+        summary.merchants,
+        summary.from.format("%Y-%m-%d"),
+        summary.to.format("%Y-%m-%d")
+    );
+
+    Ok(())
+}
+
+/// The configuration already parsed when this runs, so only what parsing can't catch is reported.
+pub fn check_config(config: &Config) -> anyhow::Result<()> {
+    let problems = config.problems();
+    for problem in &problems {
+        println!("warning: {}", problem);
+    }
+    if config.is_production() && !problems.is_empty() {
+        bail!("{} configuration problem(s) in production", problems.len());
+    }
+    println!("Configuration OK ({}, listening on {:?})", config.environment, config.listen);
+
+    Ok(())
+}
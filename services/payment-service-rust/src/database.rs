@@ -1,13 +1,115 @@
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use crate::{
+    chaos::{self, Target},
+    config::Config,
+};
+use log::LevelFilter;
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions, PgPool, Postgres, Transaction,
+};
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
-pub async fn create_pool(database_url: &str) -> anyhow::Result<PgPool> {
-    let pool = PgPoolOptions::new()
-        .max_connections(10)
-        .connect(database_url)
+/// Sets the session `statement_timeout` at connect time, so every query on the pool is bounded.
+/// Statements slower than SLOW_QUERY_THRESHOLD_MS are logged as warnings by sqlx (SQL text with its
+/// placeholders, never the bound values) and counted by `telemetry::SlowQueryCounter`.
+fn connect_options(config: &Config, url: &str) -> anyhow::Result<PgConnectOptions> {
+    let options = PgConnectOptions::from_str(url)?
+        .log_slow_statements(LevelFilter::Warn, Duration::from_millis(config.slow_query_threshold_ms));
+
+    Ok(options.options([("statement_timeout", config.db_statement_timeout_ms.to_string())]))
+}
+
+/// Transaction whose queries may run up to `timeout_ms` instead of the pool's statement timeout,
+/// for reports known to be slow. 0 lifts the limit.
+pub async fn begin_with_statement_timeout(
+    pool: &PgPool,
+    timeout_ms: u64,
+) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+        .bind(timeout_ms.to_string())
+        .execute(&mut *tx)
         .await?;
 
-    // Run migrations
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    Ok(tx)
+}
+
+/// Pool settings from the DB_POOL_* variables, shared by the primary and the replica pool.
+fn pool_options(config: &Config) -> PgPoolOptions {
+    // 0 disables the idle timeout / max lifetime
+    let optional_secs = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+
+    PgPoolOptions::new()
+        .max_connections(config.db_pool_max_connections)
+        .min_connections(config.db_pool_min_connections)
+        .acquire_timeout(Duration::from_secs(config.db_pool_acquire_timeout_secs))
+        .idle_timeout(optional_secs(config.db_pool_idle_timeout_secs))
+        .max_lifetime(optional_secs(config.db_pool_max_lifetime_secs))
+        .test_before_acquire(config.db_pool_test_before_acquire)
+        .before_acquire(|_conn, _meta| {
+            Box::pin(async move {
+                chaos::inject(Target::Database).map_err(|e| sqlx::Error::Io(std::io::Error::other(e.to_string())))?;
+                Ok(true)
+            })
+        })
+}
+
+/// Pool on the primary with pending migrations applied.
+pub async fn create_pool(config: &Config) -> anyhow::Result<PgPool> {
+    let pool = connect(config).await?;
+    migrate(&pool).await?;
 
     Ok(pool)
-}
\ No newline at end of file
+}
+
+pub async fn connect(config: &Config) -> anyhow::Result<PgPool> {
+    Ok(pool_options(config)
+        .connect_with(connect_options(config, &config.database_url)?)
+        .await?)
+}
+
+/// Runs migrations without the statement timeout meant for request queries.
+pub async fn migrate(pool: &PgPool) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("SET statement_timeout = 0").execute(&mut *conn).await?;
+    sqlx::migrate!("./migrations").run(&mut *conn).await?;
+    sqlx::query("RESET statement_timeout").execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Replica for read-only queries. Reads go to the primary while the replica fails its health check.
+pub struct ReadReplica {
+    pub pool: PgPool,
+    healthy: AtomicBool,
+}
+
+impl ReadReplica {
+    /// Connects lazily, so a replica that is down at startup doesn't keep the service from starting.
+    pub fn connect(config: &Config, database_read_url: &str) -> anyhow::Result<Self> {
+        let pool = pool_options(config).connect_lazy_with(connect_options(config, database_read_url)?);
+
+        Ok(Self {
+            pool,
+            healthy: AtomicBool::new(false),
+        })
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            if healthy {
+                tracing::info!("Read replica healthy, serving reads from it");
+            } else {
+                tracing::warn!("Read replica unhealthy, serving reads from the primary");
+            }
+        }
+    }
+}
@@ -1,34 +1,840 @@
+use crate::{
+    error::{ErrorCode, FieldError},
+    i18n,
+    models::{
+        ApiKeyUsageDay, CryptoPayment, Merchant, Payment, PaymentIntent, PaymentMethod, PaymentSplit, Refund, Subscription,
+        PaymentStatus, SubscriptionAdjustment, TransferInstructions, Wallet, WebhookSubscription, METHOD_BANK_TRANSFER,
+        METHOD_CASH_ON_DELIVERY,
+    },
+};
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal; // Bunu ekledik
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreatePaymentRequest {
     pub order_id: Uuid,
     pub user_id: Uuid,
     pub amount: Decimal, // f64 -> Decimal yapıldı
     pub currency: String,
     pub payment_method: String,
+    pub payment_method_token: Option<String>,
+    /// Where the 3-D Secure page sends the customer back to
+    pub return_url: Option<String>,
+    /// BTC, ETH or USDT for crypto payments
+    pub crypto_asset: Option<String>,
+    /// Number of installments (taksit), 1 when omitted
+    pub installments: Option<u8>,
+    /// Part of the amount paid from the wallet balance, the card covers the rest
+    pub wallet_amount: Option<Decimal>,
+    /// Gift card / voucher code, applied before the wallet and the card
+    pub voucher_code: Option<String>,
+    /// Discount code; `amount` is the gross order total it applies to
+    pub promo_code: Option<String>,
+    /// Buyer's country (ISO 3166 alpha-2) for VAT, the currency decides when omitted
+    pub country: Option<String>,
+    /// Marketplace orders: share of each seller, must add up to `amount`
+    pub splits: Option<Vec<SplitRequest>>,
+    /// Hold the captured funds until delivery is confirmed
+    #[serde(default)]
+    pub escrow: bool,
+    /// Set internally for charges not initiated by the customer (no 3-D Secure challenge)
+    #[serde(skip)]
+    pub merchant_initiated: bool,
+    #[serde(skip)]
+    pub subscription_id: Option<Uuid>,
+    #[serde(skip)]
+    pub wallet_topup: bool,
+    /// Filled in once the promo code is applied, `amount` is then the discounted amount
+    #[serde(skip)]
+    pub promotion_id: Option<Uuid>,
+    #[serde(skip)]
+    pub discount_amount: Decimal,
+    #[serde(skip)]
+    pub tax_jurisdiction: Option<String>,
+    #[serde(skip)]
+    pub tax_rate: Decimal,
+    /// Tenant the payment is taken for, resolved from the merchant key
+    #[serde(skip)]
+    pub merchant_id: Uuid,
+    /// Authenticated customer, the only one whose wallet and saved cards the payment may use; `user_id` is just
+    /// what the client sent
+    #[serde(skip)]
+    pub payer_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitRequest {
+    pub merchant_id: Uuid,
+    pub amount: Decimal,
 }
 
 #[derive(Debug, Serialize)]
 pub struct PaymentResponse {
     pub id: Uuid,
+    pub merchant_id: Uuid,
     pub order_id: Uuid,
     pub user_id: Uuid,
     pub amount: Decimal, // f64 -> Decimal yapıldı
     pub currency: String,
     pub payment_method: String,
     pub payment_status: String,
+    /// payment_status in the caller's language
+    pub status_description: String,
     pub transaction_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer_instructions: Option<TransferInstructions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crypto_deposit: Option<CryptoDepositResponse>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub splits: Vec<PaymentSplit>,
+    pub installments: InstallmentInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Decimal::is_zero")]
+    pub wallet_amount: Decimal,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub wallet_topup: bool,
+    #[serde(skip_serializing_if = "Decimal::is_zero")]
+    pub voucher_amount: Decimal,
+    /// Order total before the promo discount
+    pub gross_amount: Decimal,
+    pub discount_amount: Decimal,
+    pub method_surcharge_percent: Decimal,
+    pub method_surcharge: Decimal,
+    /// What the customer pays in total: amount plus installment and method surcharges
+    pub total_amount: Decimal,
+    pub tax: TaxInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escrow_release_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invoice_number: Option<String>,
+    /// Where to poll an async payment until it leaves PENDING
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_url: Option<String>,
+    /// Set when a repeated request for the order answered with its existing payment
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub idempotent_replay: bool,
+    pub links: PaymentLinks,
+    /// Send back as `If-Match` to change the payment
+    pub version: i32,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// What can be done with the payment in its current state; a missing link means the action isn't possible now.
+#[derive(Debug, Serialize)]
+pub struct PaymentLinks {
+    #[serde(rename = "self")]
+    pub self_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qr: Option<String>,
+    /// Admin actions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refund: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_transfer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_escrow: Option<String>,
+}
+
+impl PaymentLinks {
+    /// Mirrors the status checks of the linked endpoints.
+    pub fn for_payment(payment: &Payment) -> Self {
+        let base = format!("/api/payments/{}", payment.id);
+        let admin = format!("/api/admin/payments/{}", payment.id);
+        let status = payment.payment_status.as_str();
+        let is = |s: PaymentStatus| status == s.as_str();
+        let link = |available: bool, url: String| available.then_some(url);
+
+        let paid = is(PaymentStatus::Completed) || is(PaymentStatus::Escrowed);
+        let pending_transfer = payment.payment_method == METHOD_BANK_TRANSFER && is(PaymentStatus::Pending);
+        let awaiting_cash = payment.payment_method == METHOD_CASH_ON_DELIVERY && is(PaymentStatus::AwaitingCollection);
+
+        Self {
+            receipt: link(paid || is(PaymentStatus::Refunded), format!("{}/receipt.pdf", base)),
+            qr: link(pending_transfer || awaiting_cash, format!("{}/qr", base)),
+            refund: link(paid, format!("{}/refunds", admin)),
+            confirm_transfer: link(pending_transfer, format!("{}/confirm-transfer", admin)),
+            release_escrow: link(is(PaymentStatus::Escrowed), format!("{}/release-escrow", admin)),
+            self_: base,
+        }
+    }
+}
+
+impl From<Payment> for PaymentResponse {
+    fn from(payment: Payment) -> Self {
+        let installments = InstallmentInfo::from_payment(&payment);
+        let links = PaymentLinks::for_payment(&payment);
+        let tax = TaxInfo {
+            jurisdiction: payment.tax_jurisdiction.clone(),
+            rate: payment.tax_rate,
+            taxable_base: payment.taxable_base,
+            tax_amount: payment.tax_amount,
+        };
+
+        Self {
+            id: payment.id,
+            merchant_id: payment.merchant_id,
+            order_id: payment.order_id,
+            user_id: payment.user_id,
+            amount: payment.amount,
+            currency: payment.currency,
+            payment_method: payment.payment_method,
+            status_description: i18n::status_description(&payment.payment_status),
+            payment_status: payment.payment_status,
+            transaction_id: payment.transaction_id,
+            redirect_url: payment.three_ds_redirect_url,
+            transfer_instructions: payment.transfer_instructions.map(|i| i.0),
+            crypto_deposit: None,
+            splits: Vec::new(),
+            installments,
+            subscription_id: payment.subscription_id,
+            wallet_amount: payment.wallet_amount,
+            wallet_topup: payment.wallet_topup,
+            voucher_amount: payment.voucher_amount,
+            gross_amount: payment.gross_amount,
+            discount_amount: payment.discount_amount,
+            method_surcharge_percent: payment.method_surcharge_percent,
+            method_surcharge: payment.method_surcharge,
+            total_amount: payment.amount + payment.installment_surcharge + payment.method_surcharge,
+            tax,
+            escrow_release_at: payment.escrow_release_at.map(|t| t.to_rfc3339()),
+            invoice_number: payment.invoice_number,
+            status_url: None,
+            idempotent_replay: false,
+            links,
+            version: payment.version,
+            created_at: payment.created_at.to_rfc3339(),
+            updated_at: payment.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentCountQuery {
+    pub status: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentCountResponse {
+    pub count: i64,
+}
+
+/// Either payment ids or order ids, up to 100.
+#[derive(Debug, Deserialize)]
+pub struct PaymentLookupRequest {
+    #[serde(default)]
+    pub ids: Vec<Uuid>,
+    #[serde(default)]
+    pub order_ids: Vec<Uuid>,
+}
+
+/// One entry per requested id, in request order.
+#[derive(Debug, Serialize)]
+pub struct PaymentLookupResult {
+    /// The requested payment or order id
+    pub id: Uuid,
+    pub found: bool,
+    /// PaymentResponse, only the requested `fields` when given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment: Option<serde_json::Value>,
+}
+
+/// Sparse fieldset: `?fields=id,amount,payment_status` returns just those fields of each payment.
+#[derive(Debug, Deserialize)]
+pub struct FieldsQuery {
+    pub fields: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WaitQuery {
+    /// e.g. 30s or 500ms, plain numbers are seconds
+    pub timeout: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiptQuery {
+    /// tr or en, the Accept-Language header decides when omitted
+    pub lang: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QrQuery {
+    /// png (default) or text for the raw payload
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentQuoteRequest {
+    pub amount: Decimal,
+    pub currency: String,
+    pub payment_method: String,
+    pub installments: Option<u8>,
+    pub promo_code: Option<String>,
+    pub country: Option<String>,
+}
+
+/// Itemized price shown to the customer before they confirm the payment.
+#[derive(Debug, Serialize)]
+pub struct PaymentQuoteResponse {
+    pub gross_amount: Decimal,
+    pub discount_amount: Decimal,
+    pub amount: Decimal,
+    pub currency: String,
+    pub payment_method: String,
+    pub method_surcharge_percent: Decimal,
+    pub method_surcharge: Decimal,
+    pub installments: InstallmentInfo,
+    pub total_amount: Decimal,
+    pub tax: TaxInfo,
+}
+
+/// VAT line of the payment; `amount` = `taxable_base` + `tax_amount`.
+#[derive(Debug, Serialize)]
+pub struct TaxInfo {
+    pub jurisdiction: Option<String>,
+    pub rate: Decimal,
+    pub taxable_base: Decimal,
+    pub tax_amount: Decimal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InstallmentInfo {
+    pub count: i16,
+    pub fee_percent: Decimal,
+    pub surcharge: Decimal,
+    /// Amount charged to the card: payment amount minus the wallet and voucher parts, plus surcharges
+    pub total_amount: Decimal,
+    pub monthly_amount: Decimal,
+}
+
+impl InstallmentInfo {
+    pub fn new(count: i16, fee_percent: Decimal, surcharge: Decimal, total_amount: Decimal) -> Self {
+        let count = count.max(1);
+
+        Self {
+            count,
+            fee_percent,
+            surcharge,
+            total_amount,
+            monthly_amount: (total_amount / Decimal::from(count)).round_dp(2),
+        }
+    }
+
+    fn from_payment(payment: &Payment) -> Self {
+        let total_amount = payment.amount - payment.wallet_amount - payment.voucher_amount
+            + payment.installment_surcharge
+            + payment.method_surcharge;
+
+        Self::new(
+            payment.installment_count,
+            payment.installment_fee_percent,
+            payment.installment_surcharge,
+            total_amount,
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CryptoDepositResponse {
+    pub asset: String,
+    pub deposit_address: String,
+    pub exchange_rate: Decimal,
+    pub expected_amount: Decimal,
+    pub received_amount: Decimal,
+    /// Amount owed back to the customer when they sent too much
+    pub overpaid_amount: Decimal,
+    pub confirmations: i32,
+    pub required_confirmations: i32,
+    pub tx_hash: Option<String>,
+    pub deposit_status: String,
+}
+
+impl From<CryptoPayment> for CryptoDepositResponse {
+    fn from(crypto: CryptoPayment) -> Self {
+        Self {
+            overpaid_amount: (crypto.received_amount - crypto.expected_amount).max(Decimal::ZERO),
+            asset: crypto.asset,
+            deposit_address: crypto.deposit_address,
+            exchange_rate: crypto.exchange_rate,
+            expected_amount: crypto.expected_amount,
+            received_amount: crypto.received_amount,
+            confirmations: crypto.confirmations,
+            required_confirmations: crypto.required_confirmations,
+            tx_hash: crypto.tx_hash,
+            deposit_status: crypto.deposit_status,
+        }
+    }
+}
+
+/// Deposit update pushed by the crypto provider.
+#[derive(Debug, Deserialize)]
+pub struct CryptoWebhookRequest {
+    /// Delivery id of the provider, the same on redeliveries
+    #[serde(default)]
+    pub event_id: Option<String>,
+    pub reference: String,
+    pub received_amount: Decimal,
+    pub confirmations: i32,
+    pub tx_hash: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSubscriptionRequest {
+    pub plan_code: String,
+    pub amount: Decimal,
+    pub currency: String,
+    /// DAY, WEEK, MONTH or YEAR
+    pub billing_interval: String,
+    pub interval_count: Option<i32>,
+    /// Defaults to the user's default saved method
+    pub payment_method_id: Option<Uuid>,
+    /// First charge time, immediately when omitted
+    pub start_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSubscriptionRequest {
+    pub payment_method_id: Option<Uuid>,
+    /// ACTIVE or PAUSED
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub plan_code: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub billing_interval: String,
+    pub interval_count: i32,
+    pub payment_method_id: Uuid,
+    pub status: String,
+    pub status_description: String,
+    pub next_billing_at: String,
+    pub cancelled_at: Option<String>,
+    pub retry_count: i32,
+    pub next_retry_at: Option<String>,
+    /// Proration credit applied to upcoming charges
+    pub credit_balance: Decimal,
     pub created_at: String,
     pub updated_at: String,
 }
 
+impl From<Subscription> for SubscriptionResponse {
+    fn from(subscription: Subscription) -> Self {
+        Self {
+            id: subscription.id,
+            user_id: subscription.user_id,
+            plan_code: subscription.plan_code,
+            amount: subscription.amount,
+            currency: subscription.currency,
+            billing_interval: subscription.billing_interval,
+            interval_count: subscription.interval_count,
+            payment_method_id: subscription.payment_method_id,
+            status_description: i18n::status_description(&subscription.status),
+            status: subscription.status,
+            next_billing_at: subscription.next_billing_at.to_rfc3339(),
+            cancelled_at: subscription.cancelled_at.map(|t| t.to_rfc3339()),
+            retry_count: subscription.retry_count,
+            next_retry_at: subscription.next_retry_at.map(|t| t.to_rfc3339()),
+            credit_balance: subscription.credit_balance,
+            created_at: subscription.created_at.to_rfc3339(),
+            updated_at: subscription.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePlanRequest {
+    pub plan_code: String,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanChangeResponse {
+    pub subscription: SubscriptionResponse,
+    pub adjustment: SubscriptionAdjustment,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WalletTopUpRequest {
+    pub amount: Decimal,
+    pub currency: String,
+    /// Saved card to charge, the user's default card when omitted
+    pub payment_method_token: Option<String>,
+    pub return_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletResponse {
+    pub currency: String,
+    pub balance: Decimal,
+    pub updated_at: String,
+}
+
+impl From<Wallet> for WalletResponse {
+    fn from(wallet: Wallet) -> Self {
+        Self {
+            currency: wallet.currency,
+            balance: wallet.balance,
+            updated_at: wallet.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WalletTransactionsQuery {
+    pub currency: Option<String>,
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRefundRequest {
+    /// Remaining refundable amount when omitted
+    pub amount: Option<Decimal>,
+    /// ORIGINAL_METHOD (default) or WALLET
+    pub destination: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RefundResponse {
+    pub refund: Refund,
+    pub payment: PaymentResponse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueVoucherRequest {
+    pub amount: Decimal,
+    pub currency: String,
+    /// Generated when omitted
+    pub code: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePromotionRequest {
+    pub code: String,
+    /// PERCENT or FIXED
+    pub discount_type: String,
+    pub discount_value: Decimal,
+    pub currency: Option<String>,
+    pub min_amount: Option<Decimal>,
+    pub max_uses: Option<i32>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MerchantTermsRequest {
+    pub commission_percent: Decimal,
+    pub fixed_fee: Option<Decimal>,
+    /// Finance's reference to the merchant's bank account, copied onto new payouts
+    pub payout_account_reference: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PayoutQuery {
+    pub status: Option<String>,
+    pub merchant_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadWorkQuery {
+    /// PAYMENT, NOTIFICATION or WEBHOOK
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookSubscriptionRequest {
+    pub url: String,
+    /// Only this merchant's payment events; every event when left out
+    pub merchant_id: Option<Uuid>,
+    pub description: Option<String>,
+    /// e.g. ["payment.refunded"]; every event type when empty
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookSubscriptionRequest {
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub event_types: Option<Vec<String>>,
+    pub active: Option<bool>,
+}
+
+/// Subscription with its signing secret, which is only ever shown when created or rotated.
+#[derive(Debug, Serialize)]
+pub struct WebhookSubscriptionSecretResponse {
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookSubscriptionQuery {
+    pub merchant_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookEventQuery {
+    /// RECEIVED, PROCESSED or FAILED
+    pub status: Option<String>,
+    /// Sender, e.g. CRYPTO
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarkPayoutPaidRequest {
+    pub bank_transfer_reference: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EarningsQuery {
+    pub merchant_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// csv (default) or json
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentExportQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// parquet (default)
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeeReportQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Inclusive day range, the last 30 days when unset.
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyUsageQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentImportQuery {
+    /// Validate and report without inserting anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Also reject rows whose user_id the user service doesn't know
+    #[serde(default)]
+    pub verify_users: bool,
+}
+
+/// One payment of the legacy system, every field kept as text so each problem can be reported.
+#[derive(Debug, Deserialize)]
+pub struct LegacyPaymentRow {
+    pub transaction_id: Option<String>,
+    pub order_id: Option<String>,
+    pub user_id: Option<String>,
+    /// The platform itself when empty
+    pub merchant_id: Option<String>,
+    pub amount: Option<String>,
+    pub currency: Option<String>,
+    pub payment_method: Option<String>,
+    pub payment_status: Option<String>,
+    /// RFC 3339, or `YYYY-MM-DD HH:MM:SS` in UTC
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentImportReport {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub imported: usize,
+    pub failed: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    /// 1-based data row, the CSV header not counted
+    pub row: usize,
+    pub transaction_id: Option<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedQuery {
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeedSummary {
+    pub payments: usize,
+    pub users: usize,
+    pub merchants: usize,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionResponse {
+    pub service: &'static str,
+    pub version: &'static str,
+    pub git_sha: &'static str,
+    pub built_at: Option<DateTime<Utc>>,
+    /// release or debug
+    pub profile: &'static str,
+    /// Cargo features compiled in, e.g. chaos
+    pub features: Vec<&'static str>,
+}
+
+/// Error budget status of the availability and latency SLOs, see `slo`.
+#[derive(Debug, Serialize)]
+pub struct SloReport {
+    pub period_days: i64,
+    pub slos: Vec<SloStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SloStatus {
+    pub name: &'static str,
+    pub objective: String,
+    pub target_percent: Decimal,
+    pub period_requests: u64,
+    pub period_bad_requests: u64,
+    /// Share of the period's error budget left, negative once it is overspent
+    pub error_budget_remaining: f64,
+    pub windows: Vec<SloBurnRate>,
+    /// "page" or "ticket" while a multi-window burn-rate condition holds
+    pub alert: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SloBurnRate {
+    pub window: &'static str,
+    pub requests: u64,
+    pub bad: u64,
+    /// How many times faster than sustainable the budget is being spent; 1 uses it up exactly in the period
+    pub burn_rate: f64,
+}
+
+/// Usage of a merchant's API key against its quotas.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyUsageResponse {
+    pub merchant_id: Uuid,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub requests_per_minute_limit: u32,
+    pub payments_per_day_limit: u32,
+    pub total_requests: i64,
+    pub total_payments: i64,
+    /// Busiest day by payments, to compare with the daily quota
+    pub peak_payments_per_day: i64,
+    pub days: Vec<ApiKeyUsageDay>,
+}
+
+/// Fails `failure_percent` of the target's calls for `duration_secs` (at most an hour).
+#[cfg(feature = "chaos")]
+#[derive(Debug, Deserialize)]
+pub struct ChaosExperimentRequest {
+    /// DATABASE, REDIS or GATEWAY
+    pub target: String,
+    pub failure_percent: u8,
+    pub duration_secs: i64,
+}
+
+/// ACS result posted back after the 3-D Secure challenge.
+#[derive(Debug, Deserialize)]
+pub struct ThreeDsCallbackRequest {
+    /// EMV 3DS `transStatus`: Y, A, N, U, R
+    pub trans_status: String,
+}
+
+/// Courier's result at the door for a cash-on-delivery payment.
+#[derive(Debug, Deserialize)]
+pub struct CollectionRequest {
+    /// COLLECTED or FAILED
+    pub outcome: String,
+    pub note: Option<String>,
+}
+
+// No Debug derive: this carries the raw card number
+#[derive(Deserialize)]
+pub struct TokenizePaymentMethodRequest {
+    pub method_type: String,
+    pub card_number: String,
+    pub holder_name: String,
+    pub exp_month: u8,
+    pub exp_year: u16,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentMethodResponse {
+    pub id: Uuid,
+    pub token: String,
+    pub method_type: String,
+    pub brand: String,
+    pub last4: String,
+    pub exp_month: i16,
+    pub exp_year: i16,
+    pub holder_name: String,
+    pub is_default: bool,
+    pub created_at: String,
+}
+
+impl From<PaymentMethod> for PaymentMethodResponse {
+    fn from(method: PaymentMethod) -> Self {
+        Self {
+            id: method.id,
+            token: method.token,
+            method_type: method.method_type,
+            brand: method.brand,
+            last4: method.last4,
+            exp_month: method.exp_month,
+            exp_year: method.exp_year,
+            holder_name: method.holder_name,
+            is_default: method.is_default,
+            created_at: method.created_at.to_rfc3339(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
     pub message: String,
+    /// Set on errors, and on successful responses that still carry an outcome to act on (a declined payment)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// The invalid fields of a VALIDATION_FAILED request
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
     pub data: Option<T>,
 }
 
@@ -36,16 +842,180 @@ impl<T> ApiResponse<T> {
     pub fn success(data: T) -> Self {
         Self {
             success: true,
-            message: "Success".to_string(),
+            message: i18n::translate("Success"),
+            error_code: None,
+            errors: Vec::new(),
             data: Some(data),
         }
     }
 
-    pub fn error(message: String) -> Self {
+    pub fn error(code: ErrorCode, message: String) -> Self {
         Self {
             success: false,
-            message,
+            message: i18n::translate(&message),
+            error_code: Some(code),
+            errors: Vec::new(),
             data: None,
         }
     }
-}
\ No newline at end of file
+
+    pub fn with_error_code(mut self, code: ErrorCode) -> Self {
+        self.error_code = Some(code);
+        self
+    }
+
+    pub fn with_field_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors
+            .into_iter()
+            .map(|error| FieldError {
+                message: i18n::translate(&error.message),
+                ..error
+            })
+            .collect();
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMerchantRequest {
+    pub name: String,
+    pub contact_email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MerchantTaxDetailsRequest {
+    pub tax_id: String,
+    pub tax_office: String,
+    pub address: String,
+    pub city: String,
+}
+
+/// Unset quotas fall back to the configured defaults.
+#[derive(Debug, Deserialize)]
+pub struct MerchantQuotasRequest {
+    pub requests_per_minute: Option<i32>,
+    pub payments_per_day: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MerchantDuplicateOrdersRequest {
+    /// REPLAY or REJECT, null for the configured default
+    pub duplicate_orders: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MerchantScopesRequest {
+    /// e.g. `["payments:read"]`, null for an unrestricted key
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Onboarding result; `api_key` is not stored and can't be shown again.
+#[derive(Debug, Serialize)]
+pub struct MerchantOnboardingResponse {
+    pub merchant: Merchant,
+    pub api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GatewayCredentialsRequest {
+    pub account_id: String,
+    pub api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePaymentLinkRequest {
+    pub order_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub description: Option<String>,
+    /// Restricts the link to one customer
+    pub user_id: Option<Uuid>,
+    /// PAYMENT_LINK_TTL_HOURS when omitted
+    pub expires_in_hours: Option<i64>,
+}
+
+/// What the hosted payment page shows.
+#[derive(Debug, Serialize)]
+pub struct PaymentLinkResponse {
+    pub token: String,
+    pub url: String,
+    pub merchant_name: String,
+    pub order_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub description: Option<String>,
+    /// ACTIVE, PROCESSING, PAID or EXPIRED
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_id: Option<Uuid>,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PayPaymentLinkRequest {
+    /// Required unless the link was created for a specific customer or the payer sends their bearer token
+    pub user_id: Option<Uuid>,
+    pub payment_method: String,
+    pub payment_method_token: Option<String>,
+    pub return_url: Option<String>,
+    pub installments: Option<u8>,
+    /// Chosen by the client for each attempt, e.g. a UUID; a request repeating one is rejected as a replay
+    pub nonce: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePaymentIntentRequest {
+    pub order_id: Uuid,
+    pub user_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub promo_code: Option<String>,
+    pub country: Option<String>,
+    #[serde(default)]
+    pub escrow: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentIntentResponse {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    /// REQUIRES_CONFIRMATION, PROCESSING or SUCCEEDED
+    pub status: String,
+    /// Only returned when the intent is created; the SDK needs it to confirm
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    /// Latest payment attempt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_id: Option<Uuid>,
+    pub created_at: String,
+}
+
+impl From<PaymentIntent> for PaymentIntentResponse {
+    fn from(intent: PaymentIntent) -> Self {
+        Self {
+            id: intent.id,
+            order_id: intent.order_id,
+            amount: intent.amount,
+            currency: intent.currency,
+            status: intent.status,
+            client_secret: None,
+            payment_id: intent.payment_id,
+            created_at: intent.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// The payment method collected by the SDK.
+#[derive(Debug, Deserialize)]
+pub struct ConfirmPaymentIntentRequest {
+    pub client_secret: String,
+    pub payment_method: String,
+    pub payment_method_token: Option<String>,
+    pub return_url: Option<String>,
+    pub crypto_asset: Option<String>,
+    pub installments: Option<u8>,
+    pub wallet_amount: Option<Decimal>,
+    pub voucher_code: Option<String>,
+}
@@ -0,0 +1,241 @@
+use crate::dto::ApiResponse;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+pub type AppResult<T> = Result<T, AppError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    PaymentRequired(String),
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+    #[error("{0}")]
+    PreconditionFailed(String),
+    #[error("{0}")]
+    PreconditionRequired(String),
+    #[error("{0}")]
+    ServiceUnavailable(String),
+    #[error("The query took too long, narrow the request or retry later")]
+    StatementTimeout,
+    #[error(transparent)]
+    Database(sqlx::Error),
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+    /// Invalid request fields, all of them at once so a form can mark each
+    #[error("Validation failed")]
+    Validation(Vec<FieldError>),
+    /// Any of the above with a more specific code than its variant's, see `with_code`
+    #[error("{1}")]
+    Coded(ErrorCode, Box<AppError>),
+}
+
+/// Stable, machine-readable reason sent as `error_code` next to the translated message, for clients to branch on.
+/// Codes are never renamed; new ones may be added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    PaymentRequired,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    PreconditionFailed,
+    PreconditionRequired,
+    QuotaExceeded,
+    ValidationFailed,
+    ServiceUnavailable,
+    Maintenance,
+    StatementTimeout,
+    InternalError,
+    PaymentNotFound,
+    DuplicateOrder,
+    GatewayDeclined,
+}
+
+/// Why a single field was rejected, listed under `errors` of a VALIDATION_FAILED response.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// JSON path of the field, e.g. `amount` or `splits[1].merchant_id`
+    pub field: String,
+    pub code: FieldErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FieldErrorCode {
+    /// Missing from the request
+    Required,
+    /// Not in the expected format
+    Invalid,
+    /// Too small or too large
+    OutOfRange,
+    /// Valid on its own, but not with the rest of the request
+    NotAllowed,
+    /// Given twice where it must be unique
+    Duplicate,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, code: FieldErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Fails with every collected field error, if there is any.
+pub fn ensure_valid(errors: Vec<FieldError>) -> AppResult<()> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+impl ErrorCode {
+    /// Generic code for a status, when nothing more specific is known.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::PAYMENT_REQUIRED => ErrorCode::PaymentRequired,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+            StatusCode::PRECONDITION_FAILED => ErrorCode::PreconditionFailed,
+            StatusCode::PRECONDITION_REQUIRED => ErrorCode::PreconditionRequired,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::QuotaExceeded,
+            StatusCode::SERVICE_UNAVAILABLE => ErrorCode::ServiceUnavailable,
+            status if status.is_client_error() => ErrorCode::BadRequest,
+            _ => ErrorCode::InternalError,
+        }
+    }
+}
+
+// SQLSTATE query_canceled, raised when statement_timeout hits
+const QUERY_CANCELED: &str = "57014";
+// Transactions Postgres aborted in favour of a concurrent one; running them again is safe
+const SERIALIZATION_FAILURE: &str = "40001";
+const DEADLOCK_DETECTED: &str = "40P01";
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db) if db.code().as_deref() == Some(QUERY_CANCELED) => {
+                tracing::warn!(error = %e, "statement timeout");
+                AppError::StatementTimeout
+            }
+            _ => AppError::Database(e),
+        }
+    }
+}
+
+impl AppError {
+    /// A request with a single invalid field.
+    pub fn invalid_field(field: impl Into<String>, code: FieldErrorCode, message: impl Into<String>) -> Self {
+        AppError::Validation(vec![FieldError::new(field, code, message)])
+    }
+
+    pub fn is_serialization_failure(&self) -> bool {
+        match self {
+            AppError::Database(sqlx::Error::Database(db)) => {
+                matches!(db.code().as_deref(), Some(SERIALIZATION_FAILURE | DEADLOCK_DETECTED))
+            }
+            _ => false,
+        }
+    }
+
+    /// Same error and status, with a more specific code than the variant's own.
+    pub fn with_code(self, code: ErrorCode) -> Self {
+        AppError::Coded(code, Box::new(self))
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::Coded(code, _) => *code,
+            AppError::StatementTimeout => ErrorCode::StatementTimeout,
+            AppError::Validation(_) => ErrorCode::ValidationFailed,
+            other => ErrorCode::from_status(other.status_code()),
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Coded(_, error) => error.status_code(),
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) | AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::PaymentRequired(_) => StatusCode::PAYMENT_REQUIRED,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            AppError::ServiceUnavailable(_) | AppError::StatementTimeout => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Database(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+            AppError::Database(sqlx::Error::PoolTimedOut) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let code = self.code();
+        let error = match self {
+            AppError::Coded(_, error) => *error,
+            other => other,
+        };
+
+        let field_errors = match &error {
+            AppError::Validation(errors) => errors.clone(),
+            _ => Vec::new(),
+        };
+
+        // Internal details stay in the logs, clients get a generic message
+        let message = match &error {
+            AppError::Database(sqlx::Error::RowNotFound) => "Resource not found".to_string(),
+            AppError::Database(sqlx::Error::PoolTimedOut) => {
+                tracing::warn!("no database connection available within the acquire timeout");
+                "Service is busy, please retry".to_string()
+            }
+            AppError::Database(e) => {
+                tracing::error!(error = %e, "database error");
+                "Internal server error".to_string()
+            }
+            AppError::Internal(e) => {
+                tracing::error!(error = %e, "internal error");
+                "Internal server error".to_string()
+            }
+            other => other.to_string(),
+        };
+
+        (status, Json(ApiResponse::<()>::error(code, message).with_field_errors(field_errors))).into_response()
+    }
+}
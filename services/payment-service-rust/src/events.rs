@@ -0,0 +1,163 @@
+use crate::{
+    models::{Payment, Subscription},
+    services::volume_anomalies::Anomaly,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Every event type published on the bus, the ones webhook subscriptions can filter on.
+pub const EVENT_TYPES: &[&str] = &[
+    "payment.pending",
+    "payment.processing",
+    "payment.requires_action",
+    "payment.awaiting_collection",
+    "payment.completed",
+    "payment.escrowed",
+    "payment.failed",
+    "payment.refunded",
+    "subscription.recovered",
+    "subscription.payment_failed",
+    "subscription.cancelled",
+    "payment_volume.drop",
+    "payment_volume.spike",
+];
+
+/// Payment state change, published in-process for anything that needs to react to it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentEvent {
+    pub event_type: String,
+    pub payment_id: Uuid,
+    pub order_id: Uuid,
+    pub merchant_id: Uuid,
+    pub user_id: Uuid,
+    pub status: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl PaymentEvent {
+    pub fn from_payment(payment: &Payment) -> Self {
+        Self {
+            event_type: format!("payment.{}", payment.payment_status.to_lowercase()),
+            payment_id: payment.id,
+            order_id: payment.order_id,
+            merchant_id: payment.merchant_id,
+            user_id: payment.user_id,
+            status: payment.payment_status.clone(),
+            amount: payment.amount,
+            currency: payment.currency.clone(),
+            occurred_at: Utc::now(),
+        }
+    }
+}
+
+/// Subscription billing lifecycle (dunning) event, e.g. for customer emails.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionEvent {
+    pub event_type: String,
+    pub subscription_id: Uuid,
+    pub user_id: Uuid,
+    pub status: String,
+    pub retry_count: i32,
+    pub next_retry_at: Option<DateTime<Utc>>,
+    pub payment_id: Option<Uuid>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Unusual payment volume in a window, see `services::volume_anomalies`.
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeAnomalyEvent {
+    pub event_type: String,
+    pub metric: String,
+    pub window_start: DateTime<Utc>,
+    pub value: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub z_score: f64,
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Event {
+    Payment(PaymentEvent),
+    Subscription(SubscriptionEvent),
+    VolumeAnomaly(VolumeAnomalyEvent),
+}
+
+impl Event {
+    pub fn event_type(&self) -> &str {
+        match self {
+            Event::Payment(event) => &event.event_type,
+            Event::Subscription(event) => &event.event_type,
+            Event::VolumeAnomaly(event) => &event.event_type,
+        }
+    }
+
+    /// Merchant the event belongs to, for events about a single payment.
+    pub fn merchant_id(&self) -> Option<Uuid> {
+        match self {
+            Event::Payment(event) => Some(event.merchant_id),
+            Event::Subscription(_) | Event::VolumeAnomaly(_) => None,
+        }
+    }
+}
+
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish(&self, payment: &Payment) {
+        let event = PaymentEvent::from_payment(payment);
+        tracing::info!(event_type = %event.event_type, payment_id = %event.payment_id, "Payment event published");
+
+        // No subscribers is not an error
+        let _ = self.sender.send(Event::Payment(event));
+    }
+
+    pub fn publish_subscription(&self, event_type: &str, subscription: &Subscription, payment_id: Option<Uuid>) {
+        let event = SubscriptionEvent {
+            event_type: event_type.to_string(),
+            subscription_id: subscription.id,
+            user_id: subscription.user_id,
+            status: subscription.status.clone(),
+            retry_count: subscription.retry_count,
+            next_retry_at: subscription.next_retry_at,
+            payment_id,
+            occurred_at: Utc::now(),
+        };
+        tracing::info!(event_type = %event.event_type, subscription_id = %event.subscription_id, "Subscription event published");
+
+        let _ = self.sender.send(Event::Subscription(event));
+    }
+
+    pub fn publish_volume_anomaly(&self, anomaly: &Anomaly) {
+        let event = VolumeAnomalyEvent {
+            event_type: if anomaly.is_drop() { "payment_volume.drop" } else { "payment_volume.spike" }.to_string(),
+            metric: anomaly.metric.to_string(),
+            window_start: anomaly.window_start,
+            value: anomaly.value,
+            mean: anomaly.mean,
+            stddev: anomaly.stddev,
+            z_score: anomaly.z_score,
+            occurred_at: Utc::now(),
+        };
+        tracing::warn!(event_type = %event.event_type, metric = %event.metric, z_score = event.z_score, "Payment volume anomaly");
+
+        let _ = self.sender.send(Event::VolumeAnomaly(event));
+    }
+}
@@ -0,0 +1,625 @@
+use crate::{
+    dto::{
+        ApiKeyUsageQuery, ApiKeyUsageResponse, ApiResponse, CreateMerchantRequest, DeadWorkQuery, CreatePromotionRequest, CreateRefundRequest, EarningsQuery, FeeReportQuery,
+        GatewayCredentialsRequest, IssueVoucherRequest, MerchantDuplicateOrdersRequest, MerchantQuotasRequest, MerchantScopesRequest, MerchantTaxDetailsRequest,
+        MarkPayoutPaidRequest, MerchantOnboardingResponse, MerchantTermsRequest, PaymentExportQuery, PaymentImportQuery, PaymentImportReport, PaymentResponse, SeedQuery, SeedSummary, PayoutQuery, RefundResponse, ReportQuery,
+        CreateWebhookSubscriptionRequest, UpdateWebhookSubscriptionRequest, WebhookEventQuery, WebhookSubscriptionQuery,
+        WebhookSubscriptionSecretResponse,
+    },
+    error::{AppError, AppResult},
+    handlers::extract::{ExpectedVersion, Json, Path, PaymentsRead, PaymentsRefund, PaymentsWrite, RequireScope},
+    models::{
+        FeeReportRow, Merchant, MerchantEarnings, MerchantGatewayCredentials, MerchantStatus, MerchantTerms, Payout, Promotion, Refund, Voucher,
+        WebhookDelivery, WebhookDeliveryStats, WebhookEvent, WebhookSubscription, WorkItem,
+    },
+    services::{
+        api_key_usage, archival, bank_transfer, efatura, escrow, fees, gateway_credentials, merchants, notifications, payment_export::{self, ExportFormat},
+        outbound_webhooks, payment_import::{self, ImportFormat}, payment_service, payouts, promotions, refund_service,
+        reports::{self, ReportFormat},
+        seed, splits, user_client::UserProfile, user_profiles, vouchers, webhook_events, work_queue, AppState,
+    },
+    telemetry,
+};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "confirm_bank_transfer", skip(state))]
+pub async fn confirm_bank_transfer(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Path(id): Path<Uuid>,
+    ExpectedVersion(version): ExpectedVersion,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let payment = bank_transfer::confirm(&state.db_pool, id, version).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    tracing::info!("Bank transfer confirmed for payment {}", id);
+
+    Ok(Json(ApiResponse::success(payment.into())))
+}
+
+#[tracing::instrument(name = "create_refund", skip(state))]
+pub async fn create_refund(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRefund>,
+    Path(id): Path<Uuid>,
+    ExpectedVersion(version): ExpectedVersion,
+    Json(request): Json<CreateRefundRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<RefundResponse>>)> {
+    let (refund, payment) = refund_service::create(&state, id, version, request).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    notifications::refund_issued(&state.db_pool, &refund, &payment);
+    tracing::info!("Refunded {} of payment {} to {}", refund.amount, id, refund.destination);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse::success(RefundResponse { refund, payment: payment.into() })),
+    ))
+}
+
+pub async fn list_refunds(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Vec<Refund>>>> {
+    let refunds = refund_service::list_for_payment(&state.db_pool, id).await?;
+
+    Ok(Json(ApiResponse::success(refunds)))
+}
+
+#[tracing::instrument(name = "issue_voucher", skip(state))]
+pub async fn issue_voucher(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<IssueVoucherRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<Voucher>>)> {
+    let voucher = vouchers::issue(&state.db_pool, request).await?;
+    tracing::info!("Voucher {} issued for {} {}", voucher.id, voucher.initial_amount, voucher.currency);
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(voucher))))
+}
+
+#[tracing::instrument(name = "void_voucher", skip(state))]
+pub async fn void_voucher(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Voucher>>> {
+    let voucher = vouchers::void(&state.db_pool, id).await?;
+    tracing::info!("Voucher {} voided with {} left", id, voucher.balance);
+
+    Ok(Json(ApiResponse::success(voucher)))
+}
+
+#[tracing::instrument(name = "create_promotion", skip(state))]
+pub async fn create_promotion(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CreatePromotionRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<Promotion>>)> {
+    let promotion = promotions::create(&state.db_pool, request).await?;
+    tracing::info!("Promotion {} created", promotion.code);
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(promotion))))
+}
+
+/// Completed payments with their platform/gateway fees and net amounts.
+pub async fn fee_report(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FeeReportQuery>,
+) -> AppResult<Json<ApiResponse<Vec<FeeReportRow>>>> {
+    let rows = fees::report(state.read_pool(), &query, state.config.db_report_statement_timeout_ms).await?;
+
+    Ok(Json(ApiResponse::success(rows)))
+}
+
+#[tracing::instrument(name = "onboard_merchant", skip(state))]
+pub async fn onboard_merchant(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CreateMerchantRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<MerchantOnboardingResponse>>)> {
+    let (merchant, api_key) = merchants::onboard(&state.db_pool, request).await?;
+    tracing::info!("Merchant {} onboarded: {}", merchant.id, merchant.name);
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(MerchantOnboardingResponse { merchant, api_key }))))
+}
+
+/// API keys are issued one per merchant, so the key is addressed by its merchant id.
+pub async fn api_key_usage(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ApiKeyUsageQuery>,
+) -> AppResult<Json<ApiResponse<ApiKeyUsageResponse>>> {
+    let usage = api_key_usage::report(&state, id, &query).await?;
+
+    Ok(Json(ApiResponse::success(usage)))
+}
+
+/// Payments moved to cold storage are no longer in `payments`; this reads them back from the archive.
+pub async fn get_archived_payment(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
+    let payment = archival::get_archived(&state, id).await?;
+
+    Ok(Json(ApiResponse::success(payment)))
+}
+
+pub async fn list_merchants(State(state): State<Arc<AppState>>) -> AppResult<Json<ApiResponse<Vec<Merchant>>>> {
+    let merchants = merchants::list(&state.db_pool).await?;
+
+    Ok(Json(ApiResponse::success(merchants)))
+}
+
+pub async fn get_merchant(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Merchant>>> {
+    let merchant = merchants::get(&state.db_pool, id).await?;
+
+    Ok(Json(ApiResponse::success(merchant)))
+}
+
+#[tracing::instrument(name = "suspend_merchant", skip(state))]
+pub async fn suspend_merchant(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Merchant>>> {
+    let merchant = merchants::set_status(&state.db_pool, id, MerchantStatus::Suspended).await?;
+    tracing::info!("Merchant {} suspended", id);
+
+    Ok(Json(ApiResponse::success(merchant)))
+}
+
+#[tracing::instrument(name = "activate_merchant", skip(state))]
+pub async fn activate_merchant(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Merchant>>> {
+    let merchant = merchants::set_status(&state.db_pool, id, MerchantStatus::Active).await?;
+    tracing::info!("Merchant {} activated", id);
+
+    Ok(Json(ApiResponse::success(merchant)))
+}
+
+#[tracing::instrument(name = "set_merchant_quotas", skip(state))]
+pub async fn set_merchant_quotas(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<MerchantQuotasRequest>,
+) -> AppResult<Json<ApiResponse<Merchant>>> {
+    let merchant = merchants::set_quotas(&state.db_pool, id, request).await?;
+
+    Ok(Json(ApiResponse::success(merchant)))
+}
+
+#[tracing::instrument(name = "set_merchant_scopes", skip(state))]
+pub async fn set_merchant_scopes(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<MerchantScopesRequest>,
+) -> AppResult<Json<ApiResponse<Merchant>>> {
+    let merchant = merchants::set_scopes(&state.db_pool, id, request).await?;
+    tracing::info!("Merchant {} API key scopes set to {:?}", id, merchant.scopes);
+
+    Ok(Json(ApiResponse::success(merchant)))
+}
+
+#[tracing::instrument(name = "set_merchant_duplicate_orders", skip(state))]
+pub async fn set_merchant_duplicate_orders(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<MerchantDuplicateOrdersRequest>,
+) -> AppResult<Json<ApiResponse<Merchant>>> {
+    let merchant = merchants::set_duplicate_orders(&state.db_pool, id, request).await?;
+
+    Ok(Json(ApiResponse::success(merchant)))
+}
+
+#[tracing::instrument(name = "set_merchant_tax_details", skip(state))]
+pub async fn set_merchant_tax_details(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<MerchantTaxDetailsRequest>,
+) -> AppResult<Json<ApiResponse<Merchant>>> {
+    let merchant = merchants::set_tax_details(&state.db_pool, id, request).await?;
+
+    Ok(Json(ApiResponse::success(merchant)))
+}
+
+/// UBL-TR XML of an invoiced payment, for upload to the e-Fatura provider.
+pub async fn export_efatura(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Path(id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    let document = efatura::document_for(&state.db_pool, id).await?;
+    let disposition = format!("attachment; filename=\"{}.xml\"", document.invoice_number);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/xml".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+            (header::HeaderName::from_static("x-efatura-ettn"), document.ettn.to_string()),
+        ],
+        document.xml,
+    ))
+}
+
+pub async fn get_gateway_credentials(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<MerchantGatewayCredentials>>> {
+    let credentials = gateway_credentials::get(&state.db_pool, id).await?;
+
+    Ok(Json(ApiResponse::success(credentials)))
+}
+
+#[tracing::instrument(name = "set_gateway_credentials", skip(state, request))]
+pub async fn set_gateway_credentials(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<GatewayCredentialsRequest>,
+) -> AppResult<Json<ApiResponse<MerchantGatewayCredentials>>> {
+    let credentials = gateway_credentials::set(&state, id, request).await?;
+    tracing::info!("Merchant {} now charges through gateway account {}", id, credentials.account_id);
+
+    Ok(Json(ApiResponse::success(credentials)))
+}
+
+#[tracing::instrument(name = "remove_gateway_credentials", skip(state))]
+pub async fn remove_gateway_credentials(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    gateway_credentials::remove(&state.db_pool, id).await?;
+    tracing::info!("Merchant {} back on the platform gateway account", id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tracing::instrument(name = "set_merchant_terms", skip(state))]
+pub async fn set_merchant_terms(
+    State(state): State<Arc<AppState>>,
+    Path(merchant_id): Path<Uuid>,
+    Json(request): Json<MerchantTermsRequest>,
+) -> AppResult<Json<ApiResponse<MerchantTerms>>> {
+    let terms = splits::set_terms(&state.db_pool, merchant_id, request).await?;
+
+    Ok(Json(ApiResponse::success(terms)))
+}
+
+pub async fn merchant_earnings(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EarningsQuery>,
+) -> AppResult<Json<ApiResponse<Vec<MerchantEarnings>>>> {
+    let rows = splits::earnings(state.read_pool(), &query, state.config.db_report_statement_timeout_ms).await?;
+
+    Ok(Json(ApiResponse::success(rows)))
+}
+
+#[tracing::instrument(name = "release_escrow", skip(state))]
+pub async fn release_escrow(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Path(id): Path<Uuid>,
+    ExpectedVersion(version): ExpectedVersion,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let payment = escrow::release(&state.db_pool, id, version).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    tracing::info!("Escrow released by admin for payment {}", id);
+
+    Ok(Json(ApiResponse::success(payment.into())))
+}
+
+pub async fn list_payouts(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PayoutQuery>,
+) -> AppResult<Json<ApiResponse<Vec<Payout>>>> {
+    let payouts = payouts::list(state.read_pool(), &query).await?;
+
+    Ok(Json(ApiResponse::success(payouts)))
+}
+
+/// Runs the payout batching right away instead of waiting for the daily job.
+#[tracing::instrument(name = "generate_payouts", skip(state))]
+pub async fn generate_payouts(State(state): State<Arc<AppState>>) -> AppResult<Json<ApiResponse<Vec<Payout>>>> {
+    let payouts = payouts::generate(&state.db_pool).await?;
+    tracing::info!("Generated {} payouts", payouts.len());
+
+    Ok(Json(ApiResponse::success(payouts)))
+}
+
+#[tracing::instrument(name = "approve_payout", skip(state))]
+pub async fn approve_payout(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<Payout>>> {
+    let payout = payouts::approve(&state.db_pool, id).await?;
+
+    Ok(Json(ApiResponse::success(payout)))
+}
+
+#[tracing::instrument(name = "mark_payout_paid", skip(state))]
+pub async fn mark_payout_paid(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<MarkPayoutPaidRequest>,
+) -> AppResult<Json<ApiResponse<Payout>>> {
+    let payout = payouts::mark_paid(&state.db_pool, id, request).await?;
+
+    Ok(Json(ApiResponse::success(payout)))
+}
+
+pub async fn export_payouts(State(state): State<Arc<AppState>>) -> AppResult<impl IntoResponse> {
+    let csv = payouts::export_approved(&state.db_pool).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"payouts.csv\""),
+        ],
+        csv,
+    ))
+}
+
+pub async fn settlement_report(
+    State(state): State<Arc<AppState>>,
+    Path(merchant_id): Path<Uuid>,
+    Query(query): Query<ReportQuery>,
+) -> AppResult<Response> {
+    let format = ReportFormat::parse(query.format.as_deref())?;
+    let rows = reports::settlements(
+        state.read_pool().clone(),
+        merchant_id,
+        query,
+        format,
+        state.config.db_report_statement_timeout_ms,
+    );
+
+    Ok(report_response(format, &format!("settlements-{}", merchant_id), Body::from_stream(rows)))
+}
+
+pub async fn payout_report(
+    State(state): State<Arc<AppState>>,
+    Path(merchant_id): Path<Uuid>,
+    Query(query): Query<ReportQuery>,
+) -> AppResult<Response> {
+    let format = ReportFormat::parse(query.format.as_deref())?;
+    let rows = reports::payouts(
+        state.read_pool().clone(),
+        merchant_id,
+        query,
+        format,
+        state.config.db_report_statement_timeout_ms,
+    );
+
+    Ok(report_response(format, &format!("payouts-{}", merchant_id), Body::from_stream(rows)))
+}
+
+/// Payments of the period for the data warehouse; `from` defaults to the last 90 days like the reports.
+pub async fn export_payments(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Query(query): Query<PaymentExportQuery>,
+) -> AppResult<Response> {
+    let format = ExportFormat::parse(query.format.as_deref())?;
+    let from = payment_service::default_from(query.from);
+    let file = payment_export::payments(state.read_pool().clone(), query, state.config.db_report_statement_timeout_ms);
+    let disposition = format!("attachment; filename=\"payments-{}.{}\"", from.format("%Y%m%d"), format.extension());
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(file),
+    )
+        .into_response())
+}
+
+/// CSV or NDJSON of legacy payments, depending on Content-Type. Answers 200 with the per-row report even when
+/// some rows were rejected.
+#[tracing::instrument(name = "import_payments", skip(state, headers, body))]
+pub async fn import_payments(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Query(query): Query<PaymentImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<Json<ApiResponse<PaymentImportReport>>> {
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let format = ImportFormat::from_content_type(content_type)?;
+    let body = std::str::from_utf8(&body).map_err(|_| AppError::BadRequest("Import file must be UTF-8".to_string()))?;
+
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let verify_users = query.verify_users.then_some((state.user_client.as_ref(), token));
+
+    let report =
+        payment_import::import(&state.db_pool, &state.config, format, body, query.dry_run, verify_users).await?;
+
+    Ok(Json(ApiResponse::success(report)))
+}
+
+/// Demo data for non-production environments.
+#[tracing::instrument(name = "seed_payments", skip(state))]
+pub async fn seed_payments(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SeedQuery>,
+) -> AppResult<(StatusCode, Json<ApiResponse<SeedSummary>>)> {
+    let summary = seed::seed(&state.db_pool, &state.config, query.count.unwrap_or(seed::DEFAULT_COUNT)).await?;
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(summary))))
+}
+
+pub async fn list_dead_work(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DeadWorkQuery>,
+) -> AppResult<Json<ApiResponse<Vec<WorkItem>>>> {
+    let items = work_queue::list_dead(&state.db_pool, query.kind.as_deref()).await?;
+
+    Ok(Json(ApiResponse::success(items)))
+}
+
+#[tracing::instrument(name = "requeue_work", skip(state))]
+pub async fn requeue_work(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<WorkItem>>> {
+    let item = work_queue::requeue(&state.db_pool, id).await?;
+    tracing::info!("Dead {} work item {} requeued", item.kind, id);
+
+    Ok(Json(ApiResponse::success(item)))
+}
+
+pub async fn list_webhook_events(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<WebhookEventQuery>,
+) -> AppResult<Json<ApiResponse<Vec<WebhookEvent>>>> {
+    let events = webhook_events::list(&state.db_pool, query.status.as_deref(), query.source.as_deref()).await?;
+
+    Ok(Json(ApiResponse::success(events)))
+}
+
+/// Processes a stored inbound webhook again, answering with the event and its new status.
+#[tracing::instrument(name = "replay_webhook_event", skip(state))]
+pub async fn replay_webhook_event(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<WebhookEvent>>> {
+    let event = webhook_events::replay(&state, id).await?;
+
+    Ok(Json(ApiResponse::success(event)))
+}
+
+/// The signing secret is in the response, the only time it's shown besides a rotation.
+#[tracing::instrument(name = "create_webhook_subscription", skip(state))]
+pub async fn create_webhook_subscription(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CreateWebhookSubscriptionRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<WebhookSubscriptionSecretResponse>>)> {
+    let (subscription, secret) = outbound_webhooks::create(&state, request).await?;
+    tracing::info!("Webhook subscription {} created for {}", subscription.id, subscription.url);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse::success(WebhookSubscriptionSecretResponse { subscription, secret })),
+    ))
+}
+
+pub async fn list_webhook_subscriptions(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<WebhookSubscriptionQuery>,
+) -> AppResult<Json<ApiResponse<Vec<WebhookSubscription>>>> {
+    let subscriptions = outbound_webhooks::list(&state.db_pool, query.merchant_id).await?;
+
+    Ok(Json(ApiResponse::success(subscriptions)))
+}
+
+pub async fn get_webhook_subscription(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<WebhookSubscription>>> {
+    let subscription = outbound_webhooks::get(&state.db_pool, id).await?;
+
+    Ok(Json(ApiResponse::success(subscription)))
+}
+
+#[tracing::instrument(name = "update_webhook_subscription", skip(state))]
+pub async fn update_webhook_subscription(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateWebhookSubscriptionRequest>,
+) -> AppResult<Json<ApiResponse<WebhookSubscription>>> {
+    let subscription = outbound_webhooks::update(&state.db_pool, id, request).await?;
+
+    Ok(Json(ApiResponse::success(subscription)))
+}
+
+#[tracing::instrument(name = "delete_webhook_subscription", skip(state))]
+pub async fn delete_webhook_subscription(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    outbound_webhooks::delete(&state.db_pool, id).await?;
+    tracing::info!("Webhook subscription {} deleted", id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tracing::instrument(name = "rotate_webhook_secret", skip(state))]
+pub async fn rotate_webhook_secret(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<WebhookSubscriptionSecretResponse>>> {
+    let (subscription, secret) = outbound_webhooks::rotate_secret(&state, id).await?;
+    tracing::info!("Webhook subscription {} secret rotated", id);
+
+    Ok(Json(ApiResponse::success(WebhookSubscriptionSecretResponse { subscription, secret })))
+}
+
+/// Answers 200 with the delivery either way, its status and `last_error` tell whether the endpoint took it.
+#[tracing::instrument(name = "test_webhook_subscription", skip(state))]
+pub async fn test_webhook_subscription(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<WebhookDelivery>>> {
+    let delivery = outbound_webhooks::send_test(&state, id).await?;
+
+    Ok(Json(ApiResponse::success(delivery)))
+}
+
+pub async fn webhook_subscription_stats(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<WebhookDeliveryStats>>> {
+    let stats = outbound_webhooks::stats(&state.db_pool, id).await?;
+
+    Ok(Json(ApiResponse::success(stats)))
+}
+
+/// Name and email from the user service, served from the profile cache when it has them.
+pub async fn get_user_profile(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<UserProfile>>> {
+    let profile = user_profiles::get(&state, id)
+        .await
+        .ok_or_else(|| AppError::NotFound("User profile is not available".to_string()))?;
+
+    Ok(Json(ApiResponse::success(profile)))
+}
+
+/// Drops the cached profile, for when the user changed their name or email.
+#[tracing::instrument(name = "invalidate_user_profile", skip(state))]
+pub async fn invalidate_user_profile(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    user_profiles::invalidate(&state, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn report_response(format: ReportFormat, name: &str, body: Body) -> Response {
+    let disposition = format!("attachment; filename=\"{}.{}\"", name, format.extension());
+
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response()
+}
@@ -0,0 +1,38 @@
+use crate::{
+    chaos::{self, Experiment, Target},
+    dto::{ApiResponse, ChaosExperimentRequest},
+    error::{AppError, AppResult},
+    handlers::extract::Json,
+};
+use axum::http::StatusCode;
+
+pub async fn list_experiments() -> Json<ApiResponse<Vec<Experiment>>> {
+    Json(ApiResponse::success(chaos::active()))
+}
+
+#[tracing::instrument(name = "start_chaos_experiment")]
+pub async fn start_experiment(
+    Json(request): Json<ChaosExperimentRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<Experiment>>)> {
+    let target = Target::parse(&request.target)
+        .ok_or_else(|| AppError::BadRequest(format!("Unknown chaos target: {}", request.target)))?;
+    if request.failure_percent > 100 {
+        return Err(AppError::BadRequest("failure_percent must be between 0 and 100".to_string()));
+    }
+    if request.duration_secs <= 0 || request.duration_secs > chaos::MAX_DURATION_SECS {
+        return Err(AppError::BadRequest(format!(
+            "duration_secs must be between 1 and {}",
+            chaos::MAX_DURATION_SECS
+        )));
+    }
+
+    let experiment = chaos::start(target, request.failure_percent, request.duration_secs);
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(experiment))))
+}
+
+pub async fn stop_experiments() -> StatusCode {
+    chaos::stop_all();
+
+    StatusCode::NO_CONTENT
+}
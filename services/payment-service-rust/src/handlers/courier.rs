@@ -0,0 +1,39 @@
+use crate::{
+    dto::{ApiResponse, CollectionRequest, PaymentResponse},
+    error::AppResult,
+    handlers::extract::{ExpectedVersion, Json, Path},
+    services::{cash_on_delivery, escrow, AppState},
+    telemetry,
+};
+use axum::extract::State;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "record_collection", skip(state))]
+pub async fn record_collection(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    ExpectedVersion(version): ExpectedVersion,
+    Json(request): Json<CollectionRequest>,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let payment = cash_on_delivery::record_collection(&state.db_pool, id, version, request).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+
+    Ok(Json(ApiResponse::success(payment.into())))
+}
+
+/// Delivery confirmation from the courier app releases escrowed funds to the seller.
+#[tracing::instrument(name = "confirm_delivery", skip(state))]
+pub async fn confirm_delivery(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    ExpectedVersion(version): ExpectedVersion,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let payment = escrow::release(&state.db_pool, id, version).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    tracing::info!("Delivery confirmed, escrow released for payment {}", id);
+
+    Ok(Json(ApiResponse::success(payment.into())))
+}
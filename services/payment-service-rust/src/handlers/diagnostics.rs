@@ -0,0 +1,74 @@
+use crate::{
+    dto::{ApiResponse, SloReport},
+    error::AppResult,
+    jobs,
+    services::{api_key_usage, work_queue, AppState},
+    slo,
+};
+use axum::{extract::State, Json};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// Slow dependencies are reported as such instead of holding the snapshot up
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// One JSON snapshot of the instance for on-call triage. Secrets in the configuration are redacted.
+pub async fn diagnostics(State(state): State<Arc<AppState>>) -> AppResult<Json<Value>> {
+    let now = chrono::Utc::now();
+
+    let mut pools = serde_json::Map::new();
+    pools.insert("primary".to_string(), pool_stats(&state.db_pool).await);
+    if let Some(replica) = &state.read_replica {
+        let mut stats = pool_stats(&replica.pool).await;
+        stats["healthy"] = json!(replica.is_healthy());
+        pools.insert("replica".to_string(), stats);
+    }
+
+    let mut redis = state.redis_conn.clone();
+    let started = Instant::now();
+    let ping = tokio::time::timeout(PROBE_TIMEOUT, redis::cmd("PING").query_async::<_, String>(&mut redis)).await;
+    let redis_up = matches!(ping, Ok(Ok(_)));
+    let redis_latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let work_queue = work_queue::backlog(&state.db_pool).await?;
+    let usage_pending = if redis_up { api_key_usage::pending_merchants(&state.redis_conn).await.ok() } else { None };
+
+    Ok(Json(json!({
+        "service": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_sha": env!("BUILD_GIT_SHA"),
+        "started_at": state.started_at,
+        "uptime_seconds": (now - state.started_at).num_seconds(),
+        "database": pools,
+        "redis": { "up": redis_up, "latency_ms": redis_latency_ms },
+        "backlog": {
+            "work_queue": work_queue,
+            "api_key_usage_pending_merchants": usage_pending,
+        },
+        "jobs": jobs::statuses(),
+        "config": state.config.as_ref(),
+        "timestamp": now,
+    })))
+}
+
+/// Error budget status of this instance's SLOs.
+pub async fn slo(State(state): State<Arc<AppState>>) -> Json<ApiResponse<SloReport>> {
+    Json(ApiResponse::success(slo::report(&state.config)))
+}
+
+async fn pool_stats(pool: &PgPool) -> Value {
+    let started = Instant::now();
+    let acquired = tokio::time::timeout(PROBE_TIMEOUT, pool.acquire()).await;
+
+    json!({
+        "max_connections": pool.options().get_max_connections(),
+        "connections": pool.size(),
+        "idle": pool.num_idle(),
+        "acquire_ok": matches!(acquired, Ok(Ok(_))),
+        "acquire_ms": started.elapsed().as_secs_f64() * 1000.0,
+    })
+}
@@ -0,0 +1,309 @@
+use crate::{
+    error::{AppError, FieldErrorCode},
+    middleware::{auth::AuthUser, tenant::Tenant},
+    models::{Payment, Scope},
+    services::payment_service,
+};
+use async_trait::async_trait;
+use axum::{
+    extract::{
+        path::ErrorKind,
+        rejection::{JsonRejection, PathRejection},
+        FromRequest, FromRequestParts, Request,
+    },
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{error::Error, marker::PhantomData};
+use uuid::Uuid;
+
+/// `axum::Json` whose rejections are API errors: a wrong or missing field is reported under `errors` like any
+/// other validation failure, broken JSON as a 400.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+/// `axum::extract::Path` answering a 400 that names the parameter, e.g. for a mistyped payment id.
+#[derive(Debug)]
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::Json::<T>::from_request(request, state).await {
+            Ok(axum::Json(value)) => Ok(Json(value)),
+            Err(rejection) => Err(json_rejection(rejection)),
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Path(value)),
+            Err(rejection) => Err(path_rejection(rejection)),
+        }
+    }
+}
+
+/// Payment version from `If-Match` (`"3"` or `3`), required by endpoints that change a payment so that two
+/// admins acting on the same version can't overwrite each other.
+#[derive(Debug, Clone, Copy)]
+pub struct ExpectedVersion(pub i32);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ExpectedVersion {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(header::IF_MATCH) else {
+            return Err(AppError::PreconditionRequired(
+                "Send the payment version in If-Match to change it".to_string(),
+            ));
+        };
+
+        value
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().trim_matches('"').parse().ok())
+            .map(ExpectedVersion)
+            .ok_or_else(|| {
+                AppError::invalid_field("If-Match", FieldErrorCode::Invalid, "If-Match must be the payment version")
+            })
+    }
+}
+
+/// Guard for a handler that needs a scope, e.g. `_: RequireScope<PaymentsRefund>`. The scopes are the token's when
+/// the request carries one, otherwise the merchant API key's; a 403 names the missing scope, a request with neither
+/// gets a 401.
+#[derive(Debug)]
+pub struct RequireScope<S>(PhantomData<S>);
+
+/// Scope a `RequireScope` checks, one marker type per scope.
+pub trait RequiredScope {
+    const SCOPE: Scope;
+}
+
+#[derive(Debug)]
+pub struct PaymentsRead;
+#[derive(Debug)]
+pub struct PaymentsWrite;
+#[derive(Debug)]
+pub struct PaymentsRefund;
+#[derive(Debug)]
+pub struct PaymentsAdmin;
+
+impl RequiredScope for PaymentsRead {
+    const SCOPE: Scope = Scope::Read;
+}
+impl RequiredScope for PaymentsWrite {
+    const SCOPE: Scope = Scope::Write;
+}
+impl RequiredScope for PaymentsRefund {
+    const SCOPE: Scope = Scope::Refund;
+}
+impl RequiredScope for PaymentsAdmin {
+    const SCOPE: Scope = Scope::Admin;
+}
+
+#[async_trait]
+impl<R: RequiredScope, S: Send + Sync> FromRequestParts<S> for RequireScope<R> {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // The user's scopes already include the API key's restrictions
+        let granted = match parts.extensions.get::<AuthUser>() {
+            Some(auth) => auth.scopes,
+            None => parts
+                .extensions
+                .get::<Tenant>()
+                .and_then(|tenant| tenant.scopes)
+                .ok_or(AppError::Unauthorized)?,
+        };
+
+        if !granted.contains(R::SCOPE) {
+            return Err(AppError::Forbidden(format!("Requires the {} scope", R::SCOPE.as_str())));
+        }
+
+        Ok(RequireScope(PhantomData))
+    }
+}
+
+/// Whose payments a read may return: the tenant's, and of those only their own when the caller is a customer.
+/// Merchant staff and platform staff see the whole tenant.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewer {
+    pub merchant_id: Uuid,
+    pub customer: Option<Uuid>,
+}
+
+impl Viewer {
+    /// Someone else's payment answers like a missing one, so customers can't probe for ids.
+    pub fn visible(&self, payment: Payment) -> Result<Payment, AppError> {
+        match self.customer {
+            Some(user_id) if payment.user_id != user_id => Err(payment_service::payment_not_found(sqlx::Error::RowNotFound)),
+            _ => Ok(payment),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Viewer {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let tenant = parts
+            .extensions
+            .get::<Tenant>()
+            .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Viewer used on a route without resolve_tenant")))?;
+        let customer = parts
+            .extensions
+            .get::<AuthUser>()
+            .filter(|auth| auth.is_customer())
+            .map(|auth| auth.user_id);
+
+        Ok(Viewer { merchant_id: tenant.merchant_id, customer })
+    }
+}
+
+fn json_rejection(rejection: JsonRejection) -> AppError {
+    // serde_path_to_error knows where in the body deserialization stopped
+    let located = rejection
+        .source()
+        .and_then(|e| e.source())
+        .and_then(|e| e.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>());
+
+    match &rejection {
+        JsonRejection::JsonDataError(_) => match located {
+            Some(e) => data_error(e),
+            None => AppError::BadRequest("Request body doesn't match the expected fields".to_string()),
+        },
+        JsonRejection::JsonSyntaxError(_) => match located {
+            Some(e) => AppError::BadRequest(format!("Request body is not valid JSON: {}", e.inner())),
+            None => AppError::BadRequest("Request body is not valid JSON".to_string()),
+        },
+        JsonRejection::MissingJsonContentType(_) => {
+            AppError::UnsupportedMediaType("Expected a JSON body with Content-Type: application/json".to_string())
+        }
+        _ if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+            AppError::PayloadTooLarge("Request body is too large".to_string())
+        }
+        _ => AppError::BadRequest(rejection.body_text()),
+    }
+}
+
+fn data_error(e: &serde_path_to_error::Error<serde_json::Error>) -> AppError {
+    let path = e.path().to_string();
+    let message = e.inner().to_string();
+
+    // A missing field is reported on the object that lacks it, point at the field itself
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next());
+    let (field, code) = match missing {
+        Some(name) if path == "." => (name.to_string(), FieldErrorCode::Required),
+        Some(name) => (format!("{}.{}", path, name), FieldErrorCode::Required),
+        None => (path, FieldErrorCode::Invalid),
+    };
+
+    AppError::invalid_field(field, code, message)
+}
+
+fn path_rejection(rejection: PathRejection) -> AppError {
+    match rejection {
+        PathRejection::FailedToDeserializePathParams(e) => match e.into_kind() {
+            ErrorKind::ParseErrorAtKey { key, value, .. } => AppError::invalid_field(
+                key.clone(),
+                FieldErrorCode::Invalid,
+                format!("Invalid value for {}: {}", key, value),
+            ),
+            kind => AppError::BadRequest(kind.to_string()),
+        },
+        // The route and the extractor don't agree, not the caller's fault
+        rejection => AppError::Internal(anyhow::anyhow!(rejection.body_text())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DuplicateOrders, Scopes};
+    use axum::http::Request;
+
+    fn parts(tenant_scopes: Option<Scopes>, auth: Option<AuthUser>) -> Parts {
+        let (mut parts, _) = Request::builder().body(()).unwrap().into_parts();
+        parts.extensions.insert(Tenant {
+            merchant_id: Uuid::new_v4(),
+            requests_per_minute: 60,
+            payments_per_day: 100,
+            duplicate_orders: DuplicateOrders::Reject,
+            scopes: tenant_scopes,
+        });
+        if let Some(auth) = auth {
+            parts.extensions.insert(auth);
+        }
+        parts
+    }
+
+    fn user(role: &str, merchant_id: Option<Uuid>, scopes: Scopes) -> AuthUser {
+        AuthUser { user_id: Uuid::new_v4(), role: role.to_string(), merchant_id, scopes }
+    }
+
+    async fn require<R: RequiredScope>(parts: &mut Parts) -> Result<RequireScope<R>, AppError> {
+        RequireScope::<R>::from_request_parts(parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn token_scopes_take_precedence_over_the_key() {
+        let mut parts = parts(Some(Scopes::ALL), Some(user("CUSTOMER", None, Scopes::CUSTOMER)));
+        assert!(require::<PaymentsRead>(&mut parts).await.is_ok());
+        assert!(matches!(require::<PaymentsRefund>(&mut parts).await, Err(AppError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn key_scopes_apply_without_a_token() {
+        let mut parts = parts(Some(Scopes::parse(["payments:read"])), None);
+        assert!(require::<PaymentsRead>(&mut parts).await.is_ok());
+        assert!(matches!(require::<PaymentsWrite>(&mut parts).await, Err(AppError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn neither_token_nor_key_is_unauthorized() {
+        let mut parts = parts(None, None);
+        assert!(matches!(require::<PaymentsRead>(&mut parts).await, Err(AppError::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn only_customers_are_limited_to_their_own_payments() {
+        let customer = user("CUSTOMER", None, Scopes::CUSTOMER);
+        let user_id = customer.user_id;
+        let viewer = Viewer::from_request_parts(&mut parts(None, Some(customer)), &()).await.unwrap();
+        assert_eq!(viewer.customer, Some(user_id));
+
+        let staff = user("MERCHANT", Some(Uuid::new_v4()), Scopes::ALL);
+        let viewer = Viewer::from_request_parts(&mut parts(Some(Scopes::ALL), Some(staff)), &()).await.unwrap();
+        assert_eq!(viewer.customer, None);
+
+        let viewer = Viewer::from_request_parts(&mut parts(Some(Scopes::ALL), None), &()).await.unwrap();
+        assert_eq!(viewer.customer, None);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::error::{AppError, AppResult};
+use async_trait::async_trait;
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::convert::Infallible;
+
+const MSGPACK_TYPES: [&str; 2] = ["application/msgpack", "application/x-msgpack"];
+
+/// Response encoding picked from `Accept`: MessagePack for internal consumers that ask for it, JSON otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseFormat {
+    Json,
+    MessagePack,
+}
+
+impl ResponseFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "application/json",
+            ResponseFormat::MessagePack => "application/msgpack",
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> AppResult<Vec<u8>> {
+        match self {
+            ResponseFormat::Json => serde_json::to_vec(value).map_err(|e| AppError::Internal(e.into())),
+            // Named, so maps keep their keys like the JSON objects do
+            ResponseFormat::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| AppError::Internal(e.into())),
+        }
+    }
+
+    pub fn respond<T: Serialize>(&self, value: &T) -> AppResult<Response> {
+        Ok((
+            [
+                (header::CONTENT_TYPE, HeaderValue::from_static(self.content_type())),
+                (header::VARY, HeaderValue::from_static("Accept")),
+            ],
+            self.encode(value)?,
+        )
+            .into_response())
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ResponseFormat {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let wants_msgpack = parts
+            .headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter(|media| !media.contains("q=0") || media.contains("q=0."))
+            .filter_map(|media| media.split(';').next())
+            .any(|media| MSGPACK_TYPES.contains(&media.trim().to_ascii_lowercase().as_str()));
+
+        Ok(if wants_msgpack { ResponseFormat::MessagePack } else { ResponseFormat::Json })
+    }
+}
@@ -1,5 +1,10 @@
-use axum::Json;
+use crate::services::AppState;
+use axum::{extract::State, http::StatusCode, Json};
 use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+
+// A dependency slower than this counts as down for readiness
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[tracing::instrument(name = "health_check")]
 pub async fn health_check() -> Json<Value> {
@@ -9,4 +14,30 @@ pub async fn health_check() -> Json<Value> {
         "service": "payment-service",
         "timestamp": chrono::Utc::now().to_rfc3339()
     }))
-}
\ No newline at end of file
+}
+
+/// Ready to take traffic: the primary database and Redis both answer. 503 otherwise, so load balancers and
+/// container health checks take the instance out.
+pub async fn readiness(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Value>) {
+    let database = tokio::time::timeout(READINESS_TIMEOUT, sqlx::query("SELECT 1").execute(&state.db_pool)).await;
+    let database = matches!(database, Ok(Ok(_)));
+    let mut redis = state.redis_conn.clone();
+    let redis = tokio::time::timeout(READINESS_TIMEOUT, redis::cmd("PING").query_async::<_, String>(&mut redis)).await;
+    let redis = matches!(redis, Ok(Ok(_)));
+
+    let up = |ok: bool| if ok { "UP" } else { "DOWN" };
+    let status = if database && redis { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    if status != StatusCode::OK {
+        tracing::warn!(database, redis, "readiness check failed");
+    }
+
+    (
+        status,
+        Json(json!({
+            "status": up(database && redis),
+            "service": "payment-service",
+            "checks": { "database": up(database), "redis": up(redis) },
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })),
+    )
+}
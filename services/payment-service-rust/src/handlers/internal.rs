@@ -0,0 +1,31 @@
+use crate::{
+    dto::{ApiResponse, PaymentResponse},
+    error::AppResult,
+    handlers::extract::{Json, Path},
+    middleware::tenant::Tenant,
+    services::{escrow, payment_service, AppState},
+    telemetry,
+};
+use axum::{extract::State, Extension};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The order service reports the order delivered, which releases its escrowed payment like a courier confirmation.
+/// The caller doesn't track payment versions, so the current one is used.
+#[tracing::instrument(name = "order_delivered", skip(state, tenant))]
+pub async fn order_delivered(
+    State(state): State<Arc<AppState>>,
+    Extension(tenant): Extension<Tenant>,
+    Path(order_id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let payment = payment_service::find_active_by_order(&state.db_pool, tenant.merchant_id, order_id)
+        .await?
+        .ok_or_else(|| payment_service::payment_not_found(sqlx::Error::RowNotFound))?;
+
+    let payment = escrow::release(&state.db_pool, payment.id, payment.version).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    tracing::info!("Order {} delivered, escrow released for payment {}", order_id, payment.id);
+
+    Ok(Json(ApiResponse::success(payment.into())))
+}
@@ -0,0 +1,26 @@
+use crate::{
+    dto::ApiResponse,
+    error::{AppError, AppResult},
+    handlers::extract::Json,
+    log_sampling::{self, SamplingRule},
+};
+
+pub async fn get_rules() -> Json<ApiResponse<Vec<SamplingRule>>> {
+    Json(ApiResponse::success(log_sampling::current_rules()))
+}
+
+/// Replaces all sampling rules until the next restart, which goes back to LOG_SAMPLING.
+#[tracing::instrument(name = "set_log_sampling")]
+pub async fn set_rules(Json(rules): Json<Vec<SamplingRule>>) -> AppResult<Json<ApiResponse<Vec<SamplingRule>>>> {
+    if let Some(rule) = rules.iter().find(|rule| rule.keep_one_in == 0 || rule.target.trim().is_empty()) {
+        return Err(AppError::BadRequest(format!(
+            "Sampling rules need a target and keep_one_in of at least 1: {}",
+            rule.target
+        )));
+    }
+
+    log_sampling::set_rules(rules);
+    tracing::warn!("Log sampling rules replaced");
+
+    Ok(Json(ApiResponse::success(log_sampling::current_rules())))
+}
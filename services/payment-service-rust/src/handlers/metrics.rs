@@ -0,0 +1,128 @@
+use crate::{services::AppState, slo, telemetry};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+};
+use sqlx::PgPool;
+use std::{
+    fmt::Write,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// A saturated pool or a hung Redis must not hang the scrape as well.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Prometheus text format, or OpenMetrics when the scraper accepts it; only OpenMetrics carries the trace id
+/// exemplars on the latency histogram. Pool gauges are read from sqlx; acquire time and Redis latency are
+/// measured by probing once per scrape.
+pub async fn metrics(State(state): State<Arc<AppState>>, headers: HeaderMap) -> impl IntoResponse {
+    let openmetrics = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/openmetrics-text"));
+    let mut out = String::new();
+
+    gauge_header(&mut out, "db_pool_max_connections", "Configured maximum connections per pool");
+    gauge_header(&mut out, "db_pool_connections", "Open connections");
+    gauge_header(&mut out, "db_pool_idle_connections", "Open connections not in use");
+    gauge_header(&mut out, "db_pool_in_use_connections", "Connections checked out by queries");
+    gauge_header(&mut out, "db_pool_acquire_seconds", "Time to get a connection, sampled at scrape time");
+    gauge_header(&mut out, "db_pool_acquire_ok", "1 when the sample acquire succeeded within the probe timeout");
+
+    let mut pools = vec![("primary", &state.db_pool)];
+    if let Some(replica) = &state.read_replica {
+        pools.push(("replica", &replica.pool));
+    }
+    for (name, pool) in pools {
+        pool_metrics(&mut out, name, pool, state.config.db_pool_max_connections).await;
+    }
+
+    // OpenMetrics names the counter family without the _total suffix of its sample
+    let family = if openmetrics { "db_slow_queries" } else { "db_slow_queries_total" };
+    let _ = writeln!(out, "# HELP {} Statements over SLOW_QUERY_THRESHOLD_MS, by query summary", family);
+    let _ = writeln!(out, "# TYPE {} counter", family);
+    for (summary, count) in telemetry::slow_query_counts() {
+        let label = summary.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " ");
+        let _ = writeln!(out, "db_slow_queries_total{{query=\"{}\"}} {}", label, count);
+    }
+
+    gauge_header(&mut out, "redis_up", "1 when Redis answered PING within the probe timeout");
+    gauge_header(&mut out, "redis_ping_seconds", "PING round trip, sampled at scrape time");
+    let mut redis = state.redis_conn.clone();
+    let started = Instant::now();
+    let ping = tokio::time::timeout(PROBE_TIMEOUT, redis::cmd("PING").query_async::<_, String>(&mut redis)).await;
+    let _ = writeln!(out, "redis_up {}", u8::from(matches!(ping, Ok(Ok(_)))));
+    let _ = writeln!(out, "redis_ping_seconds {:.6}", started.elapsed().as_secs_f64());
+
+    gauge_header(&mut out, "slo_burn_rate", "Error budget burn rate per SLO and window, 1 spends it exactly in the period");
+    gauge_header(&mut out, "slo_error_budget_remaining", "Share of the period's error budget left");
+    for status in slo::report(&state.config).slos {
+        for window in &status.windows {
+            let _ = writeln!(
+                out,
+                "slo_burn_rate{{slo=\"{}\",window=\"{}\"}} {:.4}",
+                status.name, window.window, window.burn_rate
+            );
+        }
+        let _ = writeln!(out, "slo_error_budget_remaining{{slo=\"{}\"}} {:.4}", status.name, status.error_budget_remaining);
+    }
+
+    request_latency_metrics(&mut out, openmetrics);
+
+    if openmetrics {
+        out.push_str("# EOF\n");
+        ([(header::CONTENT_TYPE, "application/openmetrics-text; version=1.0.0; charset=utf-8")], out)
+    } else {
+        ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+    }
+}
+
+fn request_latency_metrics(out: &mut String, exemplars: bool) {
+    let _ = writeln!(out, "# HELP http_request_duration_seconds Time to the response head, by method, route and status");
+    let _ = writeln!(out, "# TYPE http_request_duration_seconds histogram");
+
+    for ((method, route, status), histogram) in telemetry::request_latency_histograms() {
+        let labels = format!("method=\"{}\",route=\"{}\",status=\"{}\"", method, route, status);
+        let bounds = telemetry::LATENCY_BUCKETS.iter().map(|le| le.to_string()).chain(["+Inf".to_string()]);
+        let mut cumulative = 0;
+
+        for ((le, count), exemplar) in bounds.zip(histogram.buckets).zip(&histogram.exemplars) {
+            cumulative += count;
+            let _ = write!(out, "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, le, cumulative);
+            if let Some(exemplar) = exemplar.as_ref().filter(|_| exemplars) {
+                let _ = write!(
+                    out,
+                    " # {{trace_id=\"{}\"}} {} {:.3}",
+                    exemplar.trace_id, exemplar.seconds, exemplar.timestamp
+                );
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(out, "http_request_duration_seconds_sum{{{}}} {}", labels, histogram.sum);
+        let _ = writeln!(out, "http_request_duration_seconds_count{{{}}} {}", labels, histogram.count);
+    }
+}
+
+async fn pool_metrics(out: &mut String, name: &str, pool: &PgPool, max_connections: u32) {
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+
+    let started = Instant::now();
+    let acquired = tokio::time::timeout(PROBE_TIMEOUT, pool.acquire()).await;
+    let acquire_seconds = started.elapsed().as_secs_f64();
+    let acquire_ok = matches!(acquired, Ok(Ok(_)));
+
+    let _ = writeln!(out, "db_pool_max_connections{{pool=\"{}\"}} {}", name, max_connections);
+    let _ = writeln!(out, "db_pool_connections{{pool=\"{}\"}} {}", name, size);
+    let _ = writeln!(out, "db_pool_idle_connections{{pool=\"{}\"}} {}", name, idle);
+    let _ = writeln!(out, "db_pool_in_use_connections{{pool=\"{}\"}} {}", name, size.saturating_sub(idle));
+    let _ = writeln!(out, "db_pool_acquire_seconds{{pool=\"{}\"}} {:.6}", name, acquire_seconds);
+    let _ = writeln!(out, "db_pool_acquire_ok{{pool=\"{}\"}} {}", name, u8::from(acquire_ok));
+}
+
+fn gauge_header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
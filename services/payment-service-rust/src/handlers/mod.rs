@@ -1,2 +1,19 @@
+pub mod admin;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod courier;
+pub mod diagnostics;
+pub mod extract;
+pub mod format;
 pub mod health;
-pub mod payment;
\ No newline at end of file
+pub mod internal;
+pub mod log_sampling;
+pub mod metrics;
+pub mod payment;
+pub mod payment_intent;
+pub mod payment_link;
+pub mod payment_method;
+pub mod subscription;
+pub mod version;
+pub mod wallet;
+pub mod webhook;
\ No newline at end of file
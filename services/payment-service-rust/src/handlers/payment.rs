@@ -1,93 +1,492 @@
 use crate::{
-    dto::{ApiResponse, CreatePaymentRequest, PaymentResponse},
-    services::{payment_service, AppState},
+    dto::{
+        ApiResponse, CreatePaymentRequest, FieldsQuery, PaymentCountQuery, PaymentCountResponse, PaymentLookupRequest, PaymentLookupResult, PaymentQuoteRequest, PaymentQuoteResponse, PaymentResponse, QrQuery, ReceiptQuery,
+        ThreeDsCallbackRequest, WaitQuery,
+    },
+    error::{AppError, AppResult, ErrorCode, FieldErrorCode},
+    events::Event,
+    handlers::{
+        extract::{Json, Path, PaymentsRead, PaymentsWrite, RequireScope, Viewer},
+        format::ResponseFormat,
+    },
+    middleware::{auth::AuthUser, tenant::Tenant},
+    models::{CryptoPayment, DuplicateOrders, Payment, PaymentSplit, PaymentStatus, METHOD_CRYPTO},
+    services::{async_payments, crypto_payment, invoices, payment_service, qr, receipts, splits, AppState},
+    telemetry,
 };
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    Json,
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
 };
-use std::sync::Arc;
+use chrono::DateTime;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::broadcast::error::RecvError, time::Instant};
 use uuid::Uuid;
 
-#[tracing::instrument(name = "create_payment", skip(state))]
+#[tracing::instrument(
+    name = "create_payment",
+    skip(state, headers, request),
+    fields(
+        order.id = %request.order_id,
+        payment.amount = %request.amount,
+        payment.currency = %request.currency,
+        payment.method = %request.payment_method,
+    )
+)]
 pub async fn create_payment(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<CreatePaymentRequest>,
-) -> Result<Json<ApiResponse<PaymentResponse>>, StatusCode> {
+    _: RequireScope<PaymentsWrite>,
+    Extension(tenant): Extension<Tenant>,
+    auth: Option<Extension<AuthUser>>,
+    headers: HeaderMap,
+    Json(mut request): Json<CreatePaymentRequest>,
+) -> AppResult<Response> {
     tracing::info!("Creating payment for order: {}", request.order_id);
-    let payment = payment_service::create_payment(&state.db_pool, request)
-    .await
-    .map_err(|e| {
-        tracing::error!(error = %e, "create_payment service error");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-
-    let response = PaymentResponse {
-        id: payment.id,
-        order_id: payment.order_id,
-        user_id: payment.user_id,
-        amount: payment.amount,
-        currency: payment.currency,
-        payment_method: payment.payment_method,
-        payment_status: payment.payment_status,
-        transaction_id: payment.transaction_id,
-        created_at: payment.created_at.to_rfc3339(),
-        updated_at: payment.updated_at.to_rfc3339(),
+    request.merchant_id = tenant.merchant_id;
+    let customer = auth.as_ref().filter(|Extension(auth)| auth.is_customer()).map(|Extension(auth)| auth.user_id);
+    request.payer_id = auth.map(|Extension(auth)| auth.user_id);
+
+    // Retries of the order service get the payment already made for the order
+    let order_id = request.order_id;
+    if let Some(replay) = replay_order(&state, &tenant, customer, order_id).await? {
+        return Ok(replay);
+    }
+
+    // Async mode answers right away, a worker talks to the gateway
+    let respond_async = headers
+        .get_all("prefer")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.split(',').any(|p| p.trim().eq_ignore_ascii_case("respond-async")));
+    if state.config.async_payments || respond_async {
+        // A concurrent request for the order got in first, answered like a retry after it
+        let payment = match async_payments::enqueue(&state.db_pool, request).await {
+            Err(e) if e.code() == ErrorCode::DuplicateOrder => {
+                return replay_order(&state, &tenant, customer, order_id).await?.ok_or(e);
+            }
+            result => result?,
+        };
+        telemetry::record_payment(&payment);
+        let status_url = format!("/api/payments/{}", payment.id);
+        let mut response = PaymentResponse::from(payment);
+        response.status_url = Some(status_url.clone());
+
+        return Ok((
+            StatusCode::ACCEPTED,
+            [(header::LOCATION, status_url), (header::RETRY_AFTER, "1".to_string())],
+            Json(ApiResponse::success(response)),
+        )
+            .into_response());
+    }
+
+    let payment = match payment_service::create_payment(&state, request).await {
+        Err(e) if e.code() == ErrorCode::DuplicateOrder => {
+            return replay_order(&state, &tenant, customer, order_id).await?.ok_or(e);
+        }
+        result => result?,
+    };
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+
+    // The payment row was still created, so a decline is a 200 carrying the failed payment
+    let declined = payment.payment_status == PaymentStatus::Failed.as_str();
+    let mut body = ApiResponse::success(to_response(&state, payment).await?);
+    if declined {
+        body = body.with_error_code(ErrorCode::GatewayDeclined);
+    }
+
+    Ok(Json(body).into_response())
+}
+
+/// The order's payment that didn't fail, answered as an idempotent replay; `None` when it has none. A 409 instead
+/// when the tenant rejects duplicate orders, and always for another customer's payment.
+async fn replay_order(
+    state: &AppState,
+    tenant: &Tenant,
+    customer: Option<Uuid>,
+    order_id: Uuid,
+) -> AppResult<Option<Response>> {
+    let Some(existing) = payment_service::find_active_by_order(&state.db_pool, tenant.merchant_id, order_id).await?
+    else {
+        return Ok(None);
     };
-    
+    if tenant.duplicate_orders == DuplicateOrders::Reject || customer.is_some_and(|id| id != existing.user_id) {
+        return Err(payment_service::duplicate_order());
+    }
+
+    tracing::info!(payment.id = %existing.id, "Replaying existing payment for order");
+    let mut response = to_response(state, existing).await?;
+    response.idempotent_replay = true;
 
-    Ok(Json(ApiResponse::success(response)))
+    Ok(Some(Json(ApiResponse::success(response)).into_response()))
+}
+
+/// Anonymous on purpose: a price breakdown for the checkout page, nothing about any stored payment.
+pub async fn quote_payment(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<PaymentQuoteRequest>,
+) -> AppResult<Json<ApiResponse<PaymentQuoteResponse>>> {
+    let quote = payment_service::quote(&state, request).await?;
+
+    Ok(Json(ApiResponse::success(quote)))
 }
 
 pub async fn get_payment(
     State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
+    Path(id): Path<Uuid>,
+    Query(query): Query<FieldsQuery>,
+    headers: HeaderMap,
+    format: ResponseFormat,
+) -> AppResult<Response> {
+    let payment = payment_service::get_for_merchant(state.read_pool(), viewer.merchant_id, id).await?;
+    let payment = viewer.visible(payment)?;
+
+    conditional_response(&state, &headers, format, payment, query.fields.as_deref()).await
+}
+
+/// HEAD: existence check without the payment body.
+pub async fn payment_exists(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    let exists = payment_service::exists(state.read_pool(), viewer.merchant_id, viewer.customer, id).await?;
+
+    Ok(if exists { StatusCode::OK } else { StatusCode::NOT_FOUND })
+}
+
+/// Long-poll: answers as soon as the payment leaves PENDING/PROCESSING, or with the payment as it is when the
+/// timeout is up. No database connection is held while waiting, the payment events on the bus wake it up.
+#[tracing::instrument(name = "wait_for_payment", skip(state, viewer))]
+pub async fn wait_for_payment(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
     Path(id): Path<Uuid>,
-) -> Result<Json<ApiResponse<PaymentResponse>>, StatusCode> {
-    let payment = payment_service::get_payment(&state.db_pool, id)
-    .await
-    .map_err(|e| {
-        tracing::warn!(error = %e, "get_payment not found or error");
-        StatusCode::NOT_FOUND
-    })?;
-
-    let response = PaymentResponse {
-        id: payment.id,
-        order_id: payment.order_id,
-        user_id: payment.user_id,
-        amount: payment.amount,
-        currency: payment.currency,
-        payment_method: payment.payment_method,
-        payment_status: payment.payment_status,
-        transaction_id: payment.transaction_id,
-        created_at: payment.created_at.to_rfc3339(),
-        updated_at: payment.updated_at.to_rfc3339(),
+    Query(query): Query<WaitQuery>,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let timeout = wait_timeout(query.timeout.as_deref(), state.config.long_poll_max_secs)?;
+    let deadline = Instant::now() + timeout;
+
+    // Subscribed before reading, so a change between the read and the wait isn't missed
+    let mut events = state.events.subscribe();
+    // The primary, a lagging replica would still show the payment as pending
+    let payment = payment_service::get_for_merchant(&state.db_pool, viewer.merchant_id, id).await?;
+    let mut payment = viewer.visible(payment)?;
+
+    while is_in_flight(&payment.payment_status) {
+        let changed = match tokio::time::timeout_at(deadline, events.recv()).await {
+            Err(_) => break,
+            Ok(Ok(Event::Payment(event))) => event.payment_id == id && !is_in_flight(&event.status),
+            Ok(Ok(_)) => false,
+            // Missed events may include ours
+            Ok(Err(RecvError::Lagged(_))) => true,
+            Ok(Err(RecvError::Closed)) => break,
+        };
+        if changed {
+            payment = payment_service::get_for_merchant(&state.db_pool, viewer.merchant_id, id).await?;
+        }
+    }
+
+    Ok(Json(ApiResponse::success(to_response(&state, payment).await?)))
+}
+
+fn is_in_flight(status: &str) -> bool {
+    status == PaymentStatus::Pending.as_str() || status == PaymentStatus::Processing.as_str()
+}
+
+/// `30s`, `500ms` or plain seconds; 30 seconds when not given.
+fn wait_timeout(raw: Option<&str>, max_secs: u64) -> AppResult<Duration> {
+    let Some(raw) = raw.map(str::trim) else {
+        return Ok(Duration::from_secs(30.min(max_secs)));
+    };
+
+    let parsed = if let Some(ms) = raw.strip_suffix("ms") {
+        ms.parse().ok().map(Duration::from_millis)
+    } else {
+        raw.strip_suffix('s').unwrap_or(raw).parse().ok().map(Duration::from_secs)
+    };
+    let Some(timeout) = parsed else {
+        return Err(AppError::invalid_field(
+            "timeout",
+            FieldErrorCode::Invalid,
+            "timeout must be a duration like 30s or 500ms",
+        ));
     };
+    if timeout > Duration::from_secs(max_secs) {
+        return Err(AppError::invalid_field(
+            "timeout",
+            FieldErrorCode::OutOfRange,
+            format!("timeout can be at most {}s", max_secs),
+        ));
+    }
 
-    Ok(Json(ApiResponse::success(response)))
+    Ok(timeout)
+}
+
+pub async fn count_payments(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
+    Query(query): Query<PaymentCountQuery>,
+    format: ResponseFormat,
+) -> AppResult<Response> {
+    let count = payment_service::count(state.read_pool(), viewer.merchant_id, viewer.customer, &query).await?;
+
+    format.respond(&ApiResponse::success(PaymentCountResponse { count }))
+}
+
+const MAX_LOOKUP_IDS: usize = 100;
+
+/// Batch lookup for list pages, so callers don't need a request per payment.
+pub async fn lookup_payments(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
+    Query(query): Query<FieldsQuery>,
+    format: ResponseFormat,
+    Json(request): Json<PaymentLookupRequest>,
+) -> AppResult<Response> {
+    let (mut requested, by_order) = match (request.ids.is_empty(), request.order_ids.is_empty()) {
+        (false, true) => (request.ids, false),
+        (true, false) => (request.order_ids, true),
+        _ => return Err(AppError::BadRequest("Provide either ids or order_ids".to_string())),
+    };
+    if requested.len() > MAX_LOOKUP_IDS {
+        return Err(AppError::BadRequest(format!("At most {} ids can be looked up at once", MAX_LOOKUP_IDS)));
+    }
+    // Repeated ids are answered once, at their first position
+    let mut seen = HashSet::new();
+    requested.retain(|id| seen.insert(*id));
+
+    let payments = if by_order {
+        payment_service::get_many_by_order(state.read_pool(), viewer.merchant_id, &requested).await?
+    } else {
+        payment_service::get_many(state.read_pool(), viewer.merchant_id, &requested).await?
+    };
+    let payments = payments
+        .into_iter()
+        .filter(|p| viewer.customer.is_none_or(|user_id| p.user_id == user_id))
+        .collect();
+    let mut found: HashMap<Uuid, PaymentResponse> = to_responses(&state, payments)
+        .await?
+        .into_iter()
+        .map(|p| (if by_order { p.order_id } else { p.id }, p))
+        .collect();
+
+    let mut results = Vec::with_capacity(requested.len());
+    for id in requested {
+        let payment = found.remove(&id).map(|p| sparse(p, query.fields.as_deref())).transpose()?;
+        results.push(PaymentLookupResult { id, found: payment.is_some(), payment });
+    }
+
+    format.respond(&ApiResponse::success(results))
 }
 
 pub async fn get_payment_by_order(
     State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
     Path(order_id): Path<Uuid>,
-) -> Result<Json<ApiResponse<PaymentResponse>>, StatusCode> {
-    let payment = payment_service::get_payment_by_order(&state.db_pool, order_id)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    let response = PaymentResponse {
-        id: payment.id,
-        order_id: payment.order_id,
-        user_id: payment.user_id,
-        amount: payment.amount,
-        currency: payment.currency,
-        payment_method: payment.payment_method,
-        payment_status: payment.payment_status,
-        transaction_id: payment.transaction_id,
-        created_at: payment.created_at.to_rfc3339(),
-        updated_at: payment.updated_at.to_rfc3339(),
+    Query(query): Query<FieldsQuery>,
+    headers: HeaderMap,
+    format: ResponseFormat,
+) -> AppResult<Response> {
+    let payment = payment_service::get_payment_by_order(state.read_pool(), viewer.merchant_id, order_id).await?;
+    let payment = viewer.visible(payment)?;
+
+    conditional_response(&state, &headers, format, payment, query.fields.as_deref()).await
+}
+
+pub async fn get_payment_by_invoice(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
+    Path(invoice_number): Path<String>,
+    Query(query): Query<FieldsQuery>,
+    headers: HeaderMap,
+    format: ResponseFormat,
+) -> AppResult<Response> {
+    let payment = invoices::get_by_number(state.read_pool(), viewer.merchant_id, &invoice_number).await?;
+    let payment = viewer.visible(payment)?;
+
+    conditional_response(&state, &headers, format, payment, query.fields.as_deref()).await
+}
+
+pub async fn get_receipt(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ReceiptQuery>,
+    headers: HeaderMap,
+) -> AppResult<impl IntoResponse> {
+    let payment = payment_service::get_for_merchant(&state.db_pool, viewer.merchant_id, id).await?;
+    let payment = viewer.visible(payment)?;
+    let accept_language = headers.get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok());
+    let language = receipts::language(query.lang.as_deref(), accept_language);
+
+    let pdf = receipts::pdf(&state, &payment, language).await?;
+    let disposition = format!("inline; filename=\"receipt-{}.pdf\"", payment.id);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        pdf,
+    ))
+}
+
+pub async fn get_qr_code(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    viewer: Viewer,
+    Path(id): Path<Uuid>,
+    Query(query): Query<QrQuery>,
+) -> AppResult<impl IntoResponse> {
+    let payment = payment_service::get_for_merchant(&state.db_pool, viewer.merchant_id, id).await?;
+    let payment = viewer.visible(payment)?;
+    let payload = qr::payload(&payment)?;
+
+    match query.format.as_deref().unwrap_or("png") {
+        "png" => Ok(([(header::CONTENT_TYPE, "image/png")], qr::png(&payload)?).into_response()),
+        "text" => Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], payload).into_response()),
+        other => Err(AppError::BadRequest(format!("Unknown QR format: {}", other))),
+    }
+}
+
+/// ACS result for a payment awaiting 3-D Secure. Routed behind `require_signature`, so `trans_status` comes from
+/// the ACS and not from whoever knows the payment id.
+#[tracing::instrument(name = "three_ds_callback", skip(state))]
+pub async fn three_ds_callback(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(callback): Json<ThreeDsCallbackRequest>,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let payment = payment_service::complete_three_ds(&state.db_pool, id, callback).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    tracing::info!("3-D Secure completed for payment {}: {}", id, payment.payment_status);
+
+    Ok(Json(ApiResponse::success(to_response(&state, payment).await?)))
+}
+
+/// Serves the payment with a weak ETag (hash of the body, so it covers the language and the crypto deposit too)
+/// and Last-Modified; pollers whose copy is still current get a 304 without a body.
+async fn conditional_response(
+    state: &AppState,
+    headers: &HeaderMap,
+    format: ResponseFormat,
+    payment: Payment,
+    fields: Option<&str>,
+) -> AppResult<Response> {
+    // Deposit progress is stored on the crypto payment and doesn't move payments.updated_at
+    let last_modified = (payment.payment_method != METHOD_CRYPTO).then_some(payment.updated_at);
+    let data = sparse(to_response(state, payment).await?, fields)?;
+    let body = format.encode(&ApiResponse::success(data))?;
+    // Weak, since the compression layer may change the bytes on the wire
+    let opaque_tag = format!("\"{}\"", hex::encode(&Sha256::digest(&body)[..16]));
+    let etag = format!("W/{}", opaque_tag);
+
+    // If-Modified-Since only counts when the client has no ETag to compare
+    let not_modified = match headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        Some(if_none_match) => if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == opaque_tag),
+        None => headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+            .zip(last_modified)
+            .is_some_and(|(since, modified)| modified.timestamp() <= since.timestamp()),
+    };
+
+    let mut validators = HeaderMap::new();
+    validators.insert(header::ETAG, HeaderValue::from_str(&etag).expect("hex ETag is a valid header"));
+    if let Some(modified) = last_modified {
+        let http_date = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        validators.insert(header::LAST_MODIFIED, HeaderValue::from_str(&http_date).expect("HTTP date is a valid header"));
+    }
+    validators.insert(header::CACHE_CONTROL, HeaderValue::from_static("private, no-cache"));
+    validators.insert(header::VARY, HeaderValue::from_static("Accept, Accept-Language"));
+
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, validators).into_response());
+    }
+    validators.insert(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()));
+
+    Ok((validators, body).into_response())
+}
+
+/// Keeps only the requested top-level fields; names that don't exist are ignored.
+fn sparse(response: PaymentResponse, fields: Option<&str>) -> AppResult<serde_json::Value> {
+    let value = serde_json::to_value(response).map_err(|e| AppError::Internal(e.into()))?;
+
+    match (fields, value) {
+        (Some(fields), serde_json::Value::Object(mut object)) => {
+            let wanted: HashSet<&str> = fields.split(',').map(str::trim).filter(|f| !f.is_empty()).collect();
+            object.retain(|key, _| wanted.contains(key.as_str()));
+            Ok(serde_json::Value::Object(object))
+        }
+        (_, value) => Ok(value),
+    }
+}
+
+/// Attaches method-specific details that live outside the payments table.
+async fn to_response(state: &AppState, payment: Payment) -> AppResult<PaymentResponse> {
+    let crypto = if payment.payment_method == METHOD_CRYPTO {
+        crypto_payment::get(&state.db_pool, payment.id).await?
+    } else {
+        None
     };
 
-    Ok(Json(ApiResponse::success(response)))
-}
\ No newline at end of file
+    let splits = splits::for_payment(&state.db_pool, payment.id).await?;
+
+    Ok(with_details(payment, crypto, splits))
+}
+
+/// `to_response` for many payments, with two queries for all of them instead of two per payment.
+async fn to_responses(state: &AppState, payments: Vec<Payment>) -> AppResult<Vec<PaymentResponse>> {
+    let ids: Vec<Uuid> = payments.iter().map(|p| p.id).collect();
+    let crypto_ids: Vec<Uuid> = payments.iter().filter(|p| p.payment_method == METHOD_CRYPTO).map(|p| p.id).collect();
+
+    let mut crypto: HashMap<Uuid, CryptoPayment> = if crypto_ids.is_empty() {
+        HashMap::new()
+    } else {
+        let deposits = crypto_payment::get_many(&state.db_pool, &crypto_ids).await?;
+        deposits.into_iter().map(|c| (c.payment_id, c)).collect()
+    };
+    let mut splits: HashMap<Uuid, Vec<PaymentSplit>> = HashMap::new();
+    for split in splits::for_payments(&state.db_pool, &ids).await? {
+        splits.entry(split.payment_id).or_default().push(split);
+    }
+
+    Ok(payments
+        .into_iter()
+        .map(|p| {
+            let deposit = crypto.remove(&p.id);
+            let payment_splits = splits.remove(&p.id).unwrap_or_default();
+            with_details(p, deposit, payment_splits)
+        })
+        .collect())
+}
+
+fn with_details(payment: Payment, crypto: Option<CryptoPayment>, splits: Vec<PaymentSplit>) -> PaymentResponse {
+    let mut response = PaymentResponse::from(payment);
+    response.crypto_deposit = crypto.map(Into::into);
+    response.splits = splits;
+
+    response
+}
@@ -0,0 +1,62 @@
+use crate::{
+    dto::{
+        ApiResponse, ConfirmPaymentIntentRequest, CreatePaymentIntentRequest, PaymentIntentResponse, PaymentResponse,
+    },
+    error::AppResult,
+    handlers::extract::{Json, Path, PaymentsRead, PaymentsWrite, RequireScope},
+    middleware::{auth::AuthUser, tenant::Tenant},
+    services::{payment_intents, AppState},
+    telemetry,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "create_payment_intent", skip(state))]
+pub async fn create_payment_intent(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(tenant): Extension<Tenant>,
+    Json(request): Json<CreatePaymentIntentRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<PaymentIntentResponse>>)> {
+    let (intent, client_secret) = payment_intents::create(&state.db_pool, tenant.merchant_id, request).await?;
+    tracing::info!("Payment intent {} created for order {}", intent.id, intent.order_id);
+
+    let mut response = PaymentIntentResponse::from(intent);
+    response.client_secret = Some(client_secret);
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(response))))
+}
+
+pub async fn get_payment_intent(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Extension(tenant): Extension<Tenant>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<PaymentIntentResponse>>> {
+    let intent = payment_intents::get(&state.db_pool, tenant.merchant_id, id).await?;
+
+    Ok(Json(ApiResponse::success(intent.into())))
+}
+
+#[tracing::instrument(name = "confirm_payment_intent", skip(state, request))]
+pub async fn confirm_payment_intent(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(tenant): Extension<Tenant>,
+    auth: Option<Extension<AuthUser>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ConfirmPaymentIntentRequest>,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let payer_id = auth.map(|Extension(auth)| auth.user_id);
+    let payment = payment_intents::confirm(&state, tenant.merchant_id, id, payer_id, request).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    tracing::info!("Payment intent {} confirmed with payment {}: {}", id, payment.id, payment.payment_status);
+
+    Ok(Json(ApiResponse::success(payment.into())))
+}
@@ -0,0 +1,60 @@
+use crate::{
+    dto::{ApiResponse, CreatePaymentLinkRequest, PayPaymentLinkRequest, PaymentLinkResponse, PaymentResponse},
+    error::{AppError, AppResult},
+    handlers::extract::{Json, Path, PaymentsWrite, RequireScope},
+    middleware::{auth::AuthUser, tenant::Tenant},
+    services::{payment_links, AppState},
+    telemetry,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use std::sync::Arc;
+
+/// Sales staff of the merchant (or platform admins) create links to send to customers.
+#[tracing::instrument(name = "create_payment_link", skip(state))]
+pub async fn create_payment_link(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Extension(tenant): Extension<Tenant>,
+    Json(request): Json<CreatePaymentLinkRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<PaymentLinkResponse>>)> {
+    if !auth.is_admin() && auth.merchant_id.is_none() {
+        return Err(AppError::Forbidden("Only merchant staff can create payment links".to_string()));
+    }
+
+    let link = payment_links::create(&state.db_pool, &state.config, tenant.merchant_id, auth.user_id, request).await?;
+    tracing::info!("Payment link {} created for order {}", link.id, link.order_id);
+    let response = payment_links::to_response(&state.db_pool, &state.config, link).await?;
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(response))))
+}
+
+pub async fn get_payment_link(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> AppResult<Json<ApiResponse<PaymentLinkResponse>>> {
+    let link = payment_links::get(&state.db_pool, &state.config, &token).await?;
+    let response = payment_links::to_response(&state.db_pool, &state.config, link).await?;
+
+    Ok(Json(ApiResponse::success(response)))
+}
+
+#[tracing::instrument(name = "pay_payment_link", skip(state, token))]
+pub async fn pay_payment_link(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    auth: Option<Extension<AuthUser>>,
+    Json(request): Json<PayPaymentLinkRequest>,
+) -> AppResult<Json<ApiResponse<PaymentResponse>>> {
+    let payer_id = auth.map(|Extension(auth)| auth.user_id);
+    let payment = payment_links::pay(&state, &token, payer_id, request).await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    tracing::info!("Payment link paid with payment {}: {}", payment.id, payment.payment_status);
+
+    Ok(Json(ApiResponse::success(payment.into())))
+}
@@ -0,0 +1,60 @@
+use crate::{
+    dto::{ApiResponse, PaymentMethodResponse, TokenizePaymentMethodRequest},
+    error::AppResult,
+    handlers::extract::{Json, Path, PaymentsRead, PaymentsWrite, RequireScope},
+    middleware::auth::AuthUser,
+    services::{payment_method_service, AppState},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "tokenize_payment_method", skip(state, request), fields(user_id = %auth.user_id))]
+pub async fn tokenize_payment_method(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Json(request): Json<TokenizePaymentMethodRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<PaymentMethodResponse>>)> {
+    let method = payment_method_service::tokenize(&state.db_pool, &state.vault, auth.user_id, request).await?;
+    tracing::info!("Payment method tokenized: {}", method.id);
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(method.into()))))
+}
+
+pub async fn list_payment_methods(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Extension(auth): Extension<AuthUser>,
+) -> AppResult<Json<ApiResponse<Vec<PaymentMethodResponse>>>> {
+    let methods = payment_method_service::list_for_user(&state.db_pool, auth.user_id).await?;
+
+    Ok(Json(ApiResponse::success(methods.into_iter().map(Into::into).collect())))
+}
+
+pub async fn set_default_payment_method(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<PaymentMethodResponse>>> {
+    let method = payment_method_service::set_default(&state.db_pool, auth.user_id, id).await?;
+
+    Ok(Json(ApiResponse::success(method.into())))
+}
+
+pub async fn delete_payment_method(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    payment_method_service::delete(&state.db_pool, auth.user_id, id).await?;
+    tracing::info!("Payment method deleted: {}", id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,92 @@
+use crate::{
+    dto::{
+        ApiResponse, ChangePlanRequest, CreateSubscriptionRequest, PlanChangeResponse, SubscriptionResponse,
+        UpdateSubscriptionRequest,
+    },
+    error::AppResult,
+    handlers::extract::{Json, Path, PaymentsRead, PaymentsWrite, RequireScope},
+    middleware::auth::AuthUser,
+    services::{subscription_service, AppState},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "create_subscription", skip(state), fields(user_id = %auth.user_id))]
+pub async fn create_subscription(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Json(request): Json<CreateSubscriptionRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<SubscriptionResponse>>)> {
+    let subscription = subscription_service::create(&state.db_pool, auth.user_id, request).await?;
+    tracing::info!("Subscription created: {}", subscription.id);
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(subscription.into()))))
+}
+
+pub async fn list_subscriptions(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Extension(auth): Extension<AuthUser>,
+) -> AppResult<Json<ApiResponse<Vec<SubscriptionResponse>>>> {
+    let subscriptions = subscription_service::list_for_user(state.read_pool(), auth.user_id).await?;
+
+    Ok(Json(ApiResponse::success(subscriptions.into_iter().map(Into::into).collect())))
+}
+
+pub async fn get_subscription(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Extension(auth): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<SubscriptionResponse>>> {
+    let subscription = subscription_service::get_for_user(&state.db_pool, auth.user_id, id).await?;
+
+    Ok(Json(ApiResponse::success(subscription.into())))
+}
+
+pub async fn update_subscription(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateSubscriptionRequest>,
+) -> AppResult<Json<ApiResponse<SubscriptionResponse>>> {
+    let subscription = subscription_service::update(&state.db_pool, auth.user_id, id, request).await?;
+
+    Ok(Json(ApiResponse::success(subscription.into())))
+}
+
+pub async fn cancel_subscription(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ApiResponse<SubscriptionResponse>>> {
+    let subscription = subscription_service::cancel(&state.db_pool, auth.user_id, id).await?;
+    tracing::info!("Subscription cancelled: {}", id);
+
+    Ok(Json(ApiResponse::success(subscription.into())))
+}
+
+#[tracing::instrument(name = "change_subscription_plan", skip(state), fields(user_id = %auth.user_id))]
+pub async fn change_plan(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ChangePlanRequest>,
+) -> AppResult<Json<ApiResponse<PlanChangeResponse>>> {
+    let (subscription, adjustment) = subscription_service::change_plan(&state, auth.user_id, id, request).await?;
+    tracing::info!("Subscription {} plan changed, prorated amount {}", id, adjustment.prorated_amount);
+
+    Ok(Json(ApiResponse::success(PlanChangeResponse {
+        subscription: subscription.into(),
+        adjustment,
+    })))
+}
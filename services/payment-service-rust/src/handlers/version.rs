@@ -0,0 +1,18 @@
+use crate::dto::{ApiResponse, VersionResponse};
+use axum::Json;
+use chrono::DateTime;
+
+/// What exactly is deployed, filled in by build.rs.
+pub async fn version() -> Json<ApiResponse<VersionResponse>> {
+    let built_at = env!("BUILD_TIMESTAMP").parse().ok().and_then(|secs| DateTime::from_timestamp(secs, 0));
+    let features = env!("BUILD_FEATURES");
+
+    Json(ApiResponse::success(VersionResponse {
+        service: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("BUILD_GIT_SHA"),
+        built_at,
+        profile: env!("BUILD_PROFILE"),
+        features: features.split(',').filter(|f| !f.is_empty()).collect(),
+    }))
+}
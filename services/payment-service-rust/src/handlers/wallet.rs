@@ -0,0 +1,110 @@
+use crate::{
+    dto::{
+        ApiResponse, CreatePaymentRequest, Page, PaymentResponse, WalletResponse, WalletTopUpRequest,
+        WalletTransactionsQuery,
+    },
+    error::{AppError, AppResult},
+    handlers::extract::{Json, PaymentsRead, PaymentsWrite, RequireScope},
+    middleware::auth::AuthUser,
+    models::{WalletTransaction, DEFAULT_MERCHANT_ID},
+    services::{payment_method_service, payment_service, wallets, AppState},
+    telemetry,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Extension,
+};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+pub async fn get_wallet(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Extension(auth): Extension<AuthUser>,
+) -> AppResult<Json<ApiResponse<Vec<WalletResponse>>>> {
+    let wallets = wallets::list_for_user(&state.db_pool, auth.user_id).await?;
+
+    Ok(Json(ApiResponse::success(wallets.into_iter().map(Into::into).collect())))
+}
+
+pub async fn list_transactions(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsRead>,
+    Extension(auth): Extension<AuthUser>,
+    Query(query): Query<WalletTransactionsQuery>,
+) -> AppResult<Json<ApiResponse<Page<WalletTransaction>>>> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = (page as i64 - 1) * per_page as i64;
+
+    let (items, total) = wallets::transactions_for_user(
+        state.read_pool(),
+        auth.user_id,
+        query.currency.as_deref(),
+        per_page as i64,
+        offset,
+    )
+    .await?;
+
+    Ok(Json(ApiResponse::success(Page { items, page, per_page, total })))
+}
+
+/// Charges a saved card; the wallet is credited once the payment completes (possibly after 3-D Secure).
+#[tracing::instrument(name = "wallet_topup", skip(state), fields(user_id = %auth.user_id))]
+pub async fn top_up(
+    State(state): State<Arc<AppState>>,
+    _: RequireScope<PaymentsWrite>,
+    Extension(auth): Extension<AuthUser>,
+    Json(request): Json<WalletTopUpRequest>,
+) -> AppResult<(StatusCode, Json<ApiResponse<PaymentResponse>>)> {
+    if request.amount <= Decimal::ZERO {
+        return Err(AppError::BadRequest("amount must be positive".to_string()));
+    }
+
+    let token = match request.payment_method_token {
+        Some(token) => token,
+        None => payment_method_service::find_for_user(&state.db_pool, auth.user_id, None).await?.token,
+    };
+
+    let payment = payment_service::create_payment(
+        &state,
+        CreatePaymentRequest {
+            order_id: Uuid::new_v4(),
+            user_id: auth.user_id,
+            amount: request.amount,
+            currency: request.currency.to_uppercase(),
+            payment_method: "CREDIT_CARD".to_string(),
+            payment_method_token: Some(token),
+            return_url: request.return_url,
+            crypto_asset: None,
+            installments: None,
+            wallet_amount: None,
+            voucher_code: None,
+            promo_code: None,
+            country: None,
+            splits: None,
+            escrow: false,
+            merchant_initiated: false,
+            subscription_id: None,
+            wallet_topup: true,
+            promotion_id: None,
+            discount_amount: Decimal::ZERO,
+            tax_jurisdiction: None,
+            tax_rate: Decimal::ZERO,
+            // Stored value is sold by the platform itself
+            merchant_id: DEFAULT_MERCHANT_ID,
+            payer_id: Some(auth.user_id),
+        },
+    )
+    .await?;
+    telemetry::record_payment(&payment);
+    state.events.publish(&payment);
+    tracing::info!("Wallet top-up {} created: {}", payment.id, payment.payment_status);
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(payment.into()))))
+}
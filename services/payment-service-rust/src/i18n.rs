@@ -0,0 +1,143 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::{collections::HashMap, sync::LazyLock};
+
+/// Message catalogs keyed by the English source text (or `status.<STATUS>`), `{}` marks a placeholder.
+static CATALOGS: LazyLock<HashMap<Locale, HashMap<String, String>>> = LazyLock::new(|| {
+    [
+        (Locale::En, include_str!("../locales/en.json")),
+        (Locale::Tr, include_str!("../locales/tr.json")),
+    ]
+    .into_iter()
+    .map(|(locale, raw)| (locale, serde_json::from_str(raw).expect("invalid message catalog")))
+    .collect()
+});
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Tr,
+}
+
+impl Locale {
+    pub fn parse(tag: &str) -> Option<Self> {
+        // tr-TR and tr_TR fall back to tr
+        match tag.split(['-', '_']).next()?.trim().to_lowercase().as_str() {
+            "en" => Some(Locale::En),
+            "tr" => Some(Locale::Tr),
+            _ => None,
+        }
+    }
+
+    /// Best supported language of an Accept-Language header, by q-value.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut tags: Vec<(&str, f32)> = header
+            .split(',')
+            .map(|part| {
+                let mut pieces = part.split(';');
+                let tag = pieces.next().unwrap_or_default().trim();
+                let q = pieces
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                (tag, q)
+            })
+            .filter(|(_, q)| *q > 0.0)
+            .collect();
+        tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        tags.into_iter().find_map(|(tag, _)| Self::parse(tag))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Locale::En => "en",
+            Locale::Tr => "tr",
+        }
+    }
+}
+
+/// Language of the request being handled, English outside of one (e.g. background jobs).
+pub fn current() -> Locale {
+    LOCALE.try_with(|locale| *locale).unwrap_or_default()
+}
+
+/// Translates into the current language: its catalog, then English, then the text itself.
+pub fn translate(text: &str) -> String {
+    let locale = current();
+    [locale, Locale::En]
+        .iter()
+        .filter_map(|l| CATALOGS.get(l))
+        .find_map(|catalog| lookup(catalog, text))
+        .unwrap_or_else(|| text.to_string())
+}
+
+pub fn status_description(status: &str) -> String {
+    let key = format!("status.{}", status);
+    let description = translate(&key);
+    if description == key { status.to_string() } else { description }
+}
+
+fn lookup(catalog: &HashMap<String, String>, text: &str) -> Option<String> {
+    if let Some(exact) = catalog.get(text) {
+        return Some(exact.clone());
+    }
+
+    // Messages built with format!: match the template around the placeholders and carry the values over
+    catalog.iter().filter(|(key, _)| key.contains("{}")).find_map(|(key, translation)| {
+        let values = match_template(key, text)?;
+        let mut parts = translation.split("{}");
+        let mut out = parts.next().unwrap_or_default().to_string();
+        for (value, part) in values.iter().zip(parts) {
+            out.push_str(value);
+            out.push_str(part);
+        }
+        Some(out)
+    })
+}
+
+fn match_template<'a>(template: &str, text: &'a str) -> Option<Vec<&'a str>> {
+    let parts: Vec<&str> = template.split("{}").collect();
+    let mut rest = text.strip_prefix(parts[0])?;
+    let mut values = Vec::with_capacity(parts.len() - 1);
+
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        let end = if i == parts.len() - 1 {
+            rest.strip_suffix(part).map(str::len)?
+        } else if part.is_empty() {
+            return None;
+        } else {
+            rest.find(part)?
+        };
+        values.push(&rest[..end]);
+        rest = &rest[end + part.len()..];
+    }
+
+    Some(values)
+}
+
+/// Runs the request with the language from `Accept-Language` so responses come out translated.
+pub async fn localize(request: Request, next: Next) -> Response {
+    let locale = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Locale::from_accept_language)
+        .unwrap_or_default();
+
+    let mut response = LOCALE.scope(locale, next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(locale.as_str()) {
+        response.headers_mut().insert(header::CONTENT_LANGUAGE, value);
+    }
+
+    response
+}
@@ -0,0 +1,18 @@
+use crate::services::{api_key_usage, AppState};
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        match api_key_usage::flush(&state.db_pool, &state.redis_conn).await {
+            Ok(0) => {}
+            Ok(flushed) => tracing::debug!("Flushed API key usage of {} merchants", flushed),
+            Err(e) => tracing::error!(error = %e, "API key usage flush job failed"),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::services::{bank_transfer, AppState};
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        match bank_transfer::expire_unconfirmed(&state.db_pool).await {
+            Ok(expired) if expired.is_empty() => {}
+            Ok(expired) => {
+                tracing::info!("Expired {} unconfirmed bank transfers", expired.len());
+                for payment in &expired {
+                    state.events.publish(payment);
+                }
+            }
+            Err(e) => tracing::error!(error = %e, "bank transfer expiry job failed"),
+        }
+    }
+}
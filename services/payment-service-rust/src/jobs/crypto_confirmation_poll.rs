@@ -0,0 +1,50 @@
+use crate::services::{crypto_payment, AppState};
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Fallback for providers that don't (reliably) push webhooks. Also expires underpaid deposits, after the poll so a
+/// top-up that just arrived still counts.
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let references = match crypto_payment::open_references(&state.db_pool).await {
+            Ok(references) => references,
+            Err(e) => {
+                tracing::error!(error = %e, "crypto confirmation poll failed");
+                continue;
+            }
+        };
+
+        for reference in references {
+            let update = match state.crypto_provider.deposit_update(&reference).await {
+                Ok(Some(update)) => update,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!(error = %e, reference = %reference, "crypto provider status check failed");
+                    continue;
+                }
+            };
+
+            match crypto_payment::apply_update(&state, &reference, update, None).await {
+                Ok(Some(payment)) => state.events.publish(&payment),
+                Ok(None) => {}
+                Err(e) => tracing::error!(error = %e, reference = %reference, "applying crypto deposit update failed"),
+            }
+        }
+
+        match crypto_payment::expire_underpaid(&state).await {
+            Ok(expired) if expired.is_empty() => {}
+            Ok(expired) => {
+                tracing::info!("Expired {} underpaid crypto deposits", expired.len());
+                for payment in &expired {
+                    state.events.publish(payment);
+                }
+            }
+            Err(e) => tracing::error!(error = %e, "underpaid crypto deposit expiry failed"),
+        }
+    }
+}
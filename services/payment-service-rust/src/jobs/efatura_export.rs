@@ -0,0 +1,33 @@
+use crate::services::{efatura, AppState};
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(15 * 60);
+const BATCH_SIZE: i64 = 200;
+
+/// Prepares UBL-TR documents for newly invoiced payments.
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let ids = match efatura::pending_ids(&state.db_pool, BATCH_SIZE).await {
+            Ok(ids) => ids,
+            Err(e) => {
+                tracing::error!(error = %e, "e-Fatura export job failed");
+                continue;
+            }
+        };
+
+        let mut exported = 0;
+        for id in &ids {
+            match efatura::document_for(&state.db_pool, *id).await {
+                Ok(_) => exported += 1,
+                Err(e) => tracing::warn!(error = %e, "e-Fatura export of payment {} failed", id),
+            }
+        }
+        if exported > 0 {
+            tracing::info!("Exported {} e-Fatura documents", exported);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::services::{escrow, AppState};
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        match escrow::release_expired(&state.db_pool).await {
+            Ok(released) if released.is_empty() => {}
+            Ok(released) => {
+                tracing::info!("Auto-released {} escrowed payments", released.len());
+                for payment in &released {
+                    state.events.publish(payment);
+                }
+            }
+            Err(e) => tracing::error!(error = %e, "escrow release job failed"),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::services::{alerting::Alert, AppState};
+use rust_decimal::Decimal;
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Alerts when the share of failed payments over the last ALERT_FAILURE_WINDOW_MINUTES reaches
+/// ALERT_FAILURE_RATE_PERCENT, typically a gateway outage.
+pub async fn run(state: Arc<AppState>) {
+    let config = &state.config;
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let counts = sqlx::query_as::<_, (i64, i64)>(
+            r#"
+            SELECT COUNT(*), COUNT(*) FILTER (WHERE payment_status = 'FAILED')
+            FROM payments
+            WHERE created_at > now() - make_interval(mins => $1)
+            "#,
+        )
+        .bind(config.alert_failure_window_minutes)
+        .fetch_one(&state.db_pool)
+        .await;
+
+        let (total, failed) = match counts {
+            Ok(counts) => counts,
+            Err(e) => {
+                tracing::error!(error = %e, "Failure rate check failed");
+                continue;
+            }
+        };
+        // A couple of declined cards at night are not an outage
+        if total < config.alert_failure_min_payments {
+            continue;
+        }
+
+        let rate = Decimal::from(failed * 100) / Decimal::from(total);
+        if rate < config.alert_failure_rate_percent {
+            continue;
+        }
+
+        state
+            .alerts
+            .send(Alert {
+                key: "failure_rate".to_string(),
+                title: "Payment failure rate spike".to_string(),
+                text: format!(
+                    "{} of {} payments ({}%) failed in the last {} minutes",
+                    failed,
+                    total,
+                    rate.round_dp(1),
+                    config.alert_failure_window_minutes
+                ),
+            })
+            .await;
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{
+    events::Event,
+    models::PaymentStatus,
+    services::{alerting::Alert, fx, AppState},
+};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Alerts on payments worth at least ALERT_HIGH_VALUE_USD once they go through.
+pub async fn run(state: Arc<AppState>) {
+    let Some(threshold) = state.config.alert_high_value_usd else {
+        return;
+    };
+    let mut events = state.events.subscribe();
+    let paid = [PaymentStatus::Completed.as_str(), PaymentStatus::Escrowed.as_str()];
+
+    loop {
+        let event = match events.recv().await {
+            Ok(Event::Payment(event)) if paid.contains(&event.status.as_str()) => event,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::warn!("High-value alert listener fell behind, {} events missed", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let usd = match fx::convert(&state.config, event.amount, &event.currency, "USD") {
+            Ok((usd, _)) => usd,
+            Err(e) => {
+                tracing::warn!(error = %e, "Cannot value payment {} in USD", event.payment_id);
+                continue;
+            }
+        };
+        if usd < threshold {
+            continue;
+        }
+
+        state
+            .alerts
+            .send(Alert {
+                // Escrowed then completed is still one payment
+                key: format!("high_value:{}", event.payment_id),
+                title: "High-value payment".to_string(),
+                text: format!(
+                    "Payment {} of {} {} (≈ {} USD) by user {} is {}",
+                    event.payment_id,
+                    event.amount,
+                    event.currency,
+                    usd.round_dp(2),
+                    event.user_id,
+                    event.status
+                ),
+            })
+            .await;
+    }
+}
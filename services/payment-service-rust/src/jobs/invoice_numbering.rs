@@ -0,0 +1,55 @@
+use crate::{
+    events::Event,
+    models::PaymentStatus,
+    services::{invoices, AppState},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+// Sweep for payments the event listener missed (lagged, or the assignment failed)
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const BATCH_SIZE: i64 = 100;
+
+/// Invoices payments as soon as they complete, with a periodic sweep as a safety net.
+pub async fn run(state: Arc<AppState>) {
+    let mut events = state.events.subscribe();
+    let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(Event::Payment(event)) if event.status == PaymentStatus::Completed.as_str() => {
+                    assign(&state, event.payment_id).await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("Invoice numbering fell behind, {} events left to the sweep", missed);
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = ticker.tick() => {
+                match invoices::pending_ids(&state.db_pool, BATCH_SIZE).await {
+                    Ok(ids) => {
+                        for id in ids {
+                            assign(&state, id).await;
+                        }
+                    }
+                    Err(e) => tracing::error!(error = %e, "invoice numbering sweep failed"),
+                }
+            }
+        }
+    }
+}
+
+async fn assign(state: &AppState, payment_id: Uuid) {
+    match invoices::assign(&state.db_pool, &state.config, payment_id).await {
+        Ok(Some(payment)) => tracing::info!(
+            "Invoice {} issued for payment {}",
+            payment.invoice_number.as_deref().unwrap_or_default(),
+            payment_id
+        ),
+        Ok(None) => {}
+        Err(e) => tracing::error!(error = %e, "Could not issue invoice number for payment {}", payment_id),
+    }
+}
@@ -0,0 +1,77 @@
+use crate::services::AppState;
+use serde::Serialize;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, OnceLock},
+};
+use tokio::task::JoinHandle;
+
+pub mod api_key_usage_flush;
+pub mod bank_transfer_expiry;
+pub mod crypto_confirmation_poll;
+pub mod efatura_export;
+pub mod escrow_release;
+pub mod failure_rate_alerts;
+pub mod high_value_alerts;
+pub mod invoice_numbering;
+pub mod outbound_webhooks;
+pub mod payment_archival;
+pub mod payment_partitions;
+pub mod payout_generation;
+pub mod receipt_emails;
+pub mod replica_health;
+pub mod subscription_billing;
+pub mod volume_anomalies;
+pub mod wallet_reconciliation;
+pub mod work_queue;
+
+/// Starts all background jobs on the Tokio runtime.
+pub fn spawn_all(state: Arc<AppState>) {
+    spawn("api_key_usage_flush", api_key_usage_flush::run(state.clone()));
+    spawn("bank_transfer_expiry", bank_transfer_expiry::run(state.clone()));
+    spawn("crypto_confirmation_poll", crypto_confirmation_poll::run(state.clone()));
+    spawn("efatura_export", efatura_export::run(state.clone()));
+    spawn("escrow_release", escrow_release::run(state.clone()));
+    spawn("failure_rate_alerts", failure_rate_alerts::run(state.clone()));
+    spawn("high_value_alerts", high_value_alerts::run(state.clone()));
+    spawn("invoice_numbering", invoice_numbering::run(state.clone()));
+    spawn("outbound_webhooks", outbound_webhooks::run(state.clone()));
+    spawn("payment_archival", payment_archival::run(state.clone()));
+    spawn("payment_partitions", payment_partitions::run(state.clone()));
+    spawn("payout_generation", payout_generation::run(state.clone()));
+    spawn("receipt_emails", receipt_emails::run(state.clone()));
+    spawn("replica_health", replica_health::run(state.clone()));
+    spawn("subscription_billing", subscription_billing::run(state.clone()));
+    spawn("volume_anomalies", volume_anomalies::run(state.clone()));
+    spawn("wallet_reconciliation", wallet_reconciliation::run(state.clone()));
+    spawn("work_queue", work_queue::run(state));
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobStatus {
+    pub name: &'static str,
+    /// False once the loop has exited: a panic, or a job that is disabled by configuration
+    pub running: bool,
+}
+
+type Handles = Mutex<Vec<(&'static str, JoinHandle<()>)>>;
+
+fn handles() -> &'static Handles {
+    static HANDLES: OnceLock<Handles> = OnceLock::new();
+    HANDLES.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn spawn(name: &'static str, job: impl Future<Output = ()> + Send + 'static) {
+    let handle = tokio::spawn(job);
+    handles().lock().unwrap_or_else(|e| e.into_inner()).push((name, handle));
+}
+
+/// Whether each background job is still running.
+pub fn statuses() -> Vec<JobStatus> {
+    handles()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(name, handle)| JobStatus { name, running: !handle.is_finished() })
+        .collect()
+}
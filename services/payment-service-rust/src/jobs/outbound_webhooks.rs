@@ -0,0 +1,25 @@
+use crate::services::{outbound_webhooks, AppState};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Queues a webhook delivery per subscription for every published event.
+pub async fn run(state: Arc<AppState>) {
+    let mut events = state.events.subscribe();
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::warn!("Webhook dispatcher fell behind, {} events missed", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        match outbound_webhooks::dispatch(&state.db_pool, &event).await {
+            Ok(0) => {}
+            Ok(queued) => tracing::debug!(event_type = event.event_type(), queued, "Webhook deliveries queued"),
+            Err(e) => tracing::error!(error = %e, "Could not queue webhook deliveries for {}", event.event_type()),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use crate::services::{archival, AppState};
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub async fn run(state: Arc<AppState>) {
+    let Some(storage) = &state.archive_storage else {
+        return;
+    };
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let months = match archival::due_partitions(&state.db_pool, state.config.archive_after_months).await {
+            Ok(months) => months,
+            Err(e) => {
+                tracing::error!(error = %e, "payment archival job failed");
+                continue;
+            }
+        };
+
+        // One month at a time, a failed month is retried on the next run
+        for month in months {
+            match archival::archive_month(&state.db_pool, storage, month).await {
+                Ok(rows) => tracing::info!("Archived {} payments of {}", rows, month.format("%Y-%m")),
+                Err(e) => {
+                    tracing::error!(error = %e, "archiving payments of {} failed", month.format("%Y-%m"));
+                    break;
+                }
+            }
+        }
+    }
+}
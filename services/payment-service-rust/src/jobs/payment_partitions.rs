@@ -0,0 +1,28 @@
+use crate::services::AppState;
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Months created ahead, so inserts never fall into the default partition
+const MONTHS_AHEAD: i32 = 3;
+
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let created = sqlx::query(
+            r#"
+            SELECT create_payments_partition((date_trunc('month', NOW()) + make_interval(months => m))::DATE)
+            FROM generate_series(0, $1) AS m
+            "#,
+        )
+        .bind(MONTHS_AHEAD)
+        .execute(&state.db_pool)
+        .await;
+
+        if let Err(e) = created {
+            tracing::error!(error = %e, "payment partition job failed");
+        }
+    }
+}
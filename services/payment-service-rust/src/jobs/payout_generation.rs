@@ -0,0 +1,18 @@
+use crate::services::{payouts, AppState};
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        match payouts::generate(&state.db_pool).await {
+            Ok(generated) if generated.is_empty() => {}
+            Ok(generated) => tracing::info!("Generated {} merchant payouts", generated.len()),
+            Err(e) => tracing::error!(error = %e, "payout generation job failed"),
+        }
+    }
+}
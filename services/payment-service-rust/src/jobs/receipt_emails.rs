@@ -0,0 +1,29 @@
+use crate::{
+    events::Event,
+    models::PaymentStatus,
+    services::{notifications, AppState},
+};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Queues a receipt email whenever a payment goes through, from whichever flow completed it.
+pub async fn run(state: Arc<AppState>) {
+    let mut events = state.events.subscribe();
+    let paid = [PaymentStatus::Completed.as_str(), PaymentStatus::Escrowed.as_str()];
+
+    loop {
+        let event = match events.recv().await {
+            Ok(Event::Payment(event)) if paid.contains(&event.status.as_str()) => event,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::warn!("Receipt email listener fell behind, {} events missed", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        if let Err(e) = notifications::payment_receipt(&state.db_pool, &event).await {
+            tracing::error!(error = %e, "Could not queue receipt email for payment {}", event.payment_id);
+        }
+    }
+}
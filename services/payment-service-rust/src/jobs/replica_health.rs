@@ -0,0 +1,38 @@
+use crate::services::AppState;
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(5);
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// Replicas further behind than this are treated as unhealthy
+const MAX_LAG_SECS: f64 = 30.0;
+
+pub async fn run(state: Arc<AppState>) {
+    let Some(replica) = &state.read_replica else {
+        return;
+    };
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        // Replay lag of a standby, 0 on a server that isn't one
+        let lag = sqlx::query_scalar::<_, f64>(
+            "SELECT COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()), 0)::FLOAT8",
+        )
+        .fetch_one(&replica.pool);
+
+        let healthy = match tokio::time::timeout(TIMEOUT, lag).await {
+            Ok(Ok(lag)) if lag <= MAX_LAG_SECS => true,
+            Ok(Ok(lag)) => {
+                tracing::warn!(lag_secs = lag, "read replica is lagging");
+                false
+            }
+            Ok(Err(e)) => {
+                tracing::warn!(error = %e, "read replica health check failed");
+                false
+            }
+            Err(_) => false,
+        };
+        replica.set_healthy(healthy);
+    }
+}
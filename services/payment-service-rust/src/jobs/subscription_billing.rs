@@ -0,0 +1,51 @@
+use crate::services::{subscription_service, AppState};
+use std::{sync::Arc, time::Duration};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+const BATCH_SIZE: i64 = 100;
+
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let due = match subscription_service::due_subscription_ids(&state.db_pool, BATCH_SIZE).await {
+            Ok(due) => due,
+            Err(e) => {
+                tracing::error!(error = %e, "subscription billing job failed");
+                continue;
+            }
+        };
+
+        let retries = match subscription_service::due_retry_ids(&state.db_pool, BATCH_SIZE).await {
+            Ok(retries) => retries,
+            Err(e) => {
+                tracing::error!(error = %e, "subscription dunning job failed");
+                Vec::new()
+            }
+        };
+
+        for id in due {
+            match subscription_service::bill(&state, id).await {
+                Ok(Some(payment)) => {
+                    tracing::info!(subscription_id = %id, payment_id = %payment.id, "Subscription billed: {}", payment.payment_status);
+                    state.events.publish(&payment);
+                }
+                Ok(None) => {}
+                Err(e) => tracing::error!(error = %e, subscription_id = %id, "subscription billing failed"),
+            }
+        }
+
+        for id in retries {
+            match subscription_service::retry(&state, id).await {
+                Ok(Some(payment)) => {
+                    tracing::info!(subscription_id = %id, payment_id = %payment.id, "Subscription retry: {}", payment.payment_status);
+                    state.events.publish(&payment);
+                }
+                Ok(None) => {}
+                Err(e) => tracing::error!(error = %e, subscription_id = %id, "subscription retry failed"),
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{
+    events::Event,
+    models::PaymentStatus,
+    services::{alerting::Alert, fx, volume_anomalies, AppState},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+
+/// Counts completed payments into the volume windows and scores each window once it closes, catching both
+/// outages (volume collapse) and bursts. Escrowed payments count when they are released.
+pub async fn run(state: Arc<AppState>) {
+    let config = &state.config;
+    let mut events = state.events.subscribe();
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.anomaly_window_secs.max(1) as u64));
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(Event::Payment(event)) if event.status == PaymentStatus::Completed.as_str() => event,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("Volume anomaly listener fell behind, {} events missed", missed);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                };
+                let amount_usd = match fx::convert(config, event.amount, &event.currency, "USD") {
+                    Ok((usd, _)) => usd,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Cannot value payment {} in USD", event.payment_id);
+                        continue;
+                    }
+                };
+                if let Err(e) = volume_anomalies::record(state.redis_conn.clone(), config, amount_usd).await {
+                    tracing::warn!(error = %e, "Could not count payment {} for volume anomalies", event.payment_id);
+                }
+            }
+            _ = ticker.tick() => {
+                let anomalies = match volume_anomalies::check(state.redis_conn.clone(), config).await {
+                    Ok(anomalies) => anomalies,
+                    Err(e) => {
+                        tracing::error!(error = %e, "Volume anomaly check failed");
+                        continue;
+                    }
+                };
+
+                for anomaly in anomalies {
+                    state.events.publish_volume_anomaly(&anomaly);
+                    let direction = if anomaly.is_drop() { "drop" } else { "spike" };
+                    state
+                        .alerts
+                        .send(Alert {
+                            key: format!("volume_{}:{}", direction, anomaly.metric),
+                            title: format!("Payment volume {}", direction),
+                            text: format!(
+                                "{} was {:.2} in the window starting {}, against a mean of {:.2} ± {:.2} (z-score {:.1})",
+                                anomaly.metric,
+                                anomaly.value,
+                                anomaly.window_start,
+                                anomaly.mean,
+                                anomaly.stddev,
+                                anomaly.z_score
+                            ),
+                        })
+                        .await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::services::{alerting::Alert, AppState};
+use rust_decimal::Decimal;
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
+
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Wallets listed in the alert, the rest are only counted
+const MAX_LISTED: usize = 10;
+
+/// Compares each wallet's balance with the sum of its ledger and alerts on any difference.
+pub async fn run(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let discrepancies = sqlx::query_as::<_, (Uuid, String, Decimal, Decimal)>(
+            r#"
+            SELECT w.id, w.currency, w.balance, COALESCE(SUM(t.amount), 0)
+            FROM wallets w
+            LEFT JOIN wallet_transactions t ON t.wallet_id = w.id
+            GROUP BY w.id
+            HAVING w.balance <> COALESCE(SUM(t.amount), 0)
+            ORDER BY w.id
+            "#,
+        )
+        .fetch_all(&state.db_pool)
+        .await;
+
+        let discrepancies = match discrepancies {
+            Ok(discrepancies) if discrepancies.is_empty() => continue,
+            Ok(discrepancies) => discrepancies,
+            Err(e) => {
+                tracing::error!(error = %e, "Wallet reconciliation failed");
+                continue;
+            }
+        };
+        tracing::warn!("Wallet reconciliation found {} discrepancies", discrepancies.len());
+
+        let mut text = discrepancies
+            .iter()
+            .take(MAX_LISTED)
+            .map(|(id, currency, balance, ledger)| {
+                format!("wallet {}: balance {} {}, ledger {} {}", id, balance, currency, ledger, currency)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if discrepancies.len() > MAX_LISTED {
+            text.push_str(&format!("\n… and {} more", discrepancies.len() - MAX_LISTED));
+        }
+
+        state
+            .alerts
+            .send(Alert {
+                key: "wallet_reconciliation".to_string(),
+                title: format!("Wallet reconciliation: {} discrepancies", discrepancies.len()),
+                text,
+            })
+            .await;
+    }
+}
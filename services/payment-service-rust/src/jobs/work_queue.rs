@@ -0,0 +1,75 @@
+use crate::{
+    error::AppResult,
+    models::{WorkItem, WorkItemStatus},
+    services::{
+        async_payments, notifications, outbound_webhooks,
+        work_queue::{self, WorkKind},
+        AppState,
+    },
+};
+use chrono::Duration;
+use std::sync::Arc;
+use tracing::Instrument;
+
+const IDLE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Starts the worker pool on the shared work queue.
+pub async fn run(state: Arc<AppState>) {
+    let workers: Vec<_> = (0..state.config.work_queue_workers)
+        .map(|worker| tokio::spawn(work(state.clone(), worker)))
+        .collect();
+    // Lives as long as the workers, so the job status reflects them
+    futures::future::join_all(workers).await;
+}
+
+async fn work(state: Arc<AppState>, worker: usize) {
+    let visibility_timeout = Duration::seconds(state.config.work_queue_visibility_timeout_secs);
+
+    loop {
+        let item = match work_queue::claim(&state.db_pool, visibility_timeout).await {
+            Ok(Some(item)) => item,
+            Ok(None) => {
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, worker, "could not claim work item");
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+        };
+
+        let span = tracing::info_span!("work_item", worker, id = %item.id, kind = %item.kind, attempt = item.attempts);
+        process(&state, item).instrument(span).await;
+    }
+}
+
+async fn process(state: &AppState, item: WorkItem) {
+    let result = match WorkKind::parse(&item.kind) {
+        Some(kind) => dispatch(state, kind, item.payload.0.clone()).await,
+        None => Err(anyhow::anyhow!("unknown work item kind {}", item.kind).into()),
+    };
+
+    let outcome = match result {
+        Ok(()) => work_queue::complete(&state.db_pool, item.id).await.map(|_| None),
+        Err(e) => {
+            tracing::warn!(error = %e, "work item failed");
+            work_queue::fail(&state.db_pool, &item, &e.to_string()).await.map(Some)
+        }
+    };
+
+    match outcome {
+        Ok(Some(WorkItemStatus::Dead)) => tracing::error!("work item is dead after {} attempts", item.attempts),
+        Ok(_) => {}
+        // The visibility timeout hands it to another worker
+        Err(e) => tracing::error!(error = %e, "could not record work item outcome"),
+    }
+}
+
+async fn dispatch(state: &AppState, kind: WorkKind, payload: serde_json::Value) -> AppResult<()> {
+    match kind {
+        WorkKind::Payment => async_payments::process(state, payload).await,
+        WorkKind::Notification => notifications::deliver(&state.db_pool, &state.notification_client, payload).await,
+        WorkKind::Webhook => outbound_webhooks::deliver(state, payload).await,
+    }
+}
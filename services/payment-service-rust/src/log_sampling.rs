@@ -0,0 +1,80 @@
+//! Sampling of noisy log events by target, e.g. only 1 in 100 health check logs. Rules start from LOG_SAMPLING
+//! and can be replaced at runtime through `/api/admin/log-sampling`. Errors are always kept.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    OnceLock, RwLock,
+};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingRule {
+    /// Event target prefix, usually a module path such as `payment_service::handlers::health`
+    pub target: String,
+    /// Keep one event in this many; 1 keeps everything
+    pub keep_one_in: u64,
+}
+
+struct Rule {
+    rule: SamplingRule,
+    seen: AtomicU64,
+}
+
+// Global rather than in AppState: the subscriber is built before the state exists
+fn rules() -> &'static RwLock<Vec<Rule>> {
+    static RULES: OnceLock<RwLock<Vec<Rule>>> = OnceLock::new();
+    RULES.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Replaces the rules. The most specific (longest) matching target wins.
+pub fn set_rules(mut new_rules: Vec<SamplingRule>) {
+    new_rules.sort_by_key(|rule| std::cmp::Reverse(rule.target.len()));
+    let new_rules = new_rules
+        .into_iter()
+        .map(|rule| Rule { rule: SamplingRule { keep_one_in: rule.keep_one_in.max(1), ..rule }, seen: AtomicU64::new(0) })
+        .collect();
+
+    *rules().write().unwrap_or_else(|e| e.into_inner()) = new_rules;
+}
+
+pub fn current_rules() -> Vec<SamplingRule> {
+    rules().read().unwrap_or_else(|e| e.into_inner()).iter().map(|rule| rule.rule.clone()).collect()
+}
+
+/// `target=N` pairs, comma separated.
+pub fn parse_rules(raw: &str) -> anyhow::Result<Vec<SamplingRule>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (target, keep_one_in) =
+                pair.split_once('=').ok_or_else(|| anyhow::anyhow!("expected target=N: {}", pair))?;
+            let keep_one_in: u64 = keep_one_in.trim().parse()?;
+            if keep_one_in == 0 {
+                anyhow::bail!("sampling rate must be at least 1: {}", pair);
+            }
+
+            Ok(SamplingRule { target: target.trim().to_string(), keep_one_in })
+        })
+        .collect()
+}
+
+/// Drops sampled-out events for every layer at once, so stdout, OTLP logs and span events keep the same events.
+pub struct LogSampler;
+
+impl<S: Subscriber> Layer<S> for LogSampler {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        let metadata = event.metadata();
+        if *metadata.level() == Level::ERROR {
+            return true;
+        }
+
+        let rules = rules().read().unwrap_or_else(|e| e.into_inner());
+        match rules.iter().find(|rule| metadata.target().starts_with(&rule.rule.target)) {
+            Some(rule) => rule.seen.fetch_add(1, Ordering::Relaxed) % rule.rule.keep_one_in == 0,
+            None => true,
+        }
+    }
+}
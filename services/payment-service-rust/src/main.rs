@@ -1,37 +1,77 @@
+mod chaos;
+mod cli;
 mod config;
 mod database;
 mod dto;
+mod error;
+mod events;
 mod handlers;
+mod i18n;
+mod jobs;
+mod log_sampling;
 mod middleware;
 mod models;
+mod routes;
+mod server;
 mod services;
+mod slo;
 mod telemetry;
 
-use axum::{
-    routing::{get, post},
-    Router,
-};
+use clap::Parser;
+use cli::{Cli, Command, ConfigCommand};
 use config::Config;
-use services::user_client::UserServiceClient;
+use events::EventBus;
+use middleware::rate_limit::RateLimiter;
+use services::{
+    alerting::Alerter,
+    crypto_provider::{CryptoProvider, HttpCryptoProvider, MockCryptoProvider},
+    discovery::Registration,
+    introspection::IntrospectionClient,
+    mtls,
+    notification_client::NotificationServiceClient,
+    object_storage::ObjectStorage,
+    user_client::UserServiceClient,
+    user_grpc::UserGrpcClient,
+    vault::Vault,
+};
 use std::sync::Arc;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
     // Load environment variables
     dotenv::dotenv().ok();
 
+    // Load configuration, shared by every command
+    let config = Arc::new(Config::from_env()?);
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(config).await,
+        Command::Migrate => cli::migrate(&config).await,
+        Command::Healthcheck => cli::healthcheck(&config).await,
+        Command::Seed { count } => cli::seed(&config, count).await,
+        Command::Config { command: ConfigCommand::Check } => cli::check_config(&config),
+    }
+}
+
+async fn serve(config: Arc<Config>) -> anyhow::Result<()> {
+    // Before anything that may panic
+    let _sentry = telemetry::init_sentry(config.sentry_dsn.as_deref(), &config.environment);
+
     // Initialize OpenTelemetry tracing
     telemetry::init_telemetry()?;
-
-    // Load configuration
-    let config = Arc::new(Config::from_env()?);
+    log_sampling::set_rules(config.log_sampling.clone());
     tracing::info!("Configuration loaded successfully");
 
     // Initialize database
-    let db_pool = database::create_pool(&config.database_url).await?;
+    let db_pool = database::create_pool(&config).await?;
     tracing::info!("Database connection established");
+    let read_replica = config
+        .database_read_url
+        .as_deref()
+        .map(|url| database::ReadReplica::connect(&config, url))
+        .transpose()?;
 
     // Initialize Redis
     let redis_client = redis::Client::open(config.redis_url.clone())?;
@@ -39,34 +79,90 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Redis connection established");
 
     // Initialize User Service client
-    let user_service_url = std::env::var("USER_SERVICE_URL")
-        .unwrap_or_else(|_| "http://localhost:8083".to_string());
-    let user_client = Arc::new(UserServiceClient::new(user_service_url));
+    let user_grpc = config
+        .user_service_grpc_url
+        .as_deref()
+        .map(|url| UserGrpcClient::new(url, mtls::grpc_tls(&config, mtls::USER_SERVICE)?))
+        .transpose()?;
+    let user_client = Arc::new(UserServiceClient::new(
+        config.user_service_url.clone(),
+        mtls::client_builder(&config, mtls::USER_SERVICE)?.build()?,
+        config.user_service_concurrency,
+        user_grpc,
+        config.user_service_token.clone(),
+    ));
     tracing::info!("User Service client initialized");
 
+    let introspection = IntrospectionClient::from_config(&config)?;
+    if introspection.is_some() {
+        tracing::info!("Validating tokens by OAuth2 introspection");
+    }
+
+    let notification_client = Arc::new(NotificationServiceClient::new(
+        config.notification_service_url.clone(),
+        mtls::client_builder(&config, mtls::NOTIFICATION_SERVICE)?.build()?,
+    ));
+
+    let crypto_provider: Box<dyn CryptoProvider> = match &config.crypto_provider_url {
+        Some(url) => Box::new(HttpCryptoProvider::new(url.clone(), config.crypto_provider_api_key.clone())),
+        None => {
+            tracing::warn!("CRYPTO_PROVIDER_URL not set, using mock crypto provider");
+            Box::new(MockCryptoProvider)
+        }
+    };
+
+    let archive_storage = config.archive_s3_endpoint.clone().map(|endpoint| {
+        ObjectStorage::new(
+            endpoint,
+            config.archive_s3_bucket.clone(),
+            config.archive_s3_region.clone(),
+            config.archive_s3_access_key.clone(),
+            config.archive_s3_secret_key.clone(),
+        )
+    });
+
     // Build application state
     let app_state = Arc::new(services::AppState {
         config: config.clone(),
         db_pool,
-        redis_conn,
+        read_replica,
+        redis_conn: redis_conn.clone(),
+        user_client,
+        introspection,
+        notification_client,
+        vault: Vault::new(&config.vault_encryption_key),
+        events: EventBus::new(1024),
+        crypto_provider,
+        rate_limiter: RateLimiter::new(redis_conn.clone()),
+        alerts: Alerter::new(&config, redis_conn),
+        archive_storage,
+        started_at: chrono::Utc::now(),
     });
 
+    jobs::spawn_all(app_state.clone());
+
     // Build router
-    let app = Router::new()
-        .route("/api/health", get(handlers::health::health_check))
-        .route("/api/payments", post(handlers::payment::create_payment))
-        .route("/api/payments/:id", get(handlers::payment::get_payment))
-        .route("/api/payments/order/:order_id", get(handlers::payment::get_payment_by_order))
-        .layer(TraceLayer::new_for_http())  // ← BU SATIRI EKLE
-        .layer(CorsLayer::permissive())
-        .with_state(app_state);
+    let app = routes::router(app_state);
+
+    let mut registration = Registration::from_config(&config);
+    if let Some(registration) = &mut registration {
+        // Callers with a configured URL still reach us, so a registry outage doesn't stop the service
+        if let Err(e) = registration.register().await {
+            tracing::error!(error = %e, "service registration failed");
+        }
+    }
 
     // Start server
-    let addr = format!("0.0.0.0:{}", config.port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    tracing::info!("Server listening on {}", addr);
+    let served = server::serve(app, &config).await;
+
+    if let Some(registration) = registration {
+        if let Err(e) = registration.deregister().await {
+            tracing::error!(error = %e, "service deregistration failed");
+        }
+    }
+    served?;
 
-    axum::serve(listener, app).await?;
+    telemetry::shutdown_telemetry().await;
 
     Ok(())
-}
\ No newline at end of file
+}
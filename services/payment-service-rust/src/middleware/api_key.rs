@@ -0,0 +1,40 @@
+use crate::services::AppState;
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use subtle::{Choice, ConstantTimeEq};
+
+/// Courier apps authenticate with a static API key instead of a user JWT.
+pub async fn require_courier_api_key(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let key = request
+        .headers()
+        .get("X-API-Key")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if !matches_any(&state.config.courier_api_keys, key) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Whether `presented` is one of the configured static `keys`. Digests are compared in constant time and against
+/// every key, so timing shows neither how much of a key matched nor which one.
+pub fn matches_any(keys: &[String], presented: &str) -> bool {
+    let presented = Sha256::digest(presented.as_bytes());
+    let found = keys
+        .iter()
+        .fold(Choice::from(0), |found, k| found | Sha256::digest(k.as_bytes()).as_slice().ct_eq(presented.as_slice()));
+
+    bool::from(found)
+}
@@ -1,4 +1,4 @@
-use crate::services::user_client::UserServiceClient;
+use crate::{middleware::tenant::Tenant, models::Scopes, services::AppState};
 use axum::{
     extract::{Request, State},
     http::{HeaderMap, StatusCode},
@@ -6,9 +6,43 @@ use axum::{
     response::Response,
 };
 use std::sync::Arc;
+use uuid::Uuid;
+
+/// Authenticated caller, inserted into request extensions by `auth_middleware`.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user_id: Uuid,
+    pub role: String,
+    /// Merchant the user works for, `None` for customers and platform staff
+    pub merchant_id: Option<Uuid>,
+    /// What the token allows, narrowed down further by a restricted merchant API key
+    pub scopes: Scopes,
+}
+
+impl AuthUser {
+    /// Admin API is cross-tenant, so merchant-bound admins don't qualify.
+    pub fn is_admin(&self) -> bool {
+        self.role == "ADMIN" && self.merchant_id.is_none()
+    }
+
+    /// Neither platform staff nor merchant staff: sees only the payments they made.
+    pub fn is_customer(&self) -> bool {
+        self.role != "ADMIN" && self.merchant_id.is_none()
+    }
+}
+
+/// Scopes of a token before the API key narrows them. User service tokens carry no `scope` claim; for those
+/// staff keep full access and customers get what checkout needs.
+fn token_scopes(scope: Option<&str>, role: &str, merchant_id: Option<Uuid>) -> Scopes {
+    match scope {
+        Some(scope) => Scopes::parse(scope.split_whitespace()),
+        None if role == "ADMIN" || merchant_id.is_some() => Scopes::ALL,
+        None => Scopes::CUSTOMER,
+    }
+}
 
 pub async fn auth_middleware(
-    State(user_client): State<Arc<UserServiceClient>>,
+    State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     mut request: Request,
     next: Next,
@@ -19,15 +53,102 @@ pub async fn auth_middleware(
         .and_then(|v| v.strip_prefix("Bearer "))
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    // Validate token with User Service
-    let is_valid = user_client
-        .validate_token(token)
-        .await
+    // Validate token with User Service, or the IdP with AUTH_MODE=introspection
+    let claims = match &state.introspection {
+        Some(introspection) => introspection.introspect(token).await,
+        None => state.user_client.validate_token(token).await,
+    };
+    let claims = claims
+        .map_err(|_| StatusCode::UNAUTHORIZED)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let user_id = Uuid::parse_str(&claims.user_id).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let merchant_id = claims
+        .merchant_id
+        .map(|id| Uuid::parse_str(&id))
+        .transpose()
         .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    if !is_valid {
-        return Err(StatusCode::UNAUTHORIZED);
+    // Merchant staff can't act on another tenant's data
+    let tenant = request.extensions().get::<Tenant>().copied();
+    if merchant_id.is_some_and(|id| tenant.is_some_and(|t| t.merchant_id != id)) {
+        return Err(StatusCode::FORBIDDEN);
     }
 
+    let scopes = token_scopes(claims.scope.as_deref(), &claims.role, merchant_id)
+        .intersect(tenant.and_then(|t| t.scopes).unwrap_or(Scopes::ALL));
+
+    request.extensions_mut().insert(AuthUser {
+        user_id,
+        role: claims.role,
+        merchant_id,
+        scopes,
+    });
+
     Ok(next.run(request).await)
-}
\ No newline at end of file
+}
+
+/// `auth_middleware` for public routes: a request with a bearer token gets its `AuthUser`, one without passes as is.
+/// An invalid token is still refused rather than treated as anonymous.
+pub async fn optional_auth(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    if !headers.contains_key("Authorization") {
+        return Ok(next.run(request).await);
+    }
+
+    auth_middleware(State(state), headers, request, next).await
+}
+
+/// Must run after `auth_middleware`.
+pub async fn require_admin(request: Request, next: Next) -> Result<Response, StatusCode> {
+    let is_admin = request
+        .extensions()
+        .get::<AuthUser>()
+        .map(AuthUser::is_admin)
+        .unwrap_or(false);
+
+    if !is_admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Scope;
+
+    #[test]
+    fn scope_claim_decides_when_present() {
+        let scopes = token_scopes(Some("openid payments:read"), "ADMIN", None);
+        assert!(scopes.contains(Scope::Read));
+        assert!(!scopes.contains(Scope::Admin));
+        assert_eq!(token_scopes(Some("openid"), "ADMIN", None), Scopes::parse([]));
+    }
+
+    #[test]
+    fn customer_token_without_claim_gets_customer_scopes() {
+        let scopes = token_scopes(None, "CUSTOMER", None);
+        assert_eq!(scopes, Scopes::CUSTOMER);
+        assert!(!scopes.contains(Scope::Refund));
+        assert!(!scopes.contains(Scope::Admin));
+    }
+
+    #[test]
+    fn staff_token_without_claim_is_unrestricted() {
+        assert_eq!(token_scopes(None, "ADMIN", None), Scopes::ALL);
+        assert_eq!(token_scopes(None, "MERCHANT", Some(Uuid::new_v4())), Scopes::ALL);
+    }
+
+    #[test]
+    fn merchant_bound_admin_is_neither_admin_nor_customer() {
+        let user = AuthUser { user_id: Uuid::new_v4(), role: "ADMIN".into(), merchant_id: Some(Uuid::new_v4()), scopes: Scopes::ALL };
+        assert!(!user.is_admin());
+        assert!(!user.is_customer());
+    }
+}
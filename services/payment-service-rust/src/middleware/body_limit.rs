@@ -0,0 +1,26 @@
+use crate::error::AppError;
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Gives 413s from `DefaultBodyLimit` (plain text from the extractors) the API's error shape.
+pub async fn structured_rejection(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if is_json {
+        return response;
+    }
+
+    AppError::PayloadTooLarge("Request body is too large".to_string()).into_response()
+}
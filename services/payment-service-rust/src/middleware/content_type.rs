@@ -0,0 +1,44 @@
+use crate::error::AppError;
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// JSON bodies must be UTF-8; other media types are rejected by the `Json` extractor, whose plain-text 415
+/// gets the API's error shape here.
+pub async fn require_utf8_json(request: Request, next: Next) -> Response {
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    if content_type.starts_with("application/json") {
+        let charset = content_type
+            .split(';')
+            .skip(1)
+            .filter_map(|param| param.trim().strip_prefix("charset="))
+            .map(|charset| charset.trim_matches('"'))
+            .next();
+        if let Some(charset) = charset.filter(|c| *c != "utf-8" && *c != "utf8") {
+            return AppError::UnsupportedMediaType(format!("JSON bodies must be UTF-8, got charset {}", charset))
+                .into_response();
+        }
+    }
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if response.status() != StatusCode::UNSUPPORTED_MEDIA_TYPE || is_json {
+        return response;
+    }
+
+    AppError::UnsupportedMediaType("Expected a JSON body with Content-Type: application/json".to_string())
+        .into_response()
+}
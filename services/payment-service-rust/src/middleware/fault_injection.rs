@@ -0,0 +1,41 @@
+use crate::{dto::ApiResponse, error::ErrorCode, services::AppState};
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::{sync::Arc, time::Duration};
+
+/// Longest delay a caller can ask for, so a typo doesn't park a connection for hours.
+const MAX_LATENCY_MS: u64 = 30_000;
+
+/// With FAULT_INJECTION_ENABLED=true outside production, `X-Inject-Latency: <ms>` delays the request and `X-Inject-Error: <status>` answers with
+/// that 4xx/5xx instead of running the handler, for resilience tests of the services calling us.
+pub async fn inject_faults(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if !state.config.fault_injection_enabled() {
+        return next.run(request).await;
+    }
+
+    let headers = request.headers();
+    if let Some(latency_ms) = header_number::<u64>(headers, "x-inject-latency") {
+        tracing::debug!(latency_ms, "injecting latency");
+        tokio::time::sleep(Duration::from_millis(latency_ms.min(MAX_LATENCY_MS))).await;
+    }
+
+    let injected = header_number::<u16>(headers, "x-inject-error")
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .filter(|status| status.is_client_error() || status.is_server_error());
+    if let Some(status) = injected {
+        tracing::debug!(status = status.as_u16(), "injecting error");
+        let message = format!("Injected fault: {}", status.as_u16());
+        return (status, Json(ApiResponse::<()>::error(ErrorCode::from_status(status), message))).into_response();
+    }
+
+    next.run(request).await
+}
+
+fn header_number<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
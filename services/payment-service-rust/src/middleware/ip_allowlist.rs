@@ -0,0 +1,70 @@
+use crate::{server::PeerAddr, services::AppState};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::{net::IpAddr, sync::Arc};
+
+/// Keeps the admin API to ADMIN_ALLOWED_IPS, checked before the token so outsiders learn nothing else.
+pub async fn restrict_admin(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let config = &state.config;
+    if config.admin_allowed_ips.is_empty() {
+        return Ok(next.run(request).await);
+    }
+
+    let client = request_client_ip(&request, &config.trusted_proxies);
+
+    match client {
+        Some(ip) if config.admin_allowed_ips.iter().any(|net| net.contains(&ip)) => Ok(next.run(request).await),
+        _ => {
+            tracing::warn!("Admin request from {:?} is outside ADMIN_ALLOWED_IPS", client);
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+/// `client_ip` of a request, from its peer address and headers.
+pub fn request_client_ip(request: &Request, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<PeerAddr>>()
+        .and_then(|ConnectInfo(peer)| peer.0)
+        .map(|addr| addr.ip());
+
+    client_ip(peer, request.headers(), trusted_proxies)
+}
+
+/// The peer, or behind trusted proxies the nearest X-Forwarded-For hop that isn't one of them; hops further left
+/// are written by the client and can't be believed. `None` when there is no telling, e.g. a unix socket request
+/// without the header or a malformed hop.
+fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if let Some(ip) = peer.filter(|ip| !trusted(ip)) {
+        return Some(ip);
+    }
+
+    let hops: Vec<&str> = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .collect();
+
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        let ip = hop.trim().parse::<IpAddr>().ok()?;
+        client = Some(ip);
+        if !trusted(&ip) {
+            break;
+        }
+    }
+
+    client
+}
@@ -0,0 +1,55 @@
+use crate::{
+    chaos::{self, Target},
+    error::{AppError, ErrorCode},
+    services::AppState,
+};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use redis::AsyncCommands;
+use std::{sync::Arc, time::Duration};
+
+/// `SET payment-service:maintenance 1` turns maintenance on for every replica, `DEL` turns it off.
+const FLAG_KEY: &str = "payment-service:maintenance";
+const REDIS_TIMEOUT: Duration = Duration::from_millis(100);
+const RETRY_AFTER_SECS: u32 = 60;
+
+/// While in maintenance (MAINTENANCE_MODE or the Redis flag) only reads go through, so migrations can run.
+pub async fn reject_writes(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) || !in_maintenance(&state).await {
+        return next.run(request).await;
+    }
+
+    let mut response = AppError::ServiceUnavailable(
+        "The service is in maintenance, changes are paused. Please retry shortly".to_string(),
+    )
+    .with_code(ErrorCode::Maintenance)
+    .into_response();
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+    response
+}
+
+#[tracing::instrument(name = "redis.command", skip_all, fields(db.system = "redis", db.statement = "EXISTS maintenance_flag"))]
+async fn in_maintenance(state: &AppState) -> bool {
+    if state.config.maintenance_mode {
+        return true;
+    }
+
+    // An unreachable Redis keeps the service writable
+    if let Err(e) = chaos::inject(Target::Redis) {
+        tracing::warn!(error = %e, "could not read the maintenance flag");
+        return false;
+    }
+    let mut redis = state.redis_conn.clone();
+    match tokio::time::timeout(REDIS_TIMEOUT, redis.exists::<_, bool>(FLAG_KEY)).await {
+        Ok(Ok(flag)) => flag,
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, "could not read the maintenance flag");
+            false
+        }
+        Err(_) => false,
+    }
+}
@@ -1 +1,13 @@
-pub mod auth;
\ No newline at end of file
+pub mod api_key;
+pub mod auth;
+pub mod body_limit;
+pub mod content_type;
+pub mod error_reporting;
+pub mod fault_injection;
+pub mod ip_allowlist;
+pub mod maintenance;
+pub mod propagation;
+pub mod rate_limit;
+pub mod request_metrics;
+pub mod signature;
+pub mod tenant;
@@ -0,0 +1,26 @@
+use crate::{services::AppState, telemetry};
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::baggage::BaggageExt;
+use std::sync::Arc;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Continues the caller's trace and carries its baggage on to our outbound service calls. Baggage entries
+/// listed in BAGGAGE_SPAN_ATTRIBUTES (e.g. order_id) are also set on the request span, so traces can be
+/// searched by them; anything else is only passed along.
+pub async fn extract_context(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let context = telemetry::extract_context(request.headers());
+    let span = tracing::Span::current();
+
+    for key in &state.config.baggage_span_attributes {
+        if let Some(value) = context.baggage().get(key.clone()) {
+            span.set_attribute(key.clone(), value.to_string());
+        }
+    }
+    span.set_parent(context);
+
+    next.run(request).await
+}
@@ -0,0 +1,317 @@
+use crate::{
+    chaos::{self, Target},
+    dto::ApiResponse,
+    error::ErrorCode,
+    middleware::{ip_allowlist, tenant::Tenant},
+    services::AppState,
+};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use redis::{aio::ConnectionManager, Script};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quota {
+    RequestsPerMinute,
+    PaymentsPerDay,
+}
+
+impl Quota {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Quota::RequestsPerMinute => "requests-per-minute",
+            Quota::PaymentsPerDay => "payments-per-day",
+        }
+    }
+
+    fn window_seconds(&self) -> i64 {
+        match self {
+            Quota::RequestsPerMinute => 60,
+            Quota::PaymentsPerDay => 86_400,
+        }
+    }
+}
+
+/// Whom a quota is counted for. Merchant backends and the platform's own services (any `X-Merchant-Key`) share their
+/// tenant's quota; requests without a key, i.e. customers' apps and anonymous checkout calls, are counted per client
+/// address so one busy caller can't use up the platform quota for everyone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Subject {
+    Tenant(Uuid),
+    Client(IpAddr),
+}
+
+impl Subject {
+    /// The tenant's quota when the client address can't be told, e.g. on a unix socket without X-Forwarded-For.
+    pub fn of(tenant: &Tenant, client: Option<IpAddr>) -> Self {
+        match (tenant.scopes, client) {
+            (None, Some(ip)) => Subject::Client(ip),
+            _ => Subject::Tenant(tenant.merchant_id),
+        }
+    }
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::Tenant(merchant_id) => write!(f, "{}", merchant_id),
+            Subject::Client(ip) => write!(f, "ip:{}", ip),
+        }
+    }
+}
+
+pub struct QuotaStatus {
+    pub quota: Quota,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Unix time the quota is fully available again
+    pub reset_at: i64,
+    /// Seconds until the next request would be allowed, when it isn't now
+    pub retry_after: i64,
+    pub allowed: bool,
+}
+
+/// Token bucket shared by all replicas. Refills `limit` tokens per window; the Redis clock is used so
+/// replicas with skewed clocks agree. Returns {allowed, tokens left, ms until full, ms until next token, now}.
+const TOKEN_BUCKET: &str = r#"
+local capacity = tonumber(ARGV[1])
+local window_ms = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local per_ms = capacity / window_ms
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * per_ms)
+
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', tostring(now))
+redis.call('PEXPIRE', KEYS[1], window_ms)
+
+return {allowed, math.floor(tokens), math.ceil((capacity - tokens) / per_ms), math.ceil(math.max(0, 1 - tokens) / per_ms), now}
+"#;
+
+/// A slow Redis must not hold up every request; past this the local counters decide.
+const REDIS_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Local counters kept before those of past windows are dropped, client addresses would otherwise pile up.
+const MAX_LOCAL_WINDOWS: usize = 10_000;
+
+/// Cluster-wide limits through Redis, with fixed-window counters in process memory as the fallback
+/// while Redis is unreachable (limits then hold per replica only).
+pub struct RateLimiter {
+    redis: ConnectionManager,
+    token_bucket: Script,
+    degraded: AtomicBool,
+    windows: Mutex<HashMap<(Subject, Quota), (i64, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(redis: ConnectionManager) -> Self {
+        Self {
+            redis,
+            token_bucket: Script::new(TOKEN_BUCKET),
+            degraded: AtomicBool::new(false),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts one hit against the quota unless it is already used up.
+    pub async fn hit(&self, subject: Subject, quota: Quota, limit: u32) -> QuotaStatus {
+        match self.hit_shared(subject, quota, limit).await {
+            Ok(status) => {
+                if self.degraded.swap(false, Ordering::Relaxed) {
+                    tracing::info!("Redis is back, rate limits are cluster-wide again");
+                }
+                status
+            }
+            Err(e) => {
+                if !self.degraded.swap(true, Ordering::Relaxed) {
+                    tracing::warn!(error = %e, "Redis unavailable, rate limiting per instance");
+                }
+                self.hit_local(subject, quota, limit)
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "redis.command", skip_all, fields(db.system = "redis", db.statement = "EVALSHA token_bucket"))]
+    async fn hit_shared(&self, subject: Subject, quota: Quota, limit: u32) -> anyhow::Result<QuotaStatus> {
+        chaos::inject(Target::Redis)?;
+        let key = format!("rate_limit:{}:{}", subject, quota.as_str());
+        let mut redis = self.redis.clone();
+        let mut invocation = self.token_bucket.key(key);
+        invocation.arg(limit).arg(quota.window_seconds() * 1000);
+
+        let (allowed, remaining, full_in_ms, next_in_ms, now_ms): (i64, i64, i64, i64, i64) =
+            tokio::time::timeout(REDIS_TIMEOUT, invocation.invoke_async(&mut redis)).await??;
+
+        Ok(QuotaStatus {
+            quota,
+            limit,
+            remaining: remaining.max(0) as u32,
+            reset_at: (now_ms + full_in_ms + 999) / 1000,
+            retry_after: (next_in_ms + 999) / 1000,
+            allowed: allowed == 1,
+        })
+    }
+
+    fn hit_local(&self, subject: Subject, quota: Quota, limit: u32) -> QuotaStatus {
+        let now = Utc::now().timestamp();
+        let window = now / quota.window_seconds();
+
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.len() >= MAX_LOCAL_WINDOWS {
+            windows.retain(|(_, quota), (window, _)| *window == now / quota.window_seconds());
+        }
+        let (current, count) = windows.entry((subject, quota)).or_insert((window, 0));
+        if *current != window {
+            *current = window;
+            *count = 0;
+        }
+
+        let allowed = *count < limit;
+        if allowed {
+            *count += 1;
+        }
+        let reset_at = (window + 1) * quota.window_seconds();
+
+        QuotaStatus {
+            quota,
+            limit,
+            remaining: limit - *count,
+            reset_at,
+            retry_after: if allowed { 0 } else { reset_at - now },
+            allowed,
+        }
+    }
+}
+
+/// Must run after `resolve_tenant`. Payment creation also counts against the daily payment quota.
+/// Every response carries the `X-RateLimit-*` headers of the quota closest to running out.
+pub async fn enforce_quotas(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let Some(tenant) = request.extensions().get::<Tenant>().copied() else {
+        return next.run(request).await;
+    };
+    let subject = Subject::of(&tenant, ip_allowlist::request_client_ip(&request, &state.config.trusted_proxies));
+
+    let mut tightest = state
+        .rate_limiter
+        .hit(subject, Quota::RequestsPerMinute, tenant.requests_per_minute)
+        .await;
+    if !tightest.allowed {
+        return too_many_requests(&tightest);
+    }
+
+    if creates_payment(&request) {
+        let status = state
+            .rate_limiter
+            .hit(subject, Quota::PaymentsPerDay, tenant.payments_per_day)
+            .await;
+        if !status.allowed {
+            return too_many_requests(&status);
+        }
+        if status.remaining <= tightest.remaining {
+            tightest = status;
+        }
+    }
+
+    let mut response = next.run(request).await;
+    response.headers_mut().extend(rate_limit_headers(&tightest));
+    response
+}
+
+pub fn creates_payment(request: &Request) -> bool {
+    request.method() == Method::POST && request.uri().path() == "/api/payments"
+}
+
+fn rate_limit_headers(status: &QuotaStatus) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in [
+        ("x-ratelimit-quota", status.quota.as_str().to_string()),
+        ("x-ratelimit-limit", status.limit.to_string()),
+        ("x-ratelimit-remaining", status.remaining.to_string()),
+        ("x-ratelimit-reset", status.reset_at.to_string()),
+    ] {
+        headers.insert(
+            HeaderName::from_static(name),
+            HeaderValue::from_str(&value).expect("numbers and quota names are valid headers"),
+        );
+    }
+    headers
+}
+
+fn too_many_requests(status: &QuotaStatus) -> Response {
+    let mut headers = rate_limit_headers(status);
+    headers.insert(header::RETRY_AFTER, HeaderValue::from(status.retry_after.max(1)));
+    let message = format!("Quota exceeded: {} {}", status.limit, status.quota.as_str());
+
+    (StatusCode::TOO_MANY_REQUESTS, headers, Json(ApiResponse::<()>::error(ErrorCode::QuotaExceeded, message))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DuplicateOrders, Scopes, DEFAULT_MERCHANT_ID};
+    use axum::body::Body;
+
+    fn tenant(scopes: Option<Scopes>) -> Tenant {
+        Tenant {
+            merchant_id: DEFAULT_MERCHANT_ID,
+            requests_per_minute: 600,
+            payments_per_day: 10_000,
+            duplicate_orders: DuplicateOrders::Replay,
+            scopes,
+        }
+    }
+
+    #[test]
+    fn requests_without_a_key_are_counted_per_client() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        assert_eq!(Subject::of(&tenant(None), Some(ip)), Subject::Client(ip));
+        assert_eq!(Subject::of(&tenant(None), Some(ip)).to_string(), "ip:203.0.113.7");
+    }
+
+    #[test]
+    fn keyed_requests_share_the_tenant_quota() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let subject = Subject::of(&tenant(Some(Scopes::ALL)), Some(ip));
+        assert_eq!(subject, Subject::Tenant(DEFAULT_MERCHANT_ID));
+        assert_eq!(subject.to_string(), DEFAULT_MERCHANT_ID.to_string());
+    }
+
+    #[test]
+    fn unknown_client_falls_back_to_the_tenant_quota() {
+        assert_eq!(Subject::of(&tenant(None), None), Subject::Tenant(DEFAULT_MERCHANT_ID));
+    }
+
+    #[test]
+    fn only_payment_creation_spends_the_daily_payment_quota() {
+        let request =
+            |method: Method, path: &str| Request::builder().method(method).uri(path).body(Body::empty()).unwrap();
+        assert!(creates_payment(&request(Method::POST, "/api/payments")));
+        assert!(!creates_payment(&request(Method::GET, "/api/payments")));
+        assert!(!creates_payment(&request(Method::POST, "/api/payments/lookup")));
+        assert!(!creates_payment(&request(Method::POST, "/api/payments/quote")));
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{services::AppState, slo, telemetry};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::trace::TraceContextExt;
+use std::{sync::Arc, time::Instant};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Probes and scrapes say nothing about what callers experience
+const UNTRACKED_PATHS: [&str; 2] = ["/metrics", "/api/health"];
+
+/// Records status and latency of every request for the SLOs and the latency histogram, the request's trace
+/// id as exemplar. Latency is time to the response head, streamed
+/// bodies (exports) are not waited for.
+pub async fn record(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if UNTRACKED_PATHS.iter().any(|path| request.uri().path().starts_with(path)) {
+        return next.run(request).await;
+    }
+
+    let method = request.method().to_string();
+    // Route template rather than the path, ids would make every request its own series
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let span_context = tracing::Span::current().context().span().span_context().clone();
+    let trace_id = (span_context.is_valid() && span_context.is_sampled()).then(|| span_context.trace_id().to_string());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed();
+    slo::record(&state.config, response.status(), elapsed);
+    telemetry::observe_request((method, route, response.status().as_u16()), elapsed.as_secs_f64(), trace_id);
+
+    response
+}
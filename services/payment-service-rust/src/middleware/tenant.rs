@@ -0,0 +1,130 @@
+use crate::{
+    middleware::{api_key, rate_limit},
+    models::{DuplicateOrders, MerchantStatus, Scopes, DEFAULT_MERCHANT_ID},
+    services::{api_key_usage, merchants, AppState},
+};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Merchant the request acts for, inserted into request extensions by `resolve_tenant`.
+#[derive(Debug, Clone, Copy)]
+pub struct Tenant {
+    pub merchant_id: Uuid,
+    pub requests_per_minute: u32,
+    pub payments_per_day: u32,
+    pub duplicate_orders: DuplicateOrders,
+    /// What the merchant's API key allows; `None` without a key, which on its own grants nothing
+    pub scopes: Option<Scopes>,
+}
+
+/// Merchant backends identify themselves with `X-Merchant-Key`; without it the request belongs to the platform.
+/// The platform's own services (e.g. the order service) send one of PLATFORM_API_KEYS instead.
+pub async fn resolve_tenant(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let config = &state.config;
+    let key = request
+        .headers()
+        .get("X-Merchant-Key")
+        .map(|key| key.to_str())
+        .transpose()
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let platform = |scopes| Tenant {
+        merchant_id: DEFAULT_MERCHANT_ID,
+        requests_per_minute: config.tenant_requests_per_minute,
+        payments_per_day: config.tenant_payments_per_day,
+        duplicate_orders: config.duplicate_orders,
+        scopes,
+    };
+    let tenant = match caller(key, &config.platform_api_keys) {
+        Caller::Anonymous => platform(None),
+        Caller::Platform => platform(Some(Scopes::ALL)),
+        Caller::Merchant(key) => {
+            let merchant = merchants::find_by_api_key(&state.db_pool, key)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::UNAUTHORIZED)?;
+
+            if merchant.status != MerchantStatus::Active.as_str() {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            // Off the request path, usage counts aren't worth slowing requests down for
+            let redis = state.redis_conn.clone();
+            let payment = rate_limit::creates_payment(&request);
+            tokio::spawn(async move {
+                if let Err(e) = api_key_usage::record(redis, merchant.id, payment).await {
+                    tracing::warn!(error = %e, "failed to record API key usage");
+                }
+            });
+            Tenant {
+                merchant_id: merchant.id,
+                requests_per_minute: merchant
+                    .requests_per_minute
+                    .map_or(config.tenant_requests_per_minute, |n| n as u32),
+                payments_per_day: merchant.payments_per_day.map_or(config.tenant_payments_per_day, |n| n as u32),
+                duplicate_orders: merchant
+                    .duplicate_orders
+                    .as_deref()
+                    .and_then(DuplicateOrders::parse)
+                    .unwrap_or(config.duplicate_orders),
+                scopes: Some(
+                    merchant
+                        .scopes
+                        .as_ref()
+                        .map_or(Scopes::ALL, |scopes| Scopes::parse(scopes.iter().map(String::as_str))),
+                ),
+            }
+        }
+    };
+
+    request.extensions_mut().insert(tenant);
+
+    Ok(next.run(request).await)
+}
+
+/// Who `X-Merchant-Key` says sent the request, before any merchant is looked up.
+#[derive(Debug, PartialEq)]
+enum Caller<'k> {
+    Anonymous,
+    Platform,
+    Merchant(&'k str),
+}
+
+fn caller<'k>(key: Option<&'k str>, platform_keys: &[String]) -> Caller<'k> {
+    match key {
+        None => Caller::Anonymous,
+        Some(key) if api_key::matches_any(platform_keys, key) => Caller::Platform,
+        Some(key) => Caller::Merchant(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_without_key_is_anonymous() {
+        assert_eq!(caller(None, &["platform-key".to_string()]), Caller::Anonymous);
+    }
+
+    #[test]
+    fn platform_key_is_recognised_among_several() {
+        let keys = ["order-service".to_string(), "cart-service".to_string()];
+        assert_eq!(caller(Some("cart-service"), &keys), Caller::Platform);
+    }
+
+    #[test]
+    fn any_other_key_is_looked_up_as_a_merchant() {
+        let keys = ["order-service".to_string()];
+        assert_eq!(caller(Some("order-servic"), &keys), Caller::Merchant("order-servic"));
+        assert_eq!(caller(Some(""), &[]), Caller::Merchant(""));
+    }
+}
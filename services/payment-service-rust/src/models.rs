@@ -1,6 +1,6 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{types::Json, FromRow};
 use rust_decimal::Decimal;
 use uuid::Uuid;
 
@@ -14,15 +14,210 @@ pub struct Payment {
     pub payment_method: String,
     pub payment_status: String,
     pub transaction_id: Option<String>,
+    pub payment_method_id: Option<Uuid>,
+    pub three_ds_redirect_url: Option<String>,
+    pub three_ds_status: Option<String>,
+    pub transfer_reference: Option<String>,
+    pub transfer_instructions: Option<Json<TransferInstructions>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub collection_note: Option<String>,
+    pub collected_at: Option<DateTime<Utc>>,
+    pub installment_count: i16,
+    pub installment_fee_percent: Decimal,
+    pub installment_surcharge: Decimal,
+    pub subscription_id: Option<Uuid>,
+    pub wallet_amount: Decimal,
+    pub wallet_topup: bool,
+    pub voucher_id: Option<Uuid>,
+    pub voucher_amount: Decimal,
+    pub promotion_id: Option<Uuid>,
+    pub discount_amount: Decimal,
+    pub gross_amount: Decimal,
+    pub tax_jurisdiction: Option<String>,
+    pub tax_rate: Decimal,
+    pub taxable_base: Decimal,
+    pub tax_amount: Decimal,
+    pub method_surcharge_percent: Decimal,
+    pub method_surcharge: Decimal,
+    pub escrow: bool,
+    pub escrow_release_at: Option<DateTime<Utc>>,
+    pub escrow_released_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub merchant_id: Uuid,
+    pub invoice_number: Option<String>,
+    pub invoiced_at: Option<DateTime<Utc>>,
+    /// Bumped on every update (by trigger), expected in `If-Match` by the mutating endpoints
+    pub version: i32,
+}
+
+pub const METHOD_BANK_TRANSFER: &str = "BANK_TRANSFER";
+pub const METHOD_CASH_ON_DELIVERY: &str = "CASH_ON_DELIVERY";
+pub const METHOD_CRYPTO: &str = "CRYPTO";
+pub const METHOD_WALLET: &str = "WALLET";
+pub const METHOD_VOUCHER: &str = "VOUCHER";
+
+/// Havale/EFT details shown to the customer, snapshotted when the payment is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferInstructions {
+    pub bank_name: String,
+    pub account_holder: String,
+    pub iban: String,
+    pub reference_code: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PaymentMethod {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token: String,
+    pub method_type: String,
+    pub brand: String,
+    pub last4: String,
+    pub exp_month: i16,
+    pub exp_year: i16,
+    pub holder_name: String,
+    #[serde(skip)]
+    pub encrypted_details: Vec<u8>,
+    #[serde(skip)]
+    pub nonce: Vec<u8>,
+    pub is_default: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub bin: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CryptoPayment {
+    pub payment_id: Uuid,
+    pub asset: String,
+    pub deposit_address: String,
+    pub provider_reference: String,
+    pub exchange_rate: Decimal,
+    pub expected_amount: Decimal,
+    pub received_amount: Decimal,
+    pub confirmations: i32,
+    pub required_confirmations: i32,
+    pub tx_hash: Option<String>,
+    pub deposit_status: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When the deposit first fell short, the top-up deadline counts from here
+    pub underpaid_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DepositStatus {
+    AwaitingDeposit,
+    Confirming,
+    Underpaid,
+    Overpaid,
+    Confirmed,
+    /// Underpaid and not topped up in time; the payment failed and what arrived went to the wallet
+    Expired,
+}
+
+impl DepositStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DepositStatus::AwaitingDeposit => "AWAITING_DEPOSIT",
+            DepositStatus::Confirming => "CONFIRMING",
+            DepositStatus::Underpaid => "UNDERPAID",
+            DepositStatus::Overpaid => "OVERPAID",
+            DepositStatus::Confirmed => "CONFIRMED",
+            DepositStatus::Expired => "EXPIRED",
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, DepositStatus::Overpaid | DepositStatus::Confirmed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Subscription {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub plan_code: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub billing_interval: String,
+    pub interval_count: i32,
+    pub payment_method_id: Uuid,
+    pub status: String,
+    pub next_billing_at: DateTime<Utc>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub retry_count: i32,
+    pub next_retry_at: Option<DateTime<Utc>>,
+    pub credit_balance: Decimal,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SubscriptionAdjustment {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub old_plan_code: String,
+    pub new_plan_code: String,
+    pub old_amount: Decimal,
+    pub new_amount: Decimal,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub remaining_ratio: Decimal,
+    pub prorated_amount: Decimal,
+    pub payment_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubscriptionStatus {
+    Active,
+    Paused,
+    PastDue,
+    Cancelled,
+}
+
+impl SubscriptionStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SubscriptionStatus::Active => "ACTIVE",
+            SubscriptionStatus::Paused => "PAUSED",
+            SubscriptionStatus::PastDue => "PAST_DUE",
+            SubscriptionStatus::Cancelled => "CANCELLED",
+        }
+    }
+}
+
+/// Raw card data, only ever stored encrypted inside the vault.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CardDetails {
+    pub number: String,
+    pub holder_name: String,
+    pub exp_month: u8,
+    pub exp_year: u16,
+}
+
+/// Gateway account a charge is made with, stored encrypted inside the vault.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GatewayCredentials {
+    pub account_id: String,
+    pub api_key: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PaymentStatus {
     Pending,
     Processing,
+    RequiresAction,
+    AwaitingCollection,
     Completed,
+    /// Captured but held until delivery is confirmed
+    Escrowed,
     Failed,
     Refunded,
 }
@@ -32,9 +227,632 @@ impl PaymentStatus {
         match self {
             PaymentStatus::Pending => "PENDING",
             PaymentStatus::Processing => "PROCESSING",
+            PaymentStatus::RequiresAction => "REQUIRES_ACTION",
+            PaymentStatus::AwaitingCollection => "AWAITING_COLLECTION",
             PaymentStatus::Completed => "COMPLETED",
+            PaymentStatus::Escrowed => "ESCROWED",
             PaymentStatus::Failed => "FAILED",
             PaymentStatus::Refunded => "REFUNDED",
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Wallet {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub currency: String,
+    pub balance: Decimal,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WalletTransaction {
+    pub id: Uuid,
+    pub wallet_id: Uuid,
+    pub entry_type: String,
+    pub amount: Decimal,
+    pub balance_after: Decimal,
+    pub payment_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WalletEntryType {
+    Payment,
+    PaymentReversal,
+    TopUp,
+    Refund,
+}
+
+impl WalletEntryType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            WalletEntryType::Payment => "PAYMENT",
+            WalletEntryType::PaymentReversal => "PAYMENT_REVERSAL",
+            WalletEntryType::TopUp => "TOPUP",
+            WalletEntryType::Refund => "REFUND",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Refund {
+    pub id: Uuid,
+    pub payment_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub destination: String,
+    pub reason: Option<String>,
+    pub transaction_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefundStatus {
+    /// Sent to the gateway, counts against the refundable amount until it fails
+    Pending,
+    Completed,
+    Failed,
+}
+
+impl RefundStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RefundStatus::Pending => "PENDING",
+            RefundStatus::Completed => "COMPLETED",
+            RefundStatus::Failed => "FAILED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefundDestination {
+    OriginalMethod,
+    Wallet,
+}
+
+impl RefundDestination {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RefundDestination::OriginalMethod => "ORIGINAL_METHOD",
+            RefundDestination::Wallet => "WALLET",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Voucher {
+    pub id: Uuid,
+    pub code: String,
+    pub initial_amount: Decimal,
+    pub balance: Decimal,
+    pub currency: String,
+    pub status: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub voided_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoucherStatus {
+    Active,
+    Voided,
+}
+
+impl VoucherStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            VoucherStatus::Active => "ACTIVE",
+            VoucherStatus::Voided => "VOIDED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Promotion {
+    pub id: Uuid,
+    pub code: String,
+    pub discount_type: String,
+    pub discount_value: Decimal,
+    pub currency: Option<String>,
+    pub min_amount: Decimal,
+    pub max_uses: Option<i32>,
+    pub used_count: i32,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PaymentFee {
+    pub id: Uuid,
+    pub payment_id: Uuid,
+    pub fee_type: String,
+    pub fee_rule_id: Option<Uuid>,
+    pub gateway: Option<String>,
+    pub percent: Decimal,
+    pub fixed_amount: Decimal,
+    pub amount: Decimal,
+    pub currency: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One line of the admin fee report.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct FeeReportRow {
+    pub currency: String,
+    pub payment_method: String,
+    pub payment_count: i64,
+    pub gross_amount: Decimal,
+    pub platform_fees: Decimal,
+    pub gateway_fees: Decimal,
+    pub net_amount: Decimal,
+}
+
+/// Requests and payment creations made with a merchant's API key on one day (UTC).
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ApiKeyUsageDay {
+    pub day: NaiveDate,
+    pub requests: i64,
+    pub payments: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PaymentSplit {
+    pub id: Uuid,
+    pub payment_id: Uuid,
+    pub merchant_id: Uuid,
+    /// Gross share of the seller
+    pub amount: Decimal,
+    pub currency: String,
+    pub created_at: DateTime<Utc>,
+    pub commission_percent: Decimal,
+    pub commission_amount: Decimal,
+    pub net_amount: Decimal,
+    pub payout_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MerchantTerms {
+    pub merchant_id: Uuid,
+    pub commission_percent: Decimal,
+    pub fixed_fee: Decimal,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub payout_account_reference: Option<String>,
+}
+
+/// Completed marketplace sales of one merchant in one currency.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct MerchantEarnings {
+    pub merchant_id: Uuid,
+    pub currency: String,
+    pub split_count: i64,
+    pub gross_amount: Decimal,
+    pub commission_amount: Decimal,
+    pub net_amount: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Payout {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub currency: String,
+    pub amount: Decimal,
+    pub split_count: i32,
+    pub status: String,
+    pub bank_account_reference: Option<String>,
+    pub bank_transfer_reference: Option<String>,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PayoutStatus {
+    Pending,
+    Approved,
+    Paid,
+}
+
+impl PayoutStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PayoutStatus::Pending => "PENDING",
+            PayoutStatus::Approved => "APPROVED",
+            PayoutStatus::Paid => "PAID",
+        }
+    }
+}
+
+/// The platform's own merchant (seeded by migration 024); requests without a merchant key belong to it.
+pub const DEFAULT_MERCHANT_ID: Uuid = Uuid::from_u128(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Merchant {
+    pub id: Uuid,
+    pub name: String,
+    pub contact_email: Option<String>,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Quota overrides, the configured defaults apply when unset
+    pub requests_per_minute: Option<i32>,
+    pub payments_per_day: Option<i32>,
+    /// VKN (10 digits) or TCKN (11 digits) of the seller, required for e-Fatura
+    pub tax_id: Option<String>,
+    pub tax_office: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    /// REPLAY or REJECT, the configured default applies when unset
+    pub duplicate_orders: Option<String>,
+    /// What the merchant's API key may do, e.g. `["payments:read"]` for an analytics key; unrestricted when unset
+    pub scopes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MerchantStatus {
+    Active,
+    Suspended,
+}
+
+impl MerchantStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MerchantStatus::Active => "ACTIVE",
+            MerchantStatus::Suspended => "SUSPENDED",
+        }
+    }
+}
+
+/// Answer to a payment request for an order that already has a payment that didn't fail.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum DuplicateOrders {
+    /// The existing payment, flagged `idempotent_replay`, so the order service can simply retry
+    Replay,
+    /// 409 DUPLICATE_ORDER
+    Reject,
+}
+
+impl DuplicateOrders {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DuplicateOrders::Replay => "REPLAY",
+            DuplicateOrders::Reject => "REJECT",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_uppercase().as_str() {
+            "REPLAY" => Some(DuplicateOrders::Replay),
+            "REJECT" => Some(DuplicateOrders::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// Permission on payments a token or API key grants, OAuth-style (`payments:read`, ...). Roles still decide who
+/// reaches the admin API; scopes narrow down what a token may do there and elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Read,
+    Write,
+    Refund,
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [Scope::Read, Scope::Write, Scope::Refund, Scope::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "payments:read",
+            Scope::Write => "payments:write",
+            Scope::Refund => "payments:refund",
+            Scope::Admin => "payments:admin",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        Scope::ALL.into_iter().find(|scope| scope.as_str().eq_ignore_ascii_case(raw.trim()))
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Set of scopes, small enough to copy into every request's extensions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scopes(u8);
+
+impl Scopes {
+    /// Granted when neither the token nor the API key restricts the caller
+    pub const ALL: Scopes = Scopes(0b1111);
+    /// `payments:read payments:write`, for customer tokens that carry no scope claim
+    pub const CUSTOMER: Scopes = Scopes(0b0011);
+
+    /// Scopes other services use (`openid`, `email`, ...) are ignored.
+    pub fn parse<'a>(raw: impl IntoIterator<Item = &'a str>) -> Self {
+        Scopes(raw.into_iter().filter_map(Scope::parse).fold(0, |bits, scope| bits | scope.bit()))
+    }
+
+    pub fn contains(self, scope: Scope) -> bool {
+        self.0 & scope.bit() != 0
+    }
+
+    /// Scopes granted by both, e.g. a token used together with a restricted API key.
+    pub fn intersect(self, other: Scopes) -> Scopes {
+        Scopes(self.0 & other.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MerchantGatewayCredentials {
+    pub merchant_id: Uuid,
+    pub account_id: String,
+    #[serde(skip)]
+    pub encrypted_credentials: Vec<u8>,
+    #[serde(skip)]
+    pub nonce: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Queued customer email, see `services::notifications`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Notification {
+    pub id: Uuid,
+    pub template: String,
+    pub dedup_key: String,
+    pub user_id: Uuid,
+    pub payload: Json<serde_json::Value>,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationStatus {
+    Pending,
+    Sent,
+    Failed,
+}
+
+impl NotificationStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            NotificationStatus::Pending => "PENDING",
+            NotificationStatus::Sent => "SENT",
+            NotificationStatus::Failed => "FAILED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct EfaturaDocument {
+    pub payment_id: Uuid,
+    pub invoice_number: String,
+    pub ettn: Uuid,
+    pub xml: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PaymentLink {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub token: String,
+    pub order_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub amount: Decimal,
+    pub currency: String,
+    pub description: Option<String>,
+    pub status: String,
+    pub payment_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaymentLinkStatus {
+    Active,
+    Processing,
+    Paid,
+}
+
+impl PaymentLinkStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PaymentLinkStatus::Active => "ACTIVE",
+            PaymentLinkStatus::Processing => "PROCESSING",
+            PaymentLinkStatus::Paid => "PAID",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PaymentIntent {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub order_id: Uuid,
+    pub user_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub promo_code: Option<String>,
+    pub country: Option<String>,
+    pub escrow: bool,
+    pub status: String,
+    pub payment_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaymentIntentStatus {
+    RequiresConfirmation,
+    Processing,
+    Succeeded,
+}
+
+impl PaymentIntentStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PaymentIntentStatus::RequiresConfirmation => "REQUIRES_CONFIRMATION",
+            PaymentIntentStatus::Processing => "PROCESSING",
+            PaymentIntentStatus::Succeeded => "SUCCEEDED",
+        }
+    }
+}
+
+/// Unit of background work, see `services::work_queue`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WorkItem {
+    pub id: Uuid,
+    pub kind: String,
+    pub payload: Json<serde_json::Value>,
+    pub status: String,
+    pub attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WorkBacklog {
+    pub kind: String,
+    pub status: String,
+    pub items: i64,
+    pub oldest_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorkItemStatus {
+    Queued,
+    Running,
+    /// Poison message: failed on every attempt, kept for inspection and manual requeue
+    Dead,
+}
+
+impl WorkItemStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            WorkItemStatus::Queued => "QUEUED",
+            WorkItemStatus::Running => "RUNNING",
+            WorkItemStatus::Dead => "DEAD",
+        }
+    }
+}
+
+/// Inbound webhook delivery, see `services::webhook_events`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookEvent {
+    pub id: Uuid,
+    pub source: String,
+    pub event_id: String,
+    /// Body as received; missing for events stored before payloads were kept
+    pub payload: Option<String>,
+    pub status: String,
+    pub last_error: Option<String>,
+    pub attempts: i32,
+    pub received_at: DateTime<Utc>,
+    pub processed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebhookEventStatus {
+    Received,
+    Processed,
+    /// Processing failed, kept for replay once the cause is fixed
+    Failed,
+}
+
+impl WebhookEventStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            WebhookEventStatus::Received => "RECEIVED",
+            WebhookEventStatus::Processed => "PROCESSED",
+            WebhookEventStatus::Failed => "FAILED",
+        }
+    }
+}
+
+/// Endpoint that is sent our events, see `services::outbound_webhooks`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub merchant_id: Option<Uuid>,
+    pub url: String,
+    pub description: Option<String>,
+    pub event_types: Vec<String>,
+    #[serde(skip)]
+    pub encrypted_secret: Vec<u8>,
+    #[serde(skip)]
+    pub nonce: Vec<u8>,
+    pub active: bool,
+    pub secret_rotated_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub event_type: String,
+    pub payload: Json<serde_json::Value>,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub response_time_ms: Option<i32>,
+    pub last_error: Option<String>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    /// Every attempt failed
+    Failed,
+}
+
+impl WebhookDeliveryStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            WebhookDeliveryStatus::Pending => "PENDING",
+            WebhookDeliveryStatus::Delivered => "DELIVERED",
+            WebhookDeliveryStatus::Failed => "FAILED",
+        }
+    }
+}
+
+/// Delivery counts and timings of one subscription.
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDeliveryStats {
+    pub subscription_id: Uuid,
+    pub total: i64,
+    pub delivered: i64,
+    pub pending: i64,
+    pub failed: i64,
+    /// Share of finished deliveries that went through, in percent
+    pub success_rate: Option<f64>,
+    pub average_attempts: Option<f64>,
+    pub average_response_time_ms: Option<f64>,
+    pub last_delivered_at: Option<DateTime<Utc>>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+}
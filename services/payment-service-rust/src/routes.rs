@@ -0,0 +1,264 @@
+use crate::{
+    handlers::{
+        self,
+        extract::{PaymentsAdmin, RequireScope},
+    },
+    i18n, middleware,
+    services::AppState,
+};
+use axum::{
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post, put},
+    Router,
+};
+use std::sync::Arc;
+use tower_http::{
+    compression::CompressionLayer,
+    cors::CorsLayer,
+    trace::{DefaultMakeSpan, TraceLayer},
+};
+use tracing::Level;
+
+/// Every endpoint of the API with the middleware stack around it.
+pub fn router(app_state: Arc<AppState>) -> Router {
+    let config = app_state.config.clone();
+
+    // Routes that require an authenticated user
+    let authenticated = Router::new()
+        .route(
+            "/api/payment-methods",
+            get(handlers::payment_method::list_payment_methods)
+                .post(handlers::payment_method::tokenize_payment_method),
+        )
+        .route("/api/payment-methods/:id/default", put(handlers::payment_method::set_default_payment_method))
+        .route("/api/payment-methods/:id", delete(handlers::payment_method::delete_payment_method))
+        .route(
+            "/api/subscriptions",
+            get(handlers::subscription::list_subscriptions).post(handlers::subscription::create_subscription),
+        )
+        .route(
+            "/api/subscriptions/:id",
+            get(handlers::subscription::get_subscription)
+                .patch(handlers::subscription::update_subscription)
+                .delete(handlers::subscription::cancel_subscription),
+        )
+        .route("/api/subscriptions/:id/change-plan", post(handlers::subscription::change_plan))
+        .route("/api/payment-links", post(handlers::payment_link::create_payment_link))
+        .route("/api/wallet", get(handlers::wallet::get_wallet))
+        .route("/api/wallet/transactions", get(handlers::wallet::list_transactions))
+        .route("/api/wallet/topup", post(handlers::wallet::top_up))
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::auth::auth_middleware,
+        ));
+
+    // Admin routes on payments, each handler checks the read/write/refund scope it needs
+    let admin_payments = Router::new()
+        .route(
+            "/api/admin/payments/:id/confirm-transfer",
+            post(handlers::admin::confirm_bank_transfer),
+        )
+        .route("/api/admin/payments/:id/release-escrow", post(handlers::admin::release_escrow))
+        .route("/api/admin/payments/:id/efatura", get(handlers::admin::export_efatura))
+        .route(
+            "/api/admin/payments/:id/refunds",
+            get(handlers::admin::list_refunds).post(handlers::admin::create_refund),
+        )
+        .route("/api/admin/payments/export", get(handlers::admin::export_payments))
+        .route(
+            "/api/admin/payments/import",
+            post(handlers::admin::import_payments).layer(DefaultBodyLimit::max(config.import_body_limit_bytes)),
+        )
+        .route("/api/admin/archives/payments/:id", get(handlers::admin::get_archived_payment));
+
+    // Admin routes: authenticated and ADMIN role
+    let admin = Router::new()
+        .route("/api/admin/diagnostics", get(handlers::diagnostics::diagnostics))
+        .route("/api/admin/slo", get(handlers::diagnostics::slo))
+        .route(
+            "/api/admin/log-sampling",
+            get(handlers::log_sampling::get_rules).put(handlers::log_sampling::set_rules),
+        )
+        .route("/api/admin/seed", post(handlers::admin::seed_payments))
+        .route("/api/admin/vouchers", post(handlers::admin::issue_voucher))
+        .route("/api/admin/vouchers/:id/void", post(handlers::admin::void_voucher))
+        .route("/api/admin/promotions", post(handlers::admin::create_promotion))
+        .route("/api/admin/reports/fees", get(handlers::admin::fee_report))
+        .route(
+            "/api/admin/merchants",
+            get(handlers::admin::list_merchants).post(handlers::admin::onboard_merchant),
+        )
+        .route(
+            "/api/admin/users/:id/profile",
+            get(handlers::admin::get_user_profile).delete(handlers::admin::invalidate_user_profile),
+        )
+        .route("/api/admin/api-keys/:id/usage", get(handlers::admin::api_key_usage))
+        .route("/api/admin/merchants/earnings", get(handlers::admin::merchant_earnings))
+        .route("/api/admin/merchants/:id", get(handlers::admin::get_merchant))
+        .route("/api/admin/merchants/:id/suspend", post(handlers::admin::suspend_merchant))
+        .route("/api/admin/merchants/:id/activate", post(handlers::admin::activate_merchant))
+        .route("/api/admin/merchants/:id/quotas", put(handlers::admin::set_merchant_quotas))
+        .route("/api/admin/merchants/:id/scopes", put(handlers::admin::set_merchant_scopes))
+        .route(
+            "/api/admin/merchants/:id/duplicate-orders",
+            put(handlers::admin::set_merchant_duplicate_orders),
+        )
+        .route("/api/admin/merchants/:id/tax-details", put(handlers::admin::set_merchant_tax_details))
+        .route(
+            "/api/admin/merchants/:id/gateway-credentials",
+            get(handlers::admin::get_gateway_credentials)
+                .put(handlers::admin::set_gateway_credentials)
+                .delete(handlers::admin::remove_gateway_credentials),
+        )
+        .route("/api/admin/merchants/:id/terms", put(handlers::admin::set_merchant_terms))
+        .route("/api/admin/payouts", get(handlers::admin::list_payouts))
+        .route("/api/admin/payouts/generate", post(handlers::admin::generate_payouts))
+        .route("/api/admin/payouts/export", get(handlers::admin::export_payouts))
+        .route("/api/admin/payouts/:id/approve", post(handlers::admin::approve_payout))
+        .route("/api/admin/payouts/:id/mark-paid", post(handlers::admin::mark_payout_paid))
+        .route("/api/admin/reports/merchants/:id/settlements", get(handlers::admin::settlement_report))
+        .route("/api/admin/reports/merchants/:id/payouts", get(handlers::admin::payout_report))
+        .route("/api/admin/work-queue/dead", get(handlers::admin::list_dead_work))
+        .route("/api/admin/work-queue/:id/requeue", post(handlers::admin::requeue_work))
+        .route("/api/admin/webhooks", get(handlers::admin::list_webhook_events))
+        .route("/api/admin/webhooks/:id/replay", post(handlers::admin::replay_webhook_event))
+        .route(
+            "/api/admin/webhook-subscriptions",
+            get(handlers::admin::list_webhook_subscriptions).post(handlers::admin::create_webhook_subscription),
+        )
+        .route(
+            "/api/admin/webhook-subscriptions/:id",
+            get(handlers::admin::get_webhook_subscription)
+                .patch(handlers::admin::update_webhook_subscription)
+                .delete(handlers::admin::delete_webhook_subscription),
+        )
+        .route("/api/admin/webhook-subscriptions/:id/rotate-secret", post(handlers::admin::rotate_webhook_secret))
+        .route("/api/admin/webhook-subscriptions/:id/test", post(handlers::admin::test_webhook_subscription))
+        .route("/api/admin/webhook-subscriptions/:id/stats", get(handlers::admin::webhook_subscription_stats));
+    // Only in builds with `--features chaos`
+    #[cfg(feature = "chaos")]
+    let admin = admin.route(
+        "/api/admin/chaos",
+        get(handlers::chaos::list_experiments)
+            .post(handlers::chaos::start_experiment)
+            .delete(handlers::chaos::stop_experiments),
+    );
+    // The rest of the admin API needs payments:admin, so a read-only analytics token stays on the payment routes
+    let admin = admin
+        .route_layer(axum::middleware::from_extractor::<RequireScope<PaymentsAdmin>>())
+        .merge(admin_payments)
+        .route_layer(axum::middleware::from_fn(middleware::auth::require_admin))
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::auth::auth_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::ip_allowlist::restrict_admin,
+        ));
+
+    // Payment routes for merchant backends (X-Merchant-Key) and customers (bearer token); the handlers' scope checks
+    // turn away requests with neither. Only a customer's own token lets a payment draw on their wallet or saved cards
+    let payments = Router::new()
+        .route("/api/payments", post(handlers::payment::create_payment))
+        .route("/api/payments/lookup", post(handlers::payment::lookup_payments))
+        .route("/api/payments/count", get(handlers::payment::count_payments))
+        .route(
+            "/api/payments/:id",
+            get(handlers::payment::get_payment).head(handlers::payment::payment_exists),
+        )
+        .route("/api/payments/order/:order_id", get(handlers::payment::get_payment_by_order))
+        .route("/api/payments/invoice/:invoice_number", get(handlers::payment::get_payment_by_invoice))
+        .route("/api/payments/:id/wait", get(handlers::payment::wait_for_payment))
+        .route("/api/payments/:id/receipt.pdf", get(handlers::payment::get_receipt))
+        .route("/api/payments/:id/qr", get(handlers::payment::get_qr_code))
+        .route("/api/payment-intents", post(handlers::payment_intent::create_payment_intent))
+        .route("/api/payment-intents/:id", get(handlers::payment_intent::get_payment_intent))
+        .route("/api/payment-intents/:id/confirm", post(handlers::payment_intent::confirm_payment_intent))
+        // The signed link is the credential here, anyone holding it may pay
+        .route("/api/payment-links/:token/pay", post(handlers::payment_link::pay_payment_link))
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::auth::optional_auth,
+        ));
+
+    // Courier app routes, authenticated with an API key
+    let courier = Router::new()
+        .route(
+            "/api/courier/payments/:id/collection",
+            post(handlers::courier::record_collection),
+        )
+        .route("/api/courier/payments/:id/delivered", post(handlers::courier::confirm_delivery))
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::api_key::require_courier_api_key,
+        ));
+
+    // Callbacks from other services and the 3-D Secure ACS, HMAC-signed instead of carrying a JWT. The ACS result
+    // moves money, so it's only believed with a valid signature
+    let internal = Router::new()
+        .route("/api/internal/orders/:order_id/delivered", post(handlers::internal::order_delivered))
+        .route("/api/payments/:id/3ds-callback", post(handlers::payment::three_ds_callback))
+        .route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::signature::require_signature,
+        ));
+
+    Router::new()
+        // The only anonymous routes besides the probes below: the checkout quote, hosted payment pages and the
+        // provider's webhook
+        .route("/api/payments/quote", post(handlers::payment::quote_payment))
+        .route("/api/payment-links/:token", get(handlers::payment_link::get_payment_link))
+        .route("/api/webhooks/crypto", post(handlers::webhook::crypto_webhook))
+        .merge(authenticated)
+        .merge(payments)
+        .merge(admin)
+        .merge(courier)
+        .merge(internal)
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::rate_limit::enforce_quotas,
+        ))
+        // Added after the quota layer so it doesn't count them: a busy tenant mustn't fail the kubelet's probes or
+        // the metrics scrape
+        .route("/api/health", get(handlers::health::health_check))
+        .route("/api/health/ready", get(handlers::health::readiness))
+        .route("/api/version", get(handlers::version::version))
+        .route("/metrics", get(handlers::metrics::metrics))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::maintenance::reject_writes,
+        ))
+        // Runs before the per-router auth layers, which check the user against the tenant
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::tenant::resolve_tenant,
+        ))
+        // Bodies over the limit are cut off while reading, never buffered whole
+        .layer(DefaultBodyLimit::max(config.body_limit_bytes))
+        .layer(axum::middleware::from_fn(middleware::body_limit::structured_rejection))
+        .layer(axum::middleware::from_fn(middleware::content_type::require_utf8_json))
+        // Test hooks, inert unless FAULT_INJECTION_ENABLED=true and never with ENVIRONMENT=production
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::fault_injection::inject_faults,
+        ))
+        .layer(axum::middleware::from_fn(i18n::localize))
+        // Inside the trace span, whose trace id is attached to reported errors
+        .layer(axum::middleware::from_fn(middleware::error_reporting::attach_request_context))
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::request_metrics::record,
+        ))
+        // Outermost inside the request span, everything after sees the caller's trace
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::propagation::extract_context,
+        ))
+        // gzip/brotli by Accept-Encoding; images and tiny bodies are left alone
+        .layer(CompressionLayer::new())
+        // Request span at INFO, the default DEBUG one is filtered out with RUST_LOG=info
+        .layer(TraceLayer::new_for_http().make_span_with(DefaultMakeSpan::new().level(Level::INFO)))
+        .layer(CorsLayer::permissive())
+        .with_state(app_state)
+}
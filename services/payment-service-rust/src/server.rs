@@ -0,0 +1,238 @@
+use crate::config::{ClientAuth, Config, Listen};
+use anyhow::Context;
+use axum::{extract::connect_info::Connected, serve::IncomingStream, Router};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{
+        conn::auto,
+        graceful::{GracefulShutdown, Watcher},
+    },
+    service::TowerToHyperService,
+};
+use std::{fmt, fs::File, future::Future, io::BufReader, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, UnixListener},
+};
+use tokio_rustls::{
+    rustls::{server::WebPkiClientVerifier, RootCertStore, ServerConfig},
+    TlsAcceptor,
+};
+use tower::ServiceExt;
+
+// In-flight requests get this long to finish once a shutdown signal arrives
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Other end of the connection, in request extensions as `ConnectInfo<PeerAddr>`. `None` on a unix socket, where
+/// the peer is the local reverse proxy.
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddr(pub Option<SocketAddr>);
+
+impl Connected<IncomingStream<'_>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_>) -> Self {
+        PeerAddr(Some(stream.remote_addr()))
+    }
+}
+
+impl Connected<PeerAddr> for PeerAddr {
+    fn connect_info(peer: PeerAddr) -> Self {
+        peer
+    }
+}
+
+impl fmt::Display for PeerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(addr) => addr.fmt(f),
+            None => f.write_str("unix socket"),
+        }
+    }
+}
+
+/// Serves plain HTTP on TCP by default. A certificate turns on HTTPS with HTTP/2,
+/// `LISTEN=unix:<path>` swaps the TCP port for a socket file only a local proxy can reach.
+pub async fn serve(app: Router, config: &Config) -> anyhow::Result<()> {
+    let tls = match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            Some(TlsAcceptor::from(Arc::new(tls_config(config, cert_path, key_path)?)))
+        }
+        (None, None) => None,
+        _ => anyhow::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+
+    match &config.listen {
+        Listen::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).await?;
+            tracing::info!("Server listening on {}://{}", scheme, addr);
+
+            match tls {
+                Some(tls) => {
+                    let accept = || async { listener.accept().await.map(|(stream, peer)| (stream, PeerAddr(Some(peer)))) };
+                    serve_connections(accept, Some(tls), app).await
+                }
+                None => {
+                    axum::serve(listener, app.into_make_service_with_connect_info::<PeerAddr>())
+                        .with_graceful_shutdown(shutdown_signal())
+                        .await?;
+                    Ok(())
+                }
+            }
+        }
+        Listen::Unix(path) => {
+            let listener = bind_unix(path)?;
+            tracing::info!("Server listening on {} socket {}", scheme, path.display());
+
+            let accept = || async { listener.accept().await.map(|(stream, _)| (stream, PeerAddr(None))) };
+            let served = serve_connections(accept, tls, app).await;
+            std::fs::remove_file(path).ok();
+            served
+        }
+    }
+}
+
+/// A socket file left behind by a crashed run would make the bind fail, so it's removed first.
+fn bind_unix(path: &Path) -> anyhow::Result<UnixListener> {
+    match std::fs::remove_file(path) {
+        Ok(()) => tracing::info!("Removed stale socket {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("cannot remove old socket {}", path.display())),
+    }
+
+    UnixListener::bind(path).with_context(|| format!("cannot bind unix socket {}", path.display()))
+}
+
+fn tls_config(config: &Config, cert_path: &str, key_path: &str) -> anyhow::Result<ServerConfig> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(cert_path).with_context(|| format!("cannot open TLS certificate {}", cert_path))?,
+    ))
+    .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(
+        File::open(key_path).with_context(|| format!("cannot open TLS key {}", key_path))?,
+    ))?
+    .with_context(|| format!("no private key in {}", key_path))?;
+
+    let builder = ServerConfig::builder();
+    let builder = match &config.tls_client_ca_path {
+        Some(ca_path) => builder.with_client_cert_verifier(client_verifier(ca_path, config.tls_client_auth)?),
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder.with_single_cert(certs, key)?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(server_config)
+}
+
+/// Mutual TLS for inbound connections: client certificates must chain to the CA at `ca_path`.
+fn client_verifier(
+    ca_path: &str,
+    auth: ClientAuth,
+) -> anyhow::Result<Arc<dyn tokio_rustls::rustls::server::danger::ClientCertVerifier>> {
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut BufReader::new(
+        File::open(ca_path).with_context(|| format!("cannot open client CA {}", ca_path))?,
+    )) {
+        roots.add(cert?)?;
+    }
+
+    let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+    let verifier = match auth {
+        ClientAuth::Optional => verifier.allow_unauthenticated().build()?,
+        ClientAuth::Required => verifier.build()?,
+    };
+    tracing::info!("Client certificates from {} are {:?}", ca_path, auth);
+
+    Ok(verifier)
+}
+
+/// Accept loop for the listeners `axum::serve` doesn't cover (TLS, unix sockets), with graceful shutdown.
+async fn serve_connections<A, F, S>(mut accept: A, tls: Option<TlsAcceptor>, app: Router) -> anyhow::Result<()>
+where
+    A: FnMut() -> F,
+    F: Future<Output = std::io::Result<(S, PeerAddr)>>,
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to accept connection");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        // Handshakes run on the connection's task so a slow client can't hold up accepting others
+        let tls = tls.clone();
+        let builder = builder.clone();
+        let app = app.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let result = match tls {
+                Some(tls) => match tls.accept(stream).await {
+                    Ok(stream) => serve_connection(stream, peer, &builder, app, watcher).await,
+                    Err(e) => {
+                        tracing::debug!(error = %e, "TLS handshake with {} failed", peer);
+                        return;
+                    }
+                },
+                None => serve_connection(stream, peer, &builder, app, watcher).await,
+            };
+            if let Err(e) = result {
+                tracing::debug!(error = %e, "connection from {} closed with an error", peer);
+            }
+        });
+    }
+
+    if tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, graceful.shutdown()).await.is_err() {
+        tracing::warn!("Connections still open after {:?}, closing them", SHUTDOWN_GRACE_PERIOD);
+    }
+
+    Ok(())
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    peer: PeerAddr,
+    builder: &auto::Builder<TokioExecutor>,
+    app: Router,
+    watcher: Watcher,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // What `axum::serve` does for plain TCP, so handlers see the peer either way
+    let Ok(service) = app.into_make_service_with_connect_info::<PeerAddr>().oneshot(peer).await;
+    let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(service));
+    watcher.watch(connection).await
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        if let Ok(mut signal) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            signal.recv().await;
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("Shutdown signal received");
+}
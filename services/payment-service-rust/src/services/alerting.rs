@@ -0,0 +1,94 @@
+use crate::config::{AlertFormat, Config};
+use anyhow::{bail, Result};
+use redis::aio::ConnectionManager;
+use reqwest::Client;
+use serde_json::json;
+use std::time::Duration;
+
+/// Operational alert for the on-call channel.
+#[derive(Debug)]
+pub struct Alert {
+    /// Alerts with the same key are sent once per dedup window
+    pub key: String,
+    pub title: String,
+    pub text: String,
+}
+
+/// Posts alerts to a Slack or Teams incoming webhook; does nothing when ALERT_WEBHOOK_URL is unset.
+pub struct Alerter {
+    webhook_url: Option<String>,
+    format: AlertFormat,
+    dedup_window: Duration,
+    redis: ConnectionManager,
+    client: Client,
+}
+
+impl Alerter {
+    pub fn new(config: &Config, redis: ConnectionManager) -> Self {
+        Self {
+            webhook_url: config.alert_webhook_url.clone(),
+            format: config.alert_webhook_format,
+            dedup_window: Duration::from_secs(config.alert_dedup_secs),
+            redis,
+            client: Client::builder().timeout(Duration::from_secs(5)).build().unwrap_or_default(),
+        }
+    }
+
+    /// Sends the alert unless one with the same key went out within the dedup window. Failures are logged,
+    /// alerting never fails the caller.
+    pub async fn send(&self, alert: Alert) {
+        let Some(url) = &self.webhook_url else {
+            return;
+        };
+
+        match self.claim(&alert.key).await {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::debug!(key = %alert.key, "Duplicate alert suppressed");
+                return;
+            }
+            // Better a repeated alert than a lost one
+            Err(e) => tracing::warn!(error = %e, "Alert dedup check failed"),
+        }
+
+        if let Err(e) = self.post(url, &alert).await {
+            tracing::error!(error = %e, key = %alert.key, "Could not send alert: {}", alert.title);
+        }
+    }
+
+    /// Marks the key as sent for the dedup window; false when it already was.
+    #[tracing::instrument(name = "redis.command", skip_all, fields(db.system = "redis", db.statement = "SET alert NX"))]
+    async fn claim(&self, key: &str) -> Result<bool> {
+        let mut redis = self.redis.clone();
+        let set: Option<String> = redis::cmd("SET")
+            .arg(format!("alert:{}", key))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(self.dedup_window.as_secs().max(1))
+            .query_async(&mut redis)
+            .await?;
+
+        Ok(set.is_some())
+    }
+
+    async fn post(&self, url: &str, alert: &Alert) -> Result<()> {
+        let body = match self.format {
+            AlertFormat::Slack => json!({ "text": format!("*{}*\n{}", alert.title, alert.text) }),
+            AlertFormat::Teams => json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "summary": alert.title,
+                "title": alert.title,
+                "text": alert.text,
+            }),
+        };
+
+        let response = self.client.post(url).json(&body).send().await?;
+        if !response.status().is_success() {
+            bail!("alert webhook returned {}", response.status());
+        }
+
+        Ok(())
+    }
+}
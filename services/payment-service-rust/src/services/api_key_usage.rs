@@ -0,0 +1,183 @@
+use crate::{
+    chaos::{self, Target},
+    dto::{ApiKeyUsageQuery, ApiKeyUsageResponse},
+    error::{AppError, AppResult},
+    models::ApiKeyUsageDay,
+    services::{merchants, AppState},
+};
+use chrono::{Duration, NaiveDate, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// Merchants with counters not yet written to `api_key_usage`.
+const PENDING_SET: &str = "api_key_usage:pending";
+const DEFAULT_REPORT_DAYS: i64 = 30;
+
+/// Hash of `<day>:requests` / `<day>:payments` counters for one merchant.
+fn counters_key(merchant_id: Uuid) -> String {
+    format!("api_key_usage:{}", merchant_id)
+}
+
+/// Counts a request made with the merchant's API key; `payment` when it creates a payment.
+#[tracing::instrument(name = "redis.command", skip_all, fields(db.system = "redis", db.statement = "HINCRBY api_key_usage"))]
+pub async fn record(mut redis: ConnectionManager, merchant_id: Uuid, payment: bool) -> anyhow::Result<()> {
+    chaos::inject(Target::Redis)?;
+    let day = Utc::now().date_naive();
+    let key = counters_key(merchant_id);
+
+    let mut pipe = redis::pipe();
+    pipe.hincr(&key, format!("{}:requests", day), 1).ignore();
+    if payment {
+        pipe.hincr(&key, format!("{}:payments", day), 1).ignore();
+    }
+    pipe.sadd(PENDING_SET, merchant_id.to_string()).ignore();
+    Ok(pipe.query_async(&mut redis).await?)
+}
+
+/// Merchants whose counters wait for the next flush.
+pub async fn pending_merchants(redis: &ConnectionManager) -> AppResult<usize> {
+    let mut redis = redis.clone();
+    redis.scard(PENDING_SET).await.map_err(redis_error)
+}
+
+/// Moves the Redis counters into Postgres. Returns how many merchants were flushed.
+pub async fn flush(pool: &PgPool, redis: &ConnectionManager) -> AppResult<usize> {
+    let mut redis = redis.clone();
+    let pending: Vec<String> = redis.smembers(PENDING_SET).await.map_err(redis_error)?;
+
+    let mut flushed = 0;
+    for member in pending {
+        let Ok(merchant_id) = Uuid::parse_str(&member) else {
+            continue;
+        };
+        let key = counters_key(merchant_id);
+        // Removed before the hash is taken, so a request in between marks the merchant pending again
+        let _: () = redis.srem(PENDING_SET, &member).await.map_err(redis_error)?;
+        let (counters, _): (HashMap<String, i64>, i64) = redis::pipe()
+            .atomic()
+            .hgetall(&key)
+            .del(&key)
+            .query_async(&mut redis)
+            .await
+            .map_err(redis_error)?;
+
+        if let Err(e) = persist(pool, merchant_id, &by_day(&counters)).await {
+            // Put the counts back for the next run
+            let mut pipe = redis::pipe();
+            for (field, count) in &counters {
+                pipe.hincr(&key, field, *count).ignore();
+            }
+            pipe.sadd(PENDING_SET, &member).ignore();
+            let _: () = pipe.query_async(&mut redis).await.map_err(redis_error)?;
+            return Err(e);
+        }
+        flushed += 1;
+    }
+
+    Ok(flushed)
+}
+
+#[tracing::instrument(
+    name = "db.query",
+    skip_all,
+    fields(db.system = "postgresql", db.statement = "api_key_usage.upsert", db.rows_affected = tracing::field::Empty)
+)]
+async fn persist(pool: &PgPool, merchant_id: Uuid, days: &BTreeMap<NaiveDate, (i64, i64)>) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
+    for (day, (requests, payments)) in days {
+        sqlx::query(
+            r#"
+            INSERT INTO api_key_usage (merchant_id, day, requests, payments)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (merchant_id, day) DO UPDATE
+            SET requests = api_key_usage.requests + EXCLUDED.requests,
+                payments = api_key_usage.payments + EXCLUDED.payments
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(day)
+        .bind(requests)
+        .bind(payments)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    tracing::Span::current().record("db.rows_affected", days.len());
+
+    Ok(())
+}
+
+/// Persisted days plus whatever is still waiting in Redis.
+pub async fn report(state: &AppState, merchant_id: Uuid, query: &ApiKeyUsageQuery) -> AppResult<ApiKeyUsageResponse> {
+    let merchant = merchants::get(&state.db_pool, merchant_id).await?;
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_REPORT_DAYS - 1));
+    if from > to {
+        return Err(AppError::BadRequest("from must not be after to".to_string()));
+    }
+
+    let stored = sqlx::query_as::<_, ApiKeyUsageDay>(
+        "SELECT day, requests, payments FROM api_key_usage WHERE merchant_id = $1 AND day BETWEEN $2 AND $3",
+    )
+    .bind(merchant_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let mut redis = state.redis_conn.clone();
+    let pending: HashMap<String, i64> = redis.hgetall(counters_key(merchant_id)).await.map_err(redis_error)?;
+
+    let mut days = by_day(&pending);
+    days.retain(|day, _| (from..=to).contains(day));
+    for row in stored {
+        let (requests, payments) = days.entry(row.day).or_default();
+        *requests += row.requests;
+        *payments += row.payments;
+    }
+    let days: Vec<ApiKeyUsageDay> = days
+        .into_iter()
+        .map(|(day, (requests, payments))| ApiKeyUsageDay { day, requests, payments })
+        .collect();
+
+    let config = &state.config;
+    Ok(ApiKeyUsageResponse {
+        merchant_id,
+        from,
+        to,
+        requests_per_minute_limit: merchant
+            .requests_per_minute
+            .map_or(config.tenant_requests_per_minute, |n| n as u32),
+        payments_per_day_limit: merchant.payments_per_day.map_or(config.tenant_payments_per_day, |n| n as u32),
+        total_requests: days.iter().map(|d| d.requests).sum(),
+        total_payments: days.iter().map(|d| d.payments).sum(),
+        peak_payments_per_day: days.iter().map(|d| d.payments).max().unwrap_or(0),
+        days,
+    })
+}
+
+/// Folds `<day>:<counter>` fields into (requests, payments) per day.
+fn by_day(counters: &HashMap<String, i64>) -> BTreeMap<NaiveDate, (i64, i64)> {
+    let mut days: BTreeMap<NaiveDate, (i64, i64)> = BTreeMap::new();
+    for (field, count) in counters {
+        let Some((day, counter)) = field.split_once(':') else {
+            continue;
+        };
+        let Ok(day) = day.parse::<NaiveDate>() else {
+            continue;
+        };
+        let entry = days.entry(day).or_default();
+        match counter {
+            "requests" => entry.0 += count,
+            "payments" => entry.1 += count,
+            _ => {}
+        }
+    }
+    days
+}
+
+fn redis_error(e: redis::RedisError) -> AppError {
+    AppError::Internal(e.into())
+}
@@ -0,0 +1,167 @@
+use crate::{
+    error::{AppError, AppResult},
+    services::{object_storage::ObjectStorage, AppState},
+};
+use anyhow::{bail, Context};
+use chrono::{Datelike, Months, NaiveDate, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::io::{BufRead, BufReader, Write};
+use uuid::Uuid;
+
+/// Month partitions of `payments` old enough to move to cold storage, oldest first.
+pub async fn due_partitions(pool: &PgPool, after_months: i32) -> AppResult<Vec<NaiveDate>> {
+    let today = Utc::now().date_naive();
+    let cutoff = today.with_day(1).unwrap_or(today) - Months::new(after_months.max(1) as u32);
+
+    let names: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT c.relname::TEXT FROM pg_inherits i
+        JOIN pg_class c ON c.oid = i.inhrelid
+        WHERE i.inhparent = 'payments'::regclass AND c.relname ~ '^payments_[0-9]{4}_[0-9]{2}$'
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut months: Vec<NaiveDate> = names
+        .iter()
+        .filter_map(|name| NaiveDate::parse_from_str(&format!("{}_01", &name["payments_".len()..]), "%Y_%m_%d").ok())
+        .filter(|month| *month < cutoff)
+        .collect();
+    months.sort();
+
+    Ok(months)
+}
+
+/// Exports one month partition as gzipped NDJSON, reads the object back to verify it, then detaches and drops
+/// the partition. Ids are kept in `archived_payments` so `get_archived` knows which object to open.
+pub async fn archive_month(pool: &PgPool, storage: &ObjectStorage, month: NaiveDate) -> anyhow::Result<i64> {
+    let table = partition_name(month);
+    let key = object_key(month);
+
+    // Ordered by id, so the hash can be recomputed by Postgres below
+    let export = format!("SELECT row_to_json(p)::TEXT FROM {} p ORDER BY id", table);
+    let mut rows = sqlx::query_scalar::<_, String>(&export).fetch(pool);
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    let mut hasher = Sha256::new();
+    let mut row_count: i64 = 0;
+    while let Some(row) = rows.try_next().await? {
+        gzip.write_all(row.as_bytes())?;
+        gzip.write_all(b"\n")?;
+        hasher.update(row.as_bytes());
+        hasher.update(b"\n");
+        row_count += 1;
+    }
+    drop(rows);
+    let sha256 = hex::encode(hasher.finalize());
+
+    storage.put(&key, gzip.finish()?, "application/gzip").await?;
+
+    // Only trust what the store gives back
+    let stored = storage.get(&key).await?.context("archived object missing right after upload")?;
+    let (stored_sha256, stored_count) = digest(&stored)?;
+    if stored_sha256 != sha256 || stored_count != row_count {
+        bail!("archive {} failed verification: {} rows stored, {} exported", key, stored_count, row_count);
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(&format!("ALTER TABLE payments DETACH PARTITION {}", table)).execute(&mut *tx).await?;
+
+    // A late update between export and detach would be lost with the table
+    let current: String = sqlx::query_scalar(&format!(
+        r#"
+        SELECT encode(sha256(convert_to(COALESCE(string_agg(row_to_json(p)::TEXT || E'\n', '' ORDER BY id), ''), 'UTF8')), 'hex')
+        FROM {} p
+        "#,
+        table
+    ))
+    .fetch_one(&mut *tx)
+    .await?;
+    if current != sha256 {
+        bail!("{} changed during export, retrying next run", table);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO payment_archives (month, object_key, row_count, sha256, archived_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        "#,
+    )
+    .bind(month)
+    .bind(&key)
+    .bind(row_count)
+    .bind(&sha256)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(&format!("INSERT INTO archived_payments (payment_id, month) SELECT id, $1 FROM {}", table))
+        .bind(month)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(&format!("DROP TABLE {}", table)).execute(&mut *tx).await?;
+    tx.commit().await?;
+
+    Ok(row_count)
+}
+
+/// Reads an archived payment back from cold storage, as the row was when archived.
+pub async fn get_archived(state: &AppState, payment_id: Uuid) -> AppResult<serde_json::Value> {
+    let storage = state
+        .archive_storage
+        .as_ref()
+        .ok_or_else(|| AppError::ServiceUnavailable("Payment archive is not configured".to_string()))?;
+
+    let object_key: Option<String> = sqlx::query_scalar(
+        r#"
+        SELECT a.object_key FROM archived_payments ap
+        JOIN payment_archives a ON a.month = ap.month
+        WHERE ap.payment_id = $1
+        "#,
+    )
+    .bind(payment_id)
+    .fetch_optional(&state.db_pool)
+    .await?;
+    let object_key = object_key.ok_or_else(|| AppError::NotFound("Archived payment not found".to_string()))?;
+
+    let object = storage
+        .get(&object_key)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("archive object {} is missing", object_key))?;
+
+    let id = payment_id.to_string();
+    for line in BufReader::new(GzDecoder::new(object.as_slice())).lines() {
+        let line = line.map_err(anyhow::Error::from)?;
+        if !line.contains(&id) {
+            continue;
+        }
+        let row: serde_json::Value = serde_json::from_str(&line).map_err(anyhow::Error::from)?;
+        if row["id"] == id.as_str() {
+            return Ok(row);
+        }
+    }
+
+    Err(AppError::NotFound("Archived payment not found".to_string()))
+}
+
+fn partition_name(month: NaiveDate) -> String {
+    format!("payments_{}", month.format("%Y_%m"))
+}
+
+fn object_key(month: NaiveDate) -> String {
+    format!("payments/{}.ndjson.gz", month.format("%Y/%m"))
+}
+
+/// sha256 of the uncompressed NDJSON and its line count.
+fn digest(gzipped: &[u8]) -> anyhow::Result<(String, i64)> {
+    let mut hasher = Sha256::new();
+    let mut lines = 0;
+    for line in BufReader::new(GzDecoder::new(gzipped)).lines() {
+        hasher.update(line?.as_bytes());
+        hasher.update(b"\n");
+        lines += 1;
+    }
+
+    Ok((hex::encode(hasher.finalize()), lines))
+}
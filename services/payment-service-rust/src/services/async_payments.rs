@@ -0,0 +1,93 @@
+use crate::{
+    dto::CreatePaymentRequest,
+    error::{AppError, AppResult},
+    models::{Payment, PaymentStatus},
+    services::{
+        payment_service::{self, NewPayment},
+        work_queue::{self, WorkKind},
+        AppState,
+    },
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Work item payload; the merchant and the payer aren't part of the serialized request.
+#[derive(Debug, Serialize, Deserialize)]
+struct PaymentWork {
+    payment_id: Uuid,
+    merchant_id: Uuid,
+    #[serde(default)]
+    payer_id: Option<Uuid>,
+    request: CreatePaymentRequest,
+}
+
+/// Stores a PENDING placeholder and queues the request; the caller polls the payment for the outcome.
+pub async fn enqueue(pool: &PgPool, request: CreatePaymentRequest) -> AppResult<Payment> {
+    let mut new = NewPayment::new(Uuid::new_v4(), &request, PaymentStatus::Pending);
+    new.queued = true;
+
+    let mut tx = pool.begin().await?;
+    payment_service::claim_order(&mut tx, request.merchant_id, request.order_id, new.id).await?;
+    let payment = payment_service::insert_payment(&mut *tx, new).await?;
+    let work = PaymentWork {
+        payment_id: payment.id,
+        merchant_id: payment.merchant_id,
+        payer_id: request.payer_id,
+        request,
+    };
+    work_queue::enqueue(&mut *tx, WorkKind::Payment, &work, Utc::now()).await?;
+    tx.commit().await?;
+
+    Ok(payment)
+}
+
+/// Runs a queued payment. Database and internal errors are returned so the queue retries them;
+/// anything else is the request's fault and fails the placeholder so pollers get an answer.
+pub async fn process(state: &AppState, payload: serde_json::Value) -> AppResult<()> {
+    let pool = &state.db_pool;
+    let work: PaymentWork = serde_json::from_value(payload).map_err(|e| AppError::Internal(e.into()))?;
+    let payment_id = work.payment_id;
+
+    // A redelivered item whose earlier attempt already stored the outcome
+    let queued = sqlx::query_scalar::<_, bool>("SELECT queued FROM payments WHERE id = $1")
+        .bind(payment_id)
+        .fetch_one(pool)
+        .await?;
+    if !queued {
+        return Ok(());
+    }
+
+    let mut request = work.request;
+    request.merchant_id = work.merchant_id;
+    request.payer_id = work.payer_id;
+
+    // The gateway is called with the payment id, so a retried authorization isn't charged twice
+    let payment = match payment_service::create_payment_with_id(state, payment_id, request).await {
+        Ok(payment) => Some(payment),
+        Err(e @ (AppError::Database(_) | AppError::StatementTimeout | AppError::Internal(_))) => return Err(e),
+        Err(e) => {
+            tracing::warn!(error = %e, "async payment {} rejected", payment_id);
+            fail_placeholder(pool, payment_id).await?
+        }
+    };
+
+    if let Some(payment) = payment {
+        state.events.publish(&payment);
+    }
+
+    Ok(())
+}
+
+async fn fail_placeholder(pool: &PgPool, payment_id: Uuid) -> AppResult<Option<Payment>> {
+    let payment = sqlx::query_as::<_, Payment>(
+        "UPDATE payments SET payment_status = $2, queued = FALSE, updated_at = NOW() WHERE id = $1 AND queued RETURNING *",
+    )
+    .bind(payment_id)
+    .bind(PaymentStatus::Failed.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(payment)
+}
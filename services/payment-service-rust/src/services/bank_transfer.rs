@@ -0,0 +1,103 @@
+use crate::{
+    config::Config,
+    dto::CreatePaymentRequest,
+    error::{AppError, AppResult},
+    models::{Payment, PaymentStatus, TransferInstructions, METHOD_BANK_TRANSFER},
+    services::{
+        payment_service::{self, NewPayment},
+        surcharges,
+    },
+};
+use chrono::{Duration, Utc};
+use rand::{distributions::Uniform, Rng};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Unambiguous characters only (no 0/O, 1/I), the customer types this into their banking app
+const REFERENCE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Builds a PENDING payment carrying the transfer instructions for the customer.
+pub fn prepare(config: &Config, payment_id: Uuid, request: &CreatePaymentRequest) -> NewPayment {
+    let reference_code = generate_reference();
+    let expires_at = Utc::now() + Duration::days(config.bank_transfer_expiry_days);
+
+    let mut new = NewPayment::new(payment_id, request, PaymentStatus::Pending);
+    new.method_surcharge = surcharges::quote(config, METHOD_BANK_TRANSFER, request.amount);
+    new.transfer_reference = Some(reference_code.clone());
+    new.expires_at = Some(expires_at);
+    new.transfer_instructions = Some(TransferInstructions {
+        bank_name: config.bank_transfer_bank_name.clone(),
+        account_holder: config.bank_transfer_account_holder.clone(),
+        iban: config.bank_transfer_iban.clone(),
+        reference_code,
+        amount: request.amount + new.method_surcharge.amount,
+        currency: request.currency.clone(),
+        expires_at,
+    });
+
+    new
+}
+
+/// Marks a pending transfer as received after the money shows up on the bank statement.
+pub async fn confirm(pool: &PgPool, id: Uuid, expected_version: i32) -> AppResult<Payment> {
+    let payment = sqlx::query_as::<_, Payment>(
+        r#"
+        UPDATE payments
+        SET payment_status = $2, transaction_id = transfer_reference, updated_at = $3
+        WHERE id = $1 AND payment_method = $4 AND payment_status = $5 AND version = $6
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(PaymentStatus::Completed.as_str())
+    .bind(Utc::now())
+    .bind(METHOD_BANK_TRANSFER)
+    .bind(PaymentStatus::Pending.as_str())
+    .bind(expected_version)
+    .fetch_optional(pool)
+    .await?;
+
+    match payment {
+        Some(payment) => Ok(payment),
+        None => {
+            let current = payment_service::get_payment(pool, id).await?;
+            payment_service::ensure_version(&current, expected_version)?;
+            Err(AppError::Conflict("Payment is not a pending bank transfer".to_string()))
+        }
+    }
+}
+
+/// Fails transfers that were not confirmed before their deadline, giving back what they held.
+pub async fn expire_unconfirmed(pool: &PgPool) -> AppResult<Vec<Payment>> {
+    let mut tx = pool.begin().await?;
+    let payments = sqlx::query_as::<_, Payment>(
+        r#"
+        UPDATE payments
+        SET payment_status = $1, updated_at = NOW()
+        WHERE payment_method = $2 AND payment_status = $3 AND expires_at < NOW()
+        RETURNING *
+        "#,
+    )
+    .bind(PaymentStatus::Failed.as_str())
+    .bind(METHOD_BANK_TRANSFER)
+    .bind(PaymentStatus::Pending.as_str())
+    .fetch_all(&mut *tx)
+    .await?;
+    for payment in &payments {
+        payment_service::release_holds(&mut tx, payment).await?;
+    }
+    tx.commit().await?;
+
+    Ok(payments)
+}
+
+fn generate_reference() -> String {
+    let dist = Uniform::from(0..REFERENCE_ALPHABET.len());
+    let code: String = rand::thread_rng()
+        .sample_iter(dist)
+        .take(8)
+        .map(|i| REFERENCE_ALPHABET[i] as char)
+        .collect();
+
+    format!("HVL{}", code)
+}
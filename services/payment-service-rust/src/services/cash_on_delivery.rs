@@ -0,0 +1,60 @@
+use crate::{
+    dto::CollectionRequest,
+    error::{AppError, AppResult},
+    models::{Payment, PaymentStatus, METHOD_CASH_ON_DELIVERY},
+    services::payment_service,
+};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Records the courier's result at the door: cash collected or delivery payment failed. A failed one gives back
+/// what it held (wallet and voucher parts, promo code).
+pub async fn record_collection(
+    pool: &PgPool,
+    id: Uuid,
+    expected_version: i32,
+    request: CollectionRequest,
+) -> AppResult<Payment> {
+    let (payment_status, collected_at) = match request.outcome.as_str() {
+        "COLLECTED" => (PaymentStatus::Completed, Some(Utc::now())),
+        "FAILED" => (PaymentStatus::Failed, None),
+        other => return Err(AppError::BadRequest(format!("Unknown collection outcome: {}", other))),
+    };
+
+    let mut tx = pool.begin().await?;
+    let payment = sqlx::query_as::<_, Payment>(
+        r#"
+        UPDATE payments
+        SET payment_status = $2, collected_at = $3, collection_note = $4, updated_at = $5
+        WHERE id = $1 AND payment_method = $6 AND payment_status = $7 AND version = $8
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(payment_status.as_str())
+    .bind(collected_at)
+    .bind(request.note)
+    .bind(Utc::now())
+    .bind(METHOD_CASH_ON_DELIVERY)
+    .bind(PaymentStatus::AwaitingCollection.as_str())
+    .bind(expected_version)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    match payment {
+        Some(payment) => {
+            if payment.payment_status == PaymentStatus::Failed.as_str() {
+                payment_service::release_holds(&mut tx, &payment).await?;
+            }
+            tx.commit().await?;
+
+            Ok(payment)
+        }
+        None => {
+            let current = payment_service::get_payment(pool, id).await?;
+            payment_service::ensure_version(&current, expected_version)?;
+            Err(AppError::Conflict("Payment is not awaiting cash collection".to_string()))
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    dto::CreatePaymentRequest,
+    error::{AppError, AppResult},
+    models::{CryptoPayment, DepositStatus, Payment, PaymentStatus, WalletEntryType, METHOD_CRYPTO},
+    services::{
+        crypto_provider::DepositUpdate,
+        fx,
+        payment_service::{self, NewPayment},
+        surcharges, wallets, webhook_events, AppState,
+    },
+};
+use chrono::{Duration, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+const SUPPORTED_ASSETS: [&str; 3] = ["BTC", "ETH", "USDT"];
+const CRYPTO_DECIMALS: u32 = 8;
+
+/// Creates a PENDING payment with a fresh deposit address for the requested asset, in the caller's transaction.
+pub async fn create(
+    state: &AppState,
+    conn: &mut PgConnection,
+    payment_id: Uuid,
+    request: &CreatePaymentRequest,
+) -> AppResult<Payment> {
+    let asset = request
+        .crypto_asset
+        .as_deref()
+        .map(str::to_uppercase)
+        .ok_or_else(|| AppError::BadRequest("crypto_asset is required for crypto payments".to_string()))?;
+    if !SUPPORTED_ASSETS.contains(&asset.as_str()) {
+        return Err(AppError::BadRequest(format!("Unsupported crypto asset: {}", asset)));
+    }
+
+    let surcharge = surcharges::quote(&state.config, METHOD_CRYPTO, request.amount);
+    let (expected_amount, exchange_rate) =
+        fx::convert(&state.config, request.amount + surcharge.amount, &request.currency, &asset)?;
+    let deposit = state.crypto_provider.create_deposit_address(&asset, payment_id).await?;
+
+    let mut new = NewPayment::new(payment_id, request, PaymentStatus::Pending);
+    new.method_surcharge = surcharge;
+    let payment = payment_service::insert_payment(&mut *conn, new).await?;
+
+    let now = Utc::now();
+    sqlx::query(
+        r#"
+        INSERT INTO crypto_payments (payment_id, asset, deposit_address, provider_reference, exchange_rate, expected_amount,
+                                     required_confirmations, deposit_status, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+    )
+    .bind(payment_id)
+    .bind(&asset)
+    .bind(deposit.address)
+    .bind(deposit.reference)
+    .bind(exchange_rate)
+    .bind(expected_amount.round_dp(CRYPTO_DECIMALS))
+    .bind(state.config.crypto_required_confirmations)
+    .bind(DepositStatus::AwaitingDeposit.as_str())
+    .bind(now)
+    .bind(now)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(payment)
+}
+
+pub async fn get(pool: &PgPool, payment_id: Uuid) -> AppResult<Option<CryptoPayment>> {
+    let crypto = sqlx::query_as::<_, CryptoPayment>("SELECT * FROM crypto_payments WHERE payment_id = $1")
+        .bind(payment_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(crypto)
+}
+
+/// Deposits of `payment_ids` (those paid in crypto), in no particular order.
+pub async fn get_many(pool: &PgPool, payment_ids: &[Uuid]) -> AppResult<Vec<CryptoPayment>> {
+    let crypto = sqlx::query_as::<_, CryptoPayment>("SELECT * FROM crypto_payments WHERE payment_id = ANY($1)")
+        .bind(payment_ids)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(crypto)
+}
+
+/// Provider references of deposits that can still change.
+pub async fn open_references(pool: &PgPool) -> AppResult<Vec<String>> {
+    let references = sqlx::query_scalar::<_, String>(
+        "SELECT provider_reference FROM crypto_payments WHERE deposit_status IN ($1, $2, $3)"
+    )
+    .bind(DepositStatus::AwaitingDeposit.as_str())
+    .bind(DepositStatus::Confirming.as_str())
+    .bind(DepositStatus::Underpaid.as_str())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(references)
+}
+
+/// Applies a provider update (webhook or poll). Returns the payment when it was finalized by this update.
+/// Webhook deliveries pass their event id and are applied once however often they are redelivered.
+pub async fn apply_update(
+    state: &AppState,
+    reference: &str,
+    update: DepositUpdate,
+    event_id: Option<Uuid>,
+) -> AppResult<Option<Payment>> {
+    let mut tx = state.db_pool.begin().await?;
+
+    if let Some(event_id) = event_id {
+        if !webhook_events::claim(&mut tx, event_id).await? {
+            tracing::info!(%event_id, reference, "crypto webhook event already applied, skipping");
+            return Ok(None);
+        }
+    }
+
+    let crypto = sqlx::query_as::<_, CryptoPayment>(
+        "SELECT * FROM crypto_payments WHERE provider_reference = $1 FOR UPDATE"
+    )
+    .bind(reference)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Crypto deposit not found".to_string()))?;
+
+    let current = crypto.deposit_status.as_str();
+    let closed = [DepositStatus::Confirmed, DepositStatus::Overpaid, DepositStatus::Expired];
+    if closed.iter().any(|status| status.as_str() == current) {
+        // Nothing left to apply, the event still counts as processed. A deposit arriving after the expiry is
+        // left to reconciliation with the provider
+        tx.commit().await?;
+        return Ok(None);
+    }
+
+    // Updates may arrive out of order. Received amount and confirmations only ever grow, so an older update
+    // can't take the deposit back to an earlier state
+    let update = DepositUpdate {
+        received_amount: update.received_amount.max(crypto.received_amount),
+        confirmations: update.confirmations.max(crypto.confirmations),
+        tx_hash: update.tx_hash,
+    };
+    let deposit_status = evaluate(&crypto, &update, state.config.crypto_underpayment_tolerance_percent);
+
+    sqlx::query(
+        r#"
+        UPDATE crypto_payments
+        SET received_amount = $2, confirmations = $3, tx_hash = COALESCE($4, tx_hash), deposit_status = $5, updated_at = $6,
+            underpaid_at = CASE WHEN $5 = $7 THEN COALESCE(underpaid_at, $6) ELSE underpaid_at END
+        WHERE payment_id = $1
+        "#,
+    )
+    .bind(crypto.payment_id)
+    .bind(update.received_amount)
+    .bind(update.confirmations)
+    .bind(update.tx_hash)
+    .bind(deposit_status.as_str())
+    .bind(Utc::now())
+    .bind(DepositStatus::Underpaid.as_str())
+    .execute(&mut *tx)
+    .await?;
+
+    let payment = if deposit_status.is_final() {
+        sqlx::query_as::<_, Payment>(
+            r#"
+            UPDATE payments SET payment_status = $2, transaction_id = $3, updated_at = $4
+            WHERE id = $1 AND payment_status = $5
+            RETURNING *
+            "#,
+        )
+        .bind(crypto.payment_id)
+        .bind(PaymentStatus::Completed.as_str())
+        .bind(reference)
+        .bind(Utc::now())
+        .bind(PaymentStatus::Pending.as_str())
+        .fetch_optional(&mut *tx)
+        .await?
+    } else {
+        None
+    };
+
+    tx.commit().await?;
+
+    Ok(payment)
+}
+
+/// Fails the payments of deposits still underpaid `crypto_underpaid_expiry_hours` after they fell short, so they
+/// are no longer polled. What did arrive is credited to the customer's wallet, and promo code uses are given back.
+pub async fn expire_underpaid(state: &AppState) -> AppResult<Vec<Payment>> {
+    let deadline = Utc::now() - Duration::hours(state.config.crypto_underpaid_expiry_hours);
+    let mut tx = state.db_pool.begin().await?;
+
+    let expired = sqlx::query_as::<_, CryptoPayment>(
+        r#"
+        UPDATE crypto_payments SET deposit_status = $1, updated_at = NOW()
+        WHERE deposit_status = $2 AND underpaid_at < $3
+        RETURNING *
+        "#,
+    )
+    .bind(DepositStatus::Expired.as_str())
+    .bind(DepositStatus::Underpaid.as_str())
+    .bind(deadline)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut payments = Vec::with_capacity(expired.len());
+    for crypto in expired {
+        let payment = sqlx::query_as::<_, Payment>(
+            "UPDATE payments SET payment_status = $2, updated_at = NOW() WHERE id = $1 AND payment_status = $3 RETURNING *"
+        )
+        .bind(crypto.payment_id)
+        .bind(PaymentStatus::Failed.as_str())
+        .bind(PaymentStatus::Pending.as_str())
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(payment) = payment else {
+            continue;
+        };
+
+        payment_service::release_holds(&mut tx, &payment).await?;
+        let credit = underpaid_credit(&crypto);
+        if credit > Decimal::ZERO {
+            wallets::credit(&mut tx, payment.user_id, &payment.currency, credit, WalletEntryType::Refund, Some(payment.id))
+                .await?;
+        }
+        payments.push(payment);
+    }
+    tx.commit().await?;
+
+    Ok(payments)
+}
+
+/// Value of what arrived in the payment's currency, at the rate the deposit was quoted at; rounded down, so the
+/// wallet never gets more than was sent.
+fn underpaid_credit(crypto: &CryptoPayment) -> Decimal {
+    (crypto.received_amount / crypto.exchange_rate).round_dp_with_strategy(2, RoundingStrategy::ToZero)
+}
+
+fn evaluate(crypto: &CryptoPayment, update: &DepositUpdate, tolerance_percent: Decimal) -> DepositStatus {
+    if update.received_amount.is_zero() {
+        return DepositStatus::AwaitingDeposit;
+    }
+    if update.confirmations < crypto.required_confirmations {
+        return DepositStatus::Confirming;
+    }
+
+    // Small shortfalls (network fees deducted by wallets) are accepted
+    let minimum = crypto.expected_amount * (Decimal::ONE_HUNDRED - tolerance_percent) / Decimal::ONE_HUNDRED;
+    if update.received_amount < minimum {
+        DepositStatus::Underpaid
+    } else if update.received_amount > crypto.expected_amount {
+        DepositStatus::Overpaid
+    } else {
+        DepositStatus::Confirmed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    /// 600 TRY quoted at 0.0000005 BTC per lira: 0.0003 BTC expected
+    fn deposit(received_amount: Decimal) -> CryptoPayment {
+        CryptoPayment {
+            payment_id: Uuid::new_v4(),
+            asset: "BTC".to_string(),
+            deposit_address: "bc1qexample".to_string(),
+            provider_reference: "ref".to_string(),
+            exchange_rate: dec("0.0000005"),
+            expected_amount: dec("0.0003"),
+            received_amount,
+            confirmations: 3,
+            required_confirmations: 3,
+            tx_hash: None,
+            deposit_status: DepositStatus::Underpaid.as_str().to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            underpaid_at: Some(Utc::now()),
+        }
+    }
+
+    fn update(received_amount: Decimal, confirmations: i32) -> DepositUpdate {
+        DepositUpdate { received_amount, confirmations, tx_hash: None }
+    }
+
+    #[test]
+    fn short_deposit_is_underpaid_beyond_the_tolerance() {
+        let crypto = deposit(Decimal::ZERO);
+        assert_eq!(evaluate(&crypto, &update(dec("0.0002"), 3), dec("0.5")), DepositStatus::Underpaid);
+        assert_eq!(evaluate(&crypto, &update(dec("0.0002999"), 3), dec("0.5")), DepositStatus::Confirmed);
+        assert_eq!(evaluate(&crypto, &update(dec("0.0002"), 1), dec("0.5")), DepositStatus::Confirming);
+    }
+
+    #[test]
+    fn expired_underpayment_credits_what_arrived() {
+        assert_eq!(underpaid_credit(&deposit(dec("0.0002"))), dec("400"));
+        // Rounded down to the cent
+        assert_eq!(underpaid_credit(&deposit(dec("0.00000000999"))), dec("0.01"));
+        assert_eq!(underpaid_credit(&deposit(dec("0.000000004999"))), Decimal::ZERO);
+    }
+}
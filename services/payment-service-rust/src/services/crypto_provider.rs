@@ -0,0 +1,103 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct DepositAddress {
+    pub address: String,
+    pub reference: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DepositUpdate {
+    pub received_amount: Decimal,
+    pub confirmations: i32,
+    pub tx_hash: Option<String>,
+}
+
+/// Adapter over the custody/processing provider that owns the deposit addresses.
+#[async_trait]
+pub trait CryptoProvider: Send + Sync {
+    async fn create_deposit_address(&self, asset: &str, payment_id: Uuid) -> Result<DepositAddress>;
+
+    /// `None` when the provider has nothing new (or only reports via webhooks).
+    async fn deposit_update(&self, reference: &str) -> Result<Option<DepositUpdate>>;
+}
+
+/// Used when no provider is configured: addresses are fake and status only changes via webhooks.
+pub struct MockCryptoProvider;
+
+#[async_trait]
+impl CryptoProvider for MockCryptoProvider {
+    async fn create_deposit_address(&self, asset: &str, payment_id: Uuid) -> Result<DepositAddress> {
+        Ok(DepositAddress {
+            address: format!("mock-{}-{}", asset.to_lowercase(), payment_id.simple()),
+            reference: format!("mock_{}", payment_id.simple()),
+        })
+    }
+
+    async fn deposit_update(&self, _reference: &str) -> Result<Option<DepositUpdate>> {
+        Ok(None)
+    }
+}
+
+#[derive(Serialize)]
+struct CreateAddressRequest<'a> {
+    asset: &'a str,
+    external_id: Uuid,
+}
+
+pub struct HttpCryptoProvider {
+    base_url: String,
+    api_key: String,
+    client: Client,
+}
+
+impl HttpCryptoProvider {
+    pub fn new(base_url: String, api_key: String) -> Self {
+        Self {
+            base_url,
+            api_key,
+            client: Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl CryptoProvider for HttpCryptoProvider {
+    async fn create_deposit_address(&self, asset: &str, payment_id: Uuid) -> Result<DepositAddress> {
+        let address = self
+            .client
+            .post(format!("{}/addresses", self.base_url))
+            .header("X-API-Key", &self.api_key)
+            .json(&CreateAddressRequest {
+                asset,
+                external_id: payment_id,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(address)
+    }
+
+    async fn deposit_update(&self, reference: &str) -> Result<Option<DepositUpdate>> {
+        let response = self
+            .client
+            .get(format!("{}/deposits/{}", self.base_url, reference))
+            .header("X-API-Key", &self.api_key)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+}
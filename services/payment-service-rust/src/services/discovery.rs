@@ -0,0 +1,178 @@
+use crate::config::{Config, Discovery, Listen};
+use anyhow::{bail, Result};
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+
+const SERVICE_NAME: &str = "payment-service";
+const HEALTH_PATH: &str = "/api/health/ready";
+/// Eureka drops instances that miss heartbeats for 90 seconds
+const EUREKA_RENEW_INTERVAL: Duration = Duration::from_secs(30);
+
+/// This instance's entry in Consul or Eureka, removed again on graceful shutdown.
+pub struct Registration {
+    instance: Arc<Instance>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+struct Instance {
+    registry: Discovery,
+    base_url: String,
+    instance_id: String,
+    address: String,
+    port: u16,
+    health_url: String,
+    client: Client,
+}
+
+impl Registration {
+    /// `None` when discovery is off, or there is no port to advertise (unix socket without SERVICE_ADVERTISE_PORT).
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let registry = config.service_discovery?;
+        let port = match (&config.listen, config.service_advertise_port) {
+            (_, Some(port)) => port,
+            (Listen::Tcp(addr), None) => addr.rsplit_once(':')?.1.parse().ok()?,
+            (Listen::Unix(_), None) => {
+                tracing::warn!("Listening on a unix socket without SERVICE_ADVERTISE_PORT, not registering");
+                return None;
+            }
+        };
+        let address = config.service_advertise_address.clone().unwrap_or_else(|| {
+            std::env::var("HOSTNAME").ok().filter(|h| !h.is_empty()).unwrap_or_else(|| "localhost".to_string())
+        });
+        let scheme = if config.tls_cert_path.is_some() { "https" } else { "http" };
+
+        let instance = Instance {
+            registry,
+            base_url: config.service_discovery_url.trim_end_matches('/').to_string(),
+            instance_id: format!("{}-{}-{}", SERVICE_NAME, address, port),
+            health_url: format!("{}://{}:{}{}", scheme, address, port, HEALTH_PATH),
+            address,
+            port,
+            client: Client::builder().timeout(Duration::from_secs(5)).build().unwrap_or_default(),
+        };
+
+        Some(Self { instance: Arc::new(instance), heartbeat: None })
+    }
+
+    pub async fn register(&mut self) -> Result<()> {
+        let instance = &self.instance;
+        match instance.registry {
+            Discovery::Consul => instance.register_consul().await?,
+            Discovery::Eureka => {
+                instance.register_eureka().await?;
+                let instance = instance.clone();
+                self.heartbeat = Some(tokio::spawn(async move { instance.renew_eureka().await }));
+            }
+        }
+        tracing::info!("Registered {} with {:?} at {}", instance.instance_id, instance.registry, instance.base_url);
+
+        Ok(())
+    }
+
+    pub async fn deregister(self) -> Result<()> {
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.abort();
+        }
+
+        let instance = &self.instance;
+        let response = match instance.registry {
+            Discovery::Consul => {
+                let url = format!("{}/v1/agent/service/deregister/{}", instance.base_url, instance.instance_id);
+                instance.client.put(url).send().await?
+            }
+            Discovery::Eureka => instance.client.delete(instance.eureka_instance_url()).send().await?,
+        };
+        if !response.status().is_success() {
+            bail!("{:?} deregistration returned {}", instance.registry, response.status());
+        }
+        tracing::info!("Deregistered {} from {:?}", instance.instance_id, instance.registry);
+
+        Ok(())
+    }
+}
+
+impl Instance {
+    /// Consul checks readiness itself and removes the instance if it stays critical.
+    async fn register_consul(&self) -> Result<()> {
+        let response = self
+            .client
+            .put(format!("{}/v1/agent/service/register", self.base_url))
+            .json(&json!({
+                "ID": self.instance_id,
+                "Name": SERVICE_NAME,
+                "Address": self.address,
+                "Port": self.port,
+                "Check": {
+                    "HTTP": self.health_url,
+                    "Interval": "10s",
+                    "Timeout": "3s",
+                    "TLSSkipVerify": true,
+                    "DeregisterCriticalServiceAfter": "1m",
+                },
+            }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            bail!("Consul registration returned {}", response.status());
+        }
+
+        Ok(())
+    }
+
+    async fn register_eureka(&self) -> Result<()> {
+        let secure = self.health_url.starts_with("https");
+        let response = self
+            .client
+            .post(format!("{}/apps/{}", self.base_url, SERVICE_NAME.to_uppercase()))
+            .json(&json!({
+                "instance": {
+                    "instanceId": self.instance_id,
+                    "hostName": self.address,
+                    "app": SERVICE_NAME.to_uppercase(),
+                    "ipAddr": self.address,
+                    "vipAddress": SERVICE_NAME,
+                    "status": "UP",
+                    "port": { "$": self.port, "@enabled": !secure },
+                    "securePort": { "$": self.port, "@enabled": secure },
+                    "healthCheckUrl": self.health_url,
+                    "dataCenterInfo": {
+                        "@class": "com.netflix.appinfo.InstanceInfo$DefaultDataCenterInfo",
+                        "name": "MyOwn",
+                    },
+                },
+            }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            bail!("Eureka registration returned {}", response.status());
+        }
+
+        Ok(())
+    }
+
+    /// Heartbeats until aborted; re-registers when Eureka has forgotten the instance.
+    async fn renew_eureka(&self) {
+        let mut ticker = tokio::time::interval(EUREKA_RENEW_INTERVAL);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+
+            let renewed = match self.client.put(self.eureka_instance_url()).send().await {
+                Ok(response) if response.status() == StatusCode::NOT_FOUND => self.register_eureka().await,
+                Ok(response) if !response.status().is_success() => Err(anyhow::anyhow!("returned {}", response.status())),
+                Ok(_) => Ok(()),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = renewed {
+                tracing::warn!(error = %e, "Eureka heartbeat failed");
+            }
+        }
+    }
+
+    fn eureka_instance_url(&self) -> String {
+        format!("{}/apps/{}/{}", self.base_url, SERVICE_NAME.to_uppercase(), self.instance_id)
+    }
+}
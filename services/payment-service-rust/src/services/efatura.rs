@@ -0,0 +1,159 @@
+use crate::{
+    error::{AppError, AppResult},
+    models::{EfaturaDocument, Merchant, Payment},
+    services::merchants,
+};
+use chrono::{FixedOffset, Utc};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const TURKEY_OFFSET_SECONDS: i32 = 3 * 3600;
+// GIB's placeholder TCKN for consumers who didn't give one
+const CONSUMER_TCKN: &str = "11111111111";
+// KDV in the GIB tax type code list
+const KDV_TAX_TYPE_CODE: &str = "0015";
+
+/// UBL-TR document of an invoiced payment, generated on first request and stored for the provider.
+pub async fn document_for(pool: &PgPool, payment_id: Uuid) -> AppResult<EfaturaDocument> {
+    let existing = sqlx::query_as::<_, EfaturaDocument>("SELECT * FROM efatura_documents WHERE payment_id = $1")
+        .bind(payment_id)
+        .fetch_optional(pool)
+        .await?;
+    if let Some(document) = existing {
+        return Ok(document);
+    }
+
+    let payment = sqlx::query_as::<_, Payment>("SELECT * FROM payments WHERE id = $1")
+        .bind(payment_id)
+        .fetch_one(pool)
+        .await?;
+    let (Some(invoice_number), Some(_)) = (payment.invoice_number.clone(), payment.invoiced_at) else {
+        return Err(AppError::Conflict("Payment has no invoice number yet".to_string()));
+    };
+
+    let merchant = merchants::get(pool, payment.merchant_id).await?;
+    let ettn = Uuid::new_v4();
+    let xml = ubl_tr(&payment, &invoice_number, &merchant, ettn)?;
+
+    // A concurrent export may have stored it first, keep that one
+    let document = sqlx::query_as::<_, EfaturaDocument>(
+        r#"
+        INSERT INTO efatura_documents (payment_id, invoice_number, ettn, xml, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (payment_id) DO UPDATE SET payment_id = EXCLUDED.payment_id
+        RETURNING *
+        "#,
+    )
+    .bind(payment.id)
+    .bind(&invoice_number)
+    .bind(ettn)
+    .bind(xml)
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(document)
+}
+
+/// Invoiced payments without a stored document, oldest first.
+pub async fn pending_ids(pool: &PgPool, limit: i64) -> AppResult<Vec<Uuid>> {
+    let ids = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT p.id FROM payments p
+        LEFT JOIN efatura_documents d ON d.payment_id = p.id
+        WHERE p.invoice_number IS NOT NULL AND d.payment_id IS NULL
+        ORDER BY p.invoiced_at
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids)
+}
+
+/// e-Arsiv sales invoice (buyer is a consumer) with a single line for the order, amounts are tax-inclusive.
+fn ubl_tr(payment: &Payment, invoice_number: &str, merchant: &Merchant, ettn: Uuid) -> AppResult<String> {
+    let (Some(tax_id), Some(tax_office)) = (merchant.tax_id.as_deref(), merchant.tax_office.as_deref()) else {
+        return Err(AppError::Conflict(format!("Merchant {} has no tax details for e-Fatura", merchant.id)));
+    };
+
+    let issued_at = payment
+        .invoiced_at
+        .unwrap_or(payment.updated_at)
+        .with_timezone(&FixedOffset::east_opt(TURKEY_OFFSET_SECONDS).expect("valid offset"));
+    let currency = escape(&payment.currency);
+    let tag = |name: &str, value: Decimal| {
+        format!("<cbc:{0} currencyID=\"{1}\">{2:.2}</cbc:{0}>", name, currency, value.round_dp(2))
+    };
+    let scheme = if tax_id.len() == 10 { "VKN" } else { "TCKN" };
+
+    let tax_total = format!(
+        "<cac:TaxTotal>{}<cac:TaxSubtotal>{}{}<cbc:Percent>{}</cbc:Percent><cac:TaxCategory><cac:TaxScheme><cbc:Name>KDV</cbc:Name><cbc:TaxTypeCode>{}</cbc:TaxTypeCode></cac:TaxScheme></cac:TaxCategory></cac:TaxSubtotal></cac:TaxTotal>",
+        tag("TaxAmount", payment.tax_amount),
+        tag("TaxableAmount", payment.taxable_base),
+        tag("TaxAmount", payment.tax_amount),
+        payment.tax_rate.normalize(),
+        KDV_TAX_TYPE_CODE,
+    );
+    let city = escape(merchant.city.as_deref().unwrap_or_default());
+
+    let lines = [
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        r#"<Invoice xmlns="urn:oasis:names:specification:ubl:schema:xsd:Invoice-2" xmlns:cac="urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2" xmlns:cbc="urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2">"#.to_string(),
+        "  <cbc:UBLVersionID>2.1</cbc:UBLVersionID>".to_string(),
+        "  <cbc:CustomizationID>TR1.2</cbc:CustomizationID>".to_string(),
+        "  <cbc:ProfileID>EARSIVFATURA</cbc:ProfileID>".to_string(),
+        format!("  <cbc:ID>{}</cbc:ID>", escape(invoice_number)),
+        "  <cbc:CopyIndicator>false</cbc:CopyIndicator>".to_string(),
+        format!("  <cbc:UUID>{}</cbc:UUID>", ettn),
+        format!("  <cbc:IssueDate>{}</cbc:IssueDate>", issued_at.format("%Y-%m-%d")),
+        format!("  <cbc:IssueTime>{}</cbc:IssueTime>", issued_at.format("%H:%M:%S")),
+        "  <cbc:InvoiceTypeCode>SATIS</cbc:InvoiceTypeCode>".to_string(),
+        format!("  <cbc:DocumentCurrencyCode>{}</cbc:DocumentCurrencyCode>", currency),
+        "  <cbc:LineCountNumeric>1</cbc:LineCountNumeric>".to_string(),
+        format!(
+            "  <cac:AccountingSupplierParty><cac:Party><cac:PartyIdentification><cbc:ID schemeID=\"{}\">{}</cbc:ID></cac:PartyIdentification><cac:PartyName><cbc:Name>{}</cbc:Name></cac:PartyName><cac:PostalAddress><cbc:StreetName>{}</cbc:StreetName><cbc:CitySubdivisionName>{}</cbc:CitySubdivisionName><cbc:CityName>{}</cbc:CityName><cac:Country><cbc:Name>Türkiye</cbc:Name></cac:Country></cac:PostalAddress><cac:PartyTaxScheme><cac:TaxScheme><cbc:Name>{}</cbc:Name></cac:TaxScheme></cac:PartyTaxScheme></cac:Party></cac:AccountingSupplierParty>",
+            scheme,
+            escape(tax_id),
+            escape(&merchant.name),
+            escape(merchant.address.as_deref().unwrap_or_default()),
+            city,
+            city,
+            escape(tax_office),
+        ),
+        format!(
+            "  <cac:AccountingCustomerParty><cac:Party><cac:PartyIdentification><cbc:ID schemeID=\"TCKN\">{}</cbc:ID></cac:PartyIdentification><cac:PostalAddress><cbc:CitySubdivisionName>-</cbc:CitySubdivisionName><cbc:CityName>-</cbc:CityName><cac:Country><cbc:Name>Türkiye</cbc:Name></cac:Country></cac:PostalAddress><cac:Person><cbc:FirstName>Nihai</cbc:FirstName><cbc:FamilyName>Tüketici</cbc:FamilyName></cac:Person></cac:Party></cac:AccountingCustomerParty>",
+            CONSUMER_TCKN,
+        ),
+        format!("  {}", tax_total),
+        format!(
+            "  <cac:LegalMonetaryTotal>{}{}{}{}</cac:LegalMonetaryTotal>",
+            tag("LineExtensionAmount", payment.taxable_base),
+            tag("TaxExclusiveAmount", payment.taxable_base),
+            tag("TaxInclusiveAmount", payment.amount),
+            tag("PayableAmount", payment.amount),
+        ),
+        format!(
+            "  <cac:InvoiceLine><cbc:ID>1</cbc:ID><cbc:InvoicedQuantity unitCode=\"C62\">1</cbc:InvoicedQuantity>{}{}<cac:Item><cbc:Name>Sipariş {}</cbc:Name></cac:Item><cac:Price>{}</cac:Price></cac:InvoiceLine>",
+            tag("LineExtensionAmount", payment.taxable_base),
+            tax_total,
+            payment.order_id,
+            tag("PriceAmount", payment.taxable_base),
+        ),
+        "</Invoice>".to_string(),
+    ];
+
+    Ok(lines.join("\n") + "\n")
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,52 @@
+use crate::{
+    error::{AppError, AppResult},
+    models::{Payment, PaymentStatus},
+    services::payment_service,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Hands escrowed funds over (ESCROWED -> COMPLETED), after delivery confirmation or by an admin.
+pub async fn release(pool: &PgPool, id: Uuid, expected_version: i32) -> AppResult<Payment> {
+    let payment = sqlx::query_as::<_, Payment>(
+        r#"
+        UPDATE payments
+        SET payment_status = $2, escrow_released_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND payment_status = $3 AND version = $4
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(PaymentStatus::Completed.as_str())
+    .bind(PaymentStatus::Escrowed.as_str())
+    .bind(expected_version)
+    .fetch_optional(pool)
+    .await?;
+
+    match payment {
+        Some(payment) => Ok(payment),
+        None => {
+            let current = payment_service::get_payment(pool, id).await?;
+            payment_service::ensure_version(&current, expected_version)?;
+            Err(AppError::Conflict("Payment is not held in escrow".to_string()))
+        }
+    }
+}
+
+/// Releases escrowed payments whose deadline passed without a dispute or refund.
+pub async fn release_expired(pool: &PgPool) -> AppResult<Vec<Payment>> {
+    let payments = sqlx::query_as::<_, Payment>(
+        r#"
+        UPDATE payments
+        SET payment_status = $1, escrow_released_at = NOW(), updated_at = NOW()
+        WHERE payment_status = $2 AND escrow_release_at <= NOW()
+        RETURNING *
+        "#,
+    )
+    .bind(PaymentStatus::Completed.as_str())
+    .bind(PaymentStatus::Escrowed.as_str())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(payments)
+}
@@ -0,0 +1,129 @@
+use crate::{
+    database,
+    dto::FeeReportQuery,
+    error::AppResult,
+    models::{
+        FeeReportRow, Payment, PaymentFee, PaymentStatus, METHOD_BANK_TRANSFER, METHOD_CASH_ON_DELIVERY, METHOD_CRYPTO,
+        METHOD_VOUCHER, METHOD_WALLET,
+    },
+    services::{gateway, payment_service},
+};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+const FEE_PLATFORM: &str = "PLATFORM";
+const FEE_GATEWAY: &str = "GATEWAY";
+
+#[derive(Debug, FromRow)]
+struct FeeRule {
+    id: Uuid,
+    fee_type: String,
+    percent: Decimal,
+    fixed_amount: Decimal,
+}
+
+/// Processor that handled the payment; offline, wallet and voucher payments have none.
+fn gateway_for(payment: &Payment) -> Option<&'static str> {
+    match payment.payment_method.as_str() {
+        METHOD_CRYPTO => Some("CRYPTO"),
+        METHOD_BANK_TRANSFER | METHOD_CASH_ON_DELIVERY | METHOD_WALLET | METHOD_VOUCHER => None,
+        _ => Some(gateway::NAME),
+    }
+}
+
+/// Computes platform and gateway fees with the most specific matching rule per fee type and stores them.
+/// Platform fees apply to the whole amount, gateway fees only to the part the processor actually charged.
+pub async fn record(conn: &mut PgConnection, payment: &Payment) -> AppResult<Vec<PaymentFee>> {
+    let gateway = gateway_for(payment);
+
+    let rules = sqlx::query_as::<_, FeeRule>(
+        r#"
+        SELECT DISTINCT ON (fee_type) id, fee_type, percent, fixed_amount FROM fee_rules
+        WHERE active
+          AND (payment_method IS NULL OR payment_method = $1)
+          AND (currency IS NULL OR currency = $2)
+          AND (gateway IS NULL OR gateway = $3)
+        ORDER BY fee_type,
+                 (payment_method IS NOT NULL)::INT + (currency IS NOT NULL)::INT + (gateway IS NOT NULL)::INT DESC
+        "#,
+    )
+    .bind(&payment.payment_method)
+    .bind(&payment.currency)
+    .bind(gateway)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut fees = Vec::with_capacity(rules.len());
+    for rule in rules {
+        let (base, rule_gateway) = if rule.fee_type == FEE_GATEWAY {
+            (payment.amount - payment.wallet_amount - payment.voucher_amount, gateway)
+        } else {
+            (payment.amount, None)
+        };
+        let amount = (base * rule.percent / Decimal::ONE_HUNDRED + rule.fixed_amount).round_dp(2);
+
+        let fee = sqlx::query_as::<_, PaymentFee>(
+            r#"
+            INSERT INTO payment_fees (id, payment_id, fee_type, fee_rule_id, gateway, percent, fixed_amount, amount, currency, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (payment_id, fee_type) DO UPDATE SET amount = EXCLUDED.amount
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(payment.id)
+        .bind(&rule.fee_type)
+        .bind(rule.id)
+        .bind(rule_gateway)
+        .bind(rule.percent)
+        .bind(rule.fixed_amount)
+        .bind(amount)
+        .bind(&payment.currency)
+        .bind(Utc::now())
+        .fetch_one(&mut *conn)
+        .await?;
+        fees.push(fee);
+    }
+
+    Ok(fees)
+}
+
+/// Gross, fees and net of completed payments, per currency and payment method.
+/// Runs under the report statement timeout (`timeout_ms`) rather than the pool's.
+pub async fn report(pool: &PgPool, query: &FeeReportQuery, timeout_ms: u64) -> AppResult<Vec<FeeReportRow>> {
+    let mut tx = database::begin_with_statement_timeout(pool, timeout_ms).await?;
+    let rows = sqlx::query_as::<_, FeeReportRow>(
+        r#"
+        SELECT p.currency, p.payment_method,
+               COUNT(*) AS payment_count,
+               SUM(p.amount) AS gross_amount,
+               SUM(COALESCE(f.platform_fees, 0)) AS platform_fees,
+               SUM(COALESCE(f.gateway_fees, 0)) AS gateway_fees,
+               SUM(p.amount - COALESCE(f.platform_fees, 0) - COALESCE(f.gateway_fees, 0)) AS net_amount
+        FROM payments p
+        LEFT JOIN (
+            SELECT payment_id,
+                   SUM(amount) FILTER (WHERE fee_type = $4) AS platform_fees,
+                   SUM(amount) FILTER (WHERE fee_type = $5) AS gateway_fees
+            FROM payment_fees
+            GROUP BY payment_id
+        ) f ON f.payment_id = p.id
+        WHERE p.payment_status = $1
+          AND p.created_at >= $2
+          AND ($3::TIMESTAMPTZ IS NULL OR p.created_at < $3)
+        GROUP BY p.currency, p.payment_method
+        ORDER BY p.currency, p.payment_method
+        "#,
+    )
+    .bind(PaymentStatus::Completed.as_str())
+    .bind(payment_service::default_from(query.from))
+    .bind(query.to)
+    .bind(FEE_PLATFORM)
+    .bind(FEE_GATEWAY)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    Ok(rows)
+}
@@ -0,0 +1,25 @@
+use crate::{
+    config::Config,
+    error::{AppError, AppResult},
+};
+use rust_decimal::Decimal;
+
+/// Converts using the configured USD reference prices. Returns `(converted, rate)`
+/// where `converted = amount * rate`.
+pub fn convert(config: &Config, amount: Decimal, from: &str, to: &str) -> AppResult<(Decimal, Decimal)> {
+    let rate = rate(config, from, to)?;
+    Ok((amount * rate, rate))
+}
+
+pub fn rate(config: &Config, from: &str, to: &str) -> AppResult<Decimal> {
+    let price = |code: &str| {
+        config
+            .fx_usd_prices
+            .get(&code.to_uppercase())
+            .copied()
+            .filter(|p| !p.is_zero())
+            .ok_or_else(|| AppError::BadRequest(format!("No exchange rate for currency: {}", code)))
+    };
+
+    Ok(price(from)? / price(to)?)
+}
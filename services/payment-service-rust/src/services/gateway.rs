@@ -0,0 +1,117 @@
+use crate::{
+    chaos::{self, Target},
+    config::Config,
+    models::{CardDetails, GatewayCredentials},
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+pub const NAME: &str = "MOCK";
+
+// Well-known test card that is always declined by the mock gateway
+const DECLINED_TEST_CARD: &str = "4000000000000002";
+
+pub enum GatewayOutcome {
+    Approved { transaction_id: String },
+    Declined { transaction_id: String },
+    /// Issuer requires 3-D Secure; the customer must be sent to `redirect_url`.
+    RequiresAction { transaction_id: String, redirect_url: String },
+}
+
+impl GatewayOutcome {
+    pub fn as_str(&self) -> &str {
+        match self {
+            GatewayOutcome::Approved { .. } => "APPROVED",
+            GatewayOutcome::Declined { .. } => "DECLINED",
+            GatewayOutcome::RequiresAction { .. } => "REQUIRES_ACTION",
+        }
+    }
+}
+
+/// Mock card gateway. Without card details (raw method strings) everything is approved.
+#[tracing::instrument(
+    name = "gateway_authorize",
+    skip(config, credentials, payment_id, card, amount, currency, return_url),
+    fields(
+        account_id = %credentials.account_id,
+        payment.id = %payment_id,
+        payment.amount = %amount,
+        payment.currency = %currency,
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn authorize(
+    config: &Config,
+    credentials: &GatewayCredentials,
+    payment_id: Uuid,
+    card: Option<&CardDetails>,
+    amount: Decimal,
+    currency: &str,
+    return_url: Option<&str>,
+    merchant_initiated: bool,
+) -> anyhow::Result<GatewayOutcome> {
+    chaos::inject(Target::Gateway)?;
+    check_credentials(credentials)?;
+    let transaction_id = Uuid::new_v4().to_string();
+
+    let Some(card) = card else {
+        return Ok(GatewayOutcome::Approved { transaction_id });
+    };
+
+    if card.number == DECLINED_TEST_CARD {
+        return Ok(GatewayOutcome::Declined { transaction_id });
+    }
+
+    // Merchant-initiated (recurring) charges are exempt from the 3DS challenge
+    if config.three_ds_enabled && !merchant_initiated {
+        let payment_id = payment_id.to_string();
+        let mut params = vec![("payment_id", payment_id.as_str())];
+        if let Some(return_url) = return_url {
+            params.push(("return_url", return_url));
+        }
+        let redirect_url = reqwest::Url::parse_with_params(&config.three_ds_acs_url, &params)?;
+
+        return Ok(GatewayOutcome::RequiresAction {
+            transaction_id,
+            redirect_url: redirect_url.to_string(),
+        });
+    }
+
+    Ok(GatewayOutcome::Approved { transaction_id })
+}
+
+/// Mock refund against a previous authorization, returns the refund transaction id.
+/// Must use the account the payment was authorized with. A retry with the same `idempotency_key` is answered with
+/// the first refund instead of refunding again.
+#[tracing::instrument(name = "gateway_refund", skip(credentials), fields(account_id = %credentials.account_id))]
+pub async fn refund(
+    credentials: &GatewayCredentials,
+    transaction_id: &str,
+    amount: Decimal,
+    currency: &str,
+    idempotency_key: Uuid,
+) -> anyhow::Result<String> {
+    chaos::inject(Target::Gateway)?;
+    check_credentials(credentials)?;
+
+    Ok(Uuid::new_v4().to_string())
+}
+
+/// Mock void, releasing the hold of an authorization that won't be captured (e.g. the payment couldn't be stored).
+/// Must use the account the payment was authorized with.
+#[tracing::instrument(name = "gateway_void", skip(credentials), fields(account_id = %credentials.account_id))]
+pub async fn void(credentials: &GatewayCredentials, transaction_id: &str) -> anyhow::Result<()> {
+    chaos::inject(Target::Gateway)?;
+    check_credentials(credentials)?;
+
+    Ok(())
+}
+
+// The mock accepts any key, but a real gateway would reject an empty one
+fn check_credentials(credentials: &GatewayCredentials) -> anyhow::Result<()> {
+    if credentials.api_key.is_empty() {
+        anyhow::bail!("gateway account {} has no API key", credentials.account_id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use crate::{
+    dto::GatewayCredentialsRequest,
+    error::{AppError, AppResult},
+    models::{GatewayCredentials, MerchantGatewayCredentials},
+    services::{merchants, AppState},
+};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Stores (or replaces) a merchant's own gateway account, sealed with the vault key.
+pub async fn set(
+    state: &AppState,
+    merchant_id: Uuid,
+    request: GatewayCredentialsRequest,
+) -> AppResult<MerchantGatewayCredentials> {
+    if request.account_id.trim().is_empty() || request.api_key.trim().is_empty() {
+        return Err(AppError::BadRequest("account_id and api_key are required".to_string()));
+    }
+    merchants::get(&state.db_pool, merchant_id).await?;
+
+    let credentials = GatewayCredentials {
+        account_id: request.account_id.trim().to_string(),
+        api_key: request.api_key.trim().to_string(),
+    };
+    let (encrypted_credentials, nonce) = state.vault.seal(&credentials)?;
+
+    let row = sqlx::query_as::<_, MerchantGatewayCredentials>(
+        r#"
+        INSERT INTO merchant_gateway_credentials (merchant_id, account_id, encrypted_credentials, nonce, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $5)
+        ON CONFLICT (merchant_id) DO UPDATE
+        SET account_id = EXCLUDED.account_id, encrypted_credentials = EXCLUDED.encrypted_credentials,
+            nonce = EXCLUDED.nonce, updated_at = EXCLUDED.updated_at
+        RETURNING *
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(&credentials.account_id)
+    .bind(encrypted_credentials)
+    .bind(nonce)
+    .bind(Utc::now())
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn get(pool: &PgPool, merchant_id: Uuid) -> AppResult<MerchantGatewayCredentials> {
+    let row = sqlx::query_as::<_, MerchantGatewayCredentials>(
+        "SELECT * FROM merchant_gateway_credentials WHERE merchant_id = $1"
+    )
+    .bind(merchant_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row)
+}
+
+/// The merchant goes back to being charged through the platform account.
+pub async fn remove(pool: &PgPool, merchant_id: Uuid) -> AppResult<()> {
+    let result = sqlx::query("DELETE FROM merchant_gateway_credentials WHERE merchant_id = $1")
+        .bind(merchant_id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Merchant has no gateway credentials".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Credentials to charge a merchant's payments with, resolved at charge time.
+pub async fn resolve(state: &AppState, merchant_id: Uuid) -> AppResult<GatewayCredentials> {
+    let row = sqlx::query_as::<_, MerchantGatewayCredentials>(
+        "SELECT * FROM merchant_gateway_credentials WHERE merchant_id = $1"
+    )
+    .bind(merchant_id)
+    .fetch_optional(&state.db_pool)
+    .await?;
+
+    match row {
+        Some(row) => Ok(state.vault.open(&row.encrypted_credentials, &row.nonce)?),
+        None => Ok(GatewayCredentials {
+            account_id: state.config.gateway_account_id.clone(),
+            api_key: state.config.gateway_api_key.clone(),
+        }),
+    }
+}
@@ -0,0 +1,67 @@
+use crate::error::{AppError, AppResult, FieldErrorCode};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+
+pub const MAX_INSTALLMENTS: u8 = 12;
+
+#[derive(Debug, Clone)]
+pub struct InstallmentQuote {
+    pub count: u8,
+    pub fee_percent: Decimal,
+    pub surcharge: Decimal,
+}
+
+impl InstallmentQuote {
+    pub fn single() -> Self {
+        Self {
+            count: 1,
+            fee_percent: Decimal::ZERO,
+            surcharge: Decimal::ZERO,
+        }
+    }
+}
+
+/// Looks up the fee for `count` installments, preferring the most specific BIN prefix match.
+pub async fn quote(
+    pool: &PgPool,
+    payment_method: &str,
+    bin: Option<&str>,
+    count: u8,
+    amount: Decimal,
+) -> AppResult<InstallmentQuote> {
+    if count <= 1 {
+        return Ok(InstallmentQuote::single());
+    }
+    if count > MAX_INSTALLMENTS {
+        return Err(AppError::invalid_field(
+            "installments",
+            FieldErrorCode::OutOfRange,
+            format!("At most {} installments are allowed", MAX_INSTALLMENTS),
+        ));
+    }
+
+    let fee_percent = sqlx::query_scalar::<_, Decimal>(
+        r#"
+        SELECT fee_percent FROM installment_fees
+        WHERE payment_method = $1
+          AND installments = $2
+          AND (bin_prefix IS NULL OR $3 LIKE bin_prefix || '%')
+        ORDER BY LENGTH(bin_prefix) DESC NULLS LAST
+        LIMIT 1
+        "#,
+    )
+    .bind(payment_method)
+    .bind(count as i16)
+    .bind(bin.unwrap_or(""))
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| {
+        AppError::BadRequest(format!("{} installments are not available for this payment method", count))
+    })?;
+
+    Ok(InstallmentQuote {
+        count,
+        fee_percent,
+        surcharge: (amount * fee_percent / Decimal::ONE_HUNDRED).round_dp(2),
+    })
+}
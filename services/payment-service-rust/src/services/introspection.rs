@@ -0,0 +1,99 @@
+use crate::{
+    config::{AuthMode, Config},
+    services::{mtls, user_client::TokenData},
+};
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// RFC 7662 response. Keycloak puts roles under `realm_access`; `role` and `merchant_id` are claims added by
+/// protocol mappers where the IdP is set up for this service.
+#[derive(Debug, Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    sub: Option<String>,
+    role: Option<String>,
+    merchant_id: Option<String>,
+    scope: Option<String>,
+    realm_access: Option<RealmAccess>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RealmAccess {
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// Validates tokens against an OAuth2 IdP (e.g. Keycloak) instead of the user service, with AUTH_MODE=introspection.
+pub struct IntrospectionClient {
+    url: String,
+    client_id: String,
+    client_secret: Option<String>,
+    client: Client,
+}
+
+impl IntrospectionClient {
+    /// `None` unless AUTH_MODE=introspection, which needs INTROSPECTION_URL and INTROSPECTION_CLIENT_ID.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        if !matches!(config.auth_mode, AuthMode::Introspection) {
+            return Ok(None);
+        }
+        let Some(url) = config.introspection_url.clone() else {
+            bail!("AUTH_MODE=introspection needs INTROSPECTION_URL");
+        };
+        let Some(client_id) = config.introspection_client_id.clone() else {
+            bail!("AUTH_MODE=introspection needs INTROSPECTION_CLIENT_ID");
+        };
+
+        Ok(Some(Self {
+            url,
+            client_id,
+            client_secret: config.introspection_client_secret.clone(),
+            client: mtls::client_builder(config, mtls::INTROSPECTION)?
+                .timeout(Duration::from_secs(5))
+                .build()
+                .context("building the introspection client")?,
+        }))
+    }
+
+    /// The token's claims when the IdP reports it active. Tokens without a subject (client credentials grants)
+    /// don't belong to a user and are rejected.
+    pub async fn introspect(&self, token: &str) -> Result<Option<TokenData>> {
+        let response = self
+            .client
+            .post(&self.url)
+            .basic_auth(&self.client_id, self.client_secret.as_deref())
+            .form(&[("token", token), ("token_type_hint", "access_token")])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            warn!("Token introspection failed: {}", response.status());
+            return Ok(None);
+        }
+
+        let result: IntrospectionResponse = response.json().await?;
+        let claims = match result.sub {
+            Some(sub) if result.active => {
+                let realm_roles = result.realm_access.map(|r| r.roles).unwrap_or_default();
+                let role = result.role.unwrap_or_else(|| role_from_realm(&realm_roles).to_string());
+                Some(TokenData::new(sub, role, result.merchant_id, result.scope))
+            }
+            _ => None,
+        };
+
+        info!("Token introspection result: {}", claims.is_some());
+        Ok(claims)
+    }
+}
+
+/// Realm roles are lowercase by Keycloak convention, the service's roles are uppercase.
+fn role_from_realm(roles: &[String]) -> &'static str {
+    if roles.iter().any(|r| r.eq_ignore_ascii_case("admin")) {
+        "ADMIN"
+    } else {
+        "USER"
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    config::Config,
+    error::AppResult,
+    models::{Payment, PaymentStatus},
+    services::payment_service,
+};
+use chrono::{Datelike, FixedOffset, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Invoice dates follow Turkish time (UTC+3 all year)
+const TURKEY_OFFSET_SECONDS: i32 = 3 * 3600;
+
+/// Stamps the next invoice number of the merchant's current year on a completed payment.
+/// Returns `None` when the payment isn't invoiceable or already has a number.
+pub async fn assign(pool: &PgPool, config: &Config, payment_id: Uuid) -> AppResult<Option<Payment>> {
+    let mut tx = pool.begin().await?;
+
+    // Wallet top-ups aren't sales, the invoice comes when the balance is spent
+    let payment = sqlx::query_as::<_, Payment>(
+        r#"
+        SELECT * FROM payments
+        WHERE id = $1 AND payment_status = $2 AND invoice_number IS NULL AND NOT wallet_topup
+        FOR UPDATE
+        "#,
+    )
+    .bind(payment_id)
+    .bind(PaymentStatus::Completed.as_str())
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(payment) = payment else {
+        return Ok(None);
+    };
+
+    let now = Utc::now();
+    let year = now
+        .with_timezone(&FixedOffset::east_opt(TURKEY_OFFSET_SECONDS).expect("valid offset"))
+        .year();
+
+    // The upsert holds the counter row lock until commit, concurrent assignments wait instead of skipping numbers
+    let number = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO invoice_counters (merchant_id, year, last_number)
+        VALUES ($1, $2, 1)
+        ON CONFLICT (merchant_id, year) DO UPDATE SET last_number = invoice_counters.last_number + 1
+        RETURNING last_number
+        "#,
+    )
+    .bind(payment.merchant_id)
+    .bind(year)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let payment = sqlx::query_as::<_, Payment>(
+        "UPDATE payments SET invoice_number = $2, invoiced_at = $3 WHERE id = $1 RETURNING *"
+    )
+    .bind(payment.id)
+    .bind(format!("{}{}{:09}", config.invoice_prefix, year, number))
+    .bind(now)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(payment))
+}
+
+/// Completed payments still waiting for an invoice number, oldest first.
+pub async fn pending_ids(pool: &PgPool, limit: i64) -> AppResult<Vec<Uuid>> {
+    let ids = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT id FROM payments
+        WHERE payment_status = $1 AND invoice_number IS NULL AND NOT wallet_topup
+        ORDER BY updated_at
+        LIMIT $2
+        "#,
+    )
+    .bind(PaymentStatus::Completed.as_str())
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids)
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "payments.get_by_invoice_number"))]
+pub async fn get_by_number(pool: &PgPool, merchant_id: Uuid, invoice_number: &str) -> AppResult<Payment> {
+    let payment = sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE merchant_id = $1 AND invoice_number = $2"
+    )
+    .bind(merchant_id)
+    .bind(invoice_number.trim().to_uppercase())
+    .fetch_one(pool)
+    .await
+    .map_err(payment_service::payment_not_found)?;
+
+    Ok(payment)
+}
@@ -0,0 +1,210 @@
+use crate::{
+    dto::{
+        CreateMerchantRequest, MerchantDuplicateOrdersRequest, MerchantQuotasRequest, MerchantScopesRequest,
+        MerchantTaxDetailsRequest,
+    },
+    error::{AppError, AppResult},
+    models::{DuplicateOrders, Merchant, MerchantStatus, Scope},
+    services::vault::Vault,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Creates the merchant and its API key; only the key's hash is kept.
+pub async fn onboard(pool: &PgPool, request: CreateMerchantRequest) -> AppResult<(Merchant, String)> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("Merchant name is required".to_string()));
+    }
+
+    let api_key = Vault::generate_token("mk");
+    let now = Utc::now();
+
+    let merchant = sqlx::query_as::<_, Merchant>(
+        r#"
+        INSERT INTO merchants (id, name, contact_email, status, api_key_hash, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $6)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(name)
+    .bind(request.contact_email)
+    .bind(MerchantStatus::Active.as_str())
+    .bind(hash_api_key(&api_key))
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((merchant, api_key))
+}
+
+pub async fn list(pool: &PgPool) -> AppResult<Vec<Merchant>> {
+    let merchants = sqlx::query_as::<_, Merchant>("SELECT * FROM merchants ORDER BY created_at")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(merchants)
+}
+
+pub async fn get(pool: &PgPool, id: Uuid) -> AppResult<Merchant> {
+    let merchant = sqlx::query_as::<_, Merchant>("SELECT * FROM merchants WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(merchant)
+}
+
+pub async fn find_by_api_key(pool: &PgPool, api_key: &str) -> AppResult<Option<Merchant>> {
+    let merchant = sqlx::query_as::<_, Merchant>("SELECT * FROM merchants WHERE api_key_hash = $1")
+        .bind(hash_api_key(api_key))
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(merchant)
+}
+
+/// Suspended merchants can't take payments or be paid in marketplace splits.
+pub async fn set_status(pool: &PgPool, id: Uuid, status: MerchantStatus) -> AppResult<Merchant> {
+    let merchant = sqlx::query_as::<_, Merchant>(
+        r#"
+        UPDATE merchants SET status = $2, updated_at = $3
+        WHERE id = $1 AND status <> $2
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(status.as_str())
+    .bind(Utc::now())
+    .fetch_optional(pool)
+    .await?;
+
+    match merchant {
+        Some(merchant) => Ok(merchant),
+        None => {
+            get(pool, id).await?;
+            Err(AppError::Conflict(format!("Merchant is already {}", status.as_str())))
+        }
+    }
+}
+
+pub async fn set_quotas(pool: &PgPool, id: Uuid, request: MerchantQuotasRequest) -> AppResult<Merchant> {
+    if [request.requests_per_minute, request.payments_per_day].iter().flatten().any(|n| *n <= 0) {
+        return Err(AppError::BadRequest("Quotas must be positive".to_string()));
+    }
+
+    let merchant = sqlx::query_as::<_, Merchant>(
+        r#"
+        UPDATE merchants SET requests_per_minute = $2, payments_per_day = $3, updated_at = $4
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(request.requests_per_minute)
+    .bind(request.payments_per_day)
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(merchant)
+}
+
+pub async fn set_duplicate_orders(
+    pool: &PgPool,
+    id: Uuid,
+    request: MerchantDuplicateOrdersRequest,
+) -> AppResult<Merchant> {
+    let policy = match request.duplicate_orders.as_deref() {
+        Some(raw) => Some(
+            DuplicateOrders::parse(raw)
+                .ok_or_else(|| AppError::BadRequest("duplicate_orders must be REPLAY or REJECT".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let merchant = sqlx::query_as::<_, Merchant>(
+        "UPDATE merchants SET duplicate_orders = $2, updated_at = $3 WHERE id = $1 RETURNING *",
+    )
+    .bind(id)
+    .bind(policy.map(|p| p.as_str().to_string()))
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(merchant)
+}
+
+/// Limits what the merchant's API key can do; `None` lifts the limit.
+pub async fn set_scopes(pool: &PgPool, id: Uuid, request: MerchantScopesRequest) -> AppResult<Merchant> {
+    let scopes = match request.scopes {
+        Some(scopes) => Some(
+            scopes
+                .iter()
+                .map(|raw| {
+                    Scope::parse(raw)
+                        .map(|scope| scope.as_str().to_string())
+                        .ok_or_else(|| AppError::BadRequest(format!("Unknown scope {}", raw)))
+                })
+                .collect::<AppResult<Vec<_>>>()?,
+        ),
+        None => None,
+    };
+
+    let merchant = sqlx::query_as::<_, Merchant>(
+        "UPDATE merchants SET scopes = $2, updated_at = $3 WHERE id = $1 RETURNING *",
+    )
+    .bind(id)
+    .bind(scopes)
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(merchant)
+}
+
+pub async fn set_tax_details(pool: &PgPool, id: Uuid, request: MerchantTaxDetailsRequest) -> AppResult<Merchant> {
+    let tax_id = request.tax_id.trim();
+    if !matches!(tax_id.len(), 10 | 11) || !tax_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(AppError::BadRequest("tax_id must be a 10 digit VKN or 11 digit TCKN".to_string()));
+    }
+
+    let merchant = sqlx::query_as::<_, Merchant>(
+        r#"
+        UPDATE merchants SET tax_id = $2, tax_office = $3, address = $4, city = $5, updated_at = $6
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(tax_id)
+    .bind(request.tax_office.trim())
+    .bind(request.address.trim())
+    .bind(request.city.trim())
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(merchant)
+}
+
+/// All merchants must exist and be active.
+pub async fn ensure_active(pool: &PgPool, ids: &[Uuid]) -> AppResult<()> {
+    let active: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM merchants WHERE id = ANY($1) AND status = $2")
+        .bind(ids)
+        .bind(MerchantStatus::Active.as_str())
+        .fetch_all(pool)
+        .await?;
+
+    match ids.iter().find(|id| !active.contains(id)) {
+        Some(id) => Err(AppError::BadRequest(format!("Merchant {} is unknown or suspended", id))),
+        None => Ok(()),
+    }
+}
+
+fn hash_api_key(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
@@ -1,13 +1,96 @@
-use crate::config::Config;
+use crate::{config::Config, database::ReadReplica, events::EventBus, middleware::rate_limit::RateLimiter};
+use chrono::{DateTime, Utc};
+use alerting::Alerter;
+use crypto_provider::CryptoProvider;
+use introspection::IntrospectionClient;
 use redis::aio::ConnectionManager;
 use sqlx::PgPool;
 use std::sync::Arc;
+use notification_client::NotificationServiceClient;
+use object_storage::ObjectStorage;
+use user_client::UserServiceClient;
+use vault::Vault;
 
+pub mod alerting;
+pub mod api_key_usage;
+pub mod archival;
+pub mod async_payments;
+pub mod bank_transfer;
+pub mod cash_on_delivery;
+pub mod crypto_payment;
+pub mod crypto_provider;
+pub mod discovery;
+pub mod efatura;
+pub mod escrow;
+pub mod fees;
+pub mod fx;
+pub mod gateway;
+pub mod gateway_credentials;
+pub mod installments;
+pub mod introspection;
+pub mod invoices;
+pub mod merchants;
+pub mod mtls;
+pub mod nonces;
+pub mod notification_client;
+pub mod notifications;
+pub mod object_storage;
+pub mod outbound_webhooks;
+pub mod payment_intents;
+pub mod payment_links;
+pub mod payment_method_service;
+pub mod payment_export;
+pub mod payment_import;
 pub mod payment_service;
+pub mod payouts;
+pub mod promotions;
+pub mod refund_service;
+pub mod qr;
+pub mod receipts;
+pub mod reports;
+pub mod seed;
+pub mod shadow_gateway;
+pub mod splits;
+pub mod subscription_service;
+pub mod surcharges;
+pub mod tax;
 pub mod user_client;
+pub mod user_grpc;
+pub mod user_profiles;
+pub mod vault;
+pub mod volume_anomalies;
+pub mod webhook_events;
+pub mod vouchers;
+pub mod wallets;
+pub mod work_queue;
 
 pub struct AppState {
     pub config: Arc<Config>,
     pub db_pool: PgPool,
+    /// Set with DATABASE_READ_URL, see `read_pool`
+    pub read_replica: Option<ReadReplica>,
     pub redis_conn: ConnectionManager,
-}
\ No newline at end of file
+    pub user_client: Arc<UserServiceClient>,
+    /// Replaces the user service for token validation with AUTH_MODE=introspection
+    pub introspection: Option<IntrospectionClient>,
+    pub notification_client: Arc<NotificationServiceClient>,
+    pub vault: Vault,
+    pub events: EventBus,
+    pub crypto_provider: Box<dyn CryptoProvider>,
+    pub rate_limiter: RateLimiter,
+    pub alerts: Alerter,
+    /// Cold storage for archived payment partitions, set with ARCHIVE_S3_ENDPOINT
+    pub archive_storage: Option<ObjectStorage>,
+    pub started_at: DateTime<Utc>,
+}
+
+impl AppState {
+    /// Pool for read-only queries that can tolerate replication lag: the replica while it is healthy,
+    /// the primary otherwise.
+    pub fn read_pool(&self) -> &PgPool {
+        match &self.read_replica {
+            Some(replica) if replica.is_healthy() => &replica.pool,
+            _ => &self.db_pool,
+        }
+    }
+}
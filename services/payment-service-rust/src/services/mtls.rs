@@ -0,0 +1,63 @@
+use crate::config::Config;
+use anyhow::{Context, Result};
+use reqwest::{Certificate, ClientBuilder, Identity};
+use tonic::transport::ClientTlsConfig;
+
+pub const USER_SERVICE: &str = "user-service";
+pub const NOTIFICATION_SERVICE: &str = "notification-service";
+pub const INTROSPECTION: &str = "introspection";
+
+/// Whether calls to `peer` use mutual TLS, i.e. it's listed in MTLS_PEERS and a certificate is configured.
+fn enabled(config: &Config, peer: &str) -> bool {
+    config.mtls_cert_path.is_some() && config.mtls_peers.iter().any(|p| p == peer)
+}
+
+fn read(path: &str, what: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("cannot open {} {}", what, path))
+}
+
+/// The service's client certificate, `None` when MTLS_CERT_PATH is unset.
+pub fn identity(config: &Config) -> Result<Option<Identity>> {
+    let (Some(cert_path), Some(key_path)) = (&config.mtls_cert_path, &config.mtls_key_path) else {
+        return Ok(None);
+    };
+    let identity = Identity::from_pkcs8_pem(&read(cert_path, "client certificate")?, &read(key_path, "client key")?)
+        .with_context(|| format!("{} is not a PKCS#8 PEM key for {}", key_path, cert_path))?;
+
+    Ok(Some(identity))
+}
+
+/// HTTP client for `peer`, presenting the client certificate and trusting MTLS_CA_PATH when mutual TLS is on for it.
+pub fn client_builder(config: &Config, peer: &str) -> Result<ClientBuilder> {
+    let builder = reqwest::Client::builder();
+    if !enabled(config, peer) {
+        return Ok(builder);
+    }
+
+    let mut builder = builder.identity(identity(config)?.context("MTLS_KEY_PATH is not set")?);
+    if let Some(ca_path) = &config.mtls_ca_path {
+        builder = builder.add_root_certificate(Certificate::from_pem(&read(ca_path, "CA certificate")?)?);
+    }
+    tracing::info!("Calls to {} use mutual TLS", peer);
+
+    Ok(builder)
+}
+
+/// The same for a gRPC channel to `peer` (an https:// URL). tonic has no system roots here, so it needs MTLS_CA_PATH.
+pub fn grpc_tls(config: &Config, peer: &str) -> Result<Option<ClientTlsConfig>> {
+    if !enabled(config, peer) {
+        return Ok(None);
+    }
+    let (Some(cert_path), Some(key_path)) = (&config.mtls_cert_path, &config.mtls_key_path) else {
+        anyhow::bail!("MTLS_KEY_PATH is not set");
+    };
+    let ca_path = config.mtls_ca_path.as_deref().context("mutual TLS over gRPC needs MTLS_CA_PATH")?;
+
+    let identity = tonic::transport::Identity::from_pem(
+        read(cert_path, "client certificate")?,
+        read(key_path, "client key")?,
+    );
+    let ca = tonic::transport::Certificate::from_pem(read(ca_path, "CA certificate")?);
+
+    Ok(Some(ClientTlsConfig::new().identity(identity).ca_certificate(ca)))
+}
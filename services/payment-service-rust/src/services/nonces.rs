@@ -0,0 +1,36 @@
+use crate::error::{AppError, AppResult, FieldErrorCode};
+use redis::aio::ConnectionManager;
+use std::time::Duration;
+
+/// Checks the shape of a client-chosen nonce: 16 to 128 letters, digits, `-` or `_`, e.g. a UUID.
+pub fn validate(field: &str, nonce: &str) -> AppResult<()> {
+    let valid = (16..=128).contains(&nonce.len())
+        && nonce.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::invalid_field(
+            field,
+            FieldErrorCode::Invalid,
+            "Nonce must be 16 to 128 letters, digits, - or _",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Remembers the nonce for `ttl`; false when it was used within that time, i.e. the request is a replay.
+/// `ttl` has to outlast the time the request itself stays acceptable.
+#[tracing::instrument(name = "redis.command", skip_all, fields(db.system = "redis", db.statement = "SET nonce NX"))]
+pub async fn claim(redis: &ConnectionManager, scope: &str, nonce: &str, ttl: Duration) -> AppResult<bool> {
+    let mut redis = redis.clone();
+    let set: Option<String> = redis::cmd("SET")
+        .arg(format!("nonce:{}:{}", scope, nonce))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl.as_secs().max(1))
+        .query_async(&mut redis)
+        .await
+        .map_err(|e| AppError::Internal(e.into()))?;
+
+    Ok(set.is_some())
+}
@@ -0,0 +1,48 @@
+use anyhow::{bail, Result};
+use reqwest::Client;
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+struct SendNotificationRequest<'a> {
+    template: &'a str,
+    #[serde(rename = "userId")]
+    user_id: Uuid,
+    channel: &'a str,
+    data: &'a serde_json::Value,
+}
+
+pub struct NotificationServiceClient {
+    base_url: String,
+    client: Client,
+}
+
+impl NotificationServiceClient {
+    pub fn new(base_url: String, client: Client) -> Self {
+        Self { base_url, client }
+    }
+
+    /// Asks the notification service to email a templated message; it looks up the address by user id.
+    pub async fn send_email(&self, template: &str, user_id: Uuid, data: &serde_json::Value) -> Result<()> {
+        let url = format!("{}/api/notifications", self.base_url);
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(crate::telemetry::propagation_headers())
+            .json(&SendNotificationRequest {
+                template,
+                user_id,
+                channel: "EMAIL",
+                data,
+            })
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            bail!("notification service returned {}", response.status());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    error::{AppError, AppResult},
+    events::PaymentEvent,
+    models::{Notification, NotificationStatus, Payment, Refund},
+    services::{
+        notification_client::NotificationServiceClient,
+        work_queue::{self, WorkKind},
+    },
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{types::Json, PgPool};
+use uuid::Uuid;
+
+pub const TEMPLATE_PAYMENT_RECEIPT: &str = "payment_receipt";
+pub const TEMPLATE_REFUND_ISSUED: &str = "refund_issued";
+
+/// Delivery attempts before the notification is given up on; the work queue spaces them out.
+pub const MAX_ATTEMPTS: i32 = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NotificationWork {
+    notification_id: Uuid,
+}
+
+/// Receipt email for a payment that went through (captured or held in escrow).
+pub async fn payment_receipt(pool: &PgPool, event: &PaymentEvent) -> AppResult<()> {
+    let data = json!({
+        "payment_id": event.payment_id,
+        "order_id": event.order_id,
+        "amount": event.amount,
+        "currency": event.currency,
+        "receipt_url": format!("/api/payments/{}/receipt.pdf", event.payment_id),
+    });
+
+    enqueue(pool, TEMPLATE_PAYMENT_RECEIPT, &format!("receipt:{}", event.payment_id), event.user_id, data).await
+}
+
+/// Queues the refund email in the background so a failure doesn't fail the refund response.
+pub fn refund_issued(pool: &PgPool, refund: &Refund, payment: &Payment) {
+    let pool = pool.clone();
+    let dedup_key = format!("refund:{}", refund.id);
+    let user_id = payment.user_id;
+    let data = json!({
+        "payment_id": payment.id,
+        "order_id": payment.order_id,
+        "refund_id": refund.id,
+        "amount": refund.amount,
+        "currency": refund.currency,
+        "destination": refund.destination,
+    });
+
+    tokio::spawn(async move {
+        if let Err(e) = enqueue(&pool, TEMPLATE_REFUND_ISSUED, &dedup_key, user_id, data).await {
+            tracing::error!(error = %e, "Could not queue refund notification {}", dedup_key);
+        }
+    });
+}
+
+/// Records the notification once per `dedup_key` and hands it to the work queue for delivery.
+async fn enqueue(pool: &PgPool, template: &str, dedup_key: &str, user_id: Uuid, data: serde_json::Value) -> AppResult<()> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+
+    let notification_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO notifications (id, template, dedup_key, user_id, payload, status, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (dedup_key) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(template)
+    .bind(dedup_key)
+    .bind(user_id)
+    .bind(Json(data))
+    .bind(NotificationStatus::Pending.as_str())
+    .bind(now)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(notification_id) = notification_id {
+        work_queue::enqueue(&mut *tx, WorkKind::Notification, &NotificationWork { notification_id }, now).await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Sends a queued notification. Failures are recorded and returned so the queue retries with backoff.
+pub async fn deliver(pool: &PgPool, client: &NotificationServiceClient, payload: serde_json::Value) -> AppResult<()> {
+    let work: NotificationWork = serde_json::from_value(payload).map_err(|e| AppError::Internal(e.into()))?;
+    let notification = sqlx::query_as::<_, Notification>("SELECT * FROM notifications WHERE id = $1")
+        .bind(work.notification_id)
+        .fetch_one(pool)
+        .await?;
+    if notification.status != NotificationStatus::Pending.as_str() {
+        return Ok(());
+    }
+
+    let attempts = notification.attempts + 1;
+    match client.send_email(&notification.template, notification.user_id, &notification.payload).await {
+        Ok(()) => {
+            sqlx::query("UPDATE notifications SET status = $2, attempts = $3, sent_at = $4, last_error = NULL WHERE id = $1")
+                .bind(notification.id)
+                .bind(NotificationStatus::Sent.as_str())
+                .bind(attempts)
+                .bind(Utc::now())
+                .execute(pool)
+                .await?;
+
+            Ok(())
+        }
+        Err(e) => {
+            let status = if attempts >= MAX_ATTEMPTS { NotificationStatus::Failed } else { NotificationStatus::Pending };
+
+            sqlx::query("UPDATE notifications SET status = $2, attempts = $3, last_error = $4 WHERE id = $1")
+                .bind(notification.id)
+                .bind(status.as_str())
+                .bind(attempts)
+                .bind(e.to_string())
+                .execute(pool)
+                .await?;
+
+            Err(AppError::Internal(e.context(format!(
+                "notification {} ({}) failed, attempt {}",
+                notification.id, notification.template, attempts
+            ))))
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use crate::{
+    dto::{CreateWebhookSubscriptionRequest, UpdateWebhookSubscriptionRequest},
+    error::{self, AppError, AppResult, FieldError, FieldErrorCode},
+    events::{Event, EVENT_TYPES},
+    models::{WebhookDelivery, WebhookDeliveryStats, WebhookDeliveryStatus, WebhookSubscription},
+    services::{
+        merchants,
+        vault::Vault,
+        work_queue::{self, WorkKind},
+        AppState,
+    },
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{header, Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::{types::Json, PgPool};
+use std::{
+    collections::HashSet,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Delivery attempts before a delivery is given up on; the work queue spaces them out.
+pub const MAX_ATTEMPTS: i32 = 8;
+
+/// Sent by the test endpoint only, never filtered on and left out of the delivery stats.
+pub const TEST_EVENT_TYPE: &str = "webhook.test";
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// How old a signature receivers should still accept, in seconds. Every attempt is signed anew, so a retry
+/// is never rejected for the age of the event.
+pub const SIGNATURE_TOLERANCE_SECS: i64 = 300;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DeliveryWork {
+    delivery_id: Uuid,
+}
+
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| Client::builder().timeout(DELIVERY_TIMEOUT).build().unwrap_or_default())
+}
+
+fn validate(url: Option<&str>, event_types: Option<&[String]>) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    if let Some(url) = url {
+        match Url::parse(url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => {}
+            _ => errors.push(FieldError::new("url", FieldErrorCode::Invalid, "url must be an http(s) URL")),
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for (i, event_type) in event_types.unwrap_or_default().iter().enumerate() {
+        if !EVENT_TYPES.contains(&event_type.as_str()) {
+            errors.push(FieldError::new(
+                format!("event_types[{}]", i),
+                FieldErrorCode::NotAllowed,
+                format!("Unknown event type {}", event_type),
+            ));
+        } else if !seen.insert(event_type) {
+            errors.push(FieldError::new(
+                format!("event_types[{}]", i),
+                FieldErrorCode::Duplicate,
+                format!("Event type {} is listed twice", event_type),
+            ));
+        }
+    }
+
+    errors
+}
+
+/// New subscription with a freshly generated signing secret, returned alongside it.
+pub async fn create(
+    state: &AppState,
+    request: CreateWebhookSubscriptionRequest,
+) -> AppResult<(WebhookSubscription, String)> {
+    error::ensure_valid(validate(Some(&request.url), Some(&request.event_types)))?;
+    if let Some(merchant_id) = request.merchant_id {
+        merchants::get(&state.db_pool, merchant_id).await?;
+    }
+
+    let secret = Vault::generate_token("whsec");
+    let (encrypted_secret, nonce) = state.vault.seal(&secret)?;
+    let now = Utc::now();
+
+    let subscription = sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        INSERT INTO webhook_subscriptions
+            (id, merchant_id, url, description, event_types, encrypted_secret, nonce, active,
+             secret_rotated_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, TRUE, $8, $8, $8)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(request.merchant_id)
+    .bind(request.url.trim())
+    .bind(request.description)
+    .bind(&request.event_types)
+    .bind(encrypted_secret)
+    .bind(nonce)
+    .bind(now)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    Ok((subscription, secret))
+}
+
+pub async fn list(pool: &PgPool, merchant_id: Option<Uuid>) -> AppResult<Vec<WebhookSubscription>> {
+    let subscriptions = sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        SELECT * FROM webhook_subscriptions
+        WHERE $1::UUID IS NULL OR merchant_id = $1
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subscriptions)
+}
+
+pub async fn get(pool: &PgPool, id: Uuid) -> AppResult<WebhookSubscription> {
+    sqlx::query_as::<_, WebhookSubscription>("SELECT * FROM webhook_subscriptions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No webhook subscription {}", id)))
+}
+
+/// Changes only the fields that are sent; `active: false` pauses deliveries without losing the subscription.
+pub async fn update(
+    pool: &PgPool,
+    id: Uuid,
+    request: UpdateWebhookSubscriptionRequest,
+) -> AppResult<WebhookSubscription> {
+    error::ensure_valid(validate(request.url.as_deref(), request.event_types.as_deref()))?;
+
+    sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        UPDATE webhook_subscriptions
+        SET url = COALESCE($2, url), description = COALESCE($3, description),
+            event_types = COALESCE($4, event_types), active = COALESCE($5, active), updated_at = $6
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(request.url.as_deref().map(str::trim))
+    .bind(request.description)
+    .bind(request.event_types)
+    .bind(request.active)
+    .bind(Utc::now())
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("No webhook subscription {}", id)))
+}
+
+/// Removes the subscription along with its delivery history.
+pub async fn delete(pool: &PgPool, id: Uuid) -> AppResult<()> {
+    let result = sqlx::query("DELETE FROM webhook_subscriptions WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("No webhook subscription {}", id)));
+    }
+
+    Ok(())
+}
+
+/// Replaces the signing secret, e.g. after it leaked. Deliveries made from now on use the new one.
+pub async fn rotate_secret(state: &AppState, id: Uuid) -> AppResult<(WebhookSubscription, String)> {
+    let secret = Vault::generate_token("whsec");
+    let (encrypted_secret, nonce) = state.vault.seal(&secret)?;
+
+    let subscription = sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        UPDATE webhook_subscriptions
+        SET encrypted_secret = $2, nonce = $3, secret_rotated_at = $4, updated_at = $4
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(encrypted_secret)
+    .bind(nonce)
+    .bind(Utc::now())
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("No webhook subscription {}", id)))?;
+
+    Ok((subscription, secret))
+}
+
+/// Queues a delivery of the event to every active subscription that wants it.
+pub async fn dispatch(pool: &PgPool, event: &Event) -> AppResult<usize> {
+    let subscriptions = sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        SELECT * FROM webhook_subscriptions
+        WHERE active AND (merchant_id IS NULL OR merchant_id = $1)
+          AND (cardinality(event_types) = 0 OR $2 = ANY(event_types))
+        "#,
+    )
+    .bind(event.merchant_id())
+    .bind(event.event_type())
+    .fetch_all(pool)
+    .await?;
+    if subscriptions.is_empty() {
+        return Ok(0);
+    }
+
+    let data = serde_json::to_value(event).map_err(|e| AppError::Internal(e.into()))?;
+    let mut tx = pool.begin().await?;
+    for subscription in &subscriptions {
+        let delivery = insert_delivery(&mut *tx, subscription.id, event.event_type(), &data).await?;
+        work_queue::enqueue(&mut *tx, WorkKind::Webhook, &DeliveryWork { delivery_id: delivery.id }, Utc::now()).await?;
+    }
+    tx.commit().await?;
+
+    Ok(subscriptions.len())
+}
+
+async fn insert_delivery<'e, E: sqlx::PgExecutor<'e>>(
+    executor: E,
+    subscription_id: Uuid,
+    event_type: &str,
+    data: &serde_json::Value,
+) -> AppResult<WebhookDelivery> {
+    let id = Uuid::new_v4();
+    let now = Utc::now();
+    // The delivery id doubles as the event id receivers deduplicate on, it stays the same across retries
+    let payload = json!({
+        "id": id,
+        "type": event_type,
+        "created_at": now,
+        "data": data,
+    });
+
+    let delivery = sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        INSERT INTO webhook_deliveries (id, subscription_id, event_type, payload, status, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(subscription_id)
+    .bind(event_type)
+    .bind(Json(payload))
+    .bind(WebhookDeliveryStatus::Pending.as_str())
+    .bind(now)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(delivery)
+}
+
+/// Work queue handler: one attempt at a queued delivery. Failing it schedules the retry.
+pub async fn deliver(state: &AppState, payload: serde_json::Value) -> AppResult<()> {
+    let work: DeliveryWork = serde_json::from_value(payload).map_err(|e| AppError::Internal(e.into()))?;
+    let delivery = sqlx::query_as::<_, WebhookDelivery>("SELECT * FROM webhook_deliveries WHERE id = $1")
+        .bind(work.delivery_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+    if delivery.status != WebhookDeliveryStatus::Pending.as_str() {
+        return Ok(());
+    }
+
+    let subscription = get(&state.db_pool, delivery.subscription_id).await?;
+    if !subscription.active {
+        // Paused in the meantime, the receiver asked not to be sent anything
+        finish(&state.db_pool, &delivery, WebhookDeliveryStatus::Failed, "Subscription is inactive").await?;
+        return Ok(());
+    }
+
+    let delivery = attempt(state, &subscription, &delivery, MAX_ATTEMPTS).await?;
+    if delivery.status == WebhookDeliveryStatus::Delivered.as_str() {
+        return Ok(());
+    }
+
+    Err(AppError::Internal(anyhow::anyhow!(
+        "webhook delivery {} to {} failed, attempt {}: {}",
+        delivery.id,
+        subscription.url,
+        delivery.attempts,
+        delivery.last_error.as_deref().unwrap_or_default()
+    )))
+}
+
+/// Sends a `webhook.test` event right away, once, and answers with how it went. It spells out how to verify
+/// the signature, for whoever is wiring up the receiving end.
+pub async fn send_test(state: &AppState, id: Uuid) -> AppResult<WebhookDelivery> {
+    let subscription = get(&state.db_pool, id).await?;
+    let data = json!({
+        "subscription_id": subscription.id,
+        "signature": {
+            "header": SIGNATURE_HEADER,
+            "format": "t=<unix time>,v1=<hex HMAC-SHA256 of \"<unix time>.<raw body>\" keyed with the secret>",
+            "tolerance_secs": SIGNATURE_TOLERANCE_SECS,
+        },
+    });
+    let delivery = insert_delivery(&state.db_pool, subscription.id, TEST_EVENT_TYPE, &data).await?;
+
+    attempt(state, &subscription, &delivery, 1).await
+}
+
+/// Posts the delivery and records the attempt; FAILED once `max_attempts` are used up.
+async fn attempt(
+    state: &AppState,
+    subscription: &WebhookSubscription,
+    delivery: &WebhookDelivery,
+    max_attempts: i32,
+) -> AppResult<WebhookDelivery> {
+    let body = serde_json::to_vec(&delivery.payload.0).map_err(|e| AppError::Internal(e.into()))?;
+    let secret: String = state.vault.open(&subscription.encrypted_secret, &subscription.nonce)?;
+    let signature = sign(&secret, Utc::now().timestamp(), &body);
+    let attempts = delivery.attempts + 1;
+
+    let started = Instant::now();
+    let response = client()
+        .post(&subscription.url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Id", delivery.id.to_string())
+        .header("X-Webhook-Event", &delivery.event_type)
+        .header(SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await;
+    let elapsed_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+    let (response_status, error) = match response {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            Some(format!("endpoint answered {}", response.status())),
+        ),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let status = match &error {
+        None => WebhookDeliveryStatus::Delivered,
+        Some(_) if attempts >= max_attempts => WebhookDeliveryStatus::Failed,
+        Some(_) => WebhookDeliveryStatus::Pending,
+    };
+    let now = Utc::now();
+
+    let delivery = sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2, attempts = $3, response_status = $4, response_time_ms = $5, last_error = $6,
+            last_attempt_at = $7, delivered_at = CASE WHEN $2 = 'DELIVERED' THEN $7 ELSE delivered_at END
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(delivery.id)
+    .bind(status.as_str())
+    .bind(attempts)
+    .bind(response_status)
+    .bind(elapsed_ms)
+    .bind(error)
+    .bind(now)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    Ok(delivery)
+}
+
+/// `t=<unix time>,v1=<hex HMAC-SHA256 of "<unix time>.<body>" with the subscription secret>`. Receivers recompute
+/// it over the raw body, compare in constant time and reject timestamps outside `SIGNATURE_TOLERANCE_SECS`, so a
+/// captured delivery can't be replayed later.
+fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+async fn finish(pool: &PgPool, delivery: &WebhookDelivery, status: WebhookDeliveryStatus, error: &str) -> AppResult<()> {
+    sqlx::query("UPDATE webhook_deliveries SET status = $2, last_error = $3 WHERE id = $1")
+        .bind(delivery.id)
+        .bind(status.as_str())
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Delivery counts, success rate and timings of a subscription, test deliveries left out.
+pub async fn stats(pool: &PgPool, id: Uuid) -> AppResult<WebhookDeliveryStats> {
+    get(pool, id).await?;
+
+    let stats = sqlx::query_as::<_, WebhookDeliveryStats>(
+        r#"
+        SELECT
+            $1::UUID AS subscription_id,
+            COUNT(*) AS total,
+            COUNT(*) FILTER (WHERE status = 'DELIVERED') AS delivered,
+            COUNT(*) FILTER (WHERE status = 'PENDING') AS pending,
+            COUNT(*) FILTER (WHERE status = 'FAILED') AS failed,
+            (100.0 * COUNT(*) FILTER (WHERE status = 'DELIVERED')
+                / NULLIF(COUNT(*) FILTER (WHERE status <> 'PENDING'), 0))::FLOAT8 AS success_rate,
+            (AVG(attempts) FILTER (WHERE status <> 'PENDING'))::FLOAT8 AS average_attempts,
+            AVG(response_time_ms)::FLOAT8 AS average_response_time_ms,
+            MAX(delivered_at) AS last_delivered_at,
+            MAX(last_attempt_at) FILTER (WHERE last_error IS NOT NULL) AS last_failure_at
+        FROM webhook_deliveries
+        WHERE subscription_id = $1 AND event_type <> $2
+        "#,
+    )
+    .bind(id)
+    .bind(TEST_EVENT_TYPE)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(stats)
+}
@@ -0,0 +1,256 @@
+use crate::{
+    database,
+    dto::PaymentExportQuery,
+    error::{AppError, AppResult},
+    services::payment_service,
+};
+use arrow_array::{
+    builder::{BooleanBuilder, Decimal128Builder, Int16Builder, StringBuilder, TimestampMicrosecondBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use rust_decimal::Decimal;
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Rows per Parquet row group; one group is held in memory at a time.
+const ROW_GROUP_ROWS: usize = 10_000;
+// Row groups buffered between the encoder and the HTTP response
+const CHANNEL_CAPACITY: usize = 4;
+// Money columns; amounts are stored with 2 decimals, rates with up to 4
+const DECIMAL_PRECISION: u8 = 20;
+const DECIMAL_SCALE: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn parse(format: Option<&str>) -> AppResult<Self> {
+        match format.map(str::to_lowercase).as_deref() {
+            None | Some("parquet") => Ok(ExportFormat::Parquet),
+            Some(other) => Err(AppError::BadRequest(format!("Unknown export format: {}", other))),
+        }
+    }
+
+    pub fn content_type(&self) -> &str {
+        match self {
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(&self) -> &str {
+        match self {
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// Flat payment row for the warehouse, without per-payment details like 3-D Secure or transfer instructions.
+#[derive(Debug, FromRow)]
+struct ExportRow {
+    id: Uuid,
+    order_id: Uuid,
+    user_id: Uuid,
+    merchant_id: Uuid,
+    amount: Decimal,
+    gross_amount: Decimal,
+    currency: String,
+    payment_method: String,
+    payment_status: String,
+    installment_count: i16,
+    wallet_amount: Decimal,
+    voucher_amount: Decimal,
+    discount_amount: Decimal,
+    tax_jurisdiction: Option<String>,
+    tax_rate: Decimal,
+    tax_amount: Decimal,
+    method_surcharge: Decimal,
+    escrow: bool,
+    wallet_topup: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+fn schema() -> SchemaRef {
+    let id = |name| Field::new(name, DataType::Utf8, false);
+    let money = |name| Field::new(name, DataType::Decimal128(DECIMAL_PRECISION, DECIMAL_SCALE as i8), false);
+    let timestamp = |name| Field::new(name, DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), false);
+
+    Arc::new(Schema::new(vec![
+        id("id"),
+        id("order_id"),
+        id("user_id"),
+        id("merchant_id"),
+        money("amount"),
+        money("gross_amount"),
+        Field::new("currency", DataType::Utf8, false),
+        Field::new("payment_method", DataType::Utf8, false),
+        Field::new("payment_status", DataType::Utf8, false),
+        Field::new("installment_count", DataType::Int16, false),
+        money("wallet_amount"),
+        money("voucher_amount"),
+        money("discount_amount"),
+        Field::new("tax_jurisdiction", DataType::Utf8, true),
+        money("tax_rate"),
+        money("tax_amount"),
+        money("method_surcharge"),
+        Field::new("escrow", DataType::Boolean, false),
+        Field::new("wallet_topup", DataType::Boolean, false),
+        timestamp("created_at"),
+        timestamp("updated_at"),
+    ]))
+}
+
+/// Payments created in the period as a Parquet file, streamed one row group at a time.
+pub fn payments(
+    pool: PgPool,
+    mut query: PaymentExportQuery,
+    timeout_ms: u64,
+) -> impl Stream<Item = Result<Vec<u8>, std::io::Error>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    query.from = Some(payment_service::default_from(query.from));
+
+    tokio::spawn(async move {
+        if let Err(e) = write_parquet(&pool, &query, timeout_ms, &tx).await {
+            // Ends the download with an error so a truncated file isn't mistaken for a complete one
+            tracing::error!(error = %e, "payment export failed");
+            let _ = tx.send(Err(std::io::Error::other(e))).await;
+        }
+    });
+
+    futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|item| (item, rx)) })
+}
+
+async fn write_parquet(
+    pool: &PgPool,
+    query: &PaymentExportQuery,
+    timeout_ms: u64,
+    tx: &mpsc::Sender<Result<Vec<u8>, std::io::Error>>,
+) -> anyhow::Result<()> {
+    let schema = schema();
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(ROW_GROUP_ROWS)
+        .build();
+    let mut writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(properties))?;
+
+    let mut db = database::begin_with_statement_timeout(pool, timeout_ms).await?;
+    let mut rows = sqlx::query_as::<_, ExportRow>(
+        r#"
+        SELECT id, order_id, user_id, merchant_id, amount, gross_amount, currency, payment_method, payment_status,
+               installment_count, wallet_amount, voucher_amount, discount_amount, tax_jurisdiction, tax_rate,
+               tax_amount, method_surcharge, escrow, wallet_topup, created_at, updated_at
+        FROM payments
+        WHERE created_at >= $1 AND ($2::TIMESTAMPTZ IS NULL OR created_at < $2)
+        ORDER BY created_at
+        "#,
+    )
+    .bind(query.from)
+    .bind(query.to)
+    .fetch(&mut *db);
+
+    let mut group = Vec::with_capacity(ROW_GROUP_ROWS);
+    loop {
+        let row = rows.try_next().await?;
+        let done = row.is_none();
+        group.extend(row);
+        if group.len() < ROW_GROUP_ROWS && !done {
+            continue;
+        }
+
+        if !group.is_empty() {
+            writer.write(&record_batch(&schema, &group)?)?;
+            writer.flush()?;
+            group.clear();
+        }
+        if done {
+            break;
+        }
+        // Receiver gone means the client disconnected
+        if tx.send(Ok(std::mem::take(writer.inner_mut()))).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    // Whatever is left of the last row group plus the footer
+    let _ = tx.send(Ok(writer.into_inner()?)).await;
+    Ok(())
+}
+
+fn record_batch(schema: &SchemaRef, rows: &[ExportRow]) -> anyhow::Result<RecordBatch> {
+    let uuids = |get: fn(&ExportRow) -> Uuid| -> ArrayRef {
+        let mut builder = StringBuilder::new();
+        for row in rows {
+            builder.append_value(get(row).to_string());
+        }
+        Arc::new(builder.finish())
+    };
+    let strings = |get: fn(&ExportRow) -> Option<&str>| -> ArrayRef {
+        let mut builder = StringBuilder::new();
+        for row in rows {
+            builder.append_option(get(row));
+        }
+        Arc::new(builder.finish())
+    };
+    let decimals = |get: fn(&ExportRow) -> Decimal| -> anyhow::Result<ArrayRef> {
+        let mut builder = Decimal128Builder::with_capacity(rows.len());
+        for row in rows {
+            let mut value = get(row);
+            value.rescale(DECIMAL_SCALE);
+            builder.append_value(value.mantissa());
+        }
+        Ok(Arc::new(builder.finish().with_precision_and_scale(DECIMAL_PRECISION, DECIMAL_SCALE as i8)?))
+    };
+    let booleans = |get: fn(&ExportRow) -> bool| -> ArrayRef {
+        let mut builder = BooleanBuilder::with_capacity(rows.len());
+        for row in rows {
+            builder.append_value(get(row));
+        }
+        Arc::new(builder.finish())
+    };
+    let timestamps = |get: fn(&ExportRow) -> DateTime<Utc>| -> ArrayRef {
+        let mut builder = TimestampMicrosecondBuilder::with_capacity(rows.len());
+        for row in rows {
+            builder.append_value(get(row).timestamp_micros());
+        }
+        Arc::new(builder.finish().with_timezone("UTC"))
+    };
+    let mut installments = Int16Builder::with_capacity(rows.len());
+    for row in rows {
+        installments.append_value(row.installment_count);
+    }
+
+    Ok(RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            uuids(|r| r.id),
+            uuids(|r| r.order_id),
+            uuids(|r| r.user_id),
+            uuids(|r| r.merchant_id),
+            decimals(|r| r.amount)?,
+            decimals(|r| r.gross_amount)?,
+            strings(|r| Some(&r.currency)),
+            strings(|r| Some(&r.payment_method)),
+            strings(|r| Some(&r.payment_status)),
+            Arc::new(installments.finish()),
+            decimals(|r| r.wallet_amount)?,
+            decimals(|r| r.voucher_amount)?,
+            decimals(|r| r.discount_amount)?,
+            strings(|r| r.tax_jurisdiction.as_deref()),
+            decimals(|r| r.tax_rate)?,
+            decimals(|r| r.tax_amount)?,
+            decimals(|r| r.method_surcharge)?,
+            booleans(|r| r.escrow),
+            booleans(|r| r.wallet_topup),
+            timestamps(|r| r.created_at),
+            timestamps(|r| r.updated_at),
+        ],
+    )?)
+}
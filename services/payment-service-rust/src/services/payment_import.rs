@@ -0,0 +1,344 @@
+use crate::{
+    config::Config,
+    dto::{ImportRowError, LegacyPaymentRow, PaymentImportReport},
+    error::{AppError, AppResult},
+    models::{PaymentStatus, DEFAULT_MERCHANT_ID},
+    services::user_client::UserServiceClient,
+};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Rows inserted per statement and transaction.
+pub const BATCH_SIZE: usize = 500;
+/// payments.amount is DECIMAL(10, 2)
+const MAX_AMOUNT: Decimal = Decimal::from_parts(99_999_999, 0, 0, false, 0);
+/// Only settled payments are migrated, nothing in flight
+const IMPORTABLE_STATUSES: [PaymentStatus; 3] = [PaymentStatus::Completed, PaymentStatus::Failed, PaymentStatus::Refunded];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ImportFormat {
+    pub fn from_content_type(content_type: &str) -> AppResult<Self> {
+        match content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "text/csv" => Ok(ImportFormat::Csv),
+            "application/x-ndjson" | "application/jsonl" => Ok(ImportFormat::Ndjson),
+            _ => Err(AppError::UnsupportedMediaType(
+                "Expected a text/csv or application/x-ndjson body".to_string(),
+            )),
+        }
+    }
+}
+
+/// A settled payment written as-is, without going through the gateway: validated legacy rows and seed data.
+pub struct HistoricalPayment {
+    pub id: Uuid,
+    pub transaction_id: String,
+    pub order_id: Uuid,
+    pub user_id: Uuid,
+    pub merchant_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub payment_method: String,
+    pub payment_status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Imports historical payments from the legacy system. Every row is validated, the valid ones are inserted in
+/// batches and the rest come back in the report; a file can be re-sent after fixing it since rows whose
+/// transaction id already exists are rejected as duplicates. With a user service client and the caller's token,
+/// rows of users it doesn't know are rejected too.
+pub async fn import(
+    pool: &PgPool,
+    config: &Config,
+    format: ImportFormat,
+    body: &str,
+    dry_run: bool,
+    verify_users: Option<(&UserServiceClient, &str)>,
+) -> AppResult<PaymentImportReport> {
+    let rows = parse(format, body)?;
+    let total_rows = rows.len();
+
+    let transaction_ids: Vec<String> = rows
+        .iter()
+        .filter_map(|row| row.as_ref().ok()?.transaction_id.as_deref().map(str::trim).map(str::to_string))
+        .collect();
+    let existing: HashSet<String> =
+        sqlx::query_scalar::<_, String>("SELECT transaction_id FROM payments WHERE transaction_id = ANY($1)")
+            .bind(&transaction_ids)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+    let merchants: HashSet<Uuid> = sqlx::query_scalar("SELECT id FROM merchants").fetch_all(pool).await?.into_iter().collect();
+    let users = match verify_users {
+        Some((client, token)) => {
+            let user_ids: Vec<Uuid> = rows
+                .iter()
+                .filter_map(|row| Uuid::parse_str(row.as_ref().ok()?.user_id.as_deref()?.trim()).ok())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+            let users = client.existing_users(token, &user_ids).await.map_err(|e| {
+                tracing::warn!(error = %e, "user lookup for import failed");
+                AppError::ServiceUnavailable("User service is unavailable, import without verify_users or retry".to_string())
+            })?;
+            Some(users)
+        }
+        None => None,
+    };
+
+    let mut seen = HashSet::new();
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let result = row.and_then(|row| validate(config, &row, &existing, &merchants, users.as_ref(), &mut seen).map_err(|e| (row.transaction_id, e)));
+        match result {
+            Ok(payment) => valid.push(payment),
+            Err((transaction_id, row_errors)) => errors.push(ImportRowError { row: index + 1, transaction_id, errors: row_errors }),
+        }
+    }
+
+    let imported = valid.len();
+    if !dry_run {
+        for batch in valid.chunks(BATCH_SIZE) {
+            insert_batch(pool, batch).await?;
+        }
+        tracing::info!("Imported {} legacy payments, {} rows rejected", imported, errors.len());
+    }
+
+    Ok(PaymentImportReport { dry_run, total_rows, imported, failed: errors.len(), errors })
+}
+
+type ParsedRow = Result<LegacyPaymentRow, (Option<String>, Vec<String>)>;
+
+/// Splits the body into rows; a row that can't be parsed is reported like an invalid one.
+fn parse(format: ImportFormat, body: &str) -> AppResult<Vec<ParsedRow>> {
+    let unreadable = |e: &dyn std::fmt::Display| Err((None, vec![format!("unreadable row: {}", e)]));
+
+    match format {
+        ImportFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(body.as_bytes());
+            if reader.headers().is_err() {
+                return Err(AppError::BadRequest("CSV header row is missing or invalid".to_string()));
+            }
+            Ok(reader
+                .deserialize::<LegacyPaymentRow>()
+                .map(|row| row.or_else(|e| unreadable(&e)))
+                .collect())
+        }
+        ImportFormat::Ndjson => Ok(body
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str::<LegacyPaymentRow>(line).or_else(|e| unreadable(&e)))
+            .collect()),
+    }
+}
+
+fn validate(
+    config: &Config,
+    row: &LegacyPaymentRow,
+    existing: &HashSet<String>,
+    merchants: &HashSet<Uuid>,
+    users: Option<&HashSet<Uuid>>,
+    seen: &mut HashSet<String>,
+) -> Result<HistoricalPayment, Vec<String>> {
+    let mut errors = Vec::new();
+    let field = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+    let uuid = |name: &str, value: &Option<String>, errors: &mut Vec<String>| match field(value) {
+        None => {
+            errors.push(format!("{} is required", name));
+            None
+        }
+        Some(value) => Uuid::parse_str(&value).map_err(|_| errors.push(format!("{} is not a UUID: {}", name, value))).ok(),
+    };
+
+    let transaction_id = match field(&row.transaction_id) {
+        None => {
+            errors.push("transaction_id is required".to_string());
+            None
+        }
+        Some(id) if existing.contains(&id) => {
+            errors.push(format!("transaction_id {} was already imported", id));
+            None
+        }
+        Some(id) if !seen.insert(id.clone()) => {
+            errors.push(format!("transaction_id {} appears more than once in the file", id));
+            None
+        }
+        Some(id) => Some(id),
+    };
+    let order_id = uuid("order_id", &row.order_id, &mut errors);
+    let user_id = uuid("user_id", &row.user_id, &mut errors).filter(|id| {
+        let known = users.is_none_or(|users| users.contains(id));
+        if !known {
+            errors.push(format!("user {} does not exist", id));
+        }
+        known
+    });
+    let merchant_id = match field(&row.merchant_id) {
+        None => Some(DEFAULT_MERCHANT_ID),
+        Some(_) => uuid("merchant_id", &row.merchant_id, &mut errors).filter(|id| {
+            let known = merchants.contains(id);
+            if !known {
+                errors.push(format!("merchant {} does not exist", id));
+            }
+            known
+        }),
+    };
+
+    let amount = match field(&row.amount).map(|a| a.parse::<Decimal>()) {
+        None => {
+            errors.push("amount is required".to_string());
+            None
+        }
+        Some(Err(_)) => {
+            errors.push(format!("amount is not a number: {}", row.amount.as_deref().unwrap_or_default()));
+            None
+        }
+        Some(Ok(amount)) if amount <= Decimal::ZERO || amount > MAX_AMOUNT => {
+            errors.push(format!("amount must be between 0.01 and {}", MAX_AMOUNT));
+            None
+        }
+        Some(Ok(amount)) if amount.normalize().scale() > 2 => {
+            errors.push("amount has more than 2 decimals".to_string());
+            None
+        }
+        Some(Ok(amount)) => Some(amount),
+    };
+
+    let currency = match field(&row.currency).map(|c| c.to_uppercase()) {
+        None => {
+            errors.push("currency is required".to_string());
+            None
+        }
+        Some(currency) if !config.fx_usd_prices.contains_key(&currency) => {
+            errors.push(format!("unsupported currency: {}", currency));
+            None
+        }
+        Some(currency) => Some(currency),
+    };
+
+    let payment_method = match field(&row.payment_method).map(|m| m.to_uppercase()) {
+        None => {
+            errors.push("payment_method is required".to_string());
+            None
+        }
+        Some(method) if method.len() > 50 => {
+            errors.push("payment_method is longer than 50 characters".to_string());
+            None
+        }
+        Some(method) => Some(method),
+    };
+
+    let payment_status = match field(&row.payment_status).map(|s| s.to_uppercase()) {
+        None => {
+            errors.push("payment_status is required".to_string());
+            None
+        }
+        Some(status) if !IMPORTABLE_STATUSES.iter().any(|s| s.as_str() == status) => {
+            errors.push(format!("payment_status must be COMPLETED, FAILED or REFUNDED, got {}", status));
+            None
+        }
+        Some(status) => Some(status),
+    };
+
+    let created_at = match field(&row.created_at).map(|c| parse_timestamp(&c)) {
+        None => {
+            errors.push("created_at is required".to_string());
+            None
+        }
+        Some(None) => {
+            errors.push(format!("created_at is not a timestamp: {}", row.created_at.as_deref().unwrap_or_default()));
+            None
+        }
+        Some(Some(at)) if at > Utc::now() => {
+            errors.push("created_at is in the future".to_string());
+            None
+        }
+        Some(Some(at)) => Some(at),
+    };
+
+    match (transaction_id, order_id, user_id, merchant_id, amount, currency, payment_method, payment_status, created_at) {
+        (
+            Some(transaction_id),
+            Some(order_id),
+            Some(user_id),
+            Some(merchant_id),
+            Some(amount),
+            Some(currency),
+            Some(payment_method),
+            Some(payment_status),
+            Some(created_at),
+        ) if errors.is_empty() => Ok(HistoricalPayment {
+            id: Uuid::new_v4(),
+            transaction_id,
+            order_id,
+            user_id,
+            merchant_id,
+            amount,
+            currency,
+            payment_method,
+            payment_status,
+            created_at,
+        }),
+        _ => Err(errors),
+    }
+}
+
+fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw)
+        .map(|at| at.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S").map(|at| at.and_utc()))
+        .ok()
+}
+
+/// Creates the month partitions first so old rows don't pile up in the default partition.
+#[tracing::instrument(
+    name = "db.query",
+    skip_all,
+    fields(db.system = "postgresql", db.statement = "payments.import", db.rows_affected = tracing::field::Empty)
+)]
+pub async fn insert_batch(pool: &PgPool, batch: &[HistoricalPayment]) -> AppResult<()> {
+    let months: HashSet<NaiveDate> = batch.iter().filter_map(|p| p.created_at.date_naive().with_day(1)).collect();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT create_payments_partition(month) FROM UNNEST($1::DATE[]) AS month")
+        .bind(months.into_iter().collect::<Vec<_>>())
+        .execute(&mut *tx)
+        .await?;
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO payments (id, order_id, user_id, merchant_id, amount, gross_amount, taxable_base, currency,
+                              payment_method, payment_status, transaction_id, created_at, updated_at)
+        SELECT id, order_id, user_id, merchant_id, amount, amount, amount, currency,
+               payment_method, payment_status, transaction_id, created_at, created_at
+        FROM UNNEST($1::UUID[], $2::UUID[], $3::UUID[], $4::UUID[], $5::NUMERIC[], $6::TEXT[], $7::TEXT[], $8::TEXT[],
+                    $9::TEXT[], $10::TIMESTAMPTZ[])
+            AS t(id, order_id, user_id, merchant_id, amount, currency, payment_method, payment_status, transaction_id, created_at)
+        "#,
+    )
+    .bind(batch.iter().map(|p| p.id).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.order_id).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.user_id).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.merchant_id).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.amount).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.currency.clone()).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.payment_method.clone()).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.payment_status.clone()).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.transaction_id.clone()).collect::<Vec<_>>())
+    .bind(batch.iter().map(|p| p.created_at).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await?;
+    tracing::Span::current().record("db.rows_affected", result.rows_affected());
+
+    tx.commit().await?;
+    Ok(())
+}
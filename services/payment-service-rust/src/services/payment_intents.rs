@@ -0,0 +1,162 @@
+use crate::{
+    dto::{ConfirmPaymentIntentRequest, CreatePaymentIntentRequest, CreatePaymentRequest},
+    error::{AppError, AppResult, ErrorCode},
+    models::{Payment, PaymentIntent, PaymentIntentStatus, PaymentStatus},
+    services::{payment_service, promotions, vault::Vault, AppState},
+};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Validates the order and fixes its amount; returns the intent with its client secret.
+pub async fn create(
+    pool: &PgPool,
+    merchant_id: Uuid,
+    request: CreatePaymentIntentRequest,
+) -> AppResult<(PaymentIntent, String)> {
+    let currency = request.currency.to_uppercase();
+    if request.amount <= Decimal::ZERO {
+        return Err(AppError::BadRequest("amount must be positive".to_string()));
+    }
+    if currency.len() != 3 {
+        return Err(AppError::BadRequest("currency must be an ISO 4217 code".to_string()));
+    }
+    // The code is only redeemed at confirmation, but a bad one should fail here
+    if let Some(code) = request.promo_code.as_deref() {
+        promotions::evaluate(pool, code, request.amount, &currency).await?;
+    }
+
+    if payment_service::find_active_by_order(pool, merchant_id, request.order_id).await?.is_some() {
+        return Err(AppError::Conflict("Order already has a payment".to_string()).with_code(ErrorCode::DuplicateOrder));
+    }
+
+    let id = Uuid::new_v4();
+    let client_secret = Vault::generate_token(&format!("pi_{}_secret", id.simple()));
+    let now = Utc::now();
+
+    let intent = sqlx::query_as::<_, PaymentIntent>(
+        r#"
+        INSERT INTO payment_intents (id, merchant_id, order_id, user_id, amount, currency, promo_code, country, escrow,
+                                     client_secret_hash, status, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $12)
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(merchant_id)
+    .bind(request.order_id)
+    .bind(request.user_id)
+    .bind(request.amount)
+    .bind(currency)
+    .bind(request.promo_code)
+    .bind(request.country.map(|c| c.to_uppercase()))
+    .bind(request.escrow)
+    .bind(hash_secret(&client_secret))
+    .bind(PaymentIntentStatus::RequiresConfirmation.as_str())
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((intent, client_secret))
+}
+
+pub async fn get(pool: &PgPool, merchant_id: Uuid, id: Uuid) -> AppResult<PaymentIntent> {
+    let intent = sqlx::query_as::<_, PaymentIntent>("SELECT * FROM payment_intents WHERE id = $1 AND merchant_id = $2")
+        .bind(id)
+        .bind(merchant_id)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(intent)
+}
+
+/// Charges the intent with the method collected on the client. A failed payment leaves it confirmable.
+/// `payer_id` is the customer whose token came with the request, the client secret alone doesn't open their wallet.
+pub async fn confirm(
+    state: &AppState,
+    merchant_id: Uuid,
+    id: Uuid,
+    payer_id: Option<Uuid>,
+    request: ConfirmPaymentIntentRequest,
+) -> AppResult<Payment> {
+    let pool = &state.db_pool;
+
+    let intent = sqlx::query_as::<_, PaymentIntent>(
+        r#"
+        UPDATE payment_intents SET status = $4, updated_at = NOW()
+        WHERE id = $1 AND merchant_id = $2 AND client_secret_hash = $3 AND status = $5
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(merchant_id)
+    .bind(hash_secret(&request.client_secret))
+    .bind(PaymentIntentStatus::Processing.as_str())
+    .bind(PaymentIntentStatus::RequiresConfirmation.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(intent) = intent else {
+        let intent = get(pool, merchant_id, id).await?;
+        let secret_hash = sqlx::query_scalar::<_, String>("SELECT client_secret_hash FROM payment_intents WHERE id = $1")
+            .bind(intent.id)
+            .fetch_one(pool)
+            .await?;
+        if secret_hash != hash_secret(&request.client_secret) {
+            return Err(AppError::Forbidden("Invalid client secret".to_string()));
+        }
+        return Err(AppError::Conflict(format!("Payment intent is {}", intent.status)));
+    };
+
+    let payment = payment_service::create_payment(
+        state,
+        CreatePaymentRequest {
+            order_id: intent.order_id,
+            user_id: intent.user_id,
+            amount: intent.amount,
+            currency: intent.currency.clone(),
+            payment_method: request.payment_method,
+            payment_method_token: request.payment_method_token,
+            return_url: request.return_url,
+            crypto_asset: request.crypto_asset,
+            installments: request.installments,
+            wallet_amount: request.wallet_amount,
+            voucher_code: request.voucher_code,
+            promo_code: intent.promo_code.clone(),
+            country: intent.country.clone(),
+            splits: None,
+            escrow: intent.escrow,
+            merchant_initiated: false,
+            subscription_id: None,
+            wallet_topup: false,
+            promotion_id: None,
+            discount_amount: Decimal::ZERO,
+            tax_jurisdiction: None,
+            tax_rate: Decimal::ZERO,
+            merchant_id: intent.merchant_id,
+            payer_id,
+        },
+    )
+    .await;
+
+    let status = match &payment {
+        Ok(payment) if payment.payment_status != PaymentStatus::Failed.as_str() => PaymentIntentStatus::Succeeded,
+        _ => PaymentIntentStatus::RequiresConfirmation,
+    };
+    sqlx::query(
+        "UPDATE payment_intents SET status = $2, payment_id = COALESCE($3, payment_id), updated_at = NOW() WHERE id = $1",
+    )
+    .bind(intent.id)
+    .bind(status.as_str())
+    .bind(payment.as_ref().ok().map(|p| p.id))
+    .execute(pool)
+    .await?;
+
+    payment
+}
+
+fn hash_secret(client_secret: &str) -> String {
+    hex::encode(Sha256::digest(client_secret.as_bytes()))
+}
@@ -0,0 +1,240 @@
+use crate::{
+    config::Config,
+    dto::{CreatePaymentLinkRequest, CreatePaymentRequest, PayPaymentLinkRequest, PaymentLinkResponse},
+    error::{AppError, AppResult},
+    models::{PaymentLink, PaymentLinkStatus, PaymentStatus, Payment},
+    services::{merchants, nonces, payment_service, AppState},
+};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use rust_decimal::Decimal;
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn create(
+    pool: &PgPool,
+    config: &Config,
+    merchant_id: Uuid,
+    created_by: Uuid,
+    request: CreatePaymentLinkRequest,
+) -> AppResult<PaymentLink> {
+    if request.amount <= Decimal::ZERO {
+        return Err(AppError::BadRequest("amount must be positive".to_string()));
+    }
+    let ttl_hours = request.expires_in_hours.unwrap_or(config.payment_link_ttl_hours);
+    if !(1..=24 * 30).contains(&ttl_hours) {
+        return Err(AppError::BadRequest("Payment links can be valid for 1 hour to 30 days".to_string()));
+    }
+
+    let id = Uuid::new_v4();
+    let now = Utc::now();
+    let expires_at = now + Duration::hours(ttl_hours);
+
+    let link = sqlx::query_as::<_, PaymentLink>(
+        r#"
+        INSERT INTO payment_links (id, merchant_id, token, order_id, user_id, amount, currency, description, status,
+                                   expires_at, created_by, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $12)
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(merchant_id)
+    .bind(sign(&config.payment_link_secret, id, expires_at))
+    .bind(request.order_id)
+    .bind(request.user_id)
+    .bind(request.amount)
+    .bind(request.currency.to_uppercase())
+    .bind(request.description)
+    .bind(PaymentLinkStatus::Active.as_str())
+    .bind(expires_at)
+    .bind(created_by)
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(link)
+}
+
+pub async fn get(pool: &PgPool, config: &Config, token: &str) -> AppResult<PaymentLink> {
+    let (id, _) = verify(&config.payment_link_secret, token)?;
+    let link = sqlx::query_as::<_, PaymentLink>("SELECT * FROM payment_links WHERE id = $1 AND token = $2")
+        .bind(id)
+        .bind(token)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(link)
+}
+
+/// Creates the payment for the link. Only one attempt runs at a time; a failed payment leaves the link payable,
+/// but not by replaying an earlier attempt: each request's nonce is accepted once while the link is valid.
+/// `payer_id` is the customer whose token came with the request; saved cards are only theirs to use.
+pub async fn pay(
+    state: &AppState,
+    token: &str,
+    payer_id: Option<Uuid>,
+    request: PayPaymentLinkRequest,
+) -> AppResult<Payment> {
+    let pool = &state.db_pool;
+    let (id, expires_at) = verify(&state.config.payment_link_secret, token)?;
+
+    nonces::validate("nonce", &request.nonce)?;
+    let ttl = std::time::Duration::from_secs((expires_at - Utc::now().timestamp()).max(1) as u64);
+    if !nonces::claim(&state.redis_conn, &format!("payment_link:{}", id.simple()), &request.nonce, ttl).await? {
+        tracing::warn!("Replayed payment for link {} rejected", id);
+        return Err(AppError::Conflict("This payment attempt was already submitted".to_string()));
+    }
+
+    // Paid links whose payment failed later (e.g. at 3-D Secure) can be paid again
+    let link = sqlx::query_as::<_, PaymentLink>(
+        r#"
+        UPDATE payment_links l SET status = $3, updated_at = NOW()
+        WHERE id = $1 AND token = $2 AND expires_at > NOW()
+          AND (status = $4 OR (status = $5 AND EXISTS (
+              SELECT 1 FROM payments p WHERE p.id = l.payment_id AND p.payment_status = $6)))
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(token)
+    .bind(PaymentLinkStatus::Processing.as_str())
+    .bind(PaymentLinkStatus::Active.as_str())
+    .bind(PaymentLinkStatus::Paid.as_str())
+    .bind(PaymentStatus::Failed.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(link) = link else {
+        let link = get(pool, &state.config, token).await?;
+        if link.expires_at <= Utc::now() {
+            return Err(AppError::Conflict("Payment link has expired".to_string()));
+        }
+        return Err(AppError::Conflict("Payment link is already paid or being paid".to_string()));
+    };
+
+    let user_id = match (link.user_id, payer_id.or(request.user_id)) {
+        (Some(owner), Some(payer)) if owner != payer => {
+            release(pool, link.id, None).await?;
+            return Err(AppError::Forbidden("Payment link belongs to another customer".to_string()));
+        }
+        (Some(user_id), _) | (None, Some(user_id)) => user_id,
+        (None, None) => {
+            release(pool, link.id, None).await?;
+            return Err(AppError::BadRequest("user_id is required".to_string()));
+        }
+    };
+
+    let payment = payment_service::create_payment(
+        state,
+        CreatePaymentRequest {
+            order_id: link.order_id,
+            user_id,
+            amount: link.amount,
+            currency: link.currency.clone(),
+            payment_method: request.payment_method,
+            payment_method_token: request.payment_method_token,
+            return_url: request.return_url,
+            crypto_asset: None,
+            installments: request.installments,
+            wallet_amount: None,
+            voucher_code: None,
+            promo_code: None,
+            country: None,
+            splits: None,
+            escrow: false,
+            merchant_initiated: false,
+            subscription_id: None,
+            wallet_topup: false,
+            promotion_id: None,
+            discount_amount: Decimal::ZERO,
+            tax_jurisdiction: None,
+            tax_rate: Decimal::ZERO,
+            merchant_id: link.merchant_id,
+            payer_id,
+        },
+    )
+    .await;
+
+    match &payment {
+        Ok(payment) if payment.payment_status != PaymentStatus::Failed.as_str() => {
+            sqlx::query("UPDATE payment_links SET status = $2, payment_id = $3, updated_at = NOW() WHERE id = $1")
+                .bind(link.id)
+                .bind(PaymentLinkStatus::Paid.as_str())
+                .bind(payment.id)
+                .execute(pool)
+                .await?;
+        }
+        Ok(payment) => release(pool, link.id, Some(payment.id)).await?,
+        Err(_) => release(pool, link.id, None).await?,
+    }
+
+    payment
+}
+
+pub async fn to_response(pool: &PgPool, config: &Config, link: PaymentLink) -> AppResult<PaymentLinkResponse> {
+    let merchant = merchants::get(pool, link.merchant_id).await?;
+    let status = if link.status == PaymentLinkStatus::Active.as_str() && link.expires_at <= Utc::now() {
+        "EXPIRED".to_string()
+    } else {
+        link.status
+    };
+
+    Ok(PaymentLinkResponse {
+        url: format!("{}/{}", config.payment_link_base_url.trim_end_matches('/'), link.token),
+        token: link.token,
+        merchant_name: merchant.name,
+        order_id: link.order_id,
+        amount: link.amount,
+        currency: link.currency,
+        description: link.description,
+        status,
+        payment_id: link.payment_id,
+        expires_at: link.expires_at.to_rfc3339(),
+    })
+}
+
+async fn release(pool: &PgPool, id: Uuid, failed_payment_id: Option<Uuid>) -> AppResult<()> {
+    sqlx::query(
+        "UPDATE payment_links SET status = $2, payment_id = COALESCE($3, payment_id), updated_at = NOW() WHERE id = $1"
+    )
+    .bind(id)
+    .bind(PaymentLinkStatus::Active.as_str())
+    .bind(failed_payment_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// `<link id>.<expiry unix time>.<HMAC-SHA256 of both>`, so forged or expired tokens are rejected without a lookup.
+fn sign(secret: &str, id: Uuid, expires_at: DateTime<Utc>) -> String {
+    let payload = format!("{}.{}", id.simple(), expires_at.timestamp());
+    format!("{}.{}", payload, hex::encode(mac(secret, &payload)))
+}
+
+/// The link id and its expiry as unix time.
+fn verify(secret: &str, token: &str) -> AppResult<(Uuid, i64)> {
+    let invalid = || AppError::NotFound("Payment link not found".to_string());
+
+    let (payload, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+    let signature = hex::decode(signature).map_err(|_| invalid())?;
+    let mut expected = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    expected.update(payload.as_bytes());
+    expected.verify_slice(&signature).map_err(|_| invalid())?;
+
+    let (id, expires_at) = payload.split_once('.').ok_or_else(invalid)?;
+    let expires_at: i64 = expires_at.parse().map_err(|_| invalid())?;
+    if expires_at <= Utc::now().timestamp() {
+        return Err(AppError::Conflict("Payment link has expired".to_string()));
+    }
+
+    Ok((Uuid::parse_str(id).map_err(|_| invalid())?, expires_at))
+}
+
+fn mac(secret: &str, payload: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
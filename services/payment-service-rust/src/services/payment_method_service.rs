@@ -0,0 +1,215 @@
+use crate::{
+    dto::TokenizePaymentMethodRequest,
+    error::{AppError, AppResult},
+    models::{CardDetails, PaymentMethod},
+    services::vault::Vault,
+};
+use chrono::{Datelike, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const SUPPORTED_METHOD_TYPES: [&str; 2] = ["CREDIT_CARD", "DEBIT_CARD"];
+
+pub async fn tokenize(
+    pool: &PgPool,
+    vault: &Vault,
+    user_id: Uuid,
+    request: TokenizePaymentMethodRequest,
+) -> AppResult<PaymentMethod> {
+    if !SUPPORTED_METHOD_TYPES.contains(&request.method_type.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported payment method type: {}",
+            request.method_type
+        )));
+    }
+
+    let number: String = request.card_number.chars().filter(|c| !c.is_whitespace()).collect();
+    if !luhn_valid(&number) {
+        return Err(AppError::BadRequest("Invalid card number".to_string()));
+    }
+
+    let now = Utc::now();
+    let expired = request.exp_month == 0
+        || request.exp_month > 12
+        || (request.exp_year as i32, request.exp_month as u32) < (now.year(), now.month());
+    if expired {
+        return Err(AppError::BadRequest("Card is expired or has an invalid expiry date".to_string()));
+    }
+
+    let details = CardDetails {
+        number: number.clone(),
+        holder_name: request.holder_name.clone(),
+        exp_month: request.exp_month,
+        exp_year: request.exp_year,
+    };
+    let (encrypted_details, nonce) = vault.seal(&details)?;
+
+    let method = sqlx::query_as::<_, PaymentMethod>(
+        r#"
+        INSERT INTO payment_methods (id, user_id, token, method_type, brand, last4, exp_month, exp_year, holder_name, encrypted_details, nonce, is_default, bin, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11,
+                NOT EXISTS (SELECT 1 FROM payment_methods WHERE user_id = $2 AND deleted_at IS NULL),
+                $12, $13, $14)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(Vault::generate_token("pm"))
+    .bind(request.method_type)
+    .bind(card_brand(&number))
+    .bind(&number[number.len() - 4..])
+    .bind(request.exp_month as i16)
+    .bind(request.exp_year as i16)
+    .bind(request.holder_name)
+    .bind(encrypted_details)
+    .bind(nonce)
+    .bind(&number[..6])
+    .bind(now)
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(method)
+}
+
+/// Resolves a token owned by `user_id` back to its stored method and decrypted card.
+pub async fn resolve_token(
+    pool: &PgPool,
+    vault: &Vault,
+    user_id: Uuid,
+    token: &str,
+) -> AppResult<(PaymentMethod, CardDetails)> {
+    let method = sqlx::query_as::<_, PaymentMethod>(
+        "SELECT * FROM payment_methods WHERE token = $1 AND user_id = $2 AND deleted_at IS NULL"
+    )
+    .bind(token)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("Unknown payment method token".to_string()))?;
+
+    let card: CardDetails = vault.open(&method.encrypted_details, &method.nonce)?;
+
+    Ok((method, card))
+}
+
+/// Active (not deleted) method owned by `user_id`; `None` for `id` picks the user's default.
+pub async fn find_for_user(pool: &PgPool, user_id: Uuid, id: Option<Uuid>) -> AppResult<PaymentMethod> {
+    let method = sqlx::query_as::<_, PaymentMethod>(
+        r#"
+        SELECT * FROM payment_methods
+        WHERE user_id = $1 AND deleted_at IS NULL AND (id = $2 OR ($2 IS NULL AND is_default))
+        "#,
+    )
+    .bind(user_id)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("Payment method not found".to_string()))?;
+
+    Ok(method)
+}
+
+pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<PaymentMethod>> {
+    let methods = sqlx::query_as::<_, PaymentMethod>(
+        "SELECT * FROM payment_methods WHERE user_id = $1 AND deleted_at IS NULL ORDER BY is_default DESC, created_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(methods)
+}
+
+pub async fn set_default(pool: &PgPool, user_id: Uuid, id: Uuid) -> AppResult<PaymentMethod> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "UPDATE payment_methods SET is_default = FALSE, updated_at = $2 WHERE user_id = $1 AND is_default"
+    )
+    .bind(user_id)
+    .bind(Utc::now())
+    .execute(&mut *tx)
+    .await?;
+
+    let method = sqlx::query_as::<_, PaymentMethod>(
+        r#"
+        UPDATE payment_methods SET is_default = TRUE, updated_at = $3
+        WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(Utc::now())
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Payment method not found".to_string()))?;
+
+    tx.commit().await?;
+
+    Ok(method)
+}
+
+/// Soft delete: past payments keep referencing the method, the encrypted card data is wiped.
+pub async fn delete(pool: &PgPool, user_id: Uuid, id: Uuid) -> AppResult<()> {
+    let result = sqlx::query(
+        r#"
+        UPDATE payment_methods
+        SET deleted_at = $3, updated_at = $3, is_default = FALSE, encrypted_details = '', nonce = ''
+        WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(Utc::now())
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Payment method not found".to_string()));
+    }
+
+    Ok(())
+}
+
+fn luhn_valid(number: &str) -> bool {
+    if number.len() < 12 || number.len() > 19 || !number.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+
+    let sum: u32 = number
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                d
+            }
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
+
+fn card_brand(number: &str) -> &'static str {
+    let prefix2: u32 = number[..2].parse().unwrap_or(0);
+    let prefix4: u32 = number[..4].parse().unwrap_or(0);
+
+    if number.starts_with("9792") {
+        "TROY"
+    } else if number.starts_with('4') {
+        "VISA"
+    } else if (51..=55).contains(&prefix2) || (2221..=2720).contains(&prefix4) {
+        "MASTERCARD"
+    } else if prefix2 == 34 || prefix2 == 37 {
+        "AMEX"
+    } else {
+        "UNKNOWN"
+    }
+}
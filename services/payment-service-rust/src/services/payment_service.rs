@@ -1,59 +1,936 @@
-use crate::{dto::CreatePaymentRequest, models::{Payment, PaymentStatus}};
-use anyhow::Result;
-use sqlx::PgPool;
+use crate::{
+    dto::{
+        CreatePaymentRequest, InstallmentInfo, PaymentCountQuery, PaymentQuoteRequest, PaymentQuoteResponse, SplitRequest,
+        TaxInfo, ThreeDsCallbackRequest,
+    },
+    error::{self, AppError, AppResult, ErrorCode, FieldError, FieldErrorCode},
+    models::{
+        Payment, PaymentStatus, TransferInstructions, WalletEntryType, METHOD_BANK_TRANSFER, METHOD_CASH_ON_DELIVERY,
+        METHOD_CRYPTO, METHOD_VOUCHER, METHOD_WALLET,
+    },
+    services::{
+        bank_transfer, crypto_payment,
+        gateway::{self, GatewayOutcome},
+        gateway_credentials,
+        shadow_gateway::{self, ShadowCharge},
+        installments::{self, InstallmentQuote},
+        fees, merchants, payment_method_service, promotions, splits, surcharges::{self, SurchargeQuote}, tax, vouchers, wallets,
+        AppState,
+    },
+    telemetry,
+};
+use rust_decimal::Decimal;
+use sqlx::{types::Json, PgConnection, PgExecutor, PgPool};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use uuid::Uuid;
-use chrono::Utc;
-use rust_decimal::Decimal; // Eklendi
-use rust_decimal::prelude::FromPrimitive; // Eklendi
+use chrono::{DateTime, Duration, Utc};
 
-pub async fn create_payment(
-    pool: &PgPool,
-    request: CreatePaymentRequest,
-) -> Result<Payment> {
-    let transaction_id = Uuid::new_v4().to_string();
-    let payment_status = PaymentStatus::Completed;
+/// Column values for a new `payments` row; method-specific flows fill in their extras.
+#[derive(Clone)]
+pub struct NewPayment {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub order_id: Uuid,
+    pub user_id: Uuid,
+    pub amount: Decimal,
+    pub currency: String,
+    pub payment_method: String,
+    pub payment_status: PaymentStatus,
+    pub transaction_id: Option<String>,
+    pub payment_method_id: Option<Uuid>,
+    pub three_ds_redirect_url: Option<String>,
+    pub transfer_reference: Option<String>,
+    pub transfer_instructions: Option<TransferInstructions>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub installments: InstallmentQuote,
+    pub subscription_id: Option<Uuid>,
+    pub wallet_amount: Decimal,
+    pub wallet_topup: bool,
+    pub voucher_id: Option<Uuid>,
+    pub voucher_amount: Decimal,
+    pub promotion_id: Option<Uuid>,
+    pub discount_amount: Decimal,
+    pub tax_jurisdiction: Option<String>,
+    pub tax_rate: Decimal,
+    pub method_surcharge: SurchargeQuote,
+    pub escrow_release_at: Option<DateTime<Utc>>,
+    /// Placeholder of an async payment, replaced when the worker processes it
+    pub queued: bool,
+}
+
+impl NewPayment {
+    pub fn new(id: Uuid, request: &CreatePaymentRequest, payment_status: PaymentStatus) -> Self {
+        Self {
+            id,
+            merchant_id: request.merchant_id,
+            order_id: request.order_id,
+            user_id: request.user_id,
+            amount: request.amount,
+            currency: request.currency.clone(),
+            payment_method: request.payment_method.clone(),
+            payment_status,
+            transaction_id: None,
+            payment_method_id: None,
+            three_ds_redirect_url: None,
+            transfer_reference: None,
+            transfer_instructions: None,
+            expires_at: None,
+            installments: InstallmentQuote::single(),
+            subscription_id: request.subscription_id,
+            wallet_amount: Decimal::ZERO,
+            wallet_topup: request.wallet_topup,
+            voucher_id: None,
+            voucher_amount: Decimal::ZERO,
+            promotion_id: request.promotion_id,
+            discount_amount: request.discount_amount,
+            tax_jurisdiction: request.tax_jurisdiction.clone(),
+            tax_rate: request.tax_rate,
+            method_surcharge: SurchargeQuote { percent: Decimal::ZERO, amount: Decimal::ZERO },
+            escrow_release_at: None,
+            queued: false,
+        }
+    }
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "payments.insert"))]
+pub async fn insert_payment<'e, E: PgExecutor<'e>>(executor: E, new: NewPayment) -> AppResult<Payment> {
+    let now = Utc::now();
+    let (taxable_base, tax_amount) = tax::split_inclusive(new.amount, new.tax_rate);
 
     let payment = sqlx::query_as::<_, Payment>(
         r#"
-        INSERT INTO payments (id, order_id, user_id, amount, currency, payment_method, payment_status, transaction_id, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        INSERT INTO payments (id, order_id, user_id, amount, currency, payment_method, payment_status, transaction_id, payment_method_id,
+                              three_ds_redirect_url, transfer_reference, transfer_instructions, expires_at,
+                              installment_count, installment_fee_percent, installment_surcharge, subscription_id, wallet_amount,
+                              wallet_topup, voucher_id, voucher_amount, promotion_id, discount_amount, gross_amount,
+                              tax_jurisdiction, tax_rate, taxable_base, tax_amount,
+                              method_surcharge_percent, method_surcharge, escrow, escrow_release_at, created_at, updated_at, merchant_id,
+                              queued)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23,
+                $24, $25, $26, $27, $28, $29, $30, $31, $32,
+                -- A placeholder's created_at, so the row lands on the same (id, created_at) key
+                COALESCE((SELECT created_at FROM payments WHERE id = $1 AND queued), $33),
+                $34, $35, $36)
+        -- Processing an async payment replaces its placeholder, keeping created_at
+        ON CONFLICT (id, created_at) DO UPDATE SET
+            (amount, payment_method, payment_status, transaction_id, payment_method_id, three_ds_redirect_url, transfer_reference,
+             transfer_instructions, expires_at, installment_count, installment_fee_percent, installment_surcharge,
+             subscription_id, wallet_amount, wallet_topup, voucher_id, voucher_amount, promotion_id, discount_amount,
+             gross_amount, tax_jurisdiction, tax_rate, taxable_base, tax_amount, method_surcharge_percent, method_surcharge,
+             escrow, escrow_release_at, updated_at, queued)
+          = (EXCLUDED.amount, EXCLUDED.payment_method, EXCLUDED.payment_status, EXCLUDED.transaction_id, EXCLUDED.payment_method_id,
+             EXCLUDED.three_ds_redirect_url, EXCLUDED.transfer_reference, EXCLUDED.transfer_instructions,
+             EXCLUDED.expires_at, EXCLUDED.installment_count, EXCLUDED.installment_fee_percent,
+             EXCLUDED.installment_surcharge, EXCLUDED.subscription_id, EXCLUDED.wallet_amount, EXCLUDED.wallet_topup,
+             EXCLUDED.voucher_id, EXCLUDED.voucher_amount, EXCLUDED.promotion_id, EXCLUDED.discount_amount,
+             EXCLUDED.gross_amount, EXCLUDED.tax_jurisdiction, EXCLUDED.tax_rate, EXCLUDED.taxable_base,
+             EXCLUDED.tax_amount, EXCLUDED.method_surcharge_percent, EXCLUDED.method_surcharge, EXCLUDED.escrow,
+             EXCLUDED.escrow_release_at, EXCLUDED.updated_at, FALSE)
+        WHERE payments.queued
         RETURNING *
         "#,
     )
-    .bind(Uuid::new_v4())
-    .bind(request.order_id)
-    .bind(request.user_id)
-    .bind(request.amount) // f64 yerine decimal_amount bağladık
-    .bind(request.currency)
-    .bind(request.payment_method)
-    .bind(payment_status.as_str())
-    .bind(Some(transaction_id))
-    .bind(Utc::now())
-    .bind(Utc::now())
-    .fetch_one(pool)
+    .bind(new.id)
+    .bind(new.order_id)
+    .bind(new.user_id)
+    .bind(new.amount) // f64 yerine decimal_amount bağladık
+    .bind(new.currency)
+    .bind(new.payment_method)
+    .bind(new.payment_status.as_str())
+    .bind(new.transaction_id)
+    .bind(new.payment_method_id)
+    .bind(new.three_ds_redirect_url)
+    .bind(new.transfer_reference)
+    .bind(new.transfer_instructions.map(Json))
+    .bind(new.expires_at)
+    .bind(new.installments.count as i16)
+    .bind(new.installments.fee_percent)
+    .bind(new.installments.surcharge)
+    .bind(new.subscription_id)
+    .bind(new.wallet_amount)
+    .bind(new.wallet_topup)
+    .bind(new.voucher_id)
+    .bind(new.voucher_amount)
+    .bind(new.promotion_id)
+    .bind(new.discount_amount)
+    .bind(new.amount + new.discount_amount)
+    .bind(new.tax_jurisdiction)
+    .bind(new.tax_rate)
+    .bind(taxable_base)
+    .bind(tax_amount)
+    .bind(new.method_surcharge.percent)
+    .bind(new.method_surcharge.amount)
+    .bind(new.escrow_release_at.is_some())
+    .bind(new.escrow_release_at)
+    .bind(now)
+    .bind(now)
+    .bind(new.merchant_id)
+    .bind(new.queued)
+    .fetch_one(executor)
     .await?;
 
     Ok(payment)
 }
 
-pub async fn get_payment(pool: &PgPool, id: Uuid) -> Result<Payment> {
+/// Attempts at the payment transaction, which is run again when Postgres aborts it as a deadlock or serialization failure.
+const WRITE_ATTEMPTS: u32 = 3;
+
+/// Period queries without a start only look this far back, so they stay within recent partitions.
+pub const DEFAULT_LOOKBACK_DAYS: i64 = 90;
+
+pub fn default_from(from: Option<DateTime<Utc>>) -> DateTime<Utc> {
+    from.unwrap_or_else(|| Utc::now() - Duration::days(DEFAULT_LOOKBACK_DAYS))
+}
+
+pub async fn create_payment(state: &AppState, request: CreatePaymentRequest) -> AppResult<Payment> {
+    create_payment_with_id(state, Uuid::new_v4(), request).await
+}
+
+/// Async payments are processed under the id already handed out with the 202.
+#[tracing::instrument(
+    name = "payment.create",
+    skip_all,
+    fields(
+        payment.id = %payment_id,
+        order.id = %request.order_id,
+        payment.amount = %request.amount,
+        payment.currency = %request.currency,
+        payment.method = %request.payment_method,
+    )
+)]
+pub async fn create_payment_with_id(
+    state: &AppState,
+    payment_id: Uuid,
+    mut request: CreatePaymentRequest,
+) -> AppResult<Payment> {
+    let pool = &state.db_pool;
+
+    let splits = request.splits.take().unwrap_or_default();
+    validate(&request, &splits)?;
+    if !splits.is_empty() {
+        let sellers: Vec<Uuid> = splits.iter().map(|s| s.merchant_id).collect();
+        merchants::ensure_active(pool, &sellers).await?;
+    }
+
+    // Everything below works on the discounted amount; the code is used up along with storing the payment
+    if let Some(code) = request.promo_code.as_deref() {
+        let (promotion, discount) = promotions::evaluate(pool, code, request.amount, &request.currency).await?;
+        request.amount -= discount;
+        request.promotion_id = Some(promotion.id);
+        request.discount_amount = discount;
+    }
+
+    // Loading stored value isn't a sale, VAT is due when the balance is spent
+    if !request.wallet_topup {
+        let (tax_jurisdiction, tax_rate) = tax::rate_for(&state.config, request.country.as_deref(), &request.currency);
+        request.tax_jurisdiction = tax_jurisdiction;
+        request.tax_rate = tax_rate;
+    }
+
+    let tender = prepare_tender(state, payment_id, &request).await?;
+    let mut attempt = 1;
+    let payment = loop {
+        match create_discounted(state, payment_id, &request, &splits, &tender).await {
+            Err(e) if e.is_serialization_failure() && attempt < WRITE_ATTEMPTS => {
+                tracing::warn!(attempt, error = %e, "payment write aborted by the database, retrying");
+                attempt += 1;
+            }
+            result => break result,
+        }
+    };
+
+    match &payment {
+        Ok(payment) => telemetry::record_payment(payment),
+        Err(_) => {
+            if let Some(card) = &tender.card {
+                void_authorization(state, request.merchant_id, card).await;
+            }
+        }
+    }
+
+    payment
+}
+
+/// Checks everything that doesn't need the database, reporting every invalid field.
+fn validate(request: &CreatePaymentRequest, splits: &[SplitRequest]) -> AppResult<()> {
+    let mut errors = Vec::new();
+
+    if request.amount <= Decimal::ZERO {
+        errors.push(FieldError::new("amount", FieldErrorCode::OutOfRange, "amount must be positive"));
+    }
+    if request.currency.len() != 3 || !request.currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        errors.push(FieldError::new(
+            "currency",
+            FieldErrorCode::Invalid,
+            "currency must be a three-letter ISO 4217 code",
+        ));
+    }
+    if let Some(country) = &request.country {
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            errors.push(FieldError::new(
+                "country",
+                FieldErrorCode::Invalid,
+                "country must be a two-letter ISO 3166 code",
+            ));
+        }
+    }
+
+    let is_offline = [METHOD_BANK_TRANSFER, METHOD_CASH_ON_DELIVERY, METHOD_CRYPTO]
+        .contains(&request.payment_method.as_str());
+    let pays_with_wallet = request.payment_method == METHOD_WALLET;
+    let installment_count = request.installments.unwrap_or(1);
+    if (is_offline || pays_with_wallet) && installment_count > 1 {
+        errors.push(FieldError::new(
+            "installments",
+            FieldErrorCode::NotAllowed,
+            "Installments are only available for card payments",
+        ));
+    } else if installment_count > installments::MAX_INSTALLMENTS {
+        errors.push(FieldError::new(
+            "installments",
+            FieldErrorCode::OutOfRange,
+            format!("At most {} installments are allowed", installments::MAX_INSTALLMENTS),
+        ));
+    }
+
+    if (is_offline || request.wallet_topup) && request.escrow {
+        errors.push(FieldError::new(
+            "escrow",
+            FieldErrorCode::NotAllowed,
+            "Escrow is only available for card, wallet and voucher payments",
+        ));
+    }
+
+    // Only the customer's own token opens their wallet and saved cards, never the user_id in the body
+    let uses_wallet = pays_with_wallet || request.wallet_amount.is_some_and(|amount| !amount.is_zero());
+    if uses_wallet && !is_offline && request.payer_id != Some(request.user_id) {
+        errors.push(FieldError::new(
+            if pays_with_wallet { "payment_method" } else { "wallet_amount" },
+            FieldErrorCode::NotAllowed,
+            "Paying from the wallet needs the customer's own access token",
+        ));
+    }
+    if request.payment_method_token.is_some() && request.payer_id != Some(request.user_id) {
+        errors.push(FieldError::new(
+            "payment_method_token",
+            FieldErrorCode::NotAllowed,
+            "Saved payment methods need the customer's own access token",
+        ));
+    }
+
+    if is_offline {
+        let combined = [("wallet_amount", request.wallet_amount.is_some()), ("voucher_code", request.voucher_code.is_some())];
+        for (field, _) in combined.into_iter().filter(|(_, set)| *set) {
+            errors.push(FieldError::new(
+                field,
+                FieldErrorCode::NotAllowed,
+                "Wallet balance and vouchers can only be combined with card payments",
+            ));
+        }
+    }
+
+    errors.extend(splits::validate(splits, request.amount));
+    error::ensure_valid(errors)
+}
+
+/// Stores the payment with everything that goes with it (wallet and voucher debits, top-up credit, splits, fees,
+/// the promo code's use) in one transaction, so it is written as a whole or not at all.
+async fn create_discounted(
+    state: &AppState,
+    payment_id: Uuid,
+    request: &CreatePaymentRequest,
+    splits: &[SplitRequest],
+    tender: &Tender,
+) -> AppResult<Payment> {
+    let mut tx = state.db_pool.begin().await?;
+    claim_order(&mut tx, request.merchant_id, request.order_id, payment_id).await?;
+
+    let payment = if request.payment_method == METHOD_BANK_TRANSFER {
+        // Offline methods never reach the card gateway
+        let new = bank_transfer::prepare(&state.config, payment_id, request);
+        insert_payment(&mut *tx, new).await?
+    } else if request.payment_method == METHOD_CASH_ON_DELIVERY {
+        // Cash is collected by the courier at the door
+        let mut new = NewPayment::new(payment_id, request, PaymentStatus::AwaitingCollection);
+        new.method_surcharge = surcharges::quote(&state.config, METHOD_CASH_ON_DELIVERY, request.amount);
+        insert_payment(&mut *tx, new).await?
+    } else if request.payment_method == METHOD_CRYPTO {
+        crypto_payment::create(state, &mut tx, payment_id, request).await?
+    } else {
+        create_tendered(state, &mut tx, payment_id, request, tender).await?
+    };
+
+    splits::record(&mut tx, &state.config, &payment, splits).await?;
+    // A payment that didn't go through doesn't use up the promo code
+    if payment.payment_status != PaymentStatus::Failed.as_str() {
+        fees::record(&mut tx, &payment).await?;
+        if let Some(promotion_id) = request.promotion_id {
+            promotions::reserve(&mut *tx, promotion_id).await?;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(payment)
+}
+
+/// What a voucher, wallet and card payment takes from each, worked out before its transaction.
+#[derive(Default)]
+struct Tender {
+    voucher_amount: Decimal,
+    wallet_amount: Decimal,
+    /// The card's authorization for the rest, `None` when the voucher and wallet cover it all
+    card: Option<NewPayment>,
+}
+
+/// Splits the amount between voucher, wallet and card and authorizes the card, all before the payment's transaction:
+/// no row is locked while the gateway answers, and retried writes use this one authorization. Offline methods
+/// take nothing from any of them.
+async fn prepare_tender(state: &AppState, payment_id: Uuid, request: &CreatePaymentRequest) -> AppResult<Tender> {
+    let pool = &state.db_pool;
+    let is_offline = [METHOD_BANK_TRANSFER, METHOD_CASH_ON_DELIVERY, METHOD_CRYPTO]
+        .contains(&request.payment_method.as_str());
+    if is_offline {
+        return Ok(Tender::default());
+    }
+    let pays_with_wallet = request.payment_method == METHOD_WALLET;
+
+    // Tender order: voucher first, then wallet, the card pays whatever is left
+    let voucher_amount = match request.voucher_code.as_deref() {
+        Some(code) => vouchers::find_redeemable(pool, code, &request.currency).await?.balance.min(request.amount),
+        None => Decimal::ZERO,
+    };
+    let after_voucher = request.amount - voucher_amount;
+
+    let wallet_amount = match request.wallet_amount {
+        Some(amount) => amount,
+        None if pays_with_wallet => after_voucher,
+        None => Decimal::ZERO,
+    };
+    if wallet_amount < Decimal::ZERO || wallet_amount > after_voucher {
+        return Err(AppError::invalid_field(
+            "wallet_amount",
+            FieldErrorCode::OutOfRange,
+            format!("wallet_amount must be between 0 and {}", after_voucher),
+        ));
+    }
+    if pays_with_wallet && wallet_amount != after_voucher {
+        return Err(AppError::invalid_field(
+            "wallet_amount",
+            FieldErrorCode::NotAllowed,
+            "WALLET payments must be covered by the wallet in full",
+        ));
+    }
+    // Checked again under lock, this only spares the card a hold for a payment that can't go through
+    if !wallet_amount.is_zero() {
+        let wallets = wallets::list_for_user(pool, payer_id(request)?).await?;
+        if !wallets.iter().any(|w| w.currency == request.currency && w.balance >= wallet_amount) {
+            return Err(AppError::BadRequest("Insufficient wallet balance".to_string()));
+        }
+    }
+
+    let card_amount = after_voucher - wallet_amount;
+    let card = if card_amount.is_zero() {
+        None
+    } else {
+        Some(charge_card(state, payment_id, request, card_amount).await?)
+    };
+
+    Ok(Tender { voucher_amount, wallet_amount, card })
+}
+
+/// The only user whose wallet a payment may debit, see `CreatePaymentRequest::payer_id`.
+fn payer_id(request: &CreatePaymentRequest) -> AppResult<Uuid> {
+    request
+        .payer_id
+        .ok_or_else(|| AppError::Forbidden("Paying from the wallet needs the customer's own access token".to_string()))
+}
+
+/// Voucher, wallet and card payments as split by `prepare_tender`. Voucher and wallet rows stay locked until the
+/// payment is stored, so concurrent payments can't overspend them.
+async fn create_tendered(
+    state: &AppState,
+    conn: &mut PgConnection,
+    payment_id: Uuid,
+    request: &CreatePaymentRequest,
+    tender: &Tender,
+) -> AppResult<Payment> {
+    let voucher = match request.voucher_code.as_deref() {
+        Some(code) => Some(vouchers::lock_for_redemption(conn, code, &request.currency).await?),
+        None => None,
+    };
+    // Spent by another payment since the split was made, the card was authorized for too little
+    if voucher.as_ref().map_or(Decimal::ZERO, |v| v.balance.min(request.amount)) != tender.voucher_amount {
+        return Err(AppError::Conflict("Voucher balance changed during the payment, try again".to_string()));
+    }
+
+    let wallet = if tender.wallet_amount.is_zero() {
+        None
+    } else {
+        Some(wallets::lock_for_debit(conn, payer_id(request)?, &request.currency, tender.wallet_amount).await?)
+    };
+
+    let mut new = match &tender.card {
+        Some(card) => card.clone(),
+        None => {
+            let mut new = NewPayment::new(payment_id, request, PaymentStatus::Completed);
+            new.payment_method = if tender.wallet_amount.is_zero() { METHOD_VOUCHER } else { METHOD_WALLET }.to_string();
+            new
+        }
+    };
+    if request.escrow {
+        new.escrow_release_at = Some(Utc::now() + Duration::days(state.config.escrow_auto_release_days));
+        if matches!(new.payment_status, PaymentStatus::Completed) {
+            new.payment_status = PaymentStatus::Escrowed;
+        }
+    }
+    new.wallet_amount = tender.wallet_amount;
+    new.voucher_id = voucher.as_ref().map(|v| v.id);
+    new.voucher_amount = tender.voucher_amount;
+
+    let payment = insert_payment(&mut *conn, new).await?;
+    if payment.payment_status != PaymentStatus::Failed.as_str() {
+        if let Some(wallet) = wallet {
+            wallets::debit(conn, &wallet, tender.wallet_amount, WalletEntryType::Payment, Some(payment.id)).await?;
+        }
+        if let Some(voucher) = voucher {
+            vouchers::redeem(conn, voucher.id, payment.id, tender.voucher_amount).await?;
+        }
+    }
+    if payment.wallet_topup && payment.payment_status == PaymentStatus::Completed.as_str() {
+        credit_top_up(conn, &payment).await?;
+    }
+
+    Ok(payment)
+}
+
+/// Releases the hold of a card authorization whose payment was never stored. A failure is only logged, the caller
+/// gets the payment's own error.
+async fn void_authorization(state: &AppState, merchant_id: Uuid, card: &NewPayment) {
+    let Some(transaction_id) = card.transaction_id.as_deref() else {
+        return;
+    };
+    if matches!(card.payment_status, PaymentStatus::Failed) {
+        return;
+    }
+
+    let voided = match gateway_credentials::resolve(state, merchant_id).await {
+        Ok(credentials) => gateway::void(&credentials, transaction_id).await,
+        Err(e) => Err(e.into()),
+    };
+    match voided {
+        Ok(()) => tracing::info!(transaction_id, "voided the authorization of a payment that wasn't stored"),
+        Err(e) => tracing::error!(error = %e, transaction_id, "could not void the authorization of an unsaved payment"),
+    }
+}
+
+/// Authorizes `card_amount` (the part not covered by the wallet) through the card gateway.
+async fn charge_card(
+    state: &AppState,
+    payment_id: Uuid,
+    request: &CreatePaymentRequest,
+    card_amount: Decimal,
+) -> AppResult<NewPayment> {
+    let pool = &state.db_pool;
+    let installment_count = request.installments.unwrap_or(1);
+
+    // Tokenized methods: card data is resolved from the vault, never sent by the client, and only among the
+    // authenticated customer's own cards
+    let (method, card) = match (request.payment_method_token.as_deref(), request.payer_id) {
+        (Some(token), Some(payer_id)) => {
+            let (method, card) = payment_method_service::resolve_token(pool, &state.vault, payer_id, token).await?;
+            (Some(method), Some(card))
+        }
+        (Some(_), None) => {
+            return Err(AppError::Forbidden("Saved payment methods need the customer's own access token".to_string()))
+        }
+        (None, _) => (None, None),
+    };
+
+    let method_type = method.as_ref().map(|m| m.method_type.as_str()).unwrap_or(&request.payment_method);
+    let bin = method.as_ref().and_then(|m| m.bin.as_deref());
+    let quote = installments::quote(pool, method_type, bin, installment_count, card_amount).await?;
+    let method_surcharge = surcharges::quote(&state.config, method_type, card_amount);
+
+    let credentials = gateway_credentials::resolve(state, request.merchant_id).await?;
+    tracing::Span::current().set_attribute("payment.gateway", credentials.account_id.clone());
+    let charged_amount = card_amount + quote.surcharge + method_surcharge.amount;
+    let outcome = gateway::authorize(
+        &state.config,
+        &credentials,
+        payment_id,
+        card.as_ref(),
+        charged_amount,
+        &request.currency,
+        request.return_url.as_deref(),
+        request.merchant_initiated,
+    )
+    .await?;
+    shadow_gateway::mirror(
+        state.config.clone(),
+        ShadowCharge {
+            payment_id,
+            card,
+            amount: charged_amount,
+            currency: request.currency.clone(),
+            merchant_initiated: request.merchant_initiated,
+        },
+        &outcome,
+    );
+
+    let mut new = NewPayment::new(payment_id, request, PaymentStatus::Completed);
+    new.installments = quote;
+    new.method_surcharge = method_surcharge;
+    if let Some(method) = method {
+        new.payment_method = method.method_type;
+        new.payment_method_id = Some(method.id);
+    }
+
+    match outcome {
+        GatewayOutcome::Approved { transaction_id } => {
+            new.transaction_id = Some(transaction_id);
+        }
+        GatewayOutcome::Declined { transaction_id } => {
+            new.payment_status = PaymentStatus::Failed;
+            new.transaction_id = Some(transaction_id);
+        }
+        GatewayOutcome::RequiresAction { transaction_id, redirect_url } => {
+            new.payment_status = PaymentStatus::RequiresAction;
+            new.transaction_id = Some(transaction_id);
+            new.three_ds_redirect_url = Some(redirect_url);
+        }
+    }
+
+    Ok(new)
+}
+
+/// Lookups by id, order or invoice number answer PAYMENT_NOT_FOUND rather than the generic NOT_FOUND.
+pub(crate) fn payment_not_found(e: sqlx::Error) -> AppError {
+    match e {
+        sqlx::Error::RowNotFound => AppError::NotFound("Payment not found".to_string()).with_code(ErrorCode::PaymentNotFound),
+        other => other.into(),
+    }
+}
+
+/// 412 when the payment moved on since the caller read `expected` version.
+pub fn ensure_version(payment: &Payment, expected: i32) -> AppResult<()> {
+    if payment.version != expected {
+        return Err(AppError::PreconditionFailed(format!(
+            "Payment was changed in the meantime (now version {}), reload it and retry",
+            payment.version
+        )));
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "payments.get"))]
+pub async fn get_payment(pool: &PgPool, id: Uuid) -> AppResult<Payment> {
     let payment = sqlx::query_as::<_, Payment>(
         "SELECT * FROM payments WHERE id = $1"
     )
     .bind(id)
     .fetch_one(pool)
-    .await?;
+    .await
+    .map_err(payment_not_found)?;
+
+    Ok(payment)
+}
+
+/// Tenant-scoped lookup for merchant-facing endpoints; other tenants' payments are reported as not found.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "payments.get_for_merchant"))]
+pub async fn get_for_merchant(pool: &PgPool, merchant_id: Uuid, id: Uuid) -> AppResult<Payment> {
+    let payment = sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE id = $1 AND merchant_id = $2"
+    )
+    .bind(id)
+    .bind(merchant_id)
+    .fetch_one(pool)
+    .await
+    .map_err(payment_not_found)?;
 
     Ok(payment)
 }
 
-pub async fn get_payment_by_order(pool: &PgPool, order_id: Uuid) -> Result<Payment> {
+/// `user_id` limits it to that customer's payments.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "payments.exists"))]
+pub async fn exists(pool: &PgPool, merchant_id: Uuid, user_id: Option<Uuid>, id: Uuid) -> AppResult<bool> {
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM payments WHERE id = $1 AND merchant_id = $2 AND ($3::UUID IS NULL OR user_id = $3))"
+    )
+    .bind(id)
+    .bind(merchant_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(exists)
+}
+
+/// `user_id` limits it to that customer's payments.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "payments.count"))]
+pub async fn count(pool: &PgPool, merchant_id: Uuid, user_id: Option<Uuid>, query: &PaymentCountQuery) -> AppResult<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT COUNT(*) FROM payments
+        WHERE merchant_id = $1
+          AND ($2::VARCHAR IS NULL OR payment_status = $2)
+          AND created_at >= $3
+          AND ($4::TIMESTAMPTZ IS NULL OR created_at < $4)
+          AND ($5::UUID IS NULL OR user_id = $5)
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(query.status.as_ref().map(|s| s.to_uppercase()))
+    .bind(default_from(query.from))
+    .bind(query.to)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+/// Payments of the merchant among `ids`, in no particular order.
+#[tracing::instrument(
+    name = "db.query",
+    skip_all,
+    fields(db.system = "postgresql", db.statement = "payments.get_many", db.rows_affected = tracing::field::Empty)
+)]
+pub async fn get_many(pool: &PgPool, merchant_id: Uuid, ids: &[Uuid]) -> AppResult<Vec<Payment>> {
+    let payments = sqlx::query_as::<_, Payment>("SELECT * FROM payments WHERE merchant_id = $1 AND id = ANY($2)")
+        .bind(merchant_id)
+        .bind(ids)
+        .fetch_all(pool)
+        .await?;
+    tracing::Span::current().record("db.rows_affected", payments.len());
+
+    Ok(payments)
+}
+
+/// Latest payment of each of `order_ids` (a failed attempt may have been retried).
+#[tracing::instrument(
+    name = "db.query",
+    skip_all,
+    fields(db.system = "postgresql", db.statement = "payments.get_many_by_order", db.rows_affected = tracing::field::Empty)
+)]
+pub async fn get_many_by_order(pool: &PgPool, merchant_id: Uuid, order_ids: &[Uuid]) -> AppResult<Vec<Payment>> {
+    let payments = sqlx::query_as::<_, Payment>(
+        r#"
+        SELECT DISTINCT ON (order_id) * FROM payments
+        WHERE merchant_id = $1 AND order_id = ANY($2)
+        ORDER BY order_id, created_at DESC
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(order_ids)
+    .fetch_all(pool)
+    .await?;
+    tracing::Span::current().record("db.rows_affected", payments.len());
+
+    Ok(payments)
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "payments.get_by_order"))]
+pub async fn get_payment_by_order(pool: &PgPool, merchant_id: Uuid, order_id: Uuid) -> AppResult<Payment> {
     let payment = sqlx::query_as::<_, Payment>(
-        "SELECT * FROM payments WHERE order_id = $1"
+        "SELECT * FROM payments WHERE order_id = $1 AND merchant_id = $2"
     )
     .bind(order_id)
+    .bind(merchant_id)
     .fetch_one(pool)
+    .await
+    .map_err(payment_not_found)?;
+
+    Ok(payment)
+}
+
+/// Latest payment of the order that didn't fail, i.e. one the order is paid or being paid with.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "payments.find_active_by_order"))]
+pub async fn find_active_by_order(pool: &PgPool, merchant_id: Uuid, order_id: Uuid) -> AppResult<Option<Payment>> {
+    let payment = sqlx::query_as::<_, Payment>(
+        r#"
+        SELECT * FROM payments
+        WHERE merchant_id = $1 AND order_id = $2 AND payment_status <> $3
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(order_id)
+    .bind(PaymentStatus::Failed.as_str())
+    .fetch_optional(pool)
     .await?;
 
     Ok(payment)
-}
\ No newline at end of file
+}
+
+/// Holds the order until the transaction ends and refuses it when it already has a payment other than
+/// `payment_id` that didn't fail, so two concurrent requests for one order can't both be stored. An advisory lock
+/// rather than a unique index, which the partitioned payments table can only have together with created_at.
+pub async fn claim_order(conn: &mut PgConnection, merchant_id: Uuid, order_id: Uuid, payment_id: Uuid) -> AppResult<()> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+        .bind(format!("order:{}:{}", merchant_id, order_id))
+        .execute(&mut *conn)
+        .await?;
+
+    let taken = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM payments
+            WHERE merchant_id = $1 AND order_id = $2 AND payment_status <> $3 AND id <> $4
+        )
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(order_id)
+    .bind(PaymentStatus::Failed.as_str())
+    .bind(payment_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if taken {
+        return Err(duplicate_order());
+    }
+
+    Ok(())
+}
+
+pub fn duplicate_order() -> AppError {
+    AppError::Conflict("Order already has a payment".to_string()).with_code(ErrorCode::DuplicateOrder)
+}
+
+/// Completes or fails a payment waiting on 3-D Secure, based on the ACS result. Only for results whose signature was
+/// verified (`middleware::signature`), a status from anywhere else must never reach this.
+pub async fn complete_three_ds(
+    pool: &PgPool,
+    id: Uuid,
+    callback: ThreeDsCallbackRequest,
+) -> AppResult<Payment> {
+    let trans_status = callback.trans_status.to_uppercase();
+    let payment_status = match trans_status.as_str() {
+        "Y" | "A" => PaymentStatus::Completed,
+        "N" | "U" | "R" => PaymentStatus::Failed,
+        other => return Err(AppError::BadRequest(format!("Unknown 3-D Secure status: {}", other))),
+    };
+
+    let mut tx = pool.begin().await?;
+
+    // Conditional update so concurrent/duplicate callbacks can't both apply; escrow payments are held once authorized
+    let payment = sqlx::query_as::<_, Payment>(
+        r#"
+        UPDATE payments
+        SET payment_status = CASE WHEN escrow AND $2 = $6 THEN $7 ELSE $2 END,
+            three_ds_status = $3, three_ds_redirect_url = NULL, updated_at = $4
+        WHERE id = $1 AND payment_status = $5
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(payment_status.as_str())
+    .bind(&trans_status)
+    .bind(Utc::now())
+    .bind(PaymentStatus::RequiresAction.as_str())
+    .bind(PaymentStatus::Completed.as_str())
+    .bind(PaymentStatus::Escrowed.as_str())
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    match payment {
+        Some(payment) => {
+            if payment.wallet_topup && payment.payment_status == PaymentStatus::Completed.as_str() {
+                credit_top_up(&mut tx, &payment).await?;
+            }
+            // Give back what was held while the customer was on the ACS page
+            if payment.payment_status == PaymentStatus::Failed.as_str() {
+                release_holds(&mut tx, &payment).await?;
+            }
+            tx.commit().await?;
+
+            Ok(payment)
+        }
+        None => {
+            // Distinguish unknown payments from ones that are no longer awaiting 3DS
+            get_payment(pool, id).await?;
+            Err(AppError::Conflict("Payment is not awaiting 3-D Secure authentication".to_string()))
+        }
+    }
+}
+
+/// Gives back what a payment that has now failed took when it was created: the wallet and voucher parts and the
+/// promo code use. Call in the transaction that fails the payment, once per payment.
+pub async fn release_holds(conn: &mut PgConnection, payment: &Payment) -> AppResult<()> {
+    if !payment.wallet_amount.is_zero() {
+        wallets::credit(
+            conn,
+            payment.user_id,
+            &payment.currency,
+            payment.wallet_amount,
+            WalletEntryType::PaymentReversal,
+            Some(payment.id),
+        )
+        .await?;
+    }
+    if let Some(voucher_id) = payment.voucher_id {
+        vouchers::redeem(conn, voucher_id, payment.id, -payment.voucher_amount).await?;
+    }
+    if let Some(promotion_id) = payment.promotion_id {
+        promotions::release(&mut *conn, promotion_id).await?;
+    }
+
+    Ok(())
+}
+
+async fn credit_top_up(conn: &mut PgConnection, payment: &Payment) -> AppResult<()> {
+    wallets::credit(
+        conn,
+        payment.user_id,
+        &payment.currency,
+        payment.amount,
+        WalletEntryType::TopUp,
+        Some(payment.id),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Prices a payment without creating it: promo discount, installment and method surcharges, VAT.
+pub async fn quote(state: &AppState, request: PaymentQuoteRequest) -> AppResult<PaymentQuoteResponse> {
+    let pool = &state.db_pool;
+    let currency = request.currency.to_uppercase();
+    let payment_method = request.payment_method.to_uppercase();
+    if request.amount <= Decimal::ZERO {
+        return Err(AppError::invalid_field("amount", FieldErrorCode::OutOfRange, "amount must be positive"));
+    }
+
+    let discount_amount = match request.promo_code.as_deref() {
+        Some(code) => promotions::evaluate(pool, code, request.amount, &currency).await?.1,
+        None => Decimal::ZERO,
+    };
+    let amount = request.amount - discount_amount;
+
+    let installment_count = request.installments.unwrap_or(1);
+    let installments = installments::quote(pool, &payment_method, None, installment_count, amount).await?;
+    let method_surcharge = surcharges::quote(&state.config, &payment_method, amount);
+    let total_amount = amount + installments.surcharge + method_surcharge.amount;
+
+    let (jurisdiction, rate) = tax::rate_for(&state.config, request.country.as_deref(), &currency);
+    let (taxable_base, tax_amount) = tax::split_inclusive(amount, rate);
+
+    Ok(PaymentQuoteResponse {
+        gross_amount: request.amount,
+        discount_amount,
+        amount,
+        currency,
+        payment_method,
+        method_surcharge_percent: method_surcharge.percent,
+        method_surcharge: method_surcharge.amount,
+        installments: InstallmentInfo::new(
+            installments.count as i16,
+            installments.fee_percent,
+            installments.surcharge,
+            total_amount,
+        ),
+        total_amount,
+        tax: TaxInfo { jurisdiction, rate, taxable_base, tax_amount },
+    })
+}
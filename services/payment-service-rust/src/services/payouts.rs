@@ -0,0 +1,197 @@
+use crate::{
+    dto::{MarkPayoutPaidRequest, PayoutQuery},
+    error::{AppError, AppResult},
+    models::{PaymentStatus, Payout, PayoutStatus},
+};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Batches every settled split that isn't in a payout yet into one PENDING payout per merchant and currency, less
+/// the refund adjustments of paid-out splits. Refunds of splits paid out earlier can make a payout negative, which
+/// finance nets against the merchant's next one.
+pub async fn generate(pool: &PgPool) -> AppResult<Vec<Payout>> {
+    let mut tx = pool.begin().await?;
+
+    let groups = sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+        SELECT DISTINCT s.merchant_id, s.currency
+        FROM payment_splits s
+        JOIN payments p ON p.id = s.payment_id
+        WHERE s.payout_id IS NULL AND p.payment_status = $1
+        UNION
+        SELECT a.merchant_id, a.currency
+        FROM split_adjustments a
+        JOIN payment_splits s ON s.id = a.split_id
+        WHERE a.payout_id IS NULL AND s.payout_id IS NOT NULL
+        "#,
+    )
+    .bind(PaymentStatus::Completed.as_str())
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut payouts = Vec::with_capacity(groups.len());
+    for (merchant_id, currency) in groups {
+        let now = Utc::now();
+        let payout_id = Uuid::new_v4();
+
+        sqlx::query(
+            r#"
+            INSERT INTO payouts (id, merchant_id, currency, amount, split_count, status, bank_account_reference, created_at, updated_at)
+            VALUES ($1, $2, $3, 0, 0, $4,
+                    (SELECT payout_account_reference FROM merchant_terms WHERE merchant_id = $2), $5, $5)
+            "#,
+        )
+        .bind(payout_id)
+        .bind(merchant_id)
+        .bind(&currency)
+        .bind(PayoutStatus::Pending.as_str())
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        // Claiming the splits and summing what was claimed keeps concurrent runs from double counting
+        let claimed = sqlx::query_scalar::<_, Decimal>(
+            r#"
+            UPDATE payment_splits s SET payout_id = $1
+            FROM payments p
+            WHERE p.id = s.payment_id AND p.payment_status = $4
+              AND s.payout_id IS NULL AND s.merchant_id = $2 AND s.currency = $3
+            RETURNING s.net_amount
+            "#,
+        )
+        .bind(payout_id)
+        .bind(merchant_id)
+        .bind(&currency)
+        .bind(PaymentStatus::Completed.as_str())
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // After the splits, so adjustments of the splits just claimed come along; those of splits still unpaid
+        // (escrowed payments) wait for their split
+        let adjustments = sqlx::query_scalar::<_, Decimal>(
+            r#"
+            UPDATE split_adjustments a SET payout_id = $1
+            FROM payment_splits s
+            WHERE s.id = a.split_id AND s.payout_id IS NOT NULL
+              AND a.payout_id IS NULL AND a.merchant_id = $2 AND a.currency = $3
+            RETURNING a.amount
+            "#,
+        )
+        .bind(payout_id)
+        .bind(merchant_id)
+        .bind(&currency)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let payout = sqlx::query_as::<_, Payout>(
+            "UPDATE payouts SET amount = $2, split_count = $3 WHERE id = $1 RETURNING *"
+        )
+        .bind(payout_id)
+        .bind(claimed.iter().chain(&adjustments).sum::<Decimal>())
+        .bind(claimed.len() as i32)
+        .fetch_one(&mut *tx)
+        .await?;
+        payouts.push(payout);
+    }
+
+    tx.commit().await?;
+
+    Ok(payouts)
+}
+
+pub async fn list(pool: &PgPool, query: &PayoutQuery) -> AppResult<Vec<Payout>> {
+    let payouts = sqlx::query_as::<_, Payout>(
+        r#"
+        SELECT * FROM payouts
+        WHERE ($1::VARCHAR IS NULL OR status = $1) AND ($2::UUID IS NULL OR merchant_id = $2)
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(query.status.as_deref().map(str::to_uppercase))
+    .bind(query.merchant_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(payouts)
+}
+
+pub async fn approve(pool: &PgPool, id: Uuid) -> AppResult<Payout> {
+    let payout = sqlx::query_as::<_, Payout>(
+        r#"
+        UPDATE payouts SET status = $2, approved_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND status = $3
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(PayoutStatus::Approved.as_str())
+    .bind(PayoutStatus::Pending.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    transition_result(pool, id, payout, "Only pending payouts can be approved").await
+}
+
+pub async fn mark_paid(pool: &PgPool, id: Uuid, request: MarkPayoutPaidRequest) -> AppResult<Payout> {
+    let payout = sqlx::query_as::<_, Payout>(
+        r#"
+        UPDATE payouts SET status = $2, bank_transfer_reference = $3, paid_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND status = $4
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(PayoutStatus::Paid.as_str())
+    .bind(request.bank_transfer_reference)
+    .bind(PayoutStatus::Approved.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    transition_result(pool, id, payout, "Only approved payouts can be marked paid").await
+}
+
+/// Approved payouts as CSV, the file finance uploads to the bank.
+pub async fn export_approved(pool: &PgPool) -> AppResult<String> {
+    let payouts = list(
+        pool,
+        &PayoutQuery {
+            status: Some(PayoutStatus::Approved.as_str().to_string()),
+            merchant_id: None,
+        },
+    )
+    .await?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(["payout_id", "merchant_id", "bank_account_reference", "currency", "amount"])
+        .map_err(anyhow::Error::from)?;
+    for payout in &payouts {
+        writer
+            .write_record([
+                payout.id.to_string(),
+                payout.merchant_id.to_string(),
+                payout.bank_account_reference.clone().unwrap_or_default(),
+                payout.currency.clone(),
+                payout.amount.to_string(),
+            ])
+            .map_err(anyhow::Error::from)?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    Ok(String::from_utf8(bytes).map_err(anyhow::Error::from)?)
+}
+
+async fn transition_result(pool: &PgPool, id: Uuid, payout: Option<Payout>, conflict: &str) -> AppResult<Payout> {
+    match payout {
+        Some(payout) => Ok(payout),
+        None => {
+            sqlx::query_as::<_, Payout>("SELECT * FROM payouts WHERE id = $1")
+                .bind(id)
+                .fetch_one(pool)
+                .await?;
+            Err(AppError::Conflict(conflict.to_string()))
+        }
+    }
+}
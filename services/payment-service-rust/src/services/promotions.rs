@@ -0,0 +1,198 @@
+use crate::{
+    dto::CreatePromotionRequest,
+    error::{AppError, AppResult},
+    models::Promotion,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+const DISCOUNT_PERCENT: &str = "PERCENT";
+const DISCOUNT_FIXED: &str = "FIXED";
+
+pub async fn create(pool: &PgPool, request: CreatePromotionRequest) -> AppResult<Promotion> {
+    let discount_type = request.discount_type.to_uppercase();
+    let currency = request.currency.map(|c| c.to_uppercase());
+    match discount_type.as_str() {
+        DISCOUNT_PERCENT if request.discount_value <= Decimal::ZERO || request.discount_value > Decimal::ONE_HUNDRED => {
+            return Err(AppError::BadRequest("Percent discounts must be between 0 and 100".to_string()));
+        }
+        DISCOUNT_FIXED if request.discount_value <= Decimal::ZERO || currency.is_none() => {
+            return Err(AppError::BadRequest("Fixed discounts need a positive value and a currency".to_string()));
+        }
+        DISCOUNT_PERCENT | DISCOUNT_FIXED => {}
+        other => return Err(AppError::BadRequest(format!("Unknown discount type: {}", other))),
+    }
+
+    let code = request.code.trim().to_uppercase();
+    let now = Utc::now();
+
+    let promotion = sqlx::query_as::<_, Promotion>(
+        r#"
+        INSERT INTO promotions (id, code, discount_type, discount_value, currency, min_amount, max_uses,
+                                starts_at, expires_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10)
+        ON CONFLICT (code) DO NOTHING
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(&code)
+    .bind(discount_type)
+    .bind(request.discount_value)
+    .bind(currency)
+    .bind(request.min_amount.unwrap_or(Decimal::ZERO))
+    .bind(request.max_uses)
+    .bind(request.starts_at)
+    .bind(request.expires_at)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+
+    promotion.ok_or_else(|| AppError::Conflict(format!("Promo code {} already exists", code)))
+}
+
+/// Uses up one use of a promotion checked with `evaluate`, in the transaction storing the payment so that the use
+/// goes away with it if the payment isn't stored. Undone with `release` if the payment fails later (3-D Secure,
+/// an expired bank transfer, a failed collection at the door).
+pub async fn reserve<'e, E: PgExecutor<'e>>(executor: E, promotion_id: Uuid) -> AppResult<()> {
+    // Usage limit is enforced by the conditional increment, not the earlier read
+    let reserved = sqlx::query(
+        r#"
+        UPDATE promotions SET used_count = used_count + 1, updated_at = NOW()
+        WHERE id = $1 AND (max_uses IS NULL OR used_count < max_uses)
+        "#,
+    )
+    .bind(promotion_id)
+    .execute(executor)
+    .await?;
+    if reserved.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Promo code usage limit reached".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Checks `code` and computes its discount without using it up (quotes, and payments before `reserve`).
+pub async fn evaluate(pool: &PgPool, code: &str, amount: Decimal, currency: &str) -> AppResult<(Promotion, Decimal)> {
+    let promotion = sqlx::query_as::<_, Promotion>("SELECT * FROM promotions WHERE code = $1")
+        .bind(code.trim().to_uppercase())
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Unknown promo code".to_string()))?;
+
+    let discount = discount(&promotion, amount, currency, Utc::now())?;
+
+    Ok((promotion, discount))
+}
+
+/// Discount of `promotion` on `amount`, or why the code can't be used on it. The usage limit is checked again when
+/// the use is reserved.
+fn discount(promotion: &Promotion, amount: Decimal, currency: &str, now: DateTime<Utc>) -> AppResult<Decimal> {
+    if promotion.starts_at.is_some_and(|t| t > now) || promotion.expires_at.is_some_and(|t| t <= now) {
+        return Err(AppError::BadRequest("Promo code is not active".to_string()));
+    }
+    if promotion.currency.as_deref().is_some_and(|c| c != currency) {
+        return Err(AppError::BadRequest("Promo code is not valid for this currency".to_string()));
+    }
+    if amount < promotion.min_amount {
+        return Err(AppError::BadRequest(format!("Promo code requires a minimum amount of {}", promotion.min_amount)));
+    }
+
+    let discount = if promotion.discount_type == DISCOUNT_PERCENT {
+        (amount * promotion.discount_value / Decimal::ONE_HUNDRED).round_dp(2)
+    } else {
+        promotion.discount_value
+    };
+    if discount >= amount {
+        return Err(AppError::BadRequest("Promo code cannot cover the whole amount".to_string()));
+    }
+    if promotion.max_uses.is_some_and(|max| promotion.used_count >= max) {
+        return Err(AppError::BadRequest("Promo code usage limit reached".to_string()));
+    }
+
+    Ok(discount)
+}
+
+pub async fn release<'e, E: PgExecutor<'e>>(executor: E, promotion_id: Uuid) -> AppResult<()> {
+    sqlx::query("UPDATE promotions SET used_count = GREATEST(used_count - 1, 0), updated_at = NOW() WHERE id = $1")
+        .bind(promotion_id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn promotion(discount_type: &str, discount_value: &str) -> Promotion {
+        let now = Utc::now();
+        Promotion {
+            id: Uuid::new_v4(),
+            code: "SPRING".to_string(),
+            discount_type: discount_type.to_string(),
+            discount_value: dec(discount_value),
+            currency: None,
+            min_amount: Decimal::ZERO,
+            max_uses: None,
+            used_count: 0,
+            starts_at: None,
+            expires_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn percent_discount_is_rounded_to_cents() {
+        let promotion = promotion(DISCOUNT_PERCENT, "15");
+        assert_eq!(discount(&promotion, dec("33.33"), "TRY", Utc::now()).unwrap(), dec("5.00"));
+    }
+
+    #[test]
+    fn fixed_discount_applies_only_in_its_currency() {
+        let mut promotion = promotion(DISCOUNT_FIXED, "10");
+        promotion.currency = Some("TRY".to_string());
+        assert_eq!(discount(&promotion, dec("50"), "TRY", Utc::now()).unwrap(), dec("10"));
+        assert!(discount(&promotion, dec("50"), "USD", Utc::now()).is_err());
+    }
+
+    #[test]
+    fn rejects_codes_outside_their_window() {
+        let now = Utc::now();
+        let mut promotion = promotion(DISCOUNT_PERCENT, "10");
+        promotion.starts_at = Some(now + Duration::hours(1));
+        assert!(discount(&promotion, dec("50"), "TRY", now).is_err());
+        promotion.starts_at = None;
+        promotion.expires_at = Some(now);
+        assert!(discount(&promotion, dec("50"), "TRY", now).is_err());
+    }
+
+    #[test]
+    fn rejects_amounts_below_minimum_or_fully_covered() {
+        let mut promotion = promotion(DISCOUNT_FIXED, "50");
+        assert!(discount(&promotion, dec("50"), "TRY", Utc::now()).is_err());
+        promotion.discount_value = dec("5");
+        promotion.min_amount = dec("20");
+        assert!(discount(&promotion, dec("19.99"), "TRY", Utc::now()).is_err());
+        assert!(discount(&promotion, dec("20"), "TRY", Utc::now()).is_ok());
+    }
+
+    #[test]
+    fn rejects_used_up_codes() {
+        let mut promotion = promotion(DISCOUNT_PERCENT, "10");
+        promotion.max_uses = Some(3);
+        promotion.used_count = 2;
+        assert!(discount(&promotion, dec("50"), "TRY", Utc::now()).is_ok());
+        promotion.used_count = 3;
+        assert!(discount(&promotion, dec("50"), "TRY", Utc::now()).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    error::{AppError, AppResult},
+    models::{Payment, PaymentStatus, METHOD_BANK_TRANSFER, METHOD_CASH_ON_DELIVERY},
+};
+use qrcode::{Color, QrCode};
+
+const MODULE_PIXELS: u32 = 8;
+const QUIET_ZONE_MODULES: u32 = 4;
+
+/// What the QR code encodes. Scanning only prefills the banking app or the store's terminal;
+/// the payment is confirmed through the usual transfer confirmation and courier collection endpoints.
+pub fn payload(payment: &Payment) -> AppResult<String> {
+    if payment.payment_method == METHOD_BANK_TRANSFER && payment.payment_status == PaymentStatus::Pending.as_str() {
+        let instructions = payment
+            .transfer_instructions
+            .as_ref()
+            .ok_or_else(|| AppError::Conflict("Payment has no transfer instructions".to_string()))?;
+        return Ok(format!(
+            "bitirme-pay://transfer?iban={}&name={}&amount={}&currency={}&reference={}",
+            instructions.iban,
+            encode(&instructions.account_holder),
+            instructions.amount,
+            instructions.currency,
+            instructions.reference_code,
+        ));
+    }
+
+    if payment.payment_method == METHOD_CASH_ON_DELIVERY
+        && payment.payment_status == PaymentStatus::AwaitingCollection.as_str()
+    {
+        return Ok(format!(
+            "bitirme-pay://collect?payment={}&amount={}&currency={}",
+            payment.id,
+            payment.amount + payment.method_surcharge,
+            payment.currency,
+        ));
+    }
+
+    Err(AppError::Conflict(
+        "QR codes are only available for pending bank transfers and uncollected cash payments".to_string(),
+    ))
+}
+
+/// Black-on-white grayscale PNG with the standard quiet zone around the code.
+pub fn png(payload: &str) -> anyhow::Result<Vec<u8>> {
+    let code = QrCode::new(payload.as_bytes())?;
+    let modules = code.width() as u32;
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET_ZONE_MODULES) * MODULE_PIXELS;
+
+    let mut pixels = vec![0xFFu8; (size * size) as usize];
+    for y in 0..size {
+        for x in 0..size {
+            let (mx, my) = (x / MODULE_PIXELS, y / MODULE_PIXELS);
+            let inside = (QUIET_ZONE_MODULES..QUIET_ZONE_MODULES + modules).contains(&mx)
+                && (QUIET_ZONE_MODULES..QUIET_ZONE_MODULES + modules).contains(&my);
+            if inside {
+                let module = ((my - QUIET_ZONE_MODULES) * modules + (mx - QUIET_ZONE_MODULES)) as usize;
+                if colors[module] == Color::Dark {
+                    pixels[(y * size + x) as usize] = 0;
+                }
+            }
+        }
+    }
+
+    let mut buffer = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buffer, size, size);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+
+    Ok(buffer)
+}
+
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,225 @@
+use crate::{
+    error::{AppError, AppResult},
+    i18n::Locale,
+    models::{Payment, PaymentStatus, DEFAULT_MERCHANT_ID},
+    services::{merchants, user_client::UserProfile, user_profiles, AppState},
+};
+use chrono::Utc;
+use printpdf::{BuiltinFont, Line, Mm, PdfDocument, Point};
+use rust_decimal::Decimal;
+
+/// `?lang=`, then the Accept-Language header; receipts are Turkish unless asked otherwise.
+pub fn language(lang: Option<&str>, accept_language: Option<&str>) -> Locale {
+    lang.and_then(Locale::parse)
+        .or_else(|| accept_language.and_then(Locale::from_accept_language))
+        .unwrap_or(Locale::Tr)
+}
+
+fn labels(locale: Locale) -> &'static Labels {
+    match locale {
+        Locale::Tr => &TR,
+        Locale::En => &EN,
+    }
+}
+
+struct Labels {
+    title: &'static str,
+    order: &'static str,
+    payment: &'static str,
+    transaction: &'static str,
+    date: &'static str,
+    customer: &'static str,
+    method: &'static str,
+    installments: &'static str,
+    subtotal: &'static str,
+    discount: &'static str,
+    surcharges: &'static str,
+    wallet: &'static str,
+    voucher: &'static str,
+    total: &'static str,
+    taxable_base: &'static str,
+    tax: &'static str,
+    refunded: &'static str,
+    footer: &'static str,
+}
+
+const TR: Labels = Labels {
+    title: "Ödeme Makbuzu",
+    order: "Sipariş No",
+    payment: "Ödeme No",
+    transaction: "İşlem No",
+    date: "Tarih",
+    customer: "Müşteri",
+    method: "Ödeme Yöntemi",
+    installments: "Taksit",
+    subtotal: "Ara Toplam",
+    discount: "İndirim",
+    surcharges: "Ek Ücretler",
+    wallet: "Cüzdan",
+    voucher: "Hediye Çeki",
+    total: "Toplam",
+    taxable_base: "Matrah",
+    tax: "KDV",
+    refunded: "İADE EDİLDİ",
+    footer: "Bu belge bilgi amaçlıdır, fatura yerine geçmez.",
+};
+
+const EN: Labels = Labels {
+    title: "Payment Receipt",
+    order: "Order No",
+    payment: "Payment ID",
+    transaction: "Transaction ID",
+    date: "Date",
+    customer: "Customer",
+    method: "Payment Method",
+    installments: "Installments",
+    subtotal: "Subtotal",
+    discount: "Discount",
+    surcharges: "Surcharges",
+    wallet: "Wallet",
+    voucher: "Gift Card",
+    total: "Total",
+    taxable_base: "Taxable Amount",
+    tax: "VAT",
+    refunded: "REFUNDED",
+    footer: "This document is for information only and is not an invoice.",
+};
+
+/// Receipt for a paid payment, rendered once per language and served from the cache afterwards.
+pub async fn pdf(state: &AppState, payment: &Payment, language: Locale) -> AppResult<Vec<u8>> {
+    let paid = [PaymentStatus::Completed.as_str(), PaymentStatus::Escrowed.as_str(), PaymentStatus::Refunded.as_str()];
+    if !paid.contains(&payment.payment_status.as_str()) {
+        return Err(AppError::Conflict("Receipts are only available for paid payments".to_string()));
+    }
+
+    let cached = sqlx::query_scalar::<_, Vec<u8>>(
+        "SELECT pdf FROM payment_receipts WHERE payment_id = $1 AND language = $2 AND payment_updated_at = $3"
+    )
+    .bind(payment.id)
+    .bind(language.as_str())
+    .bind(payment.updated_at)
+    .fetch_optional(&state.db_pool)
+    .await?;
+    if let Some(pdf) = cached {
+        return Ok(pdf);
+    }
+
+    let brand = if payment.merchant_id == DEFAULT_MERCHANT_ID {
+        state.config.receipt_brand_name.clone()
+    } else {
+        merchants::get(&state.db_pool, payment.merchant_id).await?.name
+    };
+    // Only fetched when rendering, a cached receipt never reaches the user service
+    let customer = user_profiles::get(state, payment.user_id).await;
+    let pdf = render(&brand, payment, customer.as_ref(), language)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO payment_receipts (payment_id, language, payment_updated_at, pdf, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (payment_id, language) DO UPDATE
+        SET payment_updated_at = EXCLUDED.payment_updated_at, pdf = EXCLUDED.pdf, created_at = EXCLUDED.created_at
+        "#,
+    )
+    .bind(payment.id)
+    .bind(language.as_str())
+    .bind(payment.updated_at)
+    .bind(&pdf)
+    .bind(Utc::now())
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok(pdf)
+}
+
+fn render(brand: &str, payment: &Payment, customer: Option<&UserProfile>, language: Locale) -> anyhow::Result<Vec<u8>> {
+    let labels = labels(language);
+    let money = |amount: Decimal| format!("{} {}", amount.round_dp(2), payment.currency);
+
+    let mut details = vec![
+        (labels.order, payment.order_id.to_string()),
+        (labels.payment, payment.id.to_string()),
+        (labels.transaction, payment.transaction_id.clone().unwrap_or_else(|| "-".to_string())),
+        (labels.date, payment.created_at.format("%d.%m.%Y %H:%M UTC").to_string()),
+        (labels.method, payment.payment_method.clone()),
+    ];
+    if let Some(customer) = customer {
+        let name = customer.full_name();
+        let value = if name.is_empty() { customer.email.clone() } else { format!("{} ({})", name, customer.email) };
+        details.insert(4, (labels.customer, value));
+    }
+    if payment.installment_count > 1 {
+        details.push((labels.installments, payment.installment_count.to_string()));
+    }
+
+    let surcharges = payment.installment_surcharge + payment.method_surcharge;
+    let mut amounts = vec![(labels.subtotal, money(payment.gross_amount))];
+    if !payment.discount_amount.is_zero() {
+        amounts.push((labels.discount, money(-payment.discount_amount)));
+    }
+    if !surcharges.is_zero() {
+        amounts.push((labels.surcharges, money(surcharges)));
+    }
+    if !payment.voucher_amount.is_zero() {
+        amounts.push((labels.voucher, money(payment.voucher_amount)));
+    }
+    if !payment.wallet_amount.is_zero() {
+        amounts.push((labels.wallet, money(payment.wallet_amount)));
+    }
+    amounts.push((labels.taxable_base, money(payment.taxable_base)));
+    amounts.push((labels.tax, format!("%{} - {}", payment.tax_rate.normalize(), money(payment.tax_amount))));
+
+    let (doc, page, layer) = PdfDocument::new(labels.title, Mm(210.0), Mm(297.0), "receipt");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+    let layer = doc.get_page(page).get_layer(layer);
+
+    layer.use_text(latin1(brand), 20.0, Mm(20.0), Mm(270.0), &bold);
+    layer.use_text(latin1(labels.title), 14.0, Mm(20.0), Mm(260.0), &regular);
+    if payment.payment_status == PaymentStatus::Refunded.as_str() {
+        layer.use_text(latin1(labels.refunded), 14.0, Mm(150.0), Mm(260.0), &bold);
+    }
+
+    let mut y = 240.0;
+    for (label, value) in &details {
+        layer.use_text(latin1(label), 10.0, Mm(20.0), Mm(y), &bold);
+        layer.use_text(latin1(value), 10.0, Mm(65.0), Mm(y), &regular);
+        y -= 7.0;
+    }
+
+    y -= 3.0;
+    layer.add_line(Line {
+        points: vec![(Point::new(Mm(20.0), Mm(y)), false), (Point::new(Mm(190.0), Mm(y)), false)],
+        is_closed: false,
+    });
+    y -= 10.0;
+
+    for (label, value) in &amounts {
+        layer.use_text(latin1(label), 10.0, Mm(20.0), Mm(y), &regular);
+        layer.use_text(latin1(value), 10.0, Mm(140.0), Mm(y), &regular);
+        y -= 7.0;
+    }
+
+    let total = payment.amount + surcharges;
+    layer.use_text(latin1(labels.total), 12.0, Mm(20.0), Mm(y - 3.0), &bold);
+    layer.use_text(money(total), 12.0, Mm(140.0), Mm(y - 3.0), &bold);
+
+    layer.use_text(latin1(labels.footer), 8.0, Mm(20.0), Mm(20.0), &regular);
+
+    Ok(doc.save_to_bytes()?)
+}
+
+/// Built-in PDF fonts use WinAnsi encoding, which lacks ş, ğ and ı; fold those to their base letters.
+fn latin1(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ş' => 's',
+            'Ş' => 'S',
+            'ğ' => 'g',
+            'Ğ' => 'G',
+            'ı' => 'i',
+            'İ' => 'I',
+            other => other,
+        })
+        .collect()
+}
@@ -0,0 +1,251 @@
+use crate::{
+    dto::CreateRefundRequest,
+    error::{AppError, AppResult},
+    models::{Payment, PaymentStatus, Refund, RefundDestination, RefundStatus, WalletEntryType, METHOD_WALLET},
+    services::{gateway, gateway_credentials, payment_service, splits, wallets, AppState},
+};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+/// Refunds a completed payment, in full or in part, either through the gateway or into the customer's wallet.
+/// The payment turns REFUNDED once nothing is left to refund.
+///
+/// A gateway refund is stored PENDING and the payment lock released before the gateway is called, so a slow gateway
+/// doesn't hold the payment row; the pending refund already counts against what's left to refund. The refund id is
+/// the gateway's idempotency key, a refund stuck in PENDING can be retried there safely.
+pub async fn create(
+    state: &AppState,
+    payment_id: Uuid,
+    expected_version: i32,
+    request: CreateRefundRequest,
+) -> AppResult<(Refund, Payment)> {
+    let pool = &state.db_pool;
+    let destination = match request.destination.as_deref().map(str::to_uppercase).as_deref() {
+        None | Some("ORIGINAL_METHOD") => RefundDestination::OriginalMethod,
+        Some("WALLET") => RefundDestination::Wallet,
+        Some(other) => return Err(AppError::BadRequest(format!("Unknown refund destination: {}", other))),
+    };
+
+    let mut tx = pool.begin().await?;
+
+    // Row lock serializes concurrent refunds of the same payment
+    let payment = sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE id = $1 FOR UPDATE"
+    )
+    .bind(payment_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    payment_service::ensure_version(&payment, expected_version)?;
+
+    // Escrowed funds haven't reached the merchant yet and can be sent back too
+    let refundable_status = [PaymentStatus::Completed.as_str(), PaymentStatus::Escrowed.as_str()];
+    if !refundable_status.contains(&payment.payment_status.as_str()) {
+        return Err(AppError::Conflict("Only completed or escrowed payments can be refunded".to_string()));
+    }
+    if payment.wallet_topup && destination == RefundDestination::Wallet {
+        return Err(AppError::BadRequest("Wallet top-ups cannot be refunded to the wallet".to_string()));
+    }
+
+    let refunded = refunded_amount(&mut tx, payment_id, None).await?;
+    let amount = refund_amount(request.amount, payment.amount - refunded)?;
+
+    let original = match destination {
+        // Wallet-paid orders go back to the wallet either way
+        RefundDestination::Wallet => None,
+        RefundDestination::OriginalMethod if payment.payment_method == METHOD_WALLET => None,
+        RefundDestination::OriginalMethod => {
+            let Some(original) = payment.transaction_id.clone().filter(|_| payment.payment_method_id.is_some()) else {
+                return Err(AppError::BadRequest(
+                    "Payment was not made with a saved card, refund it to the wallet instead".to_string(),
+                ));
+            };
+            // Wallet and voucher parts of a split payment can only be refunded to the wallet
+            let to_card = refunded_amount(&mut tx, payment_id, Some(RefundDestination::OriginalMethod)).await?;
+            let charged = payment.amount - payment.wallet_amount - payment.voucher_amount;
+            ensure_within_card_charge(charged, to_card, amount)?;
+            Some(original)
+        }
+    };
+
+    let status = if original.is_some() { RefundStatus::Pending } else { RefundStatus::Completed };
+    let refund = sqlx::query_as::<_, Refund>(
+        r#"
+        INSERT INTO refunds (id, payment_id, amount, currency, destination, reason, status, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(payment.id)
+    .bind(amount)
+    .bind(&payment.currency)
+    .bind(destination.as_str())
+    .bind(request.reason)
+    .bind(status.as_str())
+    .bind(Utc::now())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let Some(original) = original else {
+        wallets::credit(&mut tx, payment.user_id, &payment.currency, amount, WalletEntryType::Refund, Some(payment.id))
+            .await?;
+        let payment = settle(&mut tx, &payment, &refund).await?;
+        tx.commit().await?;
+        return Ok((refund, payment));
+    };
+
+    // Touching the row moves the version, a second refund sent with the same If-Match is refused
+    sqlx::query("UPDATE payments SET updated_at = NOW() WHERE id = $1")
+        .bind(payment.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let credentials = gateway_credentials::resolve(state, payment.merchant_id).await?;
+    let transaction_id = match gateway::refund(&credentials, &original, amount, &payment.currency, refund.id).await {
+        Ok(transaction_id) => transaction_id,
+        Err(e) => {
+            // Gives the amount back for another attempt
+            sqlx::query("UPDATE refunds SET status = $2 WHERE id = $1")
+                .bind(refund.id)
+                .bind(RefundStatus::Failed.as_str())
+                .execute(pool)
+                .await?;
+            return Err(e.into());
+        }
+    };
+
+    let mut tx = pool.begin().await?;
+    let payment = sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE id = $1 FOR UPDATE"
+    )
+    .bind(payment_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let refund = sqlx::query_as::<_, Refund>(
+        "UPDATE refunds SET status = $2, transaction_id = $3 WHERE id = $1 RETURNING *"
+    )
+    .bind(refund.id)
+    .bind(RefundStatus::Completed.as_str())
+    .bind(transaction_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let payment = settle(&mut tx, &payment, &refund).await?;
+    tx.commit().await?;
+
+    Ok((refund, payment))
+}
+
+/// Books a completed refund: takes it back from the sellers' splits and turns the payment REFUNDED once the
+/// completed refunds cover it. Runs with the payment row locked.
+async fn settle(conn: &mut PgConnection, payment: &Payment, refund: &Refund) -> AppResult<Payment> {
+    let refunded_before = sqlx::query_scalar::<_, Decimal>(
+        "SELECT COALESCE(SUM(amount), 0) FROM refunds WHERE payment_id = $1 AND status = $2 AND id <> $3"
+    )
+    .bind(payment.id)
+    .bind(RefundStatus::Completed.as_str())
+    .bind(refund.id)
+    .fetch_one(&mut *conn)
+    .await?;
+    splits::record_refund(conn, payment, refund, refunded_before).await?;
+
+    // Partial refunds touch the row too, so the version moves for every refund
+    let fully_refunded = refunded_before + refund.amount >= payment.amount;
+    let payment_status = if fully_refunded { PaymentStatus::Refunded.as_str() } else { &payment.payment_status };
+    let payment = sqlx::query_as::<_, Payment>(
+        "UPDATE payments SET payment_status = $2, updated_at = NOW() WHERE id = $1 RETURNING *"
+    )
+    .bind(payment.id)
+    .bind(payment_status)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(payment)
+}
+
+/// Refunds of the payment that went through or are still with the gateway, optionally to one destination only.
+async fn refunded_amount(
+    conn: &mut PgConnection,
+    payment_id: Uuid,
+    destination: Option<RefundDestination>,
+) -> AppResult<Decimal> {
+    let refunded = sqlx::query_scalar::<_, Decimal>(
+        r#"
+        SELECT COALESCE(SUM(amount), 0) FROM refunds
+        WHERE payment_id = $1 AND status <> $2 AND ($3::VARCHAR IS NULL OR destination = $3)
+        "#,
+    )
+    .bind(payment_id)
+    .bind(RefundStatus::Failed.as_str())
+    .bind(destination.map(|d| d.as_str().to_string()))
+    .fetch_one(conn)
+    .await?;
+
+    Ok(refunded)
+}
+
+/// The requested amount, or everything left when none is given.
+fn refund_amount(requested: Option<Decimal>, refundable: Decimal) -> AppResult<Decimal> {
+    let amount = requested.unwrap_or(refundable);
+    if amount <= Decimal::ZERO || amount > refundable {
+        return Err(AppError::BadRequest(format!("Refund amount must be between 0 and {}", refundable)));
+    }
+
+    Ok(amount)
+}
+
+/// The card only gets back what was charged to it, not the wallet or voucher part of the payment.
+fn ensure_within_card_charge(charged: Decimal, to_card: Decimal, amount: Decimal) -> AppResult<()> {
+    if to_card + amount > charged {
+        return Err(AppError::BadRequest("Refund exceeds the amount charged to the card".to_string()));
+    }
+
+    Ok(())
+}
+
+pub async fn list_for_payment(pool: &PgPool, payment_id: Uuid) -> AppResult<Vec<Refund>> {
+    payment_service::get_payment(pool, payment_id).await?;
+
+    let refunds = sqlx::query_as::<_, Refund>(
+        "SELECT * FROM refunds WHERE payment_id = $1 ORDER BY created_at"
+    )
+    .bind(payment_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(refunds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn refund_amount_defaults_to_what_is_left() {
+        assert_eq!(refund_amount(None, dec("40")).unwrap(), dec("40"));
+        assert_eq!(refund_amount(Some(dec("40")), dec("40")).unwrap(), dec("40"));
+        assert_eq!(refund_amount(Some(dec("0.01")), dec("40")).unwrap(), dec("0.01"));
+    }
+
+    #[test]
+    fn refund_amount_must_be_positive_and_within_what_is_left() {
+        assert!(refund_amount(Some(dec("40.01")), dec("40")).is_err());
+        assert!(refund_amount(Some(Decimal::ZERO), dec("40")).is_err());
+        assert!(refund_amount(Some(dec("-5")), dec("40")).is_err());
+        // Nothing left once fully refunded
+        assert!(refund_amount(None, Decimal::ZERO).is_err());
+    }
+
+    #[test]
+    fn card_refunds_stop_at_the_card_charge() {
+        // 100 paid as 70 by card and 30 from the wallet, 50 already back on the card
+        assert!(ensure_within_card_charge(dec("70"), dec("50"), dec("20")).is_ok());
+        assert!(ensure_within_card_charge(dec("70"), dec("50"), dec("20.01")).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{
+    database,
+    dto::ReportQuery,
+    error::{AppError, AppResult},
+    models::PaymentStatus,
+    services::payment_service,
+};
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+// Rows buffered between the DB cursor and the HTTP response
+const CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn parse(format: Option<&str>) -> AppResult<Self> {
+        match format.map(str::to_lowercase).as_deref() {
+            None | Some("csv") => Ok(ReportFormat::Csv),
+            Some("json") => Ok(ReportFormat::Json),
+            Some(other) => Err(AppError::BadRequest(format!("Unknown report format: {}", other))),
+        }
+    }
+
+    pub fn content_type(&self) -> &str {
+        match self {
+            ReportFormat::Csv => "text/csv",
+            ReportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn extension(&self) -> &str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+/// One marketplace sale of the merchant and where its money went.
+#[derive(Debug, Serialize, FromRow)]
+struct SettlementLine {
+    payment_id: Uuid,
+    order_id: Uuid,
+    paid_at: DateTime<Utc>,
+    payment_status: String,
+    currency: String,
+    gross_amount: Decimal,
+    commission_percent: Decimal,
+    commission_amount: Decimal,
+    net_amount: Decimal,
+    payout_id: Option<Uuid>,
+    payout_status: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+struct PayoutLine {
+    payout_id: Uuid,
+    created_at: DateTime<Utc>,
+    status: String,
+    currency: String,
+    amount: Decimal,
+    split_count: i32,
+    bank_account_reference: Option<String>,
+    bank_transfer_reference: Option<String>,
+    approved_at: Option<DateTime<Utc>>,
+    paid_at: Option<DateTime<Utc>>,
+}
+
+/// Settled (completed or refunded) splits of a merchant in the period, streamed row by row.
+pub fn settlements(
+    pool: PgPool,
+    merchant_id: Uuid,
+    mut query: ReportQuery,
+    format: ReportFormat,
+    timeout_ms: u64,
+) -> impl Stream<Item = Result<String, std::io::Error>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    // Reported in the JSON metadata as the period actually covered
+    query.from = Some(payment_service::default_from(query.from));
+
+    tokio::spawn(async move {
+        let Some(mut db) = begin(&pool, timeout_ms, &tx).await else {
+            return;
+        };
+        let rows = sqlx::query_as::<_, SettlementLine>(
+            r#"
+            SELECT p.id AS payment_id, p.order_id, p.created_at AS paid_at, p.payment_status, s.currency,
+                   s.amount AS gross_amount, s.commission_percent, s.commission_amount, s.net_amount,
+                   s.payout_id, po.status AS payout_status
+            FROM payment_splits s
+            JOIN payments p ON p.id = s.payment_id
+            LEFT JOIN payouts po ON po.id = s.payout_id
+            WHERE s.merchant_id = $1
+              AND p.payment_status IN ($2, $3)
+              AND p.created_at >= $4
+              AND ($5::TIMESTAMPTZ IS NULL OR p.created_at < $5)
+            ORDER BY p.created_at
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(PaymentStatus::Completed.as_str())
+        .bind(PaymentStatus::Refunded.as_str())
+        .bind(query.from)
+        .bind(query.to)
+        .fetch(&mut *db);
+
+        write_rows(rows, format, "settlements", merchant_id, &query, tx).await;
+    });
+
+    receiver_stream(rx)
+}
+
+pub fn payouts(
+    pool: PgPool,
+    merchant_id: Uuid,
+    query: ReportQuery,
+    format: ReportFormat,
+    timeout_ms: u64,
+) -> impl Stream<Item = Result<String, std::io::Error>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+
+    tokio::spawn(async move {
+        let Some(mut db) = begin(&pool, timeout_ms, &tx).await else {
+            return;
+        };
+        let rows = sqlx::query_as::<_, PayoutLine>(
+            r#"
+            SELECT id AS payout_id, created_at, status, currency, amount, split_count,
+                   bank_account_reference, bank_transfer_reference, approved_at, paid_at
+            FROM payouts
+            WHERE merchant_id = $1
+              AND ($2::TIMESTAMPTZ IS NULL OR created_at >= $2)
+              AND ($3::TIMESTAMPTZ IS NULL OR created_at < $3)
+            ORDER BY created_at
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(query.from)
+        .bind(query.to)
+        .fetch(&mut *db);
+
+        write_rows(rows, format, "payouts", merchant_id, &query, tx).await;
+    });
+
+    receiver_stream(rx)
+}
+
+/// Report transaction with the longer statement timeout; a failure is sent down the stream.
+async fn begin(
+    pool: &PgPool,
+    timeout_ms: u64,
+    tx: &mpsc::Sender<Result<String, std::io::Error>>,
+) -> Option<Transaction<'static, Postgres>> {
+    match database::begin_with_statement_timeout(pool, timeout_ms).await {
+        Ok(db) => Some(db),
+        Err(e) => {
+            tracing::error!(error = %e, "could not start report transaction");
+            let _ = tx.send(Err(std::io::Error::other(e))).await;
+            None
+        }
+    }
+}
+
+/// Encodes rows as they come off the cursor. JSON is one object with the report metadata and a `rows` array.
+/// A DB error ends the stream with an error so the client sees a truncated download rather than a short report.
+async fn write_rows<T: Serialize>(
+    mut rows: impl Stream<Item = Result<T, sqlx::Error>> + Unpin,
+    format: ReportFormat,
+    report: &str,
+    merchant_id: Uuid,
+    query: &ReportQuery,
+    tx: mpsc::Sender<Result<String, std::io::Error>>,
+) {
+    if format == ReportFormat::Json {
+        let meta = serde_json::json!({ "report": report, "merchant_id": merchant_id, "from": query.from, "to": query.to });
+        let opening = meta.to_string();
+        // Reopen the metadata object to append the rows array
+        let opening = format!("{},\"rows\":[", &opening[..opening.len() - 1]);
+        if tx.send(Ok(opening)).await.is_err() {
+            return;
+        }
+    }
+
+    let mut index = 0usize;
+    while let Some(row) = rows.next().await {
+        let chunk = row
+            .map_err(std::io::Error::other)
+            .and_then(|row| encode(&row, format, index).map_err(std::io::Error::other));
+        let failed = chunk.is_err();
+        if let Err(e) = &chunk {
+            tracing::error!(error = %e, "{} report for merchant {} failed", report, merchant_id);
+        }
+        // Receiver gone means the client disconnected
+        if tx.send(chunk).await.is_err() || failed {
+            return;
+        }
+        index += 1;
+    }
+
+    if format == ReportFormat::Json {
+        let _ = tx.send(Ok("]}".to_string())).await;
+    }
+}
+
+fn encode<T: Serialize>(row: &T, format: ReportFormat, index: usize) -> anyhow::Result<String> {
+    match format {
+        ReportFormat::Json => {
+            let json = serde_json::to_string(row)?;
+            Ok(if index == 0 { json } else { format!(",{}", json) })
+        }
+        ReportFormat::Csv => {
+            // Header comes from the first row's field names
+            let mut writer = csv::WriterBuilder::new().has_headers(index == 0).from_writer(Vec::new());
+            writer.serialize(row)?;
+            Ok(String::from_utf8(writer.into_inner().map_err(|e| anyhow::anyhow!(e.to_string()))?)?)
+        }
+    }
+}
+
+fn receiver_stream<T>(rx: mpsc::Receiver<T>) -> impl Stream<Item = T> {
+    futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|item| (item, rx)) })
+}
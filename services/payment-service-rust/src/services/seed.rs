@@ -0,0 +1,97 @@
+use crate::{
+    config::Config,
+    dto::SeedSummary,
+    error::{AppError, AppResult},
+    models::PaymentStatus,
+    services::payment_import::{self, HistoricalPayment},
+};
+use chrono::{Duration, Utc};
+use rand::{seq::SliceRandom, Rng};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const DEFAULT_COUNT: usize = 500;
+pub const MAX_COUNT: usize = 50_000;
+/// Seeded payments are spread over this many days back
+const SPAN_DAYS: i64 = 180;
+/// Distinct demo customers the payments are spread over
+const USERS: usize = 40;
+/// Marks seeded rows, so they can be told apart and cleaned up
+pub const TRANSACTION_PREFIX: &str = "SEED-";
+
+// (value, weight) pairs, roughly what production traffic looks like
+const STATUSES: [(PaymentStatus, u32); 4] = [
+    (PaymentStatus::Completed, 75),
+    (PaymentStatus::Failed, 12),
+    (PaymentStatus::Refunded, 8),
+    (PaymentStatus::Pending, 5),
+];
+const CURRENCIES: [(&str, u32); 3] = [("TRY", 80), ("EUR", 12), ("USD", 8)];
+const METHODS: [(&str, u32); 5] = [
+    ("CREDIT_CARD", 60),
+    ("DEBIT_CARD", 20),
+    ("BANK_TRANSFER", 10),
+    ("CASH_ON_DELIVERY", 6),
+    ("WALLET", 4),
+];
+
+/// Generates demo payments across statuses, currencies, methods and the last six months.
+/// Refused in production.
+pub async fn seed(pool: &PgPool, config: &Config, count: usize) -> AppResult<SeedSummary> {
+    if config.is_production() {
+        return Err(AppError::Forbidden("Seeding demo data is disabled in production".to_string()));
+    }
+    if count == 0 || count > MAX_COUNT {
+        return Err(AppError::BadRequest(format!("count must be between 1 and {}", MAX_COUNT)));
+    }
+
+    let mut merchants: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM merchants").fetch_all(pool).await?;
+    merchants.sort();
+    let payments = generate(count, &merchants);
+
+    for batch in payments.chunks(payment_import::BATCH_SIZE) {
+        payment_import::insert_batch(pool, batch).await?;
+    }
+    tracing::info!("Seeded {} demo payments", payments.len());
+
+    Ok(SeedSummary {
+        payments: payments.len(),
+        users: USERS,
+        merchants: merchants.len(),
+        from: Utc::now() - Duration::days(SPAN_DAYS),
+        to: Utc::now(),
+    })
+}
+
+fn generate(count: usize, merchants: &[Uuid]) -> Vec<HistoricalPayment> {
+    let mut rng = rand::thread_rng();
+    let users: Vec<Uuid> = (0..USERS).map(|_| Uuid::new_v4()).collect();
+    let now = Utc::now();
+
+    (0..count)
+        .map(|_| {
+            let currency = *pick(&mut rng, &CURRENCIES);
+            // 1x to 100x the smallest basket, most of them near the low end
+            let base: f64 = if currency == "TRY" { 150.0 } else { 25.0 };
+            let cents = (base * rng.gen_range(0.1f64..1.0).powi(2).recip() * 100.0) as i64;
+
+            HistoricalPayment {
+                id: Uuid::new_v4(),
+                transaction_id: format!("{}{}", TRANSACTION_PREFIX, Uuid::new_v4().simple()),
+                order_id: Uuid::new_v4(),
+                user_id: *users.choose(&mut rng).unwrap_or(&Uuid::nil()),
+                merchant_id: *merchants.choose(&mut rng).unwrap_or(&crate::models::DEFAULT_MERCHANT_ID),
+                amount: Decimal::new(cents.max(100), 2),
+                currency: currency.to_string(),
+                payment_method: pick(&mut rng, &METHODS).to_string(),
+                payment_status: pick(&mut rng, &STATUSES).as_str().to_string(),
+                created_at: now - Duration::seconds(rng.gen_range(0..SPAN_DAYS * 24 * 60 * 60)),
+            }
+        })
+        .collect()
+}
+
+fn pick<'a, T>(rng: &mut impl Rng, weighted: &'a [(T, u32)]) -> &'a T {
+    &weighted.choose_weighted(rng, |(_, weight)| *weight).expect("weights are positive").0
+}
@@ -0,0 +1,63 @@
+use crate::{
+    config::Config,
+    models::{CardDetails, GatewayCredentials},
+    services::gateway::{self, GatewayOutcome},
+};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The charge as sent to the primary gateway, replayed against the shadow account.
+pub struct ShadowCharge {
+    pub payment_id: Uuid,
+    pub card: Option<CardDetails>,
+    pub amount: Decimal,
+    pub currency: String,
+    pub merchant_initiated: bool,
+}
+
+/// Mirrors a charge to the shadow gateway in the background and logs when its outcome differs from the
+/// primary's. The shadow account is a sandbox, so the mirrored charge moves no money; its result is never
+/// used for the payment.
+pub fn mirror(config: Arc<Config>, charge: ShadowCharge, primary: &GatewayOutcome) {
+    let Some(account_id) = config.shadow_gateway_account_id.clone() else {
+        return;
+    };
+    let primary = primary.as_str().to_string();
+
+    tokio::spawn(async move {
+        let credentials = GatewayCredentials {
+            account_id,
+            api_key: config.shadow_gateway_api_key.clone(),
+        };
+        let shadow = gateway::authorize(
+            &config,
+            &credentials,
+            charge.payment_id,
+            charge.card.as_ref(),
+            charge.amount,
+            &charge.currency,
+            None,
+            charge.merchant_initiated,
+        )
+        .await;
+
+        match shadow {
+            Ok(outcome) if outcome.as_str() == primary => {
+                tracing::debug!(payment_id = %charge.payment_id, outcome = %primary, "shadow gateway agrees");
+            }
+            Ok(outcome) => tracing::warn!(
+                payment_id = %charge.payment_id,
+                primary = %primary,
+                shadow = outcome.as_str(),
+                "shadow gateway diverged"
+            ),
+            Err(e) => tracing::warn!(
+                payment_id = %charge.payment_id,
+                primary = %primary,
+                error = %e,
+                "shadow gateway failed"
+            ),
+        }
+    });
+}
@@ -0,0 +1,298 @@
+use crate::{
+    config::Config,
+    database,
+    dto::{EarningsQuery, MerchantTermsRequest, SplitRequest},
+    error::{AppError, AppResult, FieldError, FieldErrorCode},
+    models::{MerchantEarnings, MerchantTerms, Payment, PaymentSplit, PaymentStatus, Refund},
+    services::{merchants, payment_service},
+};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Split amounts must be positive, one per merchant, and add up to the order total.
+pub fn validate(splits: &[SplitRequest], total: Decimal) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if splits.is_empty() {
+        return errors;
+    }
+
+    let mut merchants = HashSet::new();
+    for (i, split) in splits.iter().enumerate() {
+        if split.amount <= Decimal::ZERO {
+            errors.push(FieldError::new(
+                format!("splits[{}].amount", i),
+                FieldErrorCode::OutOfRange,
+                "Split amounts must be positive",
+            ));
+        }
+        if !merchants.insert(split.merchant_id) {
+            errors.push(FieldError::new(
+                format!("splits[{}].merchant_id", i),
+                FieldErrorCode::Duplicate,
+                format!("Merchant {} appears in more than one split", split.merchant_id),
+            ));
+        }
+    }
+
+    let sum: Decimal = splits.iter().map(|s| s.amount).sum();
+    if sum != total {
+        errors.push(FieldError::new(
+            "splits",
+            FieldErrorCode::Invalid,
+            format!("Splits add up to {} but the payment amount is {}", sum, total),
+        ));
+    }
+
+    errors
+}
+
+/// Stores the splits with the platform commission taken from each merchant's terms.
+pub async fn record(
+    conn: &mut PgConnection,
+    config: &Config,
+    payment: &Payment,
+    splits: &[SplitRequest],
+) -> AppResult<Vec<PaymentSplit>> {
+    let now = Utc::now();
+    let mut recorded = Vec::with_capacity(splits.len());
+
+    for split in splits {
+        let terms = sqlx::query_as::<_, MerchantTerms>("SELECT * FROM merchant_terms WHERE merchant_id = $1")
+            .bind(split.merchant_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        let (percent, fixed_fee) = terms
+            .map(|t| (t.commission_percent, t.fixed_fee))
+            .unwrap_or((config.default_commission_percent, Decimal::ZERO));
+        let commission = (split.amount * percent / Decimal::ONE_HUNDRED + fixed_fee)
+            .round_dp(2)
+            .min(split.amount);
+
+        let row = sqlx::query_as::<_, PaymentSplit>(
+            r#"
+            INSERT INTO payment_splits (id, payment_id, merchant_id, amount, currency, commission_percent,
+                                        commission_amount, net_amount, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(payment.id)
+        .bind(split.merchant_id)
+        .bind(split.amount)
+        .bind(&payment.currency)
+        .bind(percent)
+        .bind(commission)
+        .bind(split.amount - commission)
+        .bind(now)
+        .fetch_one(&mut *conn)
+        .await?;
+        recorded.push(row);
+    }
+
+    Ok(recorded)
+}
+
+/// What a refund takes back from each split's net amount, pro rata to the share of the payment refunded so far.
+/// Computed on the running total, so the refunds of a fully refunded payment add up to exactly each split's net.
+pub fn refund_deductions(
+    splits: &[PaymentSplit],
+    total: Decimal,
+    refunded_before: Decimal,
+    amount: Decimal,
+) -> Vec<(Uuid, Decimal)> {
+    let share = |net: Decimal, refunded: Decimal| (net * refunded / total).round_dp(2);
+
+    splits
+        .iter()
+        .map(|s| (s.id, share(s.net_amount, refunded_before + amount) - share(s.net_amount, refunded_before)))
+        .filter(|(_, deduction)| *deduction > Decimal::ZERO)
+        .collect()
+}
+
+/// Records the refund against the payment's splits, so payouts only pay sellers what the customer kept.
+/// `refunded_before` is what earlier refunds of the payment took.
+pub async fn record_refund(
+    conn: &mut PgConnection,
+    payment: &Payment,
+    refund: &Refund,
+    refunded_before: Decimal,
+) -> AppResult<()> {
+    let splits = sqlx::query_as::<_, PaymentSplit>("SELECT * FROM payment_splits WHERE payment_id = $1")
+        .bind(payment.id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for (split_id, deduction) in refund_deductions(&splits, payment.amount, refunded_before, refund.amount) {
+        sqlx::query(
+            r#"
+            INSERT INTO split_adjustments (id, split_id, refund_id, merchant_id, currency, amount, created_at)
+            SELECT $1, id, $2, merchant_id, currency, $3, $4 FROM payment_splits WHERE id = $5
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(refund.id)
+        .bind(-deduction)
+        .bind(refund.created_at)
+        .bind(split_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn for_payment(pool: &PgPool, payment_id: Uuid) -> AppResult<Vec<PaymentSplit>> {
+    let splits = sqlx::query_as::<_, PaymentSplit>(
+        "SELECT * FROM payment_splits WHERE payment_id = $1 ORDER BY amount DESC"
+    )
+    .bind(payment_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(splits)
+}
+
+/// Splits of all of `payment_ids` at once, for list responses.
+pub async fn for_payments(pool: &PgPool, payment_ids: &[Uuid]) -> AppResult<Vec<PaymentSplit>> {
+    let splits = sqlx::query_as::<_, PaymentSplit>(
+        "SELECT * FROM payment_splits WHERE payment_id = ANY($1) ORDER BY amount DESC"
+    )
+    .bind(payment_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(splits)
+}
+
+pub async fn set_terms(pool: &PgPool, merchant_id: Uuid, request: MerchantTermsRequest) -> AppResult<MerchantTerms> {
+    merchants::get(pool, merchant_id).await?;
+    let fixed_fee = request.fixed_fee.unwrap_or(Decimal::ZERO);
+    if request.commission_percent < Decimal::ZERO
+        || request.commission_percent > Decimal::ONE_HUNDRED
+        || fixed_fee < Decimal::ZERO
+    {
+        return Err(AppError::BadRequest("Commission must be between 0 and 100 percent".to_string()));
+    }
+
+    let terms = sqlx::query_as::<_, MerchantTerms>(
+        r#"
+        INSERT INTO merchant_terms (merchant_id, commission_percent, fixed_fee, payout_account_reference, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $5)
+        ON CONFLICT (merchant_id) DO UPDATE
+        SET commission_percent = EXCLUDED.commission_percent, fixed_fee = EXCLUDED.fixed_fee,
+            payout_account_reference = COALESCE(EXCLUDED.payout_account_reference, merchant_terms.payout_account_reference),
+            updated_at = EXCLUDED.updated_at
+        RETURNING *
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(request.commission_percent)
+    .bind(fixed_fee)
+    .bind(request.payout_account_reference)
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(terms)
+}
+
+/// Sums the splits of completed payments per merchant and currency, net of what partial refunds took back.
+/// Runs under the report statement timeout (`timeout_ms`) rather than the pool's.
+pub async fn earnings(pool: &PgPool, query: &EarningsQuery, timeout_ms: u64) -> AppResult<Vec<MerchantEarnings>> {
+    let mut tx = database::begin_with_statement_timeout(pool, timeout_ms).await?;
+    let rows = sqlx::query_as::<_, MerchantEarnings>(
+        r#"
+        SELECT s.merchant_id, s.currency,
+               COUNT(*) AS split_count,
+               SUM(s.amount) AS gross_amount,
+               SUM(s.commission_amount) AS commission_amount,
+               SUM(s.net_amount + COALESCE((SELECT SUM(a.amount) FROM split_adjustments a WHERE a.split_id = s.id), 0))
+                   AS net_amount
+        FROM payment_splits s
+        JOIN payments p ON p.id = s.payment_id
+        WHERE p.payment_status = $1
+          AND ($2::UUID IS NULL OR s.merchant_id = $2)
+          AND p.created_at >= $3
+          AND ($4::TIMESTAMPTZ IS NULL OR p.created_at < $4)
+        GROUP BY s.merchant_id, s.currency
+        ORDER BY s.merchant_id, s.currency
+        "#,
+    )
+    .bind(PaymentStatus::Completed.as_str())
+    .bind(query.merchant_id)
+    .bind(payment_service::default_from(query.from))
+    .bind(query.to)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn split(amount: Decimal, net_amount: Decimal) -> PaymentSplit {
+        PaymentSplit {
+            id: Uuid::new_v4(),
+            payment_id: Uuid::nil(),
+            merchant_id: Uuid::new_v4(),
+            amount,
+            currency: "TRY".to_string(),
+            created_at: Utc::now(),
+            commission_percent: dec("10"),
+            commission_amount: amount - net_amount,
+            net_amount,
+            payout_id: None,
+        }
+    }
+
+    /// What a payout of the split would pay after the deductions
+    fn paid_out(split: &PaymentSplit, deductions: &[Vec<(Uuid, Decimal)>]) -> Decimal {
+        let taken: Decimal = deductions.iter().flatten().filter(|(id, _)| *id == split.id).map(|(_, d)| d).sum();
+        split.net_amount - taken
+    }
+
+    #[test]
+    fn partial_refund_reduces_payouts_pro_rata() {
+        let splits = [split(dec("60"), dec("54")), split(dec("40"), dec("36"))];
+
+        let deductions = [refund_deductions(&splits, dec("100"), Decimal::ZERO, dec("25"))];
+
+        assert_eq!(deductions[0], vec![(splits[0].id, dec("13.50")), (splits[1].id, dec("9.00"))]);
+        assert_eq!(paid_out(&splits[0], &deductions), dec("40.50"));
+        assert_eq!(paid_out(&splits[1], &deductions), dec("27.00"));
+    }
+
+    #[test]
+    fn refunding_everything_in_parts_takes_back_exactly_the_net() {
+        let splits = [split(dec("33.34"), dec("30.01")), split(dec("66.66"), dec("59.99"))];
+        let mut deductions = Vec::new();
+        let mut refunded = Decimal::ZERO;
+        for amount in [dec("33.33"), dec("33.33"), dec("33.34")] {
+            deductions.push(refund_deductions(&splits, dec("100"), refunded, amount));
+            refunded += amount;
+        }
+
+        for split in &splits {
+            assert_eq!(paid_out(split, &deductions), Decimal::ZERO);
+        }
+    }
+
+    #[test]
+    fn validate_reports_splits_not_adding_up() {
+        let request = |amount| SplitRequest { merchant_id: Uuid::new_v4(), amount };
+
+        assert!(validate(&[request(dec("60")), request(dec("40"))], dec("100")).is_empty());
+        let errors = validate(&[request(dec("60")), request(dec("30"))], dec("100"));
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -0,0 +1,457 @@
+use crate::{
+    dto::{ChangePlanRequest, CreatePaymentRequest, CreateSubscriptionRequest, UpdateSubscriptionRequest},
+    error::{AppError, AppResult, ErrorCode},
+    models::{Payment, PaymentStatus, Subscription, SubscriptionAdjustment, SubscriptionStatus, DEFAULT_MERCHANT_ID},
+    services::{payment_method_service, payment_service, AppState},
+};
+use chrono::{DateTime, Duration, Months, Utc};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const BILLING_INTERVALS: [&str; 4] = ["DAY", "WEEK", "MONTH", "YEAR"];
+
+pub async fn create(pool: &PgPool, user_id: Uuid, request: CreateSubscriptionRequest) -> AppResult<Subscription> {
+    if !BILLING_INTERVALS.contains(&request.billing_interval.as_str()) {
+        return Err(AppError::BadRequest(format!("Unknown billing interval: {}", request.billing_interval)));
+    }
+    let interval_count = request.interval_count.unwrap_or(1);
+    if interval_count < 1 {
+        return Err(AppError::BadRequest("interval_count must be at least 1".to_string()));
+    }
+    if request.amount <= Decimal::ZERO {
+        return Err(AppError::BadRequest("amount must be positive".to_string()));
+    }
+
+    let method = payment_method_service::find_for_user(pool, user_id, request.payment_method_id).await?;
+    let now = Utc::now();
+
+    let subscription = sqlx::query_as::<_, Subscription>(
+        r#"
+        INSERT INTO subscriptions (id, user_id, plan_code, amount, currency, billing_interval, interval_count, payment_method_id,
+                                   status, next_billing_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(request.plan_code)
+    .bind(request.amount)
+    .bind(request.currency)
+    .bind(request.billing_interval)
+    .bind(interval_count)
+    .bind(method.id)
+    .bind(SubscriptionStatus::Active.as_str())
+    .bind(request.start_at.unwrap_or(now))
+    .bind(now)
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(subscription)
+}
+
+pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<Subscription>> {
+    let subscriptions = sqlx::query_as::<_, Subscription>(
+        "SELECT * FROM subscriptions WHERE user_id = $1 ORDER BY created_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subscriptions)
+}
+
+pub async fn get_for_user(pool: &PgPool, user_id: Uuid, id: Uuid) -> AppResult<Subscription> {
+    sqlx::query_as::<_, Subscription>("SELECT * FROM subscriptions WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Subscription not found".to_string()))
+}
+
+pub async fn update(
+    pool: &PgPool,
+    user_id: Uuid,
+    id: Uuid,
+    request: UpdateSubscriptionRequest,
+) -> AppResult<Subscription> {
+    let current = get_for_user(pool, user_id, id).await?;
+    if current.status == SubscriptionStatus::Cancelled.as_str() {
+        return Err(AppError::Conflict("Subscription is cancelled".to_string()));
+    }
+
+    let status = match request.status.as_deref() {
+        None => None,
+        Some("ACTIVE") => Some(SubscriptionStatus::Active),
+        Some("PAUSED") => Some(SubscriptionStatus::Paused),
+        Some(other) => return Err(AppError::BadRequest(format!("Cannot change status to {}", other))),
+    };
+
+    let payment_method_id = match request.payment_method_id {
+        Some(method_id) => Some(payment_method_service::find_for_user(pool, user_id, Some(method_id)).await?.id),
+        None => None,
+    };
+
+    let subscription = sqlx::query_as::<_, Subscription>(
+        r#"
+        UPDATE subscriptions
+        SET payment_method_id = COALESCE($3, payment_method_id), status = COALESCE($4, status), updated_at = $5
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(payment_method_id)
+    .bind(status.map(|s| s.as_str().to_string()))
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(subscription)
+}
+
+pub async fn cancel(pool: &PgPool, user_id: Uuid, id: Uuid) -> AppResult<Subscription> {
+    let subscription = sqlx::query_as::<_, Subscription>(
+        r#"
+        UPDATE subscriptions SET status = $3, cancelled_at = $4, updated_at = $4
+        WHERE id = $1 AND user_id = $2 AND status <> $3
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(SubscriptionStatus::Cancelled.as_str())
+    .bind(Utc::now())
+    .fetch_optional(pool)
+    .await?;
+
+    match subscription {
+        Some(subscription) => Ok(subscription),
+        None => {
+            get_for_user(pool, user_id, id).await?;
+            Err(AppError::Conflict("Subscription is already cancelled".to_string()))
+        }
+    }
+}
+
+pub async fn due_subscription_ids(pool: &PgPool, limit: i64) -> AppResult<Vec<Uuid>> {
+    let ids = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM subscriptions WHERE status = $1 AND next_billing_at <= NOW() ORDER BY next_billing_at LIMIT $2"
+    )
+    .bind(SubscriptionStatus::Active.as_str())
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids)
+}
+
+pub async fn due_retry_ids(pool: &PgPool, limit: i64) -> AppResult<Vec<Uuid>> {
+    let ids = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM subscriptions WHERE status = $1 AND next_retry_at <= NOW() ORDER BY next_retry_at LIMIT $2"
+    )
+    .bind(SubscriptionStatus::PastDue.as_str())
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids)
+}
+
+/// Charges one billing period. The period is claimed (next_billing_at advanced) before charging,
+/// so a crash or a second replica can never bill the same period twice.
+pub async fn bill(state: &AppState, id: Uuid) -> AppResult<Option<Payment>> {
+    let pool = &state.db_pool;
+
+    let Some(subscription) = sqlx::query_as::<_, Subscription>(
+        "SELECT * FROM subscriptions WHERE id = $1 AND status = $2 AND next_billing_at <= NOW()"
+    )
+    .bind(id)
+    .bind(SubscriptionStatus::Active.as_str())
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let next_billing_at = advance(&subscription, subscription.next_billing_at)?;
+    let claimed = sqlx::query(
+        "UPDATE subscriptions SET next_billing_at = $3, updated_at = NOW() WHERE id = $1 AND next_billing_at = $2"
+    )
+    .bind(subscription.id)
+    .bind(subscription.next_billing_at)
+    .bind(next_billing_at)
+    .execute(pool)
+    .await?;
+    if claimed.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    if subscription.credit_balance >= subscription.amount {
+        // Period fully covered by proration credit
+        consume_credit(&state.db_pool, subscription.id, subscription.amount).await?;
+        return Ok(None);
+    }
+
+    let payment = charge(state, &subscription, subscription.amount - subscription.credit_balance).await?;
+    record_outcome(state, &subscription, &payment).await?;
+
+    Ok(Some(payment))
+}
+
+/// Dunning retry for a PAST_DUE subscription whose retry is due.
+pub async fn retry(state: &AppState, id: Uuid) -> AppResult<Option<Payment>> {
+    let pool = &state.db_pool;
+
+    // Claim the retry slot; the outcome sets the next one
+    let Some(subscription) = sqlx::query_as::<_, Subscription>(
+        r#"
+        UPDATE subscriptions SET next_retry_at = NULL, updated_at = NOW()
+        WHERE id = $1 AND status = $2 AND next_retry_at <= NOW()
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(SubscriptionStatus::PastDue.as_str())
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let amount = (subscription.amount - subscription.credit_balance).max(Decimal::ZERO);
+    let payment = charge(state, &subscription, amount).await?;
+    record_outcome(state, &subscription, &payment).await?;
+
+    Ok(Some(payment))
+}
+
+async fn charge(state: &AppState, subscription: &Subscription, amount: Decimal) -> AppResult<Payment> {
+    let method = payment_method_service::find_for_user(
+        &state.db_pool,
+        subscription.user_id,
+        Some(subscription.payment_method_id),
+    )
+    .await?;
+
+    let request = CreatePaymentRequest {
+        order_id: Uuid::new_v4(),
+        user_id: subscription.user_id,
+        amount,
+        currency: subscription.currency.clone(),
+        payment_method: method.method_type,
+        payment_method_token: Some(method.token),
+        return_url: None,
+        crypto_asset: None,
+        installments: None,
+        wallet_amount: None,
+        voucher_code: None,
+        promo_code: None,
+        country: None,
+        splits: None,
+        escrow: false,
+        merchant_initiated: true,
+        subscription_id: Some(subscription.id),
+        wallet_topup: false,
+        promotion_id: None,
+        discount_amount: Decimal::ZERO,
+        tax_jurisdiction: None,
+        tax_rate: Decimal::ZERO,
+        // Plans are sold by the platform
+        merchant_id: DEFAULT_MERCHANT_ID,
+        payer_id: Some(subscription.user_id),
+    };
+
+    payment_service::create_payment(state, request).await
+}
+
+/// Success resets dunning; failure schedules the next retry or cancels after the last one.
+async fn record_outcome(state: &AppState, subscription: &Subscription, payment: &Payment) -> AppResult<()> {
+    let failed = payment.payment_status == PaymentStatus::Failed.as_str();
+    let was_past_due = subscription.status == SubscriptionStatus::PastDue.as_str();
+
+    // Credit is only used up once the charge it reduced actually went through
+    if !failed && !subscription.credit_balance.is_zero() {
+        consume_credit(&state.db_pool, subscription.id, subscription.credit_balance).await?;
+    }
+
+    if !failed && !was_past_due {
+        return Ok(());
+    }
+
+    let (status, retry_count, next_retry_at, event_type) = if !failed {
+        (SubscriptionStatus::Active, 0, None, "subscription.recovered")
+    } else {
+        let retry_count = if was_past_due { subscription.retry_count + 1 } else { 0 };
+        match state.config.dunning_retry_days.get(retry_count as usize) {
+            Some(days) => (
+                SubscriptionStatus::PastDue,
+                retry_count,
+                Some(Utc::now() + Duration::days(*days)),
+                "subscription.payment_failed",
+            ),
+            None => (SubscriptionStatus::Cancelled, retry_count, None, "subscription.cancelled"),
+        }
+    };
+
+    let updated = sqlx::query_as::<_, Subscription>(
+        r#"
+        UPDATE subscriptions
+        SET status = $2, retry_count = $3, next_retry_at = $4,
+            cancelled_at = CASE WHEN $2 = 'CANCELLED' THEN NOW() ELSE cancelled_at END,
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(subscription.id)
+    .bind(status.as_str())
+    .bind(retry_count)
+    .bind(next_retry_at)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    state.events.publish_subscription(event_type, &updated, Some(payment.id));
+
+    Ok(())
+}
+
+async fn consume_credit(pool: &PgPool, id: Uuid, amount: Decimal) -> AppResult<()> {
+    sqlx::query(
+        "UPDATE subscriptions SET credit_balance = GREATEST(credit_balance - $2, 0), updated_at = NOW() WHERE id = $1"
+    )
+    .bind(id)
+    .bind(amount)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Mid-cycle plan change. Upgrades charge the prorated difference immediately, downgrades
+/// credit it against upcoming charges. The calculation is stored in `subscription_adjustments`.
+pub async fn change_plan(
+    state: &AppState,
+    user_id: Uuid,
+    id: Uuid,
+    request: ChangePlanRequest,
+) -> AppResult<(Subscription, SubscriptionAdjustment)> {
+    let pool = &state.db_pool;
+    if request.amount <= Decimal::ZERO {
+        return Err(AppError::BadRequest("amount must be positive".to_string()));
+    }
+
+    let subscription = get_for_user(pool, user_id, id).await?;
+    if subscription.status != SubscriptionStatus::Active.as_str() {
+        return Err(AppError::Conflict("Only active subscriptions can change plan".to_string()));
+    }
+
+    let now = Utc::now();
+    let period_end = subscription.next_billing_at;
+    let period_start = rewind(&subscription, period_end)?;
+    let remaining_ratio = remaining_ratio(period_start, period_end, now);
+    let prorated_amount = ((request.amount - subscription.amount) * remaining_ratio).round_dp(2);
+
+    let payment = if prorated_amount > Decimal::ZERO {
+        let payment = charge(state, &subscription, prorated_amount).await?;
+        state.events.publish(&payment);
+        if payment.payment_status == PaymentStatus::Failed.as_str() {
+            return Err(
+                AppError::PaymentRequired("Prorated upgrade charge was declined".to_string())
+                    .with_code(ErrorCode::GatewayDeclined),
+            );
+        }
+        Some(payment)
+    } else {
+        None
+    };
+
+    let mut tx = pool.begin().await?;
+
+    let adjustment = sqlx::query_as::<_, SubscriptionAdjustment>(
+        r#"
+        INSERT INTO subscription_adjustments (id, subscription_id, old_plan_code, new_plan_code, old_amount, new_amount,
+                                              period_start, period_end, remaining_ratio, prorated_amount, payment_id, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(subscription.id)
+    .bind(&subscription.plan_code)
+    .bind(&request.plan_code)
+    .bind(subscription.amount)
+    .bind(request.amount)
+    .bind(period_start)
+    .bind(period_end)
+    .bind(remaining_ratio)
+    .bind(prorated_amount)
+    .bind(payment.as_ref().map(|p| p.id))
+    .bind(now)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let credit = (-prorated_amount).max(Decimal::ZERO);
+    let updated = sqlx::query_as::<_, Subscription>(
+        r#"
+        UPDATE subscriptions
+        SET plan_code = $2, amount = $3, credit_balance = credit_balance + $4, updated_at = $5
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(subscription.id)
+    .bind(request.plan_code)
+    .bind(request.amount)
+    .bind(credit)
+    .bind(now)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((updated, adjustment))
+}
+
+/// Share of the current period that is still unused at `now`, between 0 and 1.
+fn remaining_ratio(period_start: DateTime<Utc>, period_end: DateTime<Utc>, now: DateTime<Utc>) -> Decimal {
+    let total = (period_end - period_start).num_seconds();
+    if total <= 0 {
+        return Decimal::ZERO;
+    }
+    let remaining = (period_end - now).num_seconds().clamp(0, total);
+
+    (Decimal::from(remaining) / Decimal::from(total)).round_dp(10)
+}
+
+fn rewind(subscription: &Subscription, from: DateTime<Utc>) -> AppResult<DateTime<Utc>> {
+    let count = subscription.interval_count.max(1) as u32;
+
+    let previous = match subscription.billing_interval.as_str() {
+        "DAY" => Some(from - Duration::days(count as i64)),
+        "WEEK" => Some(from - Duration::weeks(count as i64)),
+        "MONTH" => from.checked_sub_months(Months::new(count)),
+        "YEAR" => from.checked_sub_months(Months::new(count * 12)),
+        _ => None,
+    };
+
+    previous.ok_or_else(|| AppError::Internal(anyhow::anyhow!("cannot compute billing period for subscription {}", subscription.id)))
+}
+
+fn advance(subscription: &Subscription, from: DateTime<Utc>) -> AppResult<DateTime<Utc>> {
+    let count = subscription.interval_count.max(1) as u32;
+
+    let next = match subscription.billing_interval.as_str() {
+        "DAY" => Some(from + Duration::days(count as i64)),
+        "WEEK" => Some(from + Duration::weeks(count as i64)),
+        "MONTH" => from.checked_add_months(Months::new(count)),
+        "YEAR" => from.checked_add_months(Months::new(count * 12)),
+        _ => None,
+    };
+
+    next.ok_or_else(|| AppError::Internal(anyhow::anyhow!("cannot advance billing date for subscription {}", subscription.id)))
+}
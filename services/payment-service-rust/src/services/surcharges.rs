@@ -0,0 +1,22 @@
+use crate::config::Config;
+use rust_decimal::Decimal;
+
+#[derive(Debug, Clone)]
+pub struct SurchargeQuote {
+    pub percent: Decimal,
+    pub amount: Decimal,
+}
+
+/// Surcharge for paying `amount` with `payment_method`; methods without a configured rate are free.
+pub fn quote(config: &Config, payment_method: &str, amount: Decimal) -> SurchargeQuote {
+    let percent = config
+        .method_surcharges
+        .get(&payment_method.to_uppercase())
+        .copied()
+        .unwrap_or(Decimal::ZERO);
+
+    SurchargeQuote {
+        percent,
+        amount: (amount * percent / Decimal::ONE_HUNDRED).round_dp(2),
+    }
+}
@@ -0,0 +1,21 @@
+use crate::config::Config;
+use rust_decimal::Decimal;
+
+/// Rate for the buyer's country when configured, otherwise for the payment currency.
+/// Returns the matched jurisdiction code, or none (0%) when neither is configured.
+pub fn rate_for(config: &Config, country: Option<&str>, currency: &str) -> (Option<String>, Decimal) {
+    let country = country.map(str::to_uppercase);
+
+    country
+        .into_iter()
+        .chain(std::iter::once(currency.to_uppercase()))
+        .find_map(|code| config.tax_rates.get(&code).map(|rate| (Some(code), *rate)))
+        .unwrap_or((None, Decimal::ZERO))
+}
+
+/// Splits a tax-inclusive amount into (taxable base, tax amount).
+pub fn split_inclusive(amount: Decimal, rate: Decimal) -> (Decimal, Decimal) {
+    let base = (amount * Decimal::ONE_HUNDRED / (Decimal::ONE_HUNDRED + rate)).round_dp(2);
+
+    (base, amount - base)
+}
@@ -1,7 +1,11 @@
-use anyhow::Result;
-use reqwest::Client;
+use crate::services::user_grpc::UserGrpcClient;
+use anyhow::{bail, Result};
+use futures::{StreamExt, TryStreamExt};
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use tracing::{info, warn};
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize)]
 struct ValidateTokenResponse {
@@ -9,65 +13,171 @@ struct ValidateTokenResponse {
     data: Option<TokenData>,
 }
 
-#[derive(Debug, Deserialize)]
-struct TokenData {
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenData {
     valid: bool,
     #[serde(rename = "userId")]
-    user_id: String,
-    email: String,
+    pub user_id: String,
+    #[serde(default)]
+    pub role: String,
+    /// Set for merchant staff; absent for customers and platform staff
+    #[serde(rename = "merchantId", default)]
+    pub merchant_id: Option<String>,
+    /// Space separated OAuth scopes; tokens without the claim aren't restricted
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+impl TokenData {
+    /// Claims of a token validated elsewhere, e.g. by `introspection`.
+    pub fn new(user_id: String, role: String, merchant_id: Option<String>, scope: Option<String>) -> Self {
+        Self { valid: true, user_id, role, merchant_id, scope }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UserResponse {
+    data: Option<UserProfile>,
+}
+
+/// Name and email of a user, for receipts and admin views.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub id: Uuid,
+    pub email: String,
+    #[serde(default)]
+    pub first_name: String,
+    #[serde(default)]
+    pub last_name: String,
+}
+
+impl UserProfile {
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name).trim().to_string()
+    }
 }
 
 pub struct UserServiceClient {
     base_url: String,
     client: Client,
+    /// Requests in flight at once for the batch lookups
+    concurrency: usize,
+    /// Token validation over gRPC when USER_SERVICE_GRPC_URL is set, HTTP stays the fallback
+    grpc: Option<UserGrpcClient>,
+    /// Service account token for lookups made on the service's own behalf, e.g. profiles for receipts
+    service_token: Option<String>,
 }
 
 impl UserServiceClient {
-    pub fn new(base_url: String) -> Self {
+    pub fn new(
+        base_url: String,
+        client: Client,
+        concurrency: usize,
+        grpc: Option<UserGrpcClient>,
+        service_token: Option<String>,
+    ) -> Self {
         Self {
             base_url,
-            client: Client::new(),
+            client,
+            concurrency: concurrency.max(1),
+            grpc,
+            service_token,
         }
     }
 
-    pub async fn validate_token(&self, token: &str) -> Result<bool> {
+    /// Returns the token claims when the user service accepts the token.
+    pub async fn validate_token(&self, token: &str) -> Result<Option<TokenData>> {
+        if let Some(grpc) = &self.grpc {
+            match grpc.validate_token(token).await {
+                Ok(claims) => {
+                    let claims = claims.map(|c| TokenData {
+                        valid: c.valid,
+                        user_id: c.user_id,
+                        role: c.role,
+                        merchant_id: c.merchant_id,
+                        scope: c.scope,
+                    });
+                    info!("Token validation result (gRPC): {}", claims.is_some());
+                    return Ok(claims);
+                }
+                Err(e) => warn!(error = %e, "gRPC token validation failed, falling back to HTTP"),
+            }
+        }
+
         let url = format!("{}/api/auth/validate", self.base_url);
 
         let response = self
             .client
             .post(&url)
             .header("Authorization", format!("Bearer {}", token))
+            .headers(crate::telemetry::propagation_headers())
             .send()
             .await?;
 
         if response.status().is_success() {
             let result: ValidateTokenResponse = response.json().await?;
-            let is_valid = result.status == "success" 
-                && result.data.as_ref().map(|d| d.valid).unwrap_or(false);
-            
-            info!("Token validation result: {}", is_valid);
-            Ok(is_valid)
+            let claims = result
+                .data
+                .filter(|d| result.status == "success" && d.valid);
+
+            info!("Token validation result: {}", claims.is_some());
+            Ok(claims)
         } else {
             warn!("Token validation failed: {}", response.status());
-            Ok(false)
+            Ok(None)
         }
     }
 
-    pub async fn get_user_id_from_token(&self, token: &str) -> Result<Option<String>> {
-        let url = format!("{}/api/auth/validate", self.base_url);
+    /// Which of the users exist, looked up with the caller's token. The user service has no batch endpoint, so
+    /// the lookups run concurrently, `concurrency` at a time; any failed lookup fails the batch.
+    pub async fn existing_users(&self, token: &str, user_ids: &[Uuid]) -> Result<HashSet<Uuid>> {
+        let found: Vec<Option<Uuid>> = futures::stream::iter(user_ids.iter().copied())
+            .map(|id| async move { Ok::<_, anyhow::Error>(self.user_exists(token, id).await?.then_some(id)) })
+            .buffer_unordered(self.concurrency)
+            .try_collect()
+            .await?;
+
+        Ok(found.into_iter().flatten().collect())
+    }
+
+    async fn user_exists(&self, token: &str, user_id: Uuid) -> Result<bool> {
+        let url = format!("{}/api/users/{}", self.base_url, user_id);
 
         let response = self
             .client
-            .post(&url)
+            .get(&url)
             .header("Authorization", format!("Bearer {}", token))
+            .headers(crate::telemetry::propagation_headers())
             .send()
             .await?;
 
-        if response.status().is_success() {
-            let result: ValidateTokenResponse = response.json().await?;
-            Ok(result.data.map(|d| d.user_id))
-        } else {
-            Ok(None)
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => bail!("user service returned {} for user {}", status, user_id),
+        }
+    }
+
+    /// The user's profile, `None` when the user doesn't exist. Needs USER_SERVICE_TOKEN, see `services::user_profiles`
+    /// for the cached lookup.
+    pub async fn get_profile(&self, user_id: Uuid) -> Result<Option<UserProfile>> {
+        let Some(token) = &self.service_token else {
+            bail!("USER_SERVICE_TOKEN is not set");
+        };
+        let url = format!("{}/api/users/{}", self.base_url, user_id);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .headers(crate::telemetry::propagation_headers())
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(response.json::<UserResponse>().await?.data),
+            StatusCode::NOT_FOUND => Ok(None),
+            status => bail!("user service returned {} for user {}", status, user_id),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use std::time::Duration;
+use tonic::{
+    codec::ProstCodec,
+    codegen::http::uri::PathAndQuery,
+    metadata::MetadataMap,
+    transport::{Channel, ClientTlsConfig, Endpoint},
+    Code, Request,
+};
+
+const VALIDATE_TOKEN: &str = "/user.v1.UserService/ValidateToken";
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `user.v1.ValidateTokenRequest` of proto/user.proto.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ValidateTokenRequest {
+    #[prost(string, tag = "1")]
+    pub token: String,
+}
+
+/// `user.v1.ValidateTokenResponse` of proto/user.proto.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ValidateTokenResponse {
+    #[prost(bool, tag = "1")]
+    pub valid: bool,
+    #[prost(string, tag = "2")]
+    pub user_id: String,
+    #[prost(string, tag = "3")]
+    pub role: String,
+    #[prost(string, optional, tag = "4")]
+    pub merchant_id: Option<String>,
+    #[prost(string, optional, tag = "5")]
+    pub scope: Option<String>,
+}
+
+/// gRPC side of `UserServiceClient`, one HTTP/2 connection multiplexing every call.
+#[derive(Clone)]
+pub struct UserGrpcClient {
+    grpc: tonic::client::Grpc<Channel>,
+}
+
+impl UserGrpcClient {
+    /// Connects on first use, so the service starts even while the user service is down. `tls` is set for
+    /// mutual TLS, see `services::mtls`.
+    pub fn new(url: &str, tls: Option<ClientTlsConfig>) -> Result<Self> {
+        let mut endpoint = Endpoint::from_shared(url.to_string())?.timeout(TIMEOUT).connect_timeout(TIMEOUT);
+        if let Some(tls) = tls {
+            endpoint = endpoint.tls_config(tls)?;
+        }
+        let channel = endpoint.connect_lazy();
+
+        Ok(Self { grpc: tonic::client::Grpc::new(channel) })
+    }
+
+    /// The token's claims, `None` for a token the user service rejects. Errors are transport or server
+    /// failures, for which the caller falls back to HTTP.
+    pub async fn validate_token(&self, token: &str) -> Result<Option<ValidateTokenResponse>> {
+        let mut grpc = self.grpc.clone();
+        grpc.ready().await?;
+
+        let mut request = Request::new(ValidateTokenRequest { token: token.to_string() });
+        *request.metadata_mut() = MetadataMap::from_headers(crate::telemetry::propagation_headers());
+
+        let codec = ProstCodec::<ValidateTokenRequest, ValidateTokenResponse>::default();
+        let response = grpc.unary(request, PathAndQuery::from_static(VALIDATE_TOKEN), codec).await;
+
+        match response {
+            Ok(response) => Ok(Some(response.into_inner()).filter(|claims| claims.valid)),
+            Err(status) if status.code() == Code::Unauthenticated => Ok(None),
+            Err(status) => Err(status.into()),
+        }
+    }
+}
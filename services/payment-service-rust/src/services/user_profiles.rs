@@ -0,0 +1,52 @@
+use crate::{
+    error::{AppError, AppResult},
+    services::{user_client::UserProfile, AppState},
+};
+use redis::AsyncCommands;
+use uuid::Uuid;
+
+fn cache_key(user_id: Uuid) -> String {
+    format!("user_profile:{}", user_id)
+}
+
+/// Profile from Redis, or from the user service and cached for USER_PROFILE_CACHE_TTL_SECS. Best effort:
+/// `None` when the user is unknown or the profile can't be had, callers render without it.
+pub async fn get(state: &AppState, user_id: Uuid) -> Option<UserProfile> {
+    let mut redis = state.redis_conn.clone();
+    let cached: Option<String> = match redis.get(cache_key(user_id)).await {
+        Ok(cached) => cached,
+        Err(e) => {
+            tracing::warn!(error = %e, "user profile cache read failed");
+            None
+        }
+    };
+    if let Some(profile) = cached.and_then(|json| serde_json::from_str(&json).ok()) {
+        return Some(profile);
+    }
+
+    let profile = match state.user_client.get_profile(user_id).await {
+        Ok(profile) => profile?,
+        Err(e) => {
+            tracing::warn!(error = %e, %user_id, "could not fetch user profile");
+            return None;
+        }
+    };
+
+    if let Ok(json) = serde_json::to_string(&profile) {
+        let ttl = state.config.user_profile_cache_ttl_secs.max(1);
+        if let Err(e) = redis.set_ex::<_, _, ()>(cache_key(user_id), json, ttl).await {
+            tracing::warn!(error = %e, "user profile cache write failed");
+        }
+    }
+
+    Some(profile)
+}
+
+/// Drops the cached profile, e.g. after the user changed their name; the next lookup fetches it again.
+pub async fn invalidate(state: &AppState, user_id: Uuid) -> AppResult<()> {
+    let mut redis = state.redis_conn.clone();
+    redis
+        .del::<_, ()>(cache_key(user_id))
+        .await
+        .map_err(|e| AppError::Internal(e.into()))
+}
@@ -0,0 +1,55 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, Result};
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Local encryption for sensitive payment method data (AES-256-GCM).
+pub struct Vault {
+    cipher: Aes256Gcm,
+}
+
+impl Vault {
+    pub fn new(secret: &str) -> Self {
+        // Derive a fixed-size key so any configured secret length works
+        let key = Sha256::digest(secret.as_bytes());
+        Self {
+            cipher: Aes256Gcm::new(&key),
+        }
+    }
+
+    /// Returns `(ciphertext, nonce)`.
+    pub fn seal<T: Serialize>(&self, value: &T) -> Result<(Vec<u8>, Vec<u8>)> {
+        let plaintext = serde_json::to_vec(value)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| anyhow!("vault encryption failed"))?;
+
+        Ok((ciphertext, nonce.to_vec()))
+    }
+
+    pub fn open<T: DeserializeOwned>(&self, ciphertext: &[u8], nonce: &[u8]) -> Result<T> {
+        if nonce.len() != 12 {
+            return Err(anyhow!("invalid vault nonce length"));
+        }
+
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("vault decryption failed"))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Opaque, non-derivable reference handed out to clients.
+    pub fn generate_token(prefix: &str) -> String {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        format!("{}_{}", prefix, hex::encode(bytes))
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{
+    chaos::{self, Target},
+    config::Config,
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+const KEY_PREFIX: &str = "payment_volume";
+
+/// Completed payments (count, USD amount) in one window, counted into Redis so all instances share it.
+#[derive(Debug, Clone, Copy, Default)]
+struct Volume {
+    count: f64,
+    amount: f64,
+}
+
+/// Name and accessor of a scored value
+type Metric = (&'static str, fn(&Volume) -> f64);
+
+/// A window whose volume is more than the configured number of standard deviations from the preceding windows.
+#[derive(Debug)]
+pub struct Anomaly {
+    /// "count" or "amount_usd"
+    pub metric: &'static str,
+    pub window_start: DateTime<Utc>,
+    pub value: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub z_score: f64,
+}
+
+impl Anomaly {
+    /// Volume collapse (outage) rather than a spike (e.g. a fraud burst)
+    pub fn is_drop(&self) -> bool {
+        self.z_score < 0.0
+    }
+}
+
+fn window_of(at: DateTime<Utc>, window_secs: i64) -> i64 {
+    at.timestamp().div_euclid(window_secs)
+}
+
+fn key(window: i64) -> String {
+    format!("{}:{}", KEY_PREFIX, window)
+}
+
+/// Adds a completed payment to the current window.
+#[tracing::instrument(name = "redis.command", skip_all, fields(db.system = "redis", db.statement = "HINCRBY payment_volume"))]
+pub async fn record(mut redis: ConnectionManager, config: &Config, amount_usd: Decimal) -> Result<()> {
+    chaos::inject(Target::Redis)?;
+    let window_secs = config.anomaly_window_secs;
+    let key = key(window_of(Utc::now(), window_secs));
+
+    let mut pipe = redis::pipe();
+    pipe.hincr(&key, "count", 1).ignore();
+    pipe.cmd("HINCRBYFLOAT").arg(&key).arg("amount").arg(amount_usd.round_dp(2).to_string()).ignore();
+    // Long enough to serve as history for later windows
+    pipe.expire(&key, window_secs * (config.anomaly_history_windows + 2)).ignore();
+    Ok(pipe.query_async(&mut redis).await?)
+}
+
+/// Scores the last complete window against the `anomaly_history_windows` before it.
+#[tracing::instrument(name = "redis.command", skip_all, fields(db.system = "redis", db.statement = "HMGET payment_volume"))]
+pub async fn check(mut redis: ConnectionManager, config: &Config) -> Result<Vec<Anomaly>> {
+    chaos::inject(Target::Redis)?;
+    let window_secs = config.anomaly_window_secs;
+    let last = window_of(Utc::now(), window_secs) - 1;
+    let windows = (last - config.anomaly_history_windows)..=last;
+
+    let mut pipe = redis::pipe();
+    for window in windows.clone() {
+        pipe.cmd("HMGET").arg(key(window)).arg("count").arg("amount");
+    }
+    let raw: Vec<(Option<f64>, Option<f64>)> = pipe.query_async(&mut redis).await?;
+    // A missing window had no completed payments
+    let volumes: Vec<Volume> = raw
+        .into_iter()
+        .map(|(count, amount)| Volume { count: count.unwrap_or(0.0), amount: amount.unwrap_or(0.0) })
+        .collect();
+    let Some((current, history)) = volumes.split_last() else {
+        return Ok(Vec::new());
+    };
+
+    let threshold = config.anomaly_z_threshold.to_f64().unwrap_or(3.0);
+    let window_start = DateTime::from_timestamp(last * window_secs, 0).unwrap_or_default();
+    let metrics: [Metric; 2] = [("count", |v| v.count), ("amount_usd", |v| v.amount)];
+
+    Ok(metrics
+        .into_iter()
+        .filter_map(|(metric, value_of)| {
+            let history: Vec<f64> = history.iter().map(value_of).collect();
+            let n = history.len() as f64;
+            let mean = history.iter().sum::<f64>() / n;
+            let stddev = (history.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+            // Flat history (e.g. a fresh deployment) gives no scale to measure against
+            if stddev == 0.0 {
+                return None;
+            }
+
+            let value = value_of(current);
+            let z_score = (value - mean) / stddev;
+            (z_score.abs() > threshold).then_some(Anomaly { metric, window_start, value, mean, stddev, z_score })
+        })
+        .collect())
+}
@@ -0,0 +1,149 @@
+use crate::{
+    dto::IssueVoucherRequest,
+    error::{AppError, AppResult},
+    models::{Voucher, VoucherStatus},
+};
+use chrono::Utc;
+use rand::{distributions::Uniform, Rng};
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+// Same unambiguous alphabet as transfer references, codes get typed in by hand
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+pub async fn issue(pool: &PgPool, request: IssueVoucherRequest) -> AppResult<Voucher> {
+    if request.amount <= Decimal::ZERO {
+        return Err(AppError::BadRequest("amount must be positive".to_string()));
+    }
+
+    let code = match request.code {
+        Some(code) => code.trim().to_uppercase(),
+        None => generate_code(),
+    };
+    let now = Utc::now();
+
+    let voucher = sqlx::query_as::<_, Voucher>(
+        r#"
+        INSERT INTO vouchers (id, code, initial_amount, balance, currency, status, expires_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $3, $4, $5, $6, $7, $7)
+        ON CONFLICT (code) DO NOTHING
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(&code)
+    .bind(request.amount)
+    .bind(request.currency.to_uppercase())
+    .bind(VoucherStatus::Active.as_str())
+    .bind(request.expires_at)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+
+    voucher.ok_or_else(|| AppError::Conflict(format!("Voucher code {} already exists", code)))
+}
+
+/// Voided vouchers keep their remaining balance for the record but can no longer be redeemed.
+pub async fn void(pool: &PgPool, id: Uuid) -> AppResult<Voucher> {
+    let voucher = sqlx::query_as::<_, Voucher>(
+        r#"
+        UPDATE vouchers SET status = $2, voided_at = $3, updated_at = $3
+        WHERE id = $1 AND status <> $2
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(VoucherStatus::Voided.as_str())
+    .bind(Utc::now())
+    .fetch_optional(pool)
+    .await?;
+
+    match voucher {
+        Some(voucher) => Ok(voucher),
+        None => {
+            sqlx::query_as::<_, Voucher>("SELECT * FROM vouchers WHERE id = $1")
+                .bind(id)
+                .fetch_one(pool)
+                .await?;
+            Err(AppError::Conflict("Voucher is already voided".to_string()))
+        }
+    }
+}
+
+/// A redeemable voucher as it is now, without locking it; for working out a payment before its transaction.
+pub async fn find_redeemable(pool: &PgPool, code: &str, currency: &str) -> AppResult<Voucher> {
+    let voucher = sqlx::query_as::<_, Voucher>(
+        "SELECT * FROM vouchers WHERE code = $1 AND status = $2 AND (expires_at IS NULL OR expires_at > NOW())",
+    )
+    .bind(code.trim().to_uppercase())
+    .bind(VoucherStatus::Active.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    redeemable(voucher, currency)
+}
+
+/// Locks a redeemable voucher for the rest of the transaction.
+pub async fn lock_for_redemption(conn: &mut PgConnection, code: &str, currency: &str) -> AppResult<Voucher> {
+    let voucher = sqlx::query_as::<_, Voucher>(
+        r#"
+        SELECT * FROM vouchers
+        WHERE code = $1 AND status = $2 AND (expires_at IS NULL OR expires_at > NOW())
+        FOR UPDATE
+        "#,
+    )
+    .bind(code.trim().to_uppercase())
+    .bind(VoucherStatus::Active.as_str())
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    redeemable(voucher, currency)
+}
+
+fn redeemable(voucher: Option<Voucher>, currency: &str) -> AppResult<Voucher> {
+    let voucher = voucher.ok_or_else(|| AppError::BadRequest("Voucher is invalid or expired".to_string()))?;
+
+    if voucher.currency != currency {
+        return Err(AppError::BadRequest(format!("Voucher can only be used for {} payments", voucher.currency)));
+    }
+    if voucher.balance.is_zero() {
+        return Err(AppError::BadRequest("Voucher has no balance left".to_string()));
+    }
+
+    Ok(voucher)
+}
+
+/// Moves `amount` between the voucher balance and a payment: positive redeems, negative gives it back.
+pub async fn redeem(conn: &mut PgConnection, voucher_id: Uuid, payment_id: Uuid, amount: Decimal) -> AppResult<()> {
+    // The CHECK constraint on balance is the last line of defence against overdrawing
+    sqlx::query("UPDATE vouchers SET balance = balance - $2, updated_at = NOW() WHERE id = $1")
+        .bind(voucher_id)
+        .bind(amount)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO voucher_redemptions (id, voucher_id, payment_id, amount, created_at) VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind(Uuid::new_v4())
+    .bind(voucher_id)
+    .bind(payment_id)
+    .bind(amount)
+    .bind(Utc::now())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+fn generate_code() -> String {
+    let dist = Uniform::from(0..CODE_ALPHABET.len());
+    let chars: Vec<char> = rand::thread_rng()
+        .sample_iter(dist)
+        .take(12)
+        .map(|i| CODE_ALPHABET[i] as char)
+        .collect();
+
+    chars.chunks(4).map(|c| c.iter().collect::<String>()).collect::<Vec<_>>().join("-")
+}
@@ -0,0 +1,158 @@
+use crate::{
+    error::{AppError, AppResult},
+    models::{Wallet, WalletEntryType, WalletTransaction},
+};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<Wallet>> {
+    let wallets = sqlx::query_as::<_, Wallet>(
+        "SELECT * FROM wallets WHERE user_id = $1 ORDER BY currency"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(wallets)
+}
+
+/// Newest first; `currency` narrows the history to a single wallet.
+pub async fn transactions_for_user(
+    pool: &PgPool,
+    user_id: Uuid,
+    currency: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> AppResult<(Vec<WalletTransaction>, i64)> {
+    let transactions = sqlx::query_as::<_, WalletTransaction>(
+        r#"
+        SELECT t.* FROM wallet_transactions t
+        JOIN wallets w ON w.id = t.wallet_id
+        WHERE w.user_id = $1 AND ($2::VARCHAR IS NULL OR w.currency = $2)
+        ORDER BY t.created_at DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(user_id)
+    .bind(currency)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT COUNT(*) FROM wallet_transactions t
+        JOIN wallets w ON w.id = t.wallet_id
+        WHERE w.user_id = $1 AND ($2::VARCHAR IS NULL OR w.currency = $2)
+        "#,
+    )
+    .bind(user_id)
+    .bind(currency)
+    .fetch_one(pool)
+    .await?;
+
+    Ok((transactions, total))
+}
+
+/// Locks the user's wallet for the rest of the transaction and checks it can cover `amount`.
+pub async fn lock_for_debit(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    currency: &str,
+    amount: Decimal,
+) -> AppResult<Wallet> {
+    let wallet = sqlx::query_as::<_, Wallet>(
+        "SELECT * FROM wallets WHERE user_id = $1 AND currency = $2 FOR UPDATE"
+    )
+    .bind(user_id)
+    .bind(currency)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match wallet {
+        Some(wallet) if wallet.balance >= amount => Ok(wallet),
+        _ => Err(AppError::BadRequest("Insufficient wallet balance".to_string())),
+    }
+}
+
+/// Takes `amount` from a wallet previously locked with `lock_for_debit`.
+pub async fn debit(
+    conn: &mut PgConnection,
+    wallet: &Wallet,
+    amount: Decimal,
+    entry_type: WalletEntryType,
+    payment_id: Option<Uuid>,
+) -> AppResult<WalletTransaction> {
+    let balance = sqlx::query_scalar::<_, Decimal>(
+        "UPDATE wallets SET balance = balance - $2, updated_at = $3 WHERE id = $1 RETURNING balance"
+    )
+    .bind(wallet.id)
+    .bind(amount)
+    .bind(Utc::now())
+    .fetch_one(&mut *conn)
+    .await?;
+
+    record(conn, wallet.id, entry_type, -amount, balance, payment_id).await
+}
+
+/// Adds `amount` to the user's wallet, opening one for the currency on first use.
+pub async fn credit(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    currency: &str,
+    amount: Decimal,
+    entry_type: WalletEntryType,
+    payment_id: Option<Uuid>,
+) -> AppResult<WalletTransaction> {
+    let now = Utc::now();
+
+    let wallet = sqlx::query_as::<_, Wallet>(
+        r#"
+        INSERT INTO wallets (id, user_id, currency, balance, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $5)
+        ON CONFLICT (user_id, currency) DO UPDATE
+        SET balance = wallets.balance + EXCLUDED.balance, updated_at = EXCLUDED.updated_at
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(currency)
+    .bind(amount)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    record(conn, wallet.id, entry_type, amount, wallet.balance, payment_id).await
+}
+
+async fn record(
+    conn: &mut PgConnection,
+    wallet_id: Uuid,
+    entry_type: WalletEntryType,
+    amount: Decimal,
+    balance_after: Decimal,
+    payment_id: Option<Uuid>,
+) -> AppResult<WalletTransaction> {
+    let transaction = sqlx::query_as::<_, WalletTransaction>(
+        r#"
+        INSERT INTO wallet_transactions (id, wallet_id, entry_type, amount, balance_after, payment_id, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(wallet_id)
+    .bind(entry_type.as_str())
+    .bind(amount)
+    .bind(balance_after)
+    .bind(payment_id)
+    .bind(Utc::now())
+    .fetch_one(conn)
+    .await?;
+
+    Ok(transaction)
+}
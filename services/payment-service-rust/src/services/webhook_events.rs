@@ -0,0 +1,177 @@
+use crate::{
+    dto::CryptoWebhookRequest,
+    error::{AppError, AppResult},
+    models::{Payment, WebhookEvent, WebhookEventStatus},
+    services::{crypto_payment, crypto_provider::DepositUpdate, AppState},
+    telemetry,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+pub const SOURCE_CRYPTO: &str = "CRYPTO";
+
+/// Stores a delivery as received. A redelivery returns the stored event, processed or not.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "webhook_events.record"))]
+pub async fn record(pool: &PgPool, source: &str, event_id: &str, payload: &str) -> AppResult<WebhookEvent> {
+    let event = sqlx::query_as::<_, WebhookEvent>(
+        r#"
+        INSERT INTO webhook_events (id, source, event_id, payload, status, received_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (source, event_id) DO UPDATE SET payload = COALESCE(webhook_events.payload, EXCLUDED.payload)
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(source)
+    .bind(event_id)
+    .bind(payload)
+    .bind(WebhookEventStatus::Received.as_str())
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(event)
+}
+
+/// Marks the event as processed; false when it was processed before (a redelivery).
+/// Meant for the transaction that applies the event, so a failed attempt leaves it to the next delivery.
+pub async fn claim(conn: &mut PgConnection, id: Uuid) -> AppResult<bool> {
+    let claimed = sqlx::query(
+        r#"
+        UPDATE webhook_events SET status = $2, last_error = NULL, processed_at = $3
+        WHERE id = $1 AND status <> $2
+        "#,
+    )
+    .bind(id)
+    .bind(WebhookEventStatus::Processed.as_str())
+    .bind(Utc::now())
+    .execute(conn)
+    .await?
+    .rows_affected();
+
+    Ok(claimed == 1)
+}
+
+/// Applies a stored event and records the outcome. Events that failed are kept for `replay`.
+#[tracing::instrument(
+    name = "process_webhook_event",
+    skip(state, event),
+    fields(source = %event.source, event_id = %event.event_id)
+)]
+pub async fn process(state: &AppState, event: &WebhookEvent) -> AppResult<Option<Payment>> {
+    let result = match event.source.as_str() {
+        SOURCE_CRYPTO => process_crypto(state, event).await,
+        source => Err(AppError::BadRequest(format!("Unknown webhook source {}", source))),
+    };
+
+    match result {
+        Ok(payment) => {
+            if let Some(payment) = &payment {
+                telemetry::record_payment(payment);
+                state.events.publish(payment);
+            }
+            Ok(payment)
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "webhook event failed");
+            mark_failed(&state.db_pool, event.id, &e.to_string()).await?;
+            Err(e)
+        }
+    }
+}
+
+async fn process_crypto(state: &AppState, event: &WebhookEvent) -> AppResult<Option<Payment>> {
+    let payload = event
+        .payload
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest(format!("Event {} was stored without its payload", event.id)))?;
+    let request: CryptoWebhookRequest = serde_json::from_str(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid crypto webhook payload: {}", e)))?;
+
+    let update = DepositUpdate {
+        received_amount: request.received_amount,
+        confirmations: request.confirmations,
+        tx_hash: request.tx_hash,
+    };
+
+    let payment = crypto_payment::apply_update(state, &request.reference, update, Some(event.id)).await?;
+    if let Some(payment) = &payment {
+        tracing::info!("Crypto payment {} finalized", payment.id);
+    }
+
+    Ok(payment)
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "webhook_events.fail"))]
+async fn mark_failed(pool: &PgPool, id: Uuid, error: &str) -> AppResult<()> {
+    sqlx::query("UPDATE webhook_events SET status = $2, last_error = $3 WHERE id = $1 AND status <> $4")
+        .bind(id)
+        .bind(WebhookEventStatus::Failed.as_str())
+        .bind(error)
+        .bind(WebhookEventStatus::Processed.as_str())
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Processes a stored event again, e.g. after fixing what made it fail. Safe for processed events too,
+/// deposit updates never move a payment back.
+pub async fn replay(state: &AppState, id: Uuid) -> AppResult<WebhookEvent> {
+    let event = sqlx::query_as::<_, WebhookEvent>(
+        r#"
+        UPDATE webhook_events SET status = $2, attempts = attempts + 1, last_error = NULL
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(WebhookEventStatus::Received.as_str())
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("No webhook event {}", id)))?;
+
+    process(state, &event).await?;
+    tracing::info!("Webhook event {} replayed", id);
+
+    get(&state.db_pool, id).await
+}
+
+pub async fn get(pool: &PgPool, id: Uuid) -> AppResult<WebhookEvent> {
+    sqlx::query_as::<_, WebhookEvent>("SELECT * FROM webhook_events WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No webhook event {}", id)))
+}
+
+/// Latest events, optionally of one status and source.
+#[tracing::instrument(
+    name = "db.query",
+    skip_all,
+    fields(db.system = "postgresql", db.statement = "webhook_events.list", db.rows_affected = tracing::field::Empty)
+)]
+pub async fn list(pool: &PgPool, status: Option<&str>, source: Option<&str>) -> AppResult<Vec<WebhookEvent>> {
+    let events = sqlx::query_as::<_, WebhookEvent>(
+        r#"
+        SELECT * FROM webhook_events
+        WHERE ($1::VARCHAR IS NULL OR status = $1) AND ($2::VARCHAR IS NULL OR source = $2)
+        ORDER BY received_at DESC
+        LIMIT 500
+        "#,
+    )
+    .bind(status.map(|s| s.to_uppercase()))
+    .bind(source.map(|s| s.to_uppercase()))
+    .fetch_all(pool)
+    .await?;
+    tracing::Span::current().record("db.rows_affected", events.len());
+
+    Ok(events)
+}
+
+/// Event id for providers that don't send one: deliveries with the same content are the same event.
+pub fn content_id(payload: &str) -> String {
+    hex::encode(Sha256::digest(payload.as_bytes()))
+}
@@ -0,0 +1,198 @@
+use crate::{
+    error::{AppError, AppResult},
+    models::{WorkBacklog, WorkItem, WorkItemStatus},
+    services::{notifications, outbound_webhooks},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{types::Json, PgExecutor, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorkKind {
+    /// Async payment, see `services::async_payments`
+    Payment,
+    /// Customer email, see `services::notifications`
+    Notification,
+    /// Outbound webhook delivery, see `services::outbound_webhooks`
+    Webhook,
+}
+
+impl WorkKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            WorkKind::Payment => "PAYMENT",
+            WorkKind::Notification => "NOTIFICATION",
+            WorkKind::Webhook => "WEBHOOK",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "PAYMENT" => Some(WorkKind::Payment),
+            "NOTIFICATION" => Some(WorkKind::Notification),
+            "WEBHOOK" => Some(WorkKind::Webhook),
+            _ => None,
+        }
+    }
+
+    /// Attempts before the item is parked as DEAD.
+    pub fn max_attempts(&self) -> i32 {
+        match self {
+            WorkKind::Payment => 3,
+            WorkKind::Notification => notifications::MAX_ATTEMPTS,
+            WorkKind::Webhook => outbound_webhooks::MAX_ATTEMPTS,
+        }
+    }
+
+    /// Backoff before the next attempt, doubling each time.
+    fn retry_delay(&self, attempts: i32) -> Duration {
+        let base = match self {
+            WorkKind::Payment => Duration::seconds(5),
+            WorkKind::Notification => Duration::minutes(1),
+            WorkKind::Webhook => Duration::seconds(30),
+        };
+        base * (1 << attempts.clamp(0, 10))
+    }
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "work_items.enqueue"))]
+pub async fn enqueue<'e, E: PgExecutor<'e>, T: Serialize>(
+    executor: E,
+    kind: WorkKind,
+    payload: &T,
+    run_at: DateTime<Utc>,
+) -> AppResult<Uuid> {
+    let id = Uuid::new_v4();
+    let payload = serde_json::to_value(payload).map_err(|e| AppError::Internal(e.into()))?;
+    sqlx::query(
+        r#"
+        INSERT INTO work_items (id, kind, payload, status, run_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, NOW(), NOW())
+        "#,
+    )
+    .bind(id)
+    .bind(kind.as_str())
+    .bind(Json(payload))
+    .bind(WorkItemStatus::Queued.as_str())
+    .bind(run_at)
+    .execute(executor)
+    .await?;
+
+    Ok(id)
+}
+
+/// Takes the next due item, or a running one whose visibility timeout passed (its worker died or hung).
+pub async fn claim(pool: &PgPool, visibility_timeout: Duration) -> AppResult<Option<WorkItem>> {
+    let item = sqlx::query_as::<_, WorkItem>(
+        r#"
+        UPDATE work_items SET status = $1, attempts = attempts + 1, locked_until = NOW() + $2, updated_at = NOW()
+        WHERE id = (
+            SELECT id FROM work_items
+            WHERE (status = $3 AND run_at <= NOW()) OR (status = $1 AND locked_until < NOW())
+            ORDER BY run_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+        "#,
+    )
+    .bind(WorkItemStatus::Running.as_str())
+    .bind(visibility_timeout)
+    .bind(WorkItemStatus::Queued.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(item)
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "work_items.complete"))]
+pub async fn complete(pool: &PgPool, id: Uuid) -> AppResult<()> {
+    sqlx::query("DELETE FROM work_items WHERE id = $1").bind(id).execute(pool).await?;
+
+    Ok(())
+}
+
+/// Schedules a retry with backoff, or parks the item as DEAD once its attempts are used up.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "work_items.fail"))]
+pub async fn fail(pool: &PgPool, item: &WorkItem, error: &str) -> AppResult<WorkItemStatus> {
+    let kind = WorkKind::parse(&item.kind);
+    let status = match kind {
+        Some(kind) if item.attempts < kind.max_attempts() => WorkItemStatus::Queued,
+        _ => WorkItemStatus::Dead,
+    };
+    let run_at = Utc::now() + kind.map(|k| k.retry_delay(item.attempts)).unwrap_or_else(Duration::zero);
+
+    sqlx::query(
+        r#"
+        UPDATE work_items SET status = $2, run_at = $3, locked_until = NULL, last_error = $4, updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(item.id)
+    .bind(status.as_str())
+    .bind(run_at)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(status)
+}
+
+#[tracing::instrument(
+    name = "db.query",
+    skip_all,
+    fields(db.system = "postgresql", db.statement = "work_items.list_dead", db.rows_affected = tracing::field::Empty)
+)]
+/// Items per kind and status, with the oldest due time: the queue's backlog at a glance.
+pub async fn backlog(pool: &PgPool) -> AppResult<Vec<WorkBacklog>> {
+    let backlog = sqlx::query_as::<_, WorkBacklog>(
+        r#"
+        SELECT kind, status, COUNT(*) AS items, MIN(run_at) AS oldest_run_at
+        FROM work_items
+        GROUP BY kind, status
+        ORDER BY kind, status
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(backlog)
+}
+
+pub async fn list_dead(pool: &PgPool, kind: Option<&str>) -> AppResult<Vec<WorkItem>> {
+    let items = sqlx::query_as::<_, WorkItem>(
+        r#"
+        SELECT * FROM work_items
+        WHERE status = $1 AND ($2::VARCHAR IS NULL OR kind = $2)
+        ORDER BY updated_at DESC
+        LIMIT 500
+        "#,
+    )
+    .bind(WorkItemStatus::Dead.as_str())
+    .bind(kind.map(|k| k.to_uppercase()))
+    .fetch_all(pool)
+    .await?;
+    tracing::Span::current().record("db.rows_affected", items.len());
+
+    Ok(items)
+}
+
+/// Puts a dead item back in the queue with a fresh set of attempts.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "postgresql", db.statement = "work_items.requeue"))]
+pub async fn requeue(pool: &PgPool, id: Uuid) -> AppResult<WorkItem> {
+    let item = sqlx::query_as::<_, WorkItem>(
+        r#"
+        UPDATE work_items SET status = $2, attempts = 0, run_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND status = $3
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(WorkItemStatus::Queued.as_str())
+    .bind(WorkItemStatus::Dead.as_str())
+    .fetch_optional(pool)
+    .await?;
+
+    item.ok_or_else(|| AppError::NotFound(format!("No dead work item {}", id)))
+}
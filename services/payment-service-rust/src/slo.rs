@@ -0,0 +1,138 @@
+use crate::{
+    config::Config,
+    dto::{SloBurnRate, SloReport, SloStatus},
+};
+use axum::http::StatusCode;
+use chrono::Utc;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+/// Burn-rate windows in minutes
+const WINDOWS: [(&str, i64); 4] = [("5m", 5), ("30m", 30), ("1h", 60), ("6h", 360)];
+
+/// Multi-window alert conditions from the SRE workbook for a 30 day period: both windows must burn faster
+/// than the rate, the short one confirming the problem is still going on.
+struct AlertRule {
+    severity: &'static str,
+    long_window: &'static str,
+    short_window: &'static str,
+    burn_rate: f64,
+}
+
+const ALERT_RULES: [AlertRule; 2] = [
+    AlertRule { severity: "page", long_window: "1h", short_window: "5m", burn_rate: 14.4 },
+    AlertRule { severity: "ticket", long_window: "6h", short_window: "30m", burn_rate: 6.0 },
+];
+
+/// Requests finished in one minute. `errors` are 5xx responses, `slow` ones over SLO_LATENCY_THRESHOLD_MS.
+#[derive(Debug, Clone, Copy, Default)]
+struct Minute {
+    minute: i64,
+    total: u64,
+    errors: u64,
+    slow: u64,
+}
+
+fn minutes() -> &'static Mutex<VecDeque<Minute>> {
+    static MINUTES: OnceLock<Mutex<VecDeque<Minute>>> = OnceLock::new();
+    MINUTES.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+/// Counts a finished request against the availability and latency SLOs. Counts are per instance and reset
+/// on restart.
+pub fn record(config: &Config, status: StatusCode, latency: Duration) {
+    let minute = Utc::now().timestamp().div_euclid(60);
+    let mut minutes = minutes().lock().unwrap_or_else(|e| e.into_inner());
+
+    if minutes.back().is_none_or(|last| last.minute != minute) {
+        minutes.push_back(Minute { minute, ..Default::default() });
+        let oldest = minute - config.slo_period_days * 24 * 60;
+        while minutes.front().is_some_and(|first| first.minute <= oldest) {
+            minutes.pop_front();
+        }
+    }
+    let Some(current) = minutes.back_mut() else {
+        return;
+    };
+    current.total += 1;
+    current.errors += u64::from(status.is_server_error());
+    current.slow += u64::from(latency.as_millis() > u128::from(config.slo_latency_threshold_ms));
+}
+
+/// Burn rates per window and error budget left in the period, for both SLOs.
+pub fn report(config: &Config) -> SloReport {
+    let now = Utc::now().timestamp().div_euclid(60);
+    let minutes = minutes().lock().unwrap_or_else(|e| e.into_inner()).clone();
+    // (total, bad) over the last `window` minutes, the current one included
+    let sum = |window: i64, bad: fn(&Minute) -> u64| {
+        minutes
+            .iter()
+            .filter(|m| m.minute > now - window)
+            .fold((0, 0), |(total, sum), m| (total + m.total, sum + bad(m)))
+    };
+    let period = config.slo_period_days * 24 * 60;
+
+    let slos = [
+        (
+            "availability",
+            format!("{}% of requests without a 5xx response", config.slo_availability_target_percent),
+            config.slo_availability_target_percent,
+            (|m| m.errors) as fn(&Minute) -> u64,
+        ),
+        (
+            "latency",
+            format!(
+                "{}% of requests served within {} ms",
+                config.slo_latency_target_percent, config.slo_latency_threshold_ms
+            ),
+            config.slo_latency_target_percent,
+            |m| m.slow,
+        ),
+    ];
+
+    let slos = slos
+        .into_iter()
+        .map(|(name, objective, target, bad)| {
+            let budget = ((Decimal::ONE_HUNDRED - target) / Decimal::ONE_HUNDRED).to_f64().unwrap_or(0.0);
+            let burn_rate = |(total, bad): (u64, u64)| {
+                if total == 0 || budget <= 0.0 {
+                    0.0
+                } else {
+                    bad as f64 / total as f64 / budget
+                }
+            };
+
+            let windows: Vec<SloBurnRate> = WINDOWS
+                .iter()
+                .map(|(window, length)| {
+                    let (requests, bad) = sum(*length, bad);
+                    SloBurnRate { window, requests, bad, burn_rate: burn_rate((requests, bad)) }
+                })
+                .collect();
+            let rate_of = |window: &str| windows.iter().find(|w| w.window == window).map_or(0.0, |w| w.burn_rate);
+            let alert = ALERT_RULES
+                .iter()
+                .find(|rule| rate_of(rule.long_window) > rule.burn_rate && rate_of(rule.short_window) > rule.burn_rate)
+                .map(|rule| rule.severity);
+
+            // A period burn rate of 1 spends exactly the whole budget
+            let (requests, bad_requests) = sum(period, bad);
+            SloStatus {
+                name,
+                objective,
+                target_percent: target,
+                period_requests: requests,
+                period_bad_requests: bad_requests,
+                error_budget_remaining: 1.0 - burn_rate((requests, bad_requests)),
+                windows,
+                alert,
+            }
+        })
+        .collect();
+
+    SloReport { period_days: config.slo_period_days, slos }
+}
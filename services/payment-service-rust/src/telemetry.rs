@@ -1,12 +1,36 @@
-use opentelemetry::{global, KeyValue};
+use crate::{log_sampling::LogSampler, models::Payment};
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector, TextMapCompositePropagator},
+    Context as OtelContext, KeyValue,
+};
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::{
+    logs,
+    propagation::{BaggagePropagator, TraceContextPropagator},
+    resource::{EnvResourceDetector, OsResourceDetector, ProcessResourceDetector, ResourceDetector},
     runtime,
     trace::{self, RandomIdGenerator, Sampler},
     Resource,
 };
-use std::time::Duration;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use rust_decimal::prelude::ToPrimitive;
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_subscriber::{
+    filter::{LevelFilter, Targets},
+    layer::{Context, SubscriberExt},
+    util::SubscriberInitExt,
+    Layer,
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 pub fn init_telemetry() -> anyhow::Result<()> {
     let service_name = std::env::var("OTEL_SERVICE_NAME")
@@ -21,6 +45,12 @@ pub fn init_telemetry() -> anyhow::Result<()> {
     let otlp_endpoint = std::env::var("OTEL_ENDPOINT")
         .unwrap_or_else(|_| "http://localhost:4317".to_string());
 
+    let resource = detect_resource().merge(&Resource::new(vec![
+        KeyValue::new("service.name", service_name.clone()),
+        KeyValue::new("service.version", service_version),
+        KeyValue::new("deployment.environment", environment),
+    ]));
+
     // Create OTLP tracer
     let tracer = opentelemetry_otlp::new_pipeline()
         .tracing()
@@ -34,30 +64,325 @@ pub fn init_telemetry() -> anyhow::Result<()> {
             trace::config()
                 .with_sampler(Sampler::AlwaysOn)
                 .with_id_generator(RandomIdGenerator::default())
-                .with_resource(Resource::new(vec![
-                    KeyValue::new("service.name", service_name.clone()),
-                    KeyValue::new("service.version", service_version),
-                    KeyValue::new("deployment.environment", environment),
-                ])),
+                .with_resource(resource.clone()),
         )
         .install_batch(runtime::Tokio)?;
 
+    // Log records to the same collector, replacing log shipping from stdout
+    let export_logs = std::env::var("OTEL_LOGS_ENABLED").map_or(true, |v| v != "false");
+    let log_bridge = if export_logs {
+        let logger = opentelemetry_otlp::new_pipeline()
+            .logging()
+            .with_log_config(logs::config().with_resource(resource))
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(otlp_endpoint.clone())
+                    .with_timeout(Duration::from_secs(3)),
+            )
+            .install_batch(runtime::Tokio)?;
+        // The exporter's own HTTP/2 client logging would feed back into the export
+        let exporter_internals = Targets::new()
+            .with_default(LevelFilter::TRACE)
+            .with_target("h2", LevelFilter::OFF)
+            .with_target("hyper", LevelFilter::OFF)
+            .with_target("tonic", LevelFilter::OFF)
+            .with_target("tower", LevelFilter::OFF)
+            .with_target("opentelemetry", LevelFilter::OFF);
+        Some(WithSpanContext(OpenTelemetryTracingBridge::new(logger.provider())).with_filter(exporter_internals))
+    } else {
+        None
+    };
+
+    // W3C traceparent and baggage, in and out
+    global::set_text_map_propagator(TextMapCompositePropagator::new(vec![
+        Box::new(TraceContextPropagator::new()),
+        Box::new(BaggagePropagator::new()),
+    ]));
+
     // Initialize tracing subscriber with OpenTelemetry layer
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
         ))
+        .with(LogSampler)
         .with(tracing_subscriber::fmt::layer())
+        .with(SlowQueryCounter)
+        // Errors become Sentry events, lower levels breadcrumbs; inert without SENTRY_DSN
+        .with(sentry_tracing::layer())
         .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .with(log_bridge)
         .init();
 
     tracing::info!("✅ OpenTelemetry initialized for {}", service_name);
-    tracing::info!("📡 Sending traces to: {}", otlp_endpoint);
+    tracing::info!("📡 Sending traces{} to: {}", if export_logs { " and logs" } else { "" }, otlp_endpoint);
 
     Ok(())
 }
 
+/// Sentry client reporting panics and error events, tagged with the release. Keep the guard for the process
+/// lifetime: dropping it flushes pending events.
+pub fn init_sentry(dsn: Option<&str>, environment: &str) -> Option<sentry::ClientInitGuard> {
+    let guard = sentry::init((
+        dsn?,
+        sentry::ClientOptions {
+            release: Some(format!("{}@{}+{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), env!("BUILD_GIT_SHA")).into()),
+            environment: Some(environment.to_string().into()),
+            attach_stacktrace: true,
+            ..Default::default()
+        },
+    ));
+
+    Some(guard)
+}
+
 pub async fn shutdown_telemetry() {
     global::shutdown_tracer_provider();
+    global::shutdown_logger_provider();
     tracing::info!("OpenTelemetry shutdown complete");
-}
\ No newline at end of file
+}
+
+/// Business identifiers of a payment as attributes of the current span, so traces can be searched by them.
+pub fn record_payment(payment: &Payment) {
+    let span = tracing::Span::current();
+    span.set_attribute("payment.id", payment.id.to_string());
+    span.set_attribute("order.id", payment.order_id.to_string());
+    span.set_attribute("merchant.id", payment.merchant_id.to_string());
+    span.set_attribute("payment.amount", payment.amount.to_f64().unwrap_or_default());
+    span.set_attribute("payment.currency", payment.currency.clone());
+    span.set_attribute("payment.method", payment.payment_method.clone());
+    span.set_attribute("payment.status", payment.payment_status.clone());
+}
+
+/// Caller's trace context and baggage from the traceparent/baggage headers of an incoming request.
+pub fn extract_context(headers: &axum::http::HeaderMap) -> OtelContext {
+    global::get_text_map_propagator(|propagator| propagator.extract(&RequestHeaders(headers)))
+}
+
+/// traceparent and baggage headers for a call to another of our services, continuing the current span's
+/// trace. Not for third parties: baggage may carry business keys.
+pub fn propagation_headers() -> reqwest::header::HeaderMap {
+    let context = tracing::Span::current().context();
+    let mut headers = OutgoingHeaders(reqwest::header::HeaderMap::new());
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut headers));
+
+    headers.0
+}
+
+struct RequestHeaders<'a>(&'a axum::http::HeaderMap);
+
+impl Extractor for RequestHeaders<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+struct OutgoingHeaders(reqwest::header::HeaderMap);
+
+impl Injector for OutgoingHeaders {
+    fn set(&mut self, key: &str, value: String) {
+        let name = reqwest::header::HeaderName::from_bytes(key.as_bytes());
+        let value = reqwest::header::HeaderValue::from_str(&value);
+        if let (Ok(name), Ok(value)) = (name, value) {
+            self.0.insert(name, value);
+        }
+    }
+}
+fn slow_queries() -> &'static Mutex<BTreeMap<String, u64>> {
+    static SLOW_QUERIES: OnceLock<Mutex<BTreeMap<String, u64>>> = OnceLock::new();
+    SLOW_QUERIES.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Slow statements seen so far, by sqlx's query summary (the first words of the SQL).
+pub fn slow_query_counts() -> BTreeMap<String, u64> {
+    slow_queries().lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Upper bounds of the request latency histogram buckets, in seconds; +Inf is implied.
+pub const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Method, matched route and status code of a request.
+pub type RequestLabels = (String, String, u16);
+
+/// Latest trace seen in a histogram bucket, so a dashboard can jump from the bucket to a trace.
+#[derive(Debug, Clone)]
+pub struct Exemplar {
+    pub trace_id: String,
+    pub seconds: f64,
+    pub timestamp: f64,
+}
+
+/// Non-cumulative bucket counts; the last bucket is +Inf.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    pub buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    pub exemplars: [Option<Exemplar>; LATENCY_BUCKETS.len() + 1],
+    pub sum: f64,
+    pub count: u64,
+}
+
+fn request_latencies() -> &'static Mutex<BTreeMap<RequestLabels, LatencyHistogram>> {
+    static REQUEST_LATENCIES: OnceLock<Mutex<BTreeMap<RequestLabels, LatencyHistogram>>> = OnceLock::new();
+    REQUEST_LATENCIES.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Adds a request to the latency histogram, with its trace as the bucket's exemplar when there is one.
+pub fn observe_request(labels: RequestLabels, seconds: f64, trace_id: Option<String>) {
+    let bucket = LATENCY_BUCKETS.iter().position(|le| seconds <= *le).unwrap_or(LATENCY_BUCKETS.len());
+    let mut latencies = request_latencies().lock().unwrap_or_else(|e| e.into_inner());
+    let histogram = latencies.entry(labels).or_default();
+
+    histogram.buckets[bucket] += 1;
+    histogram.sum += seconds;
+    histogram.count += 1;
+    if let Some(trace_id) = trace_id {
+        let timestamp = chrono::Utc::now().timestamp_micros() as f64 / 1e6;
+        histogram.exemplars[bucket] = Some(Exemplar { trace_id, seconds, timestamp });
+    }
+}
+
+pub fn request_latency_histograms() -> BTreeMap<RequestLabels, LatencyHistogram> {
+    request_latencies().lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Runs the log bridge with the current span's OpenTelemetry context attached, so records carry its trace and
+/// span id: tracing-opentelemetry keeps the context on the span, while the log SDK reads `Context::current()`.
+struct WithSpanContext<L>(L);
+
+impl<S: Subscriber, L: Layer<S>> Layer<S> for WithSpanContext<L> {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let _attached = tracing::Span::current().context().attach();
+        self.0.on_event(event, ctx);
+    }
+}
+
+/// Counts sqlx's slow statement events; sqlx only sets `slow_threshold` on those.
+struct SlowQueryCounter;
+
+impl<S: Subscriber> Layer<S> for SlowQueryCounter {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target() != "sqlx::query" || metadata.fields().field("slow_threshold").is_none() {
+            return;
+        }
+
+        let mut summary = SummaryVisitor(String::new());
+        event.record(&mut summary);
+        *slow_queries()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(summary.0)
+            .or_default() += 1;
+    }
+}
+
+struct SummaryVisitor(String);
+
+impl Visit for SummaryVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "summary" {
+            self.0 = value.to_string();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "summary" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+/// Host, container and Kubernetes attributes, so spans of different replicas can be told apart.
+/// OTEL_RESOURCE_ATTRIBUTES is applied last and overrides anything detected.
+fn detect_resource() -> Resource {
+    Resource::from_detectors(
+        Duration::from_secs(1),
+        vec![
+            Box::new(OsResourceDetector),
+            Box::new(ProcessResourceDetector),
+            Box::new(HostResourceDetector),
+            Box::new(ContainerResourceDetector),
+            Box::new(KubernetesResourceDetector),
+            Box::new(EnvResourceDetector::new()),
+        ],
+    )
+}
+
+struct HostResourceDetector;
+
+impl ResourceDetector for HostResourceDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        let mut attributes = vec![KeyValue::new("host.arch", std::env::consts::ARCH)];
+        let host_name = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        if let Some(host_name) = host_name {
+            attributes.push(KeyValue::new("host.name", host_name));
+        }
+
+        Resource::new(attributes)
+    }
+}
+
+/// Container id, the runtime's 64 hex digit id: in the cgroup path with cgroup v1, in the mount of
+/// /etc/hostname (`.../containers/<id>/hostname`) with cgroup v2.
+struct ContainerResourceDetector;
+
+impl ResourceDetector for ContainerResourceDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        let find_id = |text: &str| {
+            text.split(|c: char| !c.is_ascii_hexdigit())
+                .find(|part| part.len() == 64)
+                .map(str::to_string)
+        };
+        let from_cgroup = || std::fs::read_to_string("/proc/self/cgroup").ok().and_then(|cgroup| find_id(&cgroup));
+        // Other mounts carry overlay layer ids of the same length
+        let from_mounts = || {
+            std::fs::read_to_string("/proc/self/mountinfo")
+                .ok()
+                .and_then(|mounts| mounts.lines().filter(|line| line.contains("/containers/")).find_map(find_id))
+        };
+        let container_id = from_cgroup().or_else(from_mounts);
+
+        match container_id {
+            Some(id) => Resource::new(vec![KeyValue::new("container.id", id)]),
+            None => Resource::empty(),
+        }
+    }
+}
+
+/// Pod, namespace and node from the downward API variables set in k8s/deployment.yaml.
+struct KubernetesResourceDetector;
+
+const SERVICE_ACCOUNT_NAMESPACE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+
+impl ResourceDetector for KubernetesResourceDetector {
+    fn detect(&self, _timeout: Duration) -> Resource {
+        if std::env::var_os("KUBERNETES_SERVICE_HOST").is_none() {
+            return Resource::empty();
+        }
+
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let namespace = env("K8S_NAMESPACE")
+            .or_else(|| std::fs::read_to_string(SERVICE_ACCOUNT_NAMESPACE).ok().map(|ns| ns.trim().to_string()));
+        let attributes = [
+            ("k8s.pod.name", env("K8S_POD_NAME").or_else(|| env("HOSTNAME"))),
+            ("k8s.pod.uid", env("K8S_POD_UID")),
+            ("k8s.namespace.name", namespace),
+            ("k8s.node.name", env("K8S_NODE_NAME")),
+            ("k8s.container.name", env("K8S_CONTAINER_NAME")),
+        ];
+
+        Resource::new(
+            attributes
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|value| KeyValue::new(key, value))),
+        )
+    }
+}
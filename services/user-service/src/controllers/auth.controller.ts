@@ -122,6 +122,7 @@ export class AuthController {
           valid: true,
           userId: payload.userId,
           email: payload.email,
+          role: payload.role,
         },
       };
 